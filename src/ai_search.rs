@@ -0,0 +1,251 @@
+// Azure AI Search へのエクスポートモジュール
+// 保存済みの分析結果をAzure AI Searchのインデックスに登録し、全文検索できるようにする
+
+use anyhow::{Result, anyhow, bail};
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
+use log::info;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeSet;
+
+use crate::{encryption, outputs};
+
+// Azure AI Search の設定
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct AiSearchConfig {
+    /// 分析完了後に自動でエクスポートするか
+    pub enabled: bool,
+    /// 検索サービスのエンドポイント（https://<service>.search.windows.net）
+    pub endpoint: String,
+    /// 管理者APIキー
+    pub key: String,
+    /// インデックス名
+    pub index_name: String,
+    /// REST APIバージョン
+    pub api_version: String,
+}
+
+impl Default for AiSearchConfig {
+    fn default() -> Self {
+        AiSearchConfig {
+            enabled: false,
+            endpoint: String::new(),
+            key: String::new(),
+            index_name: "azure-credit-burner".to_string(),
+            api_version: "2023-11-01".to_string(),
+        }
+    }
+}
+
+// インデックスに登録するドキュメント
+#[derive(Serialize, Debug, Clone)]
+struct SearchDocument {
+    id: String,
+    repo: String,
+    debate_type: String,
+    category: String,
+    endpoint: String,
+    turn: i32,
+    timestamp: String,
+    question: String,
+    content: String,
+}
+
+// サマリーのドキュメントの議論タイプとカテゴリ
+const SUMMARY_TYPE: &str = "summary";
+const SUMMARY_CATEGORY: &str = "サマリー";
+
+// 1リクエストで登録するドキュメント数（上限は1000）
+const UPLOAD_BATCH_SIZE: usize = 500;
+
+// Azure AI Search クライアント
+pub struct AiSearchClient {
+    client: reqwest::Client,
+    config: AiSearchConfig,
+}
+
+impl AiSearchClient {
+    pub fn new(config: AiSearchConfig) -> Result<Self> {
+        if config.endpoint.is_empty() || config.key.is_empty() {
+            bail!("Azure AI Search のエンドポイントまたはキーが設定されていません");
+        }
+
+        Ok(AiSearchClient {
+            client: reqwest::Client::new(),
+            config,
+        })
+    }
+
+    // インデックスを作成（既存の場合は定義を更新）
+    async fn ensure_index(&self) -> Result<()> {
+        let url = format!(
+            "{}/indexes/{}?api-version={}",
+            self.config.endpoint.trim_end_matches('/'),
+            self.config.index_name,
+            self.config.api_version
+        );
+
+        let definition = json!({
+            "name": self.config.index_name,
+            "fields": [
+                { "name": "id", "type": "Edm.String", "key": true, "filterable": true },
+                { "name": "repo", "type": "Edm.String", "searchable": true, "filterable": true, "facetable": true },
+                { "name": "debate_type", "type": "Edm.String", "searchable": true, "filterable": true, "facetable": true },
+                { "name": "category", "type": "Edm.String", "searchable": true, "filterable": true, "facetable": true },
+                { "name": "endpoint", "type": "Edm.String", "filterable": true, "facetable": true },
+                { "name": "turn", "type": "Edm.Int32", "filterable": true, "sortable": true },
+                { "name": "timestamp", "type": "Edm.String", "filterable": true, "sortable": true },
+                { "name": "question", "type": "Edm.String", "searchable": true },
+                { "name": "content", "type": "Edm.String", "searchable": true }
+            ]
+        });
+
+        let response = self
+            .client
+            .put(&url)
+            .header("api-key", &self.config.key)
+            .json(&definition)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await?;
+            return Err(anyhow!(
+                "インデックス作成エラー: ステータス {}, レスポンス: {}",
+                status,
+                error_text
+            ));
+        }
+
+        Ok(())
+    }
+
+    // ドキュメントを登録
+    async fn upload(&self, documents: &[SearchDocument]) -> Result<()> {
+        let url = format!(
+            "{}/indexes/{}/docs/index?api-version={}",
+            self.config.endpoint.trim_end_matches('/'),
+            self.config.index_name,
+            self.config.api_version
+        );
+
+        let actions: Vec<serde_json::Value> = documents
+            .iter()
+            .map(|doc| {
+                let mut value = serde_json::to_value(doc).unwrap_or_default();
+                value["@search.action"] = json!("mergeOrUpload");
+                value
+            })
+            .collect();
+
+        let response = self
+            .client
+            .post(&url)
+            .header("api-key", &self.config.key)
+            .json(&json!({ "value": actions }))
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await?;
+            return Err(anyhow!(
+                "ドキュメント登録エラー: ステータス {}, レスポンス: {}",
+                status,
+                error_text
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+// 保存済みの分析結果をすべてインデックスに登録し、登録件数を返す
+pub async fn export_outputs(base_dir: &str, config: &AiSearchConfig) -> Result<usize> {
+    let client = AiSearchClient::new(config.clone())?;
+
+    info!(
+        "🔎 Azure AI Search インデックスを準備中: {}",
+        config.index_name
+    );
+    client.ensure_index().await?;
+
+    // 各ターンの最後の質問と回答を1ドキュメントにする
+    let responses = outputs::load_responses(base_dir);
+    let repos: BTreeSet<String> = responses
+        .iter()
+        .map(|(_, response)| response.repo.clone())
+        .collect();
+    let mut documents: Vec<SearchDocument> = responses
+        .into_iter()
+        .filter_map(|(path, response)| {
+            let answer = response
+                .messages
+                .iter()
+                .rev()
+                .find(|m| m.role == "assistant")?;
+            let question = response
+                .messages
+                .iter()
+                .rev()
+                .find(|m| m.role == "user")
+                .map(|m| m.content.clone())
+                .unwrap_or_default();
+
+            Some(SearchDocument {
+                // キーに使える文字（英数字, -, _, =）のみになるようエンコード
+                id: URL_SAFE_NO_PAD.encode(path.to_string_lossy().as_bytes()),
+                repo: response.repo.clone(),
+                debate_type: response.debate_type.clone(),
+                category: response.category.clone().unwrap_or_default(),
+                endpoint: response.endpoint.clone(),
+                turn: response.turn as i32,
+                timestamp: response.timestamp.clone(),
+                question,
+                content: answer.content.clone(),
+            })
+        })
+        .collect();
+
+    // リポジトリごとのサマリー（summary.md）も1ドキュメントにする
+    for repo in &repos {
+        let Some((owner, name)) = repo.split_once('/') else {
+            continue;
+        };
+        let Some(repo_dir) = outputs::repo_dirs(base_dir, owner, name).into_iter().next() else {
+            continue;
+        };
+        let path = format!("{}/summary.md", repo_dir);
+        let Ok(summary) = encryption::read_to_string(&path) else {
+            continue;
+        };
+        documents.push(SearchDocument {
+            id: URL_SAFE_NO_PAD.encode(path.as_bytes()),
+            repo: repo.clone(),
+            debate_type: SUMMARY_TYPE.to_string(),
+            category: SUMMARY_CATEGORY.to_string(),
+            endpoint: String::new(),
+            turn: 0,
+            timestamp: std::fs::metadata(&path)
+                .and_then(|metadata| metadata.modified())
+                .map(|modified| chrono::DateTime::<chrono::Utc>::from(modified).to_rfc3339())
+                .unwrap_or_default(),
+            question: String::new(),
+            content: summary,
+        });
+    }
+
+    for batch in documents.chunks(UPLOAD_BATCH_SIZE) {
+        client.upload(batch).await?;
+        info!("📤 {}件のドキュメントを登録しました", batch.len());
+    }
+
+    info!(
+        "✅ Azure AI Search へのエクスポート完了: {}件",
+        documents.len()
+    );
+
+    Ok(documents.len())
+}
@@ -0,0 +1,388 @@
+// Azure Resource Manager（管理API）モジュール
+// Entra ID のサービスプリンシパルで認証し、Azure OpenAI アカウントとデプロイメントを検出する
+
+use anyhow::{Result, anyhow};
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::llm::schemas::openai_response::{DeploymentInfo, Endpoint};
+use crate::redact;
+
+// 管理APIのベースURL
+const ARM_BASE_URL: &str = "https://management.azure.com";
+
+// Cognitive Services リソースプロバイダーのAPIバージョン
+const COGNITIVE_API_VERSION: &str = "2023-05-01";
+
+// デプロイメント自動検出の設定
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct DiscoveryConfig {
+    /// 起動時にエンドポイントを自動検出するか
+    pub enabled: bool,
+    /// テナントID（空の場合は AZURE_TENANT_ID）
+    pub tenant_id: String,
+    /// サービスプリンシパルのクライアントID（空の場合は AZURE_CLIENT_ID）
+    pub client_id: String,
+    /// サービスプリンシパルのシークレット（空の場合は AZURE_CLIENT_SECRET）
+    pub client_secret: String,
+    /// 検索するサブスクリプションIDの一覧
+    pub subscription_ids: Vec<String>,
+    /// 検索するリソースグループ（空の場合はサブスクリプション全体）
+    pub resource_groups: Vec<String>,
+    /// 設定ファイルのエンドポイントを検出結果で置き換えるか（falseの場合は追加）
+    pub replace_endpoints: bool,
+}
+
+// 設定値が空なら環境変数から読み込む
+fn setting_or_env(value: &str, var: &str) -> Result<String> {
+    if !value.is_empty() {
+        return Ok(value.to_string());
+    }
+    std::env::var(var).map_err(|_| anyhow!("{} が設定されていません", var))
+}
+
+// 管理APIのクライアント
+pub struct ArmClient {
+    client: reqwest::Client,
+    token: String,
+}
+
+// クライアント資格情報フローで指定したスコープのアクセストークンを取得
+pub async fn access_token(
+    client: &reqwest::Client,
+    config: &DiscoveryConfig,
+    scope: &str,
+) -> Result<String> {
+    let tenant_id = setting_or_env(&config.tenant_id, "AZURE_TENANT_ID")?;
+    let client_id = setting_or_env(&config.client_id, "AZURE_CLIENT_ID")?;
+    let client_secret = setting_or_env(&config.client_secret, "AZURE_CLIENT_SECRET")?;
+
+    let response = client
+        .post(format!(
+            "https://login.microsoftonline.com/{}/oauth2/v2.0/token",
+            tenant_id
+        ))
+        .form(&[
+            ("grant_type", "client_credentials"),
+            ("client_id", client_id.as_str()),
+            ("client_secret", client_secret.as_str()),
+            ("scope", scope),
+        ])
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_text = response.text().await?;
+        return Err(anyhow!(
+            "Entra ID トークン取得エラー: ステータス {}, レスポンス: {}",
+            status,
+            error_text
+        ));
+    }
+
+    let body: Value = response.json().await?;
+    body["access_token"]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| anyhow!("トークンのレスポンスに access_token がありません"))
+}
+
+impl ArmClient {
+    // 管理APIのアクセストークンを取得
+    pub async fn new(config: &DiscoveryConfig) -> Result<Self> {
+        let client = reqwest::Client::new();
+        let token = access_token(&client, config, "https://management.azure.com/.default").await?;
+        Ok(ArmClient { client, token })
+    }
+
+    // 管理APIにリクエストを送信
+    pub async fn request(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        api_version: &str,
+        body: Option<&Value>,
+    ) -> Result<Value> {
+        let mut request = self
+            .client
+            .request(method, format!("{}{}", ARM_BASE_URL, path))
+            .query(&[("api-version", api_version)])
+            .bearer_auth(&self.token);
+        if let Some(body) = body {
+            request = request.json(body);
+        }
+
+        let response = request.send().await?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await?;
+            return Err(anyhow!(
+                "管理API エラー: {} - ステータス {}, レスポンス: {}",
+                path,
+                status,
+                error_text
+            ));
+        }
+
+        let text = response.text().await?;
+        if text.is_empty() {
+            return Ok(Value::Null);
+        }
+        Ok(serde_json::from_str(&text)?)
+    }
+
+    // 一覧APIの結果をページングしながらすべて取得
+    pub async fn list(&self, path: &str, api_version: &str) -> Result<Vec<Value>> {
+        let mut items = Vec::new();
+        let mut page = self
+            .request(reqwest::Method::GET, path, api_version, None)
+            .await?;
+
+        loop {
+            if let Some(values) = page["value"].as_array() {
+                items.extend(values.iter().cloned());
+            }
+            let Some(next_link) = page["nextLink"].as_str() else {
+                break;
+            };
+            page = self
+                .client
+                .get(next_link)
+                .bearer_auth(&self.token)
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+        }
+
+        Ok(items)
+    }
+
+    // Azure OpenAI のアカウント一覧を取得
+    pub async fn list_openai_accounts(
+        &self,
+        subscription_id: &str,
+        resource_group: Option<&str>,
+    ) -> Result<Vec<Value>> {
+        let path = match resource_group {
+            Some(resource_group) => format!(
+                "/subscriptions/{}/resourceGroups/{}/providers/Microsoft.CognitiveServices/accounts",
+                subscription_id, resource_group
+            ),
+            None => format!(
+                "/subscriptions/{}/providers/Microsoft.CognitiveServices/accounts",
+                subscription_id
+            ),
+        };
+
+        let accounts = self.list(&path, COGNITIVE_API_VERSION).await?;
+        Ok(accounts
+            .into_iter()
+            .filter(|account| matches!(account["kind"].as_str(), Some("OpenAI" | "AIServices")))
+            .collect())
+    }
+
+    // アカウントのデプロイメント一覧を取得
+    pub async fn list_deployments(&self, account_id: &str) -> Result<Vec<DeploymentInfo>> {
+        let deployments = self
+            .list(
+                &format!("{}/deployments", account_id),
+                COGNITIVE_API_VERSION,
+            )
+            .await?;
+
+        Ok(deployments
+            .iter()
+            .map(|deployment| DeploymentInfo {
+                name: deployment["name"].as_str().unwrap_or_default().to_string(),
+                model: deployment["properties"]["model"]["name"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string(),
+                model_version: deployment["properties"]["model"]["version"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string(),
+                sku: deployment["sku"]["name"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string(),
+                capacity: deployment["sku"]["capacity"].as_u64().unwrap_or(0),
+            })
+            .collect())
+    }
+
+    // アカウントのキーを取得
+    pub async fn list_keys(&self, account_id: &str) -> Result<Vec<String>> {
+        let keys = self
+            .request(
+                reqwest::Method::POST,
+                &format!("{}/listKeys", account_id),
+                COGNITIVE_API_VERSION,
+                None,
+            )
+            .await?;
+
+        // key2 は key1 が無効になったときの予備として使う
+        let keys: Vec<String> = ["key1", "key2"]
+            .iter()
+            .filter_map(|name| keys[name].as_str().map(|key| key.to_string()))
+            .collect();
+        if keys.is_empty() {
+            return Err(anyhow!("キーを取得できません: {}", account_id));
+        }
+        redact::add_secrets(&keys);
+        Ok(keys)
+    }
+}
+
+// エンドポイントに記録するテナントID（未設定なら None）
+pub fn tenant_id(config: &DiscoveryConfig) -> Option<String> {
+    setting_or_env(&config.tenant_id, "AZURE_TENANT_ID").ok()
+}
+
+// アカウント情報からエンドポイント設定を作成
+pub async fn account_to_endpoint(
+    client: &ArmClient,
+    account: &Value,
+    tenant: Option<&str>,
+) -> Result<Endpoint> {
+    let account_id = account["id"]
+        .as_str()
+        .ok_or_else(|| anyhow!("アカウントIDがありません"))?;
+    let name = account["name"].as_str().unwrap_or_default().to_string();
+    let endpoint = account["properties"]["endpoint"]
+        .as_str()
+        .ok_or_else(|| anyhow!("エンドポイントURLがありません: {}", name))?
+        .trim_end_matches('/')
+        .to_string();
+
+    // アカウントIDは /subscriptions/<サブスクリプションID>/resourceGroups/... の形式
+    let subscription = account_id
+        .split('/')
+        .skip_while(|segment| !segment.eq_ignore_ascii_case("subscriptions"))
+        .nth(1)
+        .map(str::to_string);
+
+    let mut keys = client.list_keys(account_id).await?;
+    let key = keys.remove(0);
+    Ok(Endpoint {
+        name,
+        key,
+        keys,
+        endpoint,
+        deployments: client.list_deployments(account_id).await?,
+        subscription,
+        tenant: tenant.map(str::to_string),
+        ..Default::default()
+    })
+}
+
+// 設定されたサブスクリプション（とリソースグループ）のアカウントをすべて取得
+pub async fn list_configured_accounts(
+    client: &ArmClient,
+    config: &DiscoveryConfig,
+) -> Result<Vec<Value>> {
+    if config.subscription_ids.is_empty() {
+        return Err(anyhow!(
+            "検出対象のサブスクリプションIDが設定されていません"
+        ));
+    }
+
+    let resource_groups: Vec<Option<&str>> = if config.resource_groups.is_empty() {
+        vec![None]
+    } else {
+        config
+            .resource_groups
+            .iter()
+            .map(|rg| Some(rg.as_str()))
+            .collect()
+    };
+
+    let mut accounts = Vec::new();
+    for subscription_id in &config.subscription_ids {
+        for resource_group in &resource_groups {
+            match client
+                .list_openai_accounts(subscription_id, *resource_group)
+                .await
+            {
+                Ok(found) => accounts.extend(found),
+                Err(e) => error!(
+                    "❌ アカウント一覧の取得エラー: {} {} - {}",
+                    subscription_id,
+                    resource_group.unwrap_or_default(),
+                    e
+                ),
+            }
+        }
+    }
+
+    Ok(accounts)
+}
+
+// 設定されたサブスクリプションからエンドポイントを検出
+pub async fn discover_endpoints(config: &DiscoveryConfig) -> Result<Vec<Endpoint>> {
+    let client = ArmClient::new(config).await?;
+    let tenant = tenant_id(config);
+    let mut endpoints = Vec::new();
+
+    for account in list_configured_accounts(&client, config).await? {
+        match account_to_endpoint(&client, &account, tenant.as_deref()).await {
+            Ok(endpoint) => {
+                info!(
+                    "🔍 エンドポイントを検出しました: {} ({}, デプロイメント数: {})",
+                    endpoint.name,
+                    endpoint.endpoint,
+                    endpoint.deployments.len()
+                );
+                endpoints.push(endpoint);
+            }
+            Err(e) => error!("❌ アカウント情報の取得エラー: {}", e),
+        }
+    }
+
+    Ok(endpoints)
+}
+
+// 検出結果を出力する前にAPIキーを環境変数の参照に置き換える
+// （標準出力やシェルの履歴にキーを残さない。設定ファイルではこの環境変数にキーを設定する）
+pub fn redact_keys(endpoints: &mut [Endpoint]) {
+    for endpoint in endpoints {
+        let env_name: String = endpoint
+            .name
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() {
+                    c.to_ascii_uppercase()
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        endpoint.key = format!("${{AZURE_OPENAI_KEY_{}}}", env_name);
+        for (i, key) in endpoint.keys.iter_mut().enumerate() {
+            *key = format!("${{AZURE_OPENAI_KEY_{}_{}}}", env_name, i + 2);
+        }
+    }
+}
+
+// 検出したエンドポイントを設定に反映
+pub fn merge_endpoints(endpoints: &mut Vec<Endpoint>, discovered: Vec<Endpoint>, replace: bool) {
+    if replace {
+        *endpoints = discovered;
+        return;
+    }
+
+    for endpoint in discovered {
+        let exists = endpoints.iter().any(|existing| {
+            existing.endpoint.trim_end_matches('/') == endpoint.endpoint.trim_end_matches('/')
+        });
+        if !exists {
+            endpoints.push(endpoint);
+        }
+    }
+}
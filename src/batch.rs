@@ -0,0 +1,626 @@
+// Azure OpenAI Batch API モジュール
+// 各議論の同じターンの質問をまとめてバッチジョブとして投入し、結果を待って次のターンへ進める
+
+use anyhow::{Result, bail};
+use chrono::Utc;
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time;
+
+use crate::compaction::Compactor;
+use crate::embeddings::RagRetriever;
+use crate::error::BurnerError;
+use crate::journal::{Journal, TaskState};
+use crate::llm::schemas::github_response::RepoInfo;
+use crate::llm::schemas::openai_response::{
+    ChatMessage, Endpoint, OpenAIResponse, RESPONSE_SCHEMA_VERSION, ResponseData,
+};
+use crate::work_queue::DebateJob;
+use crate::{
+    Config, DeepQuestions, GitHubClient, LlmClient, budget, credits, deadline, find_duplicate_run,
+    get_next_question, ledger, prepare_debate_messages, runs, save_response, seed,
+};
+
+// Batch APIの設定
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct BatchConfig {
+    /// Batch APIモードを有効にするか
+    pub enabled: bool,
+    /// バッチ用（グローバルバッチ）のデプロイメント名
+    pub deployment: String,
+    /// 1議論あたりの最大ターン数
+    pub max_turns: usize,
+    /// 1回答あたりの最大トークン数
+    pub max_tokens: usize,
+    /// バッチの完了期限
+    pub completion_window: String,
+    /// ジョブの状態確認の間隔（秒）
+    pub poll_interval_secs: u64,
+}
+
+impl Default for BatchConfig {
+    fn default() -> Self {
+        BatchConfig {
+            enabled: false,
+            deployment: "gpt-4o-batch".to_string(),
+            max_turns: 20,
+            max_tokens: 4000,
+            completion_window: "24h".to_string(),
+            poll_interval_secs: 60,
+        }
+    }
+}
+
+// バッチジョブの情報
+#[derive(Deserialize, Debug)]
+struct BatchJob {
+    id: String,
+    status: String,
+    output_file_id: Option<String>,
+    error_file_id: Option<String>,
+    request_counts: Option<RequestCounts>,
+}
+
+// バッチ内のリクエスト件数
+#[derive(Deserialize, Debug)]
+struct RequestCounts {
+    total: usize,
+    completed: usize,
+    failed: usize,
+}
+
+// バッチ結果ファイルの1行
+#[derive(Deserialize, Debug)]
+struct BatchOutputLine {
+    custom_id: String,
+    response: Option<BatchOutputResponse>,
+}
+
+#[derive(Deserialize, Debug)]
+struct BatchOutputResponse {
+    status_code: u16,
+    body: serde_json::Value,
+}
+
+// バッチモードで進行中の議論
+struct Conversation {
+    task_id: String,
+    repo_info: RepoInfo,
+    debate_type: String,
+    endpoint_index: usize,
+    messages: Vec<ChatMessage>,
+    // 次に実行するターン
+    turn: usize,
+    category: String,
+    rag: RagRetriever,
+    // 質問の出題順
+    deep_questions: DeepQuestions,
+    // 古いターンの要約
+    compactor: Compactor,
+    active: bool,
+}
+
+impl LlmClient {
+    // バッチジョブを作成
+    async fn create_batch(&self, input_file_id: &str, completion_window: &str) -> Result<BatchJob> {
+        let url = self.provider.resource_url("batches");
+
+        let request_body = json!({
+            "input_file_id": input_file_id,
+            "endpoint": self.provider.batch_endpoint(),
+            "completion_window": completion_window,
+        });
+
+        self.post_with_retry(&url, &request_body).await
+    }
+
+    // バッチジョブの状態を取得
+    async fn get_batch(&self, batch_id: &str) -> Result<BatchJob> {
+        let url = self.provider.resource_url(&format!("batches/{}", batch_id));
+        self.get_with_retry(&url).await
+    }
+}
+
+// 1エンドポイント分のリクエストをバッチで実行し、custom_idごとの (回答, トークン数) を返す
+async fn run_batch(
+    client: &LlmClient,
+    requests: Vec<String>,
+    round: usize,
+    config: &BatchConfig,
+) -> Result<HashMap<String, (String, usize)>> {
+    let filename = format!("batch_round{}.jsonl", round);
+    let file = client
+        .upload_file(
+            (requests.join("\n") + "\n").into_bytes(),
+            &filename,
+            "batch",
+        )
+        .await?;
+    client.wait_for_file_processed(&file.id).await?;
+
+    let mut job = client
+        .create_batch(&file.id, &config.completion_window)
+        .await?;
+    info!(
+        "[{}] 📦 バッチジョブを作成しました: {} ({}件, ラウンド {})",
+        client.endpoint.name,
+        job.id,
+        requests.len(),
+        round
+    );
+
+    // 終了するまでポーリング
+    let mut last_status = String::new();
+    loop {
+        if job.status != last_status {
+            let counts = job
+                .request_counts
+                .as_ref()
+                .map(|c| format!(" ({}/{} 完了, {} 失敗)", c.completed, c.total, c.failed))
+                .unwrap_or_default();
+            info!(
+                "[{}] ⏳ バッチジョブ {}: {}{}",
+                client.endpoint.name, job.id, job.status, counts
+            );
+            last_status = job.status.clone();
+        }
+
+        match job.status.as_str() {
+            "completed" | "failed" | "expired" | "cancelled" => break,
+            _ => {
+                time::sleep(Duration::from_secs(config.poll_interval_secs)).await;
+                job = client.get_batch(&job.id).await?;
+            }
+        }
+    }
+
+    if let Some(error_file_id) = &job.error_file_id {
+        warn!(
+            "[{}] ⚠️ バッチ内で失敗したリクエストがあります（エラーファイル: {}）",
+            client.endpoint.name, error_file_id
+        );
+    }
+
+    // 期限切れやキャンセルでも完了済みの分は結果ファイルに含まれる
+    let Some(output_file_id) = job.output_file_id else {
+        bail!("バッチジョブ {} が {} で終了しました", job.id, job.status);
+    };
+
+    let content = client.get_file_content(&output_file_id).await?;
+    let mut results = HashMap::new();
+
+    for line in content.lines().filter(|line| !line.trim().is_empty()) {
+        let output: BatchOutputLine = match serde_json::from_str(line) {
+            Ok(output) => output,
+            Err(e) => {
+                warn!("⚠️ バッチ結果の解析エラー: {}", e);
+                continue;
+            }
+        };
+
+        let Some(response) = output.response else {
+            continue;
+        };
+        if response.status_code != 200 {
+            warn!(
+                "⚠️ バッチ内のリクエストが失敗しました: {} (ステータス {})",
+                output.custom_id, response.status_code
+            );
+            continue;
+        }
+
+        match serde_json::from_value::<OpenAIResponse>(response.body) {
+            Ok(openai_response) => {
+                if let Some(choice) = openai_response.choices.into_iter().next() {
+                    // コンテンツフィルターで打ち切られた回答は結果として使わない
+                    if let Some(error) = BurnerError::from_finish_reason(&choice.finish_reason) {
+                        warn!(
+                            "⚠️ バッチ内の回答が使えません: {} - {}",
+                            output.custom_id, error
+                        );
+                        continue;
+                    }
+                    results.insert(
+                        output.custom_id,
+                        (choice.message.content, openai_response.usage.total_tokens),
+                    );
+                }
+            }
+            Err(e) => warn!("⚠️ バッチ結果の解析エラー: {} - {}", output.custom_id, e),
+        }
+    }
+
+    Ok(results)
+}
+
+// すべての議論をBatch APIで実行
+pub async fn run_batch_debates(
+    github_client: Arc<GitHubClient>,
+    endpoints: Arc<Vec<Endpoint>>,
+    task_configs: Vec<(DebateJob, usize)>,
+    config: Arc<Config>,
+    journal: Arc<Journal>,
+) -> Result<()> {
+    let clients: Vec<LlmClient> = endpoints
+        .iter()
+        .map(|endpoint| LlmClient::new(endpoint.clone()))
+        .collect();
+
+    // 各議論の初期メッセージを準備（途中まで保存された議論はその続きから再開する）
+    let mut conversations = Vec::new();
+    for (job, endpoint_index) in task_configs {
+        // 新しく始める議論は、同じ分析が完了済みでないか確認する
+        let saved = journal.load_progress(&job.task_id, &job.repo_info);
+        if saved.is_none() {
+            match find_duplicate_run(
+                &github_client,
+                &journal,
+                &job,
+                &config.batch.deployment,
+                &config,
+            )
+            .await
+            {
+                Ok(Some(original)) => {
+                    journal.record_duplicate(
+                        &job.task_id,
+                        &job.repo_info,
+                        &job.debate_type,
+                        &original.task_id,
+                    );
+                    continue;
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    error!(
+                        "❌ リポジトリのクローンエラー: {}/{} - {}",
+                        job.repo_info.owner, job.repo_info.repo, e
+                    );
+                    journal.record_failure(
+                        &job.task_id,
+                        &job.repo_info,
+                        &job.debate_type,
+                        None,
+                        &e,
+                    );
+                    continue;
+                }
+            }
+        }
+
+        let DebateJob {
+            task_id,
+            repo_info,
+            debate_type,
+            ..
+        } = job;
+        let endpoint_index = endpoint_index % clients.len();
+        let client = &clients[endpoint_index];
+        let deep_questions = DeepQuestions::for_debate(&github_client, &repo_info, &task_id).await;
+        let mut rag = RagRetriever::new(&config.output_dir, &repo_info, &config.embeddings);
+
+        let (messages, turn, category, context_summary) = match saved {
+            Some(saved) => {
+                info!(
+                    "⏩ 途中から再開します: {}/{} ({}) - ターン {} まで保存済み",
+                    repo_info.owner, repo_info.repo, debate_type, saved.turn
+                );
+                let (category, next_question) =
+                    get_next_question(&repo_info, &deep_questions, saved.turn);
+                let next_question = rag.augment_question(client, &category, next_question).await;
+                let mut messages = saved.messages;
+                messages.push(ChatMessage {
+                    role: "user".to_string(),
+                    content: next_question,
+                    ..Default::default()
+                });
+                (messages, saved.turn + 1, category, saved.context_summary)
+            }
+            None => {
+                match prepare_debate_messages(
+                    client,
+                    &github_client,
+                    &repo_info,
+                    &debate_type,
+                    &config,
+                )
+                .await
+                {
+                    Ok(messages) => (messages, 1, "概要".to_string(), None),
+                    Err(e) => {
+                        error!(
+                            "❌ リポジトリファイル取得エラー: {}/{} - {}",
+                            repo_info.owner, repo_info.repo, e
+                        );
+                        journal.record_failure(
+                            &task_id,
+                            &repo_info,
+                            &debate_type,
+                            Some(&client.endpoint.name),
+                            &e,
+                        );
+                        continue;
+                    }
+                }
+            }
+        };
+
+        journal.record(
+            &task_id,
+            &repo_info,
+            &debate_type,
+            TaskState::Running,
+            Some(&client.endpoint.name),
+            None,
+        );
+        let compactor = Compactor::new(&config.compaction, &debate_type, context_summary);
+        conversations.push(Conversation {
+            task_id,
+            repo_info,
+            debate_type,
+            endpoint_index,
+            messages,
+            turn,
+            category,
+            rag,
+            deep_questions,
+            compactor,
+            active: true,
+        });
+    }
+
+    runs::note_model(&config.batch.deployment);
+    info!(
+        "📦 Batch APIモードで {} 件の議論を実行します（デプロイメント: {}）",
+        conversations.len(),
+        config.batch.deployment
+    );
+
+    let mut interrupted = false;
+    for round in 1..=config.batch.max_turns {
+        // 最大ターン数に達した議論は完了
+        for conversation in conversations.iter_mut() {
+            if conversation.active && conversation.turn > config.batch.max_turns {
+                conversation.active = false;
+                journal.record(
+                    &conversation.task_id,
+                    &conversation.repo_info,
+                    &conversation.debate_type,
+                    TaskState::Done,
+                    Some(&clients[conversation.endpoint_index].endpoint.name),
+                    None,
+                );
+            }
+        }
+
+        // トークン予算に達したリポジトリと、クレジットが枯渇したエンドポイントの議論は
+        // 実行中のまま止める（次回の実行で再開する）
+        for conversation in conversations.iter_mut() {
+            let endpoint = &clients[conversation.endpoint_index].endpoint.name;
+            if conversation.active
+                && (budget::exhausted(&conversation.repo_info) || credits::is_exhausted(endpoint))
+            {
+                conversation.active = false;
+            }
+        }
+
+        // エンドポイントごとにリクエストをまとめる
+        let mut requests: BTreeMap<usize, Vec<String>> = BTreeMap::new();
+        for (index, conversation) in conversations.iter_mut().enumerate() {
+            if !conversation.active {
+                continue;
+            }
+            // 長くなった会話履歴は古いターンを要約して送信する
+            let context = conversation
+                .compactor
+                .prepare(
+                    &clients[conversation.endpoint_index],
+                    &conversation.repo_info,
+                    &conversation.messages,
+                )
+                .await;
+            let mut request = json!({
+                "custom_id": index.to_string(),
+                "method": "POST",
+                "url": clients[conversation.endpoint_index].provider.batch_endpoint(),
+                "body": {
+                    "model": config.batch.deployment,
+                    "messages": context,
+                    "max_completion_tokens": config.batch.max_tokens,
+                },
+            });
+            if let Some(seed) = seed::get() {
+                request["body"]["seed"] = json!(seed);
+            }
+            requests
+                .entry(conversation.endpoint_index)
+                .or_default()
+                .push(serde_json::to_string(&request)?);
+        }
+
+        if requests.is_empty() {
+            break;
+        }
+
+        // 実行期限が近ければ新しいラウンドを開始しない
+        if deadline::reached() {
+            info!(
+                "⏳ 実行期限が近いためラウンド {} 以降を中断します（次回の実行で再開します）",
+                round
+            );
+            interrupted = true;
+            break;
+        }
+        if ledger::ceiling_reached() {
+            interrupted = true;
+            break;
+        }
+
+        // エンドポイントごとのバッチを並行して実行
+        let batches = requests.into_iter().map(|(endpoint_index, lines)| {
+            let client = &clients[endpoint_index];
+            let batch_config = &config.batch;
+            async move {
+                (
+                    endpoint_index,
+                    run_batch(client, lines, round, batch_config).await,
+                )
+            }
+        });
+
+        let mut results = HashMap::new();
+        for (endpoint_index, result) in futures::future::join_all(batches).await {
+            match result {
+                Ok(batch_results) => results.extend(batch_results),
+                Err(e) => {
+                    error!(
+                        "[{}] ❌ バッチエラー: {}",
+                        endpoints[endpoint_index].name, e
+                    );
+                    if let Some(BurnerError::QuotaExhausted { message }) = BurnerError::of(&e) {
+                        credits::record_exhausted(&endpoints[endpoint_index].name, message);
+                    }
+                }
+            }
+        }
+
+        // 結果を各議論に反映して次の質問を用意
+        for (index, conversation) in conversations.iter_mut().enumerate() {
+            if !conversation.active {
+                continue;
+            }
+
+            let turn = conversation.turn;
+            let client = &clients[conversation.endpoint_index];
+            let Some((response, tokens_used)) = results.remove(&index.to_string()) else {
+                // クレジットが枯渇したエンドポイントの議論は実行中のまま止める
+                if credits::is_exhausted(&client.endpoint.name) {
+                    conversation.active = false;
+                    continue;
+                }
+                error!(
+                    "❌ 回答が得られなかったため議論を終了します: {}/{} ({}) - ターン {}",
+                    conversation.repo_info.owner,
+                    conversation.repo_info.repo,
+                    conversation.debate_type,
+                    turn
+                );
+                conversation.active = false;
+                journal.record(
+                    &conversation.task_id,
+                    &conversation.repo_info,
+                    &conversation.debate_type,
+                    TaskState::Failed,
+                    Some(&client.endpoint.name),
+                    Some(format!("ターン {} の回答が得られませんでした", turn)),
+                );
+                continue;
+            };
+
+            budget::record(&conversation.repo_info, tokens_used);
+            conversation.messages.push(ChatMessage {
+                role: "assistant".to_string(),
+                content: response,
+                ..Default::default()
+            });
+
+            let mut response_data = ResponseData {
+                schema_version: RESPONSE_SCHEMA_VERSION,
+                repo: format!(
+                    "{}/{}",
+                    conversation.repo_info.owner, conversation.repo_info.repo
+                ),
+                debate_type: conversation.debate_type.clone(),
+                turn,
+                timestamp: Utc::now().to_rfc3339(),
+                endpoint: client.endpoint.name.clone(),
+                category: Some(conversation.category.clone()),
+                messages: conversation.messages.clone(),
+                tokens_used,
+                context_summary: conversation.compactor.summary(),
+                run_id: runs::current_id(),
+                file: None,
+                metrics: None,
+                task_id: Some(conversation.task_id.clone()),
+                rejected: Vec::new(),
+                content_hash: None,
+                duplicate_of: None,
+                model: Some(config.batch.deployment.clone()),
+                draft: None,
+            };
+
+            match save_response(
+                &config.output_dir,
+                &conversation.repo_info,
+                &mut response_data,
+            )
+            .await
+            {
+                Ok(Some(filename)) => {
+                    info!(
+                        "[{}] 保存完了: {} (トークン数: {}{})",
+                        client.endpoint.name,
+                        filename,
+                        tokens_used,
+                        ledger::cost_note(&response_data)
+                    );
+                    journal.record_turn(&conversation.task_id, &response_data, &filename);
+                }
+                Ok(None) => {}
+                Err(e) => error!(
+                    "[{}] 保存エラー: {}/{} - ターン {} - {}",
+                    client.endpoint.name,
+                    conversation.repo_info.owner,
+                    conversation.repo_info.repo,
+                    turn,
+                    e
+                ),
+            }
+
+            let (category, next_question) =
+                get_next_question(&conversation.repo_info, &conversation.deep_questions, turn);
+            let next_question = conversation
+                .rag
+                .augment_question(client, &category, next_question)
+                .await;
+            conversation.category = category;
+            conversation.turn += 1;
+            conversation.messages.push(ChatMessage {
+                role: "user".to_string(),
+                content: next_question,
+                ..Default::default()
+            });
+        }
+    }
+
+    // 最後のラウンドで最大ターン数に達した議論を完了にする
+    for conversation in conversations
+        .iter()
+        .filter(|conversation| conversation.active)
+    {
+        let state = if conversation.turn > config.batch.max_turns {
+            TaskState::Done
+        } else if interrupted {
+            // 実行中の状態のまま残し、次回の実行で保存済みのターンから再開する
+            deadline::note_interrupted();
+            continue;
+        } else {
+            TaskState::Failed
+        };
+        journal.record(
+            &conversation.task_id,
+            &conversation.repo_info,
+            &conversation.debate_type,
+            state,
+            Some(&clients[conversation.endpoint_index].endpoint.name),
+            None,
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,227 @@
+// トークン予算モジュール
+// リポジトリごとと全体のトークン使用量を集計し、予算に達したリポジトリの議論を止める
+// 1つの巨大なリポジトリが割り当てをすべて使い切らないようにする
+// 使用量を budget.json に保存し、中断した実行の再開やデーモンの再起動でも予算を数え直さない
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+use crate::llm::schemas::github_response::RepoInfo;
+
+struct Budget {
+    max_tokens_per_repo: Option<usize>,
+    max_total_tokens: Option<usize>,
+    usage: Mutex<Usage>,
+    // 使用量を保存するファイル（None なら保存しない）
+    state_path: Option<PathBuf>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct Usage {
+    per_repo: BTreeMap<String, usize>,
+    total: usize,
+    // 予算到達をログに出力済みのリポジトリ（全体の予算は空文字）
+    #[serde(skip)]
+    reported: HashSet<String>,
+}
+
+static BUDGET: OnceLock<Budget> = OnceLock::new();
+
+// 保存していない使用量があるか、保存中か（保存は別スレッドでまとめて行う）
+static DIRTY: AtomicBool = AtomicBool::new(false);
+static WRITING: AtomicBool = AtomicBool::new(false);
+
+fn repo_key(repo_info: &RepoInfo) -> String {
+    format!("{}/{}", repo_info.owner, repo_info.repo)
+}
+
+// 使用量を保存するファイルのパス（ドライランは本番と混ざらないよう別ファイル）
+pub fn state_path(output_dir: &str, dry_run: bool) -> String {
+    if dry_run {
+        format!("{}/budget.dry-run.json", output_dir)
+    } else {
+        format!("{}/budget.json", output_dir)
+    }
+}
+
+// トークン予算を設定（どちらも未設定なら何もしない）
+// state_path を指定した場合は保存済みの使用量を読み込み、以降の使用量も保存する
+pub fn configure(
+    max_tokens_per_repo: Option<usize>,
+    max_total_tokens: Option<usize>,
+    state_path: Option<String>,
+) {
+    if max_tokens_per_repo.is_none() && max_total_tokens.is_none() {
+        return;
+    }
+    if let Some(max) = max_tokens_per_repo {
+        info!("💰 リポジトリごとのトークン予算: {}", max);
+    }
+    if let Some(max) = max_total_tokens {
+        info!("💰 全体のトークン予算: {}", max);
+    }
+    let state_path = state_path.map(PathBuf::from);
+    let usage = match &state_path {
+        Some(path) if path.exists() => match fs::read_to_string(path)
+            .map_err(anyhow::Error::from)
+            .and_then(|content| Ok(serde_json::from_str::<Usage>(&content)?))
+        {
+            Ok(usage) => {
+                info!(
+                    "💰 保存済みのトークン使用量を読み込みました: {} ({})",
+                    usage.total,
+                    path.display()
+                );
+                usage
+            }
+            Err(e) => {
+                warn!(
+                    "⚠️ 保存済みのトークン使用量を読み込めません: {} - {}",
+                    path.display(),
+                    e
+                );
+                Usage::default()
+            }
+        },
+        _ => Usage::default(),
+    };
+    let _ = BUDGET.set(Budget {
+        max_tokens_per_repo,
+        max_total_tokens,
+        usage: Mutex::new(usage),
+        state_path,
+    });
+}
+
+// 使用量をファイルに保存（一時ファイルに書いてから置き換える）
+fn persist(budget: &Budget) {
+    let Some(path) = &budget.state_path else {
+        return;
+    };
+    let content = serde_json::to_string_pretty(&*budget.usage.lock().unwrap());
+    let result = content.map_err(anyhow::Error::from).and_then(|content| {
+        let temp = path.with_extension("json.tmp");
+        fs::write(&temp, content)?;
+        fs::rename(&temp, path)?;
+        Ok(())
+    });
+    if let Err(e) = result {
+        warn!(
+            "⚠️ トークン使用量を保存できません: {} - {}",
+            path.display(),
+            e
+        );
+    }
+}
+
+// 保存していない使用量がなくなるまで保存する（保存中に記録された使用量はまとめて次の保存に含める）
+fn write_pending(budget: &'static Budget) {
+    loop {
+        while DIRTY.swap(false, Ordering::SeqCst) {
+            persist(budget);
+        }
+        WRITING.store(false, Ordering::SeqCst);
+        // 保存を終えた直後に記録された使用量を取りこぼさない
+        if !DIRTY.load(Ordering::SeqCst) || WRITING.swap(true, Ordering::SeqCst) {
+            return;
+        }
+    }
+}
+
+// 使用量の保存を別スレッドで行う（非同期のランタイムの外では、その場で書き込む）
+fn persist_in_background(budget: &'static Budget) {
+    DIRTY.store(true, Ordering::SeqCst);
+    if WRITING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) => {
+            handle.spawn_blocking(move || write_pending(budget));
+        }
+        Err(_) => write_pending(budget),
+    }
+}
+
+// 使用したトークン数を記録
+pub fn record(repo_info: &RepoInfo, tokens_used: usize) {
+    let Some(budget) = BUDGET.get() else {
+        return;
+    };
+    if tokens_used == 0 {
+        return;
+    }
+    let mut usage = budget.usage.lock().unwrap();
+    *usage.per_repo.entry(repo_key(repo_info)).or_default() += tokens_used;
+    usage.total += tokens_used;
+    drop(usage);
+    if budget.state_path.is_some() {
+        persist_in_background(budget);
+    }
+}
+
+// 別スレッドでの保存を待ち、最後の使用量を保存する（終了前に呼ぶ）
+pub fn flush() {
+    let Some(budget) = BUDGET.get() else {
+        return;
+    };
+    while WRITING.load(Ordering::SeqCst) {
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    if DIRTY.swap(false, Ordering::SeqCst) {
+        persist(budget);
+    }
+}
+
+// リポジトリまたは全体の予算に達したか（予算に達した場合は初回だけログに出力）
+pub fn exhausted(repo_info: &RepoInfo) -> bool {
+    let Some(budget) = BUDGET.get() else {
+        return false;
+    };
+    let mut usage = budget.usage.lock().unwrap();
+    let repo = repo_key(repo_info);
+
+    if let Some(max) = budget.max_total_tokens.filter(|max| usage.total >= *max) {
+        if usage.reported.insert(String::new()) {
+            warn!(
+                "💰 全体のトークン予算に達しました: {} / {} - 新しいターンを開始しません",
+                usage.total, max
+            );
+        }
+        return true;
+    }
+
+    let used = usage.per_repo.get(&repo).copied().unwrap_or_default();
+    if let Some(max) = budget.max_tokens_per_repo.filter(|max| used >= *max) {
+        if usage.reported.insert(repo.clone()) {
+            warn!(
+                "💰 リポジトリのトークン予算に達しました: {} ({} / {}) - このリポジトリの議論を止めます",
+                repo, used, max
+            );
+        }
+        return true;
+    }
+    false
+}
+
+// リポジトリごとのトークン使用量をログに出力
+pub fn log_summary() {
+    let Some(budget) = BUDGET.get() else {
+        return;
+    };
+    let usage = budget.usage.lock().unwrap();
+    info!("💰 トークン使用量（合計: {}）", usage.total);
+    for (repo, used) in &usage.per_repo {
+        let status = if usage.reported.contains(repo) {
+            "予算到達"
+        } else {
+            "予算内"
+        };
+        info!("   {} - {} トークン ({})", repo, used, status);
+    }
+}
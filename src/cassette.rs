@@ -0,0 +1,177 @@
+// HTTPの記録・再生モジュール
+// 実際のAPIレスポンス（429やコンテンツフィルターのエラーを含む）をカセットファイルに記録し、後から通信せずに再生する
+
+use anyhow::{Result, anyhow};
+use futures::future::BoxFuture;
+use log::info;
+use reqwest::RequestBuilder;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::io::Write;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use crate::keys::KeyRing;
+use crate::provider::LlmProvider;
+
+// 記録したリクエストとレスポンス
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct Interaction {
+    method: String,
+    url: String,
+    status: u16,
+    body: String,
+}
+
+impl Interaction {
+    // 再生時の照合キー（メソッドとクエリを除いたURL）
+    fn key(method: &str, url: &reqwest::Url) -> String {
+        let mut url = url.clone();
+        url.set_query(None);
+        format!("{} {}", method, url)
+    }
+}
+
+// カセットの動作モード
+enum Cassette {
+    // レスポンスをJSONL形式で追記していく
+    Record(Mutex<std::fs::File>),
+    // 同じキーのリクエストには記録した順にレスポンスを返す
+    Replay(Mutex<HashMap<String, VecDeque<Interaction>>>),
+}
+
+// プロセス全体で共有するカセット
+static CASSETTE: OnceLock<Arc<Cassette>> = OnceLock::new();
+
+// 記録モードを開始
+pub fn start_recording(path: &str) -> Result<()> {
+    if let Some(parent) = std::path::Path::new(path).parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+
+    CASSETTE
+        .set(Arc::new(Cassette::Record(Mutex::new(file))))
+        .map_err(|_| anyhow!("カセットはすでに設定されています"))?;
+    info!("📼 HTTPレスポンスを記録します: {}", path);
+    Ok(())
+}
+
+// 再生モードを開始
+pub fn start_replay(path: &str) -> Result<()> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| anyhow!("カセットを読み込めません: {} - {}", path, e))?;
+
+    let mut interactions: HashMap<String, VecDeque<Interaction>> = HashMap::new();
+    let mut count = 0;
+    for line in content.lines().filter(|line| !line.trim().is_empty()) {
+        let interaction: Interaction = serde_json::from_str(line)?;
+        let url = reqwest::Url::parse(&interaction.url)?;
+        interactions
+            .entry(Interaction::key(&interaction.method, &url))
+            .or_default()
+            .push_back(interaction);
+        count += 1;
+    }
+
+    CASSETTE
+        .set(Arc::new(Cassette::Replay(Mutex::new(interactions))))
+        .map_err(|_| anyhow!("カセットはすでに設定されています"))?;
+    info!("📼 カセットを再生します: {} ({}件)", path, count);
+    Ok(())
+}
+
+// カセットが設定されていればプロバイダーを包む
+pub fn wrap(inner: Box<dyn LlmProvider>) -> Box<dyn LlmProvider> {
+    match CASSETTE.get() {
+        Some(cassette) => Box::new(CassetteProvider {
+            inner,
+            cassette: cassette.clone(),
+        }),
+        None => inner,
+    }
+}
+
+// 記録・再生を行うプロバイダー（URLと認証は元のプロバイダーに従う）
+struct CassetteProvider {
+    inner: Box<dyn LlmProvider>,
+    cassette: Arc<Cassette>,
+}
+
+// 記録した内容からレスポンスを組み立てる
+fn to_response(status: u16, body: String) -> Result<reqwest::Response> {
+    let response = http::Response::builder().status(status).body(body)?;
+    Ok(reqwest::Response::from(response))
+}
+
+impl LlmProvider for CassetteProvider {
+    fn deployment_url(&self, deployment: &str, operation: &str) -> String {
+        self.inner.deployment_url(deployment, operation)
+    }
+
+    fn resource_url(&self, path: &str) -> String {
+        self.inner.resource_url(path)
+    }
+
+    fn authorize(&self, request: RequestBuilder) -> RequestBuilder {
+        self.inner.authorize(request)
+    }
+
+    fn key_ring(&self) -> Option<&KeyRing> {
+        self.inner.key_ring()
+    }
+
+    fn model_in_body(&self) -> bool {
+        self.inner.model_in_body()
+    }
+
+    fn batch_endpoint(&self) -> &str {
+        self.inner.batch_endpoint()
+    }
+
+    fn send(&self, request: RequestBuilder) -> BoxFuture<'_, Result<reqwest::Response>> {
+        Box::pin(async move {
+            match self.cassette.as_ref() {
+                Cassette::Record(file) => {
+                    // 送信前にメソッドとURLを控えておく
+                    let (client, built) = request.build_split();
+                    let built = built?;
+                    let method = built.method().to_string();
+                    let url = built.url().to_string();
+
+                    let response = self
+                        .inner
+                        .send(RequestBuilder::from_parts(client, built))
+                        .await?;
+                    let status = response.status().as_u16();
+                    let body = response.text().await?;
+
+                    let interaction = Interaction {
+                        method,
+                        url,
+                        status,
+                        body: body.clone(),
+                    };
+                    let line = serde_json::to_string(&interaction)?;
+                    writeln!(file.lock().unwrap(), "{}", line)?;
+
+                    to_response(status, body)
+                }
+                Cassette::Replay(interactions) => {
+                    let built = request.build()?;
+                    let key = Interaction::key(built.method().as_str(), built.url());
+                    let interaction = interactions
+                        .lock()
+                        .unwrap()
+                        .get_mut(&key)
+                        .and_then(|queue| queue.pop_front())
+                        .ok_or_else(|| anyhow!("カセットに記録がありません: {}", key))?;
+
+                    to_response(interaction.status, interaction.body)
+                }
+            }
+        })
+    }
+}
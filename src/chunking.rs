@@ -0,0 +1,244 @@
+// 巨大なリポジトリのディレクトリ単位の分析モジュール
+// 最大ファイル数に収まらないリポジトリのファイルをディレクトリごとのまとまり（チャンク）に分け、
+// チャンクごとに小さな分析を行ってその要約を議論のシステムプロンプトに加える
+// （先頭の max_files 件だけでなく、モノレポ全体を踏まえて議論できるようにする）
+
+use anyhow::Result;
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use tokio::fs;
+
+use crate::generation::{self, Generation};
+use crate::llm::schemas::{
+    github_response::{FileInfo, RepoInfo},
+    openai_response::ChatMessage,
+};
+use crate::{GitHubClient, LlmClient, budget, encryption, outputs};
+
+// ディレクトリ単位の分析の設定
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ChunkingConfig {
+    /// ファイル数が最大ファイル数を超えるリポジトリをディレクトリごとに分析するか
+    pub enabled: bool,
+    /// チャンクに分けるディレクトリの深さ（1 ならトップレベルのディレクトリごと）
+    pub depth: usize,
+    /// 1つのチャンクで読み込む最大ファイル数
+    pub max_files_per_chunk: usize,
+    /// 分析する最大チャンク数（ファイル数の多いチャンクから分析する）
+    pub max_chunks: usize,
+    /// 1つのチャンクの分析に渡すファイル内容の最大文字数
+    pub max_input_chars: usize,
+    /// チャンクの分析の最大出力トークン数
+    pub max_tokens: usize,
+    /// チャンクの分析に使うデプロイメント名（省略時は議論と同じモデル）
+    pub deployment: Option<String>,
+}
+
+impl Default for ChunkingConfig {
+    fn default() -> Self {
+        ChunkingConfig {
+            enabled: false,
+            depth: 1,
+            max_files_per_chunk: 30,
+            max_chunks: 20,
+            max_input_chars: 60000,
+            max_tokens: 1500,
+            deployment: None,
+        }
+    }
+}
+
+// チャンクの分析の要約の保存先（リポジトリの出力ディレクトリ以下）
+const CHUNKS_DIR: &str = "chunks";
+
+// ルート直下のファイルのチャンク名
+const ROOT_CHUNK: &str = "(ルート)";
+
+// チャンクの分析の指示
+const CHUNK_PROMPT: &str = "あなたは大規模なリポジトリの一部を担当するシニアエンジニアです。\
+以下はリポジトリの1つのディレクトリのファイルです。指定された観点から、このディレクトリの役割、主要なコンポーネント、\
+他のディレクトリとの依存関係、目立つ長所と問題点を、コードを具体的に引用しながら簡潔にMarkdownでまとめてください。\
+この要約は、リポジトリ全体を議論する別の会話に渡されます。ファイルに含まれない内容を作らないでください。";
+
+// ファイルのチャンク名（リポジトリのルートからの相対パスの先頭 depth 個のディレクトリ）
+fn chunk_name(rel_path: &str, depth: usize) -> String {
+    let directories: Vec<&str> = rel_path.split('/').collect();
+    let directories = &directories[..directories.len() - 1];
+    if directories.is_empty() {
+        return ROOT_CHUNK.to_string();
+    }
+    directories[..directories.len().min(depth.max(1))].join("/")
+}
+
+// 1つのチャンクを分析する
+#[allow(clippy::too_many_arguments)]
+async fn analyze_chunk(
+    client: &LlmClient,
+    config: &ChunkingConfig,
+    generation: &Generation,
+    repo_info: &RepoInfo,
+    debate_type: &str,
+    name: &str,
+    total_files: usize,
+    files: &[FileInfo],
+) -> Result<(String, usize)> {
+    let mut contents = String::new();
+    for file in files {
+        let section = format!("\n--- {} ---\n{}\n", file.path, file.content);
+        if contents.chars().count() + section.chars().count() > config.max_input_chars {
+            break;
+        }
+        contents.push_str(&section);
+    }
+    let messages = vec![
+        ChatMessage {
+            role: "system".to_string(),
+            content: CHUNK_PROMPT.to_string(),
+            ..Default::default()
+        },
+        ChatMessage {
+            role: "user".to_string(),
+            content: format!(
+                "リポジトリ: {}/{}\n観点: {}\nディレクトリ: {}（ファイル数: {}、読み込んだファイル: {}）\n{}",
+                repo_info.owner,
+                repo_info.repo,
+                debate_type,
+                name,
+                total_files,
+                files.len(),
+                contents
+            ),
+            ..Default::default()
+        },
+    ];
+    let (message, tokens_used, _) = client
+        .chat_completion_with_fallback(&messages, generation, &[])
+        .await?;
+    Ok((message.content, tokens_used))
+}
+
+// 最大ファイル数に収まらないリポジトリをディレクトリごとに分析し、システムプロンプトに加える要約を返す
+// （最大ファイル数に収まるリポジトリは None）
+pub async fn analyze_chunks(
+    client: &LlmClient,
+    github_client: &GitHubClient,
+    repo_info: &RepoInfo,
+    debate_type: &str,
+    base_dir: &str,
+    config: &ChunkingConfig,
+) -> Result<Option<String>> {
+    let (repo_dir, all_files) = github_client.list_code_files(repo_info).await?;
+    if all_files.len() <= repo_info.max_files {
+        return Ok(None);
+    }
+
+    // ディレクトリごとにまとめ、ファイル数の多いチャンクから分析する
+    let mut chunks: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+    for path in all_files {
+        let rel_path = outputs::slash_path(path.strip_prefix(&repo_dir).unwrap_or(&path));
+        chunks
+            .entry(chunk_name(&rel_path, config.depth))
+            .or_default()
+            .push(path);
+    }
+    let mut chunks: Vec<(String, Vec<PathBuf>)> = chunks.into_iter().collect();
+    chunks.sort_by_key(|(_, paths)| std::cmp::Reverse(paths.len()));
+    if chunks.len() > config.max_chunks {
+        warn!(
+            "⚠️ チャンクが多いため、ファイル数の多い {} 件だけを分析します: {}/{} ({} 件)",
+            config.max_chunks,
+            repo_info.owner,
+            repo_info.repo,
+            chunks.len()
+        );
+        chunks.truncate(config.max_chunks);
+    }
+    info!(
+        "🧩 ディレクトリごとに分析します: {}/{} ({}) - {} チャンク",
+        repo_info.owner,
+        repo_info.repo,
+        debate_type,
+        chunks.len()
+    );
+
+    let mut generation = generation::resolve(&client.endpoint, debate_type);
+    generation.max_tokens = config.max_tokens;
+    if let Some(deployment) = &config.deployment {
+        generation.model = deployment.clone();
+        generation.reasoning = generation::is_reasoning(&client.endpoint, deployment);
+    }
+
+    let mut summaries = String::new();
+    let mut analyzed = 0;
+    for (name, paths) in &chunks {
+        let total_files = paths.len();
+        let files = github_client
+            .read_files(
+                repo_info,
+                &repo_dir,
+                paths.iter().take(config.max_files_per_chunk).cloned(),
+            )
+            .await?;
+        if files.is_empty() {
+            continue;
+        }
+        match analyze_chunk(
+            client,
+            config,
+            &generation,
+            repo_info,
+            debate_type,
+            name,
+            total_files,
+            &files,
+        )
+        .await
+        {
+            Ok((summary, tokens_used)) => {
+                budget::record(repo_info, tokens_used);
+                info!(
+                    "[{}] 🧩 チャンクの分析完了: {}/{} - {} (トークン数: {})",
+                    client.endpoint.name, repo_info.owner, repo_info.repo, name, tokens_used
+                );
+                summaries.push_str(&format!(
+                    "\n### {}（{} ファイル）\n{}\n",
+                    name,
+                    total_files,
+                    summary.trim()
+                ));
+                analyzed += 1;
+            }
+            Err(e) => error!(
+                "[{}] ⚠️ チャンクの分析エラー: {}/{} - {} - {}",
+                client.endpoint.name, repo_info.owner, repo_info.repo, name, e
+            ),
+        }
+    }
+    if analyzed == 0 {
+        return Ok(None);
+    }
+
+    // 議論とは別に、チャンクの分析の要約を残す
+    let mut path =
+        PathBuf::from(outputs::repo_dirs(base_dir, &repo_info.owner, &repo_info.repo).remove(0));
+    path.push(CHUNKS_DIR);
+    fs::create_dir_all(&path).await?;
+    path.push(format!("{}.md", outputs::path_segment(debate_type)));
+    encryption::write(
+        &path,
+        format!(
+            "# {}/{} - {}\n{}",
+            repo_info.owner, repo_info.repo, debate_type, summaries
+        ),
+    )
+    .await?;
+
+    Ok(Some(format!(
+        "\n\n【ディレクトリごとの分析】\nこのリポジトリはファイル数が多いため、上のファイル一覧とサンプルは一部です。\
+以下は {} 個のディレクトリを個別に分析した要約です。リポジトリ全体を踏まえて議論してください。\n{}",
+        analyzed, summaries
+    )))
+}
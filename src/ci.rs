@@ -0,0 +1,248 @@
+// テストと CI の検出モジュール
+// リポジトリの CI の設定（GitHub Actions など）、カバレッジの設定、テストのディレクトリを検出して要約し、
+// 「テスト品質」の深掘り質問に添える（テストの状況を知らないまま質問しないようにする）
+
+use anyhow::{Result, anyhow};
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use tokio::fs;
+
+use crate::llm::schemas::github_response::RepoInfo;
+use crate::{GitHubClient, outputs};
+
+// テストと CI の検出の設定
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct CiConfig {
+    /// 「テスト品質」の質問にテストと CI の要約を添えるか
+    pub enabled: bool,
+    /// 1つの CI の設定ファイルから抜き出す最大行数
+    pub max_lines_per_file: usize,
+    /// 表示するテストのディレクトリの最大数（テストファイルの多い順）
+    pub max_test_dirs: usize,
+}
+
+impl Default for CiConfig {
+    fn default() -> Self {
+        CiConfig {
+            enabled: true,
+            max_lines_per_file: 20,
+            max_test_dirs: 10,
+        }
+    }
+}
+
+// 要約を添える深掘り質問のカテゴリ
+pub const TEST_CATEGORY: &str = "テスト品質";
+
+// CI のサービス名と設定ファイル（リポジトリのルートからの相対パス）
+const CI_FILES: [(&str, &str); 8] = [
+    ("GitLab CI", ".gitlab-ci.yml"),
+    ("CircleCI", ".circleci/config.yml"),
+    ("Azure Pipelines", "azure-pipelines.yml"),
+    ("Travis CI", ".travis.yml"),
+    ("Jenkins", "Jenkinsfile"),
+    ("Bitbucket Pipelines", "bitbucket-pipelines.yml"),
+    ("Drone", ".drone.yml"),
+    ("AppVeyor", "appveyor.yml"),
+];
+
+// GitHub Actions のワークフローのディレクトリ
+const WORKFLOWS_DIR: &str = ".github/workflows";
+
+// カバレッジの設定ファイル
+const COVERAGE_FILES: [&str; 9] = [
+    "codecov.yml",
+    ".codecov.yml",
+    ".coveragerc",
+    "coverage.xml",
+    "tarpaulin.toml",
+    ".tarpaulin.toml",
+    ".nycrc",
+    ".nycrc.json",
+    "jest.config.js",
+];
+
+// CI の設定から抜き出す行（ジョブ名・実行するコマンド・使うアクション）
+const KEY_PREFIXES: [&str; 8] = [
+    "name:", "run:", "- run:", "uses:", "- uses:", "script:", "stage(", "sh ",
+];
+
+static CONFIG: OnceLock<CiConfig> = OnceLock::new();
+
+// リポジトリ（owner/repo）ごとの要約（議論ごとに検出し直さない）
+static SUMMARIES: OnceLock<Mutex<HashMap<String, String>>> = OnceLock::new();
+
+// テストと CI の検出の設定を登録する
+pub fn configure(config: &CiConfig) {
+    let _ = CONFIG.set(config.clone());
+}
+
+// テストのファイルか（テスト用のディレクトリにあるか、テストの命名規則に従うファイル）
+fn is_test_file(rel_path: &str) -> bool {
+    let mut segments: Vec<&str> = rel_path.split('/').collect();
+    let file_name = segments.pop().unwrap_or_default();
+    if segments.iter().any(|segment| {
+        matches!(
+            *segment,
+            "test" | "tests" | "__tests__" | "spec" | "specs" | "testing" | "e2e"
+        )
+    }) {
+        return true;
+    }
+    let stem = file_name.split('.').next().unwrap_or_default();
+    stem.starts_with("test_")
+        || stem.ends_with("_test")
+        || stem.ends_with("Test")
+        || stem.ends_with("Tests")
+        || file_name.contains(".test.")
+        || file_name.contains(".spec.")
+}
+
+// CI の設定ファイルからジョブ名やコマンドの行を抜き出す
+async fn key_lines(path: &Path, max_lines: usize) -> Vec<String> {
+    let Ok(content) = fs::read_to_string(path).await else {
+        return Vec::new();
+    };
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| KEY_PREFIXES.iter().any(|prefix| line.starts_with(prefix)))
+        .take(max_lines)
+        .map(|line| line.chars().take(160).collect())
+        .collect()
+}
+
+// CI の設定ファイル（サービス名と相対パス）
+async fn ci_files(repo_dir: &Path) -> Vec<(&'static str, PathBuf)> {
+    let mut files = Vec::new();
+    if let Ok(mut entries) = fs::read_dir(repo_dir.join(WORKFLOWS_DIR)).await {
+        let mut workflows = Vec::new();
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let path = entry.path();
+            if matches!(
+                path.extension().and_then(|ext| ext.to_str()),
+                Some("yml" | "yaml")
+            ) {
+                workflows.push(path);
+            }
+        }
+        workflows.sort();
+        files.extend(workflows.into_iter().map(|path| ("GitHub Actions", path)));
+    }
+    for (service, file) in CI_FILES {
+        let path = repo_dir.join(file);
+        if fs::metadata(&path).await.is_ok() {
+            files.push((service, path));
+        }
+    }
+    files
+}
+
+// テストと CI の状況を要約する
+async fn summarize(config: &CiConfig, repo_dir: &Path, paths: &[PathBuf]) -> String {
+    let relative = |path: &Path| outputs::slash_path(path.strip_prefix(repo_dir).unwrap_or(path));
+    let mut text = String::from("【テストとCIの状況】\n");
+
+    // CI の設定
+    let ci_files = ci_files(repo_dir).await;
+    if ci_files.is_empty() {
+        text.push_str("CI の設定は見つかりませんでした。\n");
+    } else {
+        text.push_str("CI の設定:\n");
+        for (service, path) in &ci_files {
+            text.push_str(&format!("- {}: {}\n", service, relative(path)));
+            for line in key_lines(path, config.max_lines_per_file).await {
+                text.push_str(&format!("    {}\n", line));
+            }
+        }
+    }
+
+    // カバレッジの設定
+    let mut coverage = Vec::new();
+    for file in COVERAGE_FILES {
+        if fs::metadata(repo_dir.join(file)).await.is_ok() {
+            coverage.push(file);
+        }
+    }
+    if !coverage.is_empty() {
+        text.push_str(&format!("カバレッジの設定: {}\n", coverage.join(", ")));
+    }
+
+    // テストのファイル（ディレクトリごとの件数）
+    let mut test_dirs: BTreeMap<String, usize> = BTreeMap::new();
+    let mut inline_tests = 0;
+    for path in paths {
+        let rel_path = relative(path);
+        if is_test_file(&rel_path) {
+            let dir = rel_path
+                .rsplit_once('/')
+                .map(|(dir, _)| format!("{}/", dir))
+                .unwrap_or_else(|| "(ルート)".to_string());
+            *test_dirs.entry(dir).or_default() += 1;
+        } else if rel_path.ends_with(".rs")
+            && fs::read_to_string(path)
+                .await
+                .is_ok_and(|content| content.contains("#[cfg(test)]"))
+        {
+            // Rust はソースファイル内の単体テストも数える
+            inline_tests += 1;
+        }
+    }
+    let test_files: usize = test_dirs.values().sum();
+    if test_files == 0 && inline_tests == 0 {
+        text.push_str(&format!(
+            "テストのファイルは見つかりませんでした（コードファイル {} 件）。\n",
+            paths.len()
+        ));
+    } else {
+        text.push_str(&format!(
+            "テストのファイル: {} 件（コードファイル {} 件中）\n",
+            test_files,
+            paths.len()
+        ));
+        let mut test_dirs: Vec<_> = test_dirs.into_iter().collect();
+        test_dirs.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        for (dir, count) in test_dirs.iter().take(config.max_test_dirs) {
+            text.push_str(&format!("- {} ({} 件)\n", dir, count));
+        }
+        if inline_tests > 0 {
+            text.push_str(&format!(
+                "ソースファイル内の単体テスト（#[cfg(test)]）: {} 件\n",
+                inline_tests
+            ));
+        }
+    }
+    text
+}
+
+// リポジトリのテストと CI の要約（無効なら None）
+pub async fn test_context(
+    github_client: &GitHubClient,
+    repo_info: &RepoInfo,
+) -> Result<Option<String>> {
+    let Some(config) = CONFIG.get().filter(|config| config.enabled) else {
+        return Ok(None);
+    };
+    let repo = format!("{}/{}", repo_info.owner, repo_info.repo);
+    let cache = SUMMARIES.get_or_init(|| Mutex::new(HashMap::new()));
+    if let Some(cached) = cache.lock().unwrap().get(&repo) {
+        return Ok(Some(cached.clone()));
+    }
+
+    let (repo_dir, paths) = github_client
+        .list_code_files(repo_info)
+        .await
+        .map_err(|e| anyhow!("テストと CI の設定を検出できません: {} - {}", repo, e))?;
+    let summary = summarize(config, &repo_dir, &paths).await;
+    info!(
+        "🧪 テストと CI の状況: {} - {}",
+        repo,
+        summary.lines().nth(1).unwrap_or_default()
+    );
+    cache.lock().unwrap().insert(repo, summary.clone());
+    Ok(Some(summary))
+}
@@ -0,0 +1,134 @@
+// クローンの設定モジュール
+// サブモジュールと Git LFS のオブジェクトを取得するかを決め、取得しない場合はその旨をログに残す
+// （ベンダリングしたサブモジュールを含むリポジトリで、コードの半分しか分析していないことに気づけるようにする）
+
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::Path;
+use std::time::Duration;
+use tokio::process::Command;
+
+// クローンの設定
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct CloneConfig {
+    /// サブモジュールも取得するか
+    pub submodules: bool,
+    /// Git LFS のオブジェクトを取得するか（git-lfs が必要。無効ならポインターファイルを分析対象から外す）
+    pub lfs: bool,
+    /// クローンする前に GitHub API で確認するリポジトリの最大サイズ（MB、省略時は確認しない）
+    pub max_repo_size_mb: Option<u64>,
+    /// 最大サイズを超えたリポジトリの扱い
+    pub oversize: OversizePolicy,
+    /// git clone の時間制限（秒、0なら無制限）
+    pub timeout_secs: u64,
+    /// 議論を始める前にすべてのリポジトリを並行してクローンするか
+    pub prefetch: bool,
+    /// 事前クローンの同時実行数
+    pub prefetch_concurrency: usize,
+}
+
+impl Default for CloneConfig {
+    fn default() -> Self {
+        CloneConfig {
+            submodules: false,
+            lfs: false,
+            max_repo_size_mb: None,
+            oversize: OversizePolicy::default(),
+            timeout_secs: 600,
+            prefetch: true,
+            prefetch_concurrency: 4,
+        }
+    }
+}
+
+impl CloneConfig {
+    pub fn timeout(&self) -> Option<Duration> {
+        (self.timeout_secs > 0).then(|| Duration::from_secs(self.timeout_secs))
+    }
+}
+
+// 最大サイズを超えたリポジトリの扱い
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OversizePolicy {
+    /// クローンせずにスキップする
+    #[default]
+    Skip,
+    /// 警告を出してクローンする
+    Warn,
+}
+
+// GitHub API で調べたリポジトリのサイズ（KB）
+// api_url は github_app.api_url（GitHub Enterprise Server では https://<ホスト>/api/v3）
+pub async fn repo_size_kb(
+    api_url: &str,
+    owner: &str,
+    repo: &str,
+    token: Option<&str>,
+) -> Result<u64> {
+    let mut request = reqwest::Client::new()
+        .get(format!(
+            "{}/repos/{}/{}",
+            api_url.trim_end_matches('/'),
+            owner,
+            repo
+        ))
+        .header("Accept", "application/vnd.github+json")
+        .header("User-Agent", "azure-credit-burner");
+    if let Some(token) = token {
+        request = request.bearer_auth(token);
+    }
+
+    let response = request.send().await?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_text = response.text().await?;
+        return Err(anyhow!(
+            "GitHub API エラー: ステータス {}, レスポンス: {}",
+            status,
+            error_text
+        ));
+    }
+    let body: Value = response.json().await?;
+    body["size"]
+        .as_u64()
+        .ok_or_else(|| anyhow!("リポジトリ情報に size がありません"))
+}
+
+// Git LFS のポインターファイルの先頭
+const LFS_POINTER_PREFIX: &str = "version https://git-lfs.github.com/spec/";
+
+// Git LFS のポインターファイルか（オブジェクトを取得していないファイル）
+pub fn is_lfs_pointer(content: &str) -> bool {
+    content.starts_with(LFS_POINTER_PREFIX)
+}
+
+// .gitmodules に登録されたサブモジュールのパス
+pub async fn submodule_paths(repo_dir: &Path) -> Vec<String> {
+    if !repo_dir.join(".gitmodules").exists() {
+        return Vec::new();
+    }
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo_dir)
+        .args(["config", "-f", ".gitmodules", "--get-regexp"])
+        .arg(r"^submodule\..*\.path$")
+        .output()
+        .await;
+    match output {
+        Ok(output) if output.status.success() => String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|line| line.split_once(' ').map(|(_, path)| path.to_string()))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+// Git LFS で管理しているファイルがあるか（.gitattributes に filter=lfs がある）
+pub async fn uses_lfs(repo_dir: &Path) -> bool {
+    tokio::fs::read_to_string(repo_dir.join(".gitattributes"))
+        .await
+        .is_ok_and(|attributes| attributes.contains("filter=lfs"))
+}
@@ -0,0 +1,239 @@
+// 会話履歴の圧縮モジュール
+// 議論タイプごとの戦略（全履歴・直近Nターン・要約＋直近のターン）に従って送信する会話を作る
+// 保存する会話履歴はそのまま残し、要約は ResponseData の context_summary に保存する
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::llm::schemas::github_response::RepoInfo;
+use crate::llm::schemas::openai_response::{ChatMessage, ContextSummary};
+use crate::{LlmClient, budget, generation};
+
+// 要約の指示
+const COMPACTION_PROMPT: &str = "あなたはコードレビューの議論の記録係です。\
+以下はリポジトリについての議論の前半です。後続の議論で参照できるよう、\
+指摘された問題点・合意した結論・未解決の論点・言及されたファイルや関数の名前を漏らさず、\
+Markdownの箇条書きで簡潔に要約してください。";
+
+// 送信する会話履歴の戦略（システムプロンプトと最初の質問は常に送信する）
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "strategy", rename_all = "snake_case")]
+pub enum HistoryStrategy {
+    /// すべてのターンを送信する
+    Full,
+    /// 直近の turns ターンだけを送信する
+    Window { turns: usize },
+    /// 要約していないターンが max_turns を超えたら、直近の keep_recent_turns ターン以外を要約する
+    SummaryWindow {
+        max_turns: usize,
+        keep_recent_turns: usize,
+    },
+}
+
+impl Default for HistoryStrategy {
+    fn default() -> Self {
+        HistoryStrategy::SummaryWindow {
+            max_turns: 10,
+            keep_recent_turns: 4,
+        }
+    }
+}
+
+// 会話履歴の圧縮の設定
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct CompactionConfig {
+    /// 既定の戦略
+    pub default: HistoryStrategy,
+    /// 議論タイプごとの戦略
+    pub debate_types: HashMap<String, HistoryStrategy>,
+    /// 要約の最大トークン数
+    pub max_tokens: usize,
+}
+
+impl Default for CompactionConfig {
+    fn default() -> Self {
+        CompactionConfig {
+            default: HistoryStrategy::default(),
+            debate_types: HashMap::new(),
+            max_tokens: 2000,
+        }
+    }
+}
+
+// 回答済みのターン数
+fn completed_turns(messages: &[ChatMessage]) -> usize {
+    messages
+        .iter()
+        .filter(|message| message.role == "assistant")
+        .count()
+}
+
+// システムプロンプト・最初の質問と直近の turns ターンを並べる
+fn window(messages: &[ChatMessage], turns: usize) -> Vec<ChatMessage> {
+    let dropped = completed_turns(messages).saturating_sub(turns);
+    let start = 2 * dropped + 1;
+    if dropped == 0 || messages.len() < 2 || start > messages.len() {
+        return messages.to_vec();
+    }
+
+    let mut context = messages[..2].to_vec();
+    context.extend_from_slice(&messages[start..]);
+    context
+}
+
+// 1つの議論の会話履歴を圧縮する
+// 会話履歴は [system, 最初の質問, 回答1, 質問2, 回答2, ...] の順に並んでいる前提
+pub struct Compactor {
+    strategy: HistoryStrategy,
+    max_tokens: usize,
+    summary: Option<ContextSummary>,
+}
+
+impl Compactor {
+    // 議論タイプの戦略を選ぶ（保存済みの要約があれば引き継ぐ）
+    pub fn new(
+        config: &CompactionConfig,
+        debate_type: &str,
+        summary: Option<ContextSummary>,
+    ) -> Self {
+        Compactor {
+            strategy: config
+                .debate_types
+                .get(debate_type)
+                .unwrap_or(&config.default)
+                .clone(),
+            max_tokens: config.max_tokens,
+            summary,
+        }
+    }
+
+    // 現在の要約
+    pub fn summary(&self) -> Option<ContextSummary> {
+        self.summary.clone()
+    }
+
+    // 要約済みのターン数
+    fn summarized_turns(&self) -> usize {
+        self.summary.as_ref().map_or(0, |summary| summary.turns)
+    }
+
+    // 送信する会話を作成（必要なら古いターンを要約してから）
+    pub async fn prepare(
+        &mut self,
+        client: &LlmClient,
+        repo_info: &RepoInfo,
+        messages: &[ChatMessage],
+    ) -> Vec<ChatMessage> {
+        match self.strategy {
+            HistoryStrategy::Full => messages.to_vec(),
+            HistoryStrategy::Window { turns } => window(messages, turns),
+            HistoryStrategy::SummaryWindow {
+                max_turns,
+                keep_recent_turns,
+            } => {
+                self.compact(client, repo_info, messages, max_turns, keep_recent_turns)
+                    .await;
+                self.context(messages)
+            }
+        }
+    }
+
+    // 要約していないターンが上限を超えたら、直近のターン以外を要約に加える
+    async fn compact(
+        &mut self,
+        client: &LlmClient,
+        repo_info: &RepoInfo,
+        messages: &[ChatMessage],
+        max_turns: usize,
+        keep_recent_turns: usize,
+    ) {
+        let completed = completed_turns(messages);
+        let summarized = self.summarized_turns();
+        if completed.saturating_sub(summarized) <= max_turns {
+            return;
+        }
+
+        // 回答k は messages[2k]、質問k+1 は messages[2k+1]
+        let turns = completed.saturating_sub(keep_recent_turns);
+        let start = (2 * summarized + 1).max(2);
+        let end = 2 * turns + 1;
+        if turns <= summarized || end > messages.len() || messages[end - 1].role != "assistant" {
+            return;
+        }
+
+        let mut transcript = String::new();
+        if let Some(summary) = &self.summary {
+            transcript.push_str(&format!(
+                "## ターン 1〜{} の要約\n{}\n\n",
+                summary.turns, summary.content
+            ));
+        }
+        transcript.push_str(&format!("## ターン {}〜{} の議論\n", summarized + 1, turns));
+        for message in &messages[start..end] {
+            let speaker = if message.role == "assistant" {
+                "回答"
+            } else {
+                "質問"
+            };
+            transcript.push_str(&format!("### {}\n{}\n\n", speaker, message.content));
+        }
+
+        let request = vec![
+            ChatMessage {
+                role: "system".to_string(),
+                content: COMPACTION_PROMPT.to_string(),
+                ..Default::default()
+            },
+            ChatMessage {
+                role: "user".to_string(),
+                content: transcript,
+                ..Default::default()
+            },
+        ];
+        match client
+            .chat_completion(&request, generation::default_model(), self.max_tokens, 0.3)
+            .await
+        {
+            Ok((content, tokens_used)) => {
+                budget::record(repo_info, tokens_used);
+                info!(
+                    "[{}] 🗜️ 会話履歴を圧縮しました: {}/{} - ターン 1〜{} を要約 (トークン数: {})",
+                    client.endpoint.name, repo_info.owner, repo_info.repo, turns, tokens_used
+                );
+                self.summary = Some(ContextSummary { turns, content });
+            }
+            Err(e) => {
+                // 要約できなければ前回の要約のまま送信する
+                warn!(
+                    "[{}] ⚠️ 会話履歴の圧縮エラー: {}/{} - {}",
+                    client.endpoint.name, repo_info.owner, repo_info.repo, e
+                );
+            }
+        }
+    }
+
+    // システムプロンプト・最初の質問・要約・要約していないターンを並べる
+    fn context(&self, messages: &[ChatMessage]) -> Vec<ChatMessage> {
+        let Some(summary) = &self.summary else {
+            return messages.to_vec();
+        };
+        let start = 2 * summary.turns + 1;
+        if messages.len() < 2 || start > messages.len() {
+            return messages.to_vec();
+        }
+
+        let mut context = messages[..2].to_vec();
+        context.push(ChatMessage {
+            role: "assistant".to_string(),
+            content: format!(
+                "（ここまでの議論の要約: ターン 1〜{}）\n{}",
+                summary.turns, summary.content
+            ),
+            ..Default::default()
+        });
+        context.extend_from_slice(&messages[start..]);
+        context
+    }
+}
@@ -0,0 +1,390 @@
+// モデルの A/B 比較モジュール
+// 同じ議論を2つのデプロイメント（モデル）で並行して進め、ターンごとに同じ質問への2つの回答と
+// 判定用モデルの判定を1つのファイルに保存する（議論あたりの消費は2倍になり、モデル比較のデータセットになる）
+
+use anyhow::{Result, anyhow, bail};
+use chrono::prelude::*;
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::{fs, time};
+
+use crate::error::BurnerError;
+use crate::generation::Generation;
+use crate::llm::schemas::openai_response::ChatMessage;
+use crate::scheduler::Scheduler;
+use crate::work_queue::DebateJob;
+use crate::{
+    Config, DebateOutcome, DeepQuestions, GitHubClient, LlmClient, budget, deadline, encryption,
+    generation, get_next_question, ledger, outputs, prepare_debate_messages, runs,
+};
+
+// A/B 比較の設定
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct CompareConfig {
+    /// 議論を2つのモデルで並行して進めるか（ロールプレイ討論とファイル単位のレビューは対象外）
+    pub enabled: bool,
+    /// 比較する2つのデプロイメント名（議論に割り当てたエンドポイントで実行する）
+    pub models: Vec<String>,
+    /// 1つの議論のターン数
+    pub turns: usize,
+    /// 判定に使うデプロイメント名（省略時は議論と同じモデル）
+    pub judge_deployment: Option<String>,
+    /// 判定の最大出力トークン数
+    pub judge_max_tokens: usize,
+}
+
+impl Default for CompareConfig {
+    fn default() -> Self {
+        CompareConfig {
+            enabled: false,
+            models: Vec::new(),
+            turns: 20,
+            judge_deployment: None,
+            judge_max_tokens: 1000,
+        }
+    }
+}
+
+// 重複実行の検出に使うモデルの表記（同じモデルの通常の議論と区別する）
+pub fn label(config: &CompareConfig) -> String {
+    config.models.join(" vs ")
+}
+
+// 比較結果を保存するディレクトリ（リポジトリの出力ディレクトリ以下）
+const COMPARISONS_DIR: &str = "comparisons";
+
+// 1つのモデルの回答
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CompareAnswer {
+    // 回答したモデル（デプロイメント名）
+    pub model: String,
+    pub content: String,
+    pub tokens_used: usize,
+    pub latency_ms: u64,
+}
+
+// 判定用モデルの判定
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Verdict {
+    // "a" / "b" / "tie"
+    pub winner: String,
+    pub reason: String,
+    pub judge_model: String,
+}
+
+// 1ターンの比較結果
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ComparisonRecord {
+    pub repo: String,
+    pub debate_type: String,
+    pub turn: usize,
+    pub timestamp: String,
+    pub endpoint: String,
+    pub category: String,
+    pub question: String,
+    pub a: CompareAnswer,
+    pub b: CompareAnswer,
+    // 判定に失敗した場合は None
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verdict: Option<Verdict>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run_id: Option<String>,
+    pub task_id: String,
+}
+
+// 判定の指示
+const VERDICT_PROMPT: &str = "あなたはコードレビューの品質を評価する審査員です。\
+以下の質問に対する2つの回答（回答A・回答B）を比べ、具体性（コードや設計への言及）、技術的な正確さ、実用的な提案の有無の観点から、\
+より優れた回答を選んでください。差がほとんどない場合は引き分けとしてください。\
+回答の順番や長さで判断せず、判定の根拠を1〜2文で添えてください。";
+
+// 構造化出力の JSON スキーマ（strict モードではすべてのプロパティを必須にする）
+fn schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "winner": { "type": "string", "enum": ["a", "b", "tie"] },
+            "reason": { "type": "string" },
+        },
+        "required": ["winner", "reason"],
+        "additionalProperties": false,
+    })
+}
+
+#[derive(Deserialize)]
+struct VerdictResponse {
+    winner: String,
+    reason: String,
+}
+
+// 2つの回答を判定用モデルに比べさせる
+async fn judge(
+    client: &LlmClient,
+    config: &CompareConfig,
+    question: &str,
+    a: &str,
+    b: &str,
+) -> Result<Verdict> {
+    let judge_model = config
+        .judge_deployment
+        .clone()
+        .unwrap_or_else(|| generation::default_model().to_string());
+    let messages = vec![
+        ChatMessage {
+            role: "system".to_string(),
+            content: VERDICT_PROMPT.to_string(),
+            ..Default::default()
+        },
+        ChatMessage {
+            role: "user".to_string(),
+            content: format!(
+                "【質問】\n{}\n\n【回答A】\n{}\n\n【回答B】\n{}",
+                question, a, b
+            ),
+            ..Default::default()
+        },
+    ];
+    let (response, _) = client
+        .structured_completion(
+            &messages,
+            &judge_model,
+            config.judge_max_tokens,
+            "verdict",
+            schema(),
+        )
+        .await?;
+    let parsed: VerdictResponse = serde_json::from_str(&response)
+        .map_err(|e| anyhow!("判定結果のJSONを解析できません: {} - {}", e, response))?;
+    Ok(Verdict {
+        winner: parsed.winner,
+        reason: parsed.reason,
+        judge_model,
+    })
+}
+
+// 1つのモデルで回答させる（フォールバックせず、指定したモデルだけで回答させる）
+async fn answer(
+    client: &LlmClient,
+    config: &Config,
+    messages: &[ChatMessage],
+    generation: &Generation,
+) -> Result<CompareAnswer> {
+    let started = Instant::now();
+    let limit = config.timeouts.turn_for(generation.reasoning);
+    let completion = client.chat_completion_with_tools(messages, generation, &[]);
+    let (message, tokens_used) = deadline::with_timeout(limit, "ターン", completion).await?;
+    Ok(CompareAnswer {
+        model: generation.model.clone(),
+        content: message.content,
+        tokens_used,
+        latency_ms: started.elapsed().as_millis() as u64,
+    })
+}
+
+// 比較結果を保存する
+async fn save_record(base_dir: &str, job: &DebateJob, record: &ComparisonRecord) -> Result<String> {
+    let repo_info = &job.repo_info;
+    let repo_dir = outputs::repo_dirs(base_dir, &repo_info.owner, &repo_info.repo).remove(0);
+    let mut path = PathBuf::from(repo_dir);
+    path.push(COMPARISONS_DIR);
+    fs::create_dir_all(&path).await?;
+    path.push(format!(
+        "{}_{}_turn{}.json",
+        outputs::path_segment(&record.debate_type),
+        record.turn,
+        Utc::now().format("%Y%m%d_%H%M%S")
+    ));
+    let json_data = encryption::seal(serde_json::to_string_pretty(record)?)?;
+    fs::write(&path, json_data).await?;
+    Ok(outputs::slash_path(&path))
+}
+
+// 2つのモデルで議論を並行して進め、ターンごとに判定する
+pub async fn compare_runner(
+    github_client: &GitHubClient,
+    openai_client: &LlmClient,
+    job: &DebateJob,
+    endpoint_index: usize,
+    config: &Arc<Config>,
+    scheduler: &Scheduler,
+) -> Result<DebateOutcome> {
+    let endpoint = &openai_client.endpoint;
+    let repo_info = &job.repo_info;
+    let compare = &config.compare;
+    let [model_a, model_b] = compare.models.as_slice() else {
+        bail!(
+            "比較するモデル（compare.models）は2つ指定してください: {:?}",
+            compare.models
+        );
+    };
+
+    info!(
+        "[{}] ⚖️ A/B 比較開始: {}/{} ({}) - A: {} / B: {}",
+        endpoint.name, repo_info.owner, repo_info.repo, job.debate_type, model_a, model_b
+    );
+
+    // 議論タイプの最大トークン数・温度などは同じにし、モデルだけを変える
+    let resolved = generation::resolve(endpoint, &job.debate_type);
+    let side = |model: &String| Generation {
+        model: model.clone(),
+        reasoning: generation::is_reasoning(endpoint, model),
+        fallback_models: Vec::new(),
+        ..resolved.clone()
+    };
+    let (generation_a, generation_b) = (side(model_a), side(model_b));
+
+    // 途中から再開せず、同じ初期メッセージから2つの会話を始める
+    let mut messages_a = prepare_debate_messages(
+        openai_client,
+        github_client,
+        repo_info,
+        &job.debate_type,
+        config,
+    )
+    .await?;
+    let mut messages_b = messages_a.clone();
+    let deep_questions = DeepQuestions::for_debate(github_client, repo_info, &job.task_id).await;
+    let mut category = "概要".to_string();
+
+    let mut wins = [0usize; 3];
+    let mut consecutive_errors = 0;
+    let mut turn = 1;
+    while turn <= compare.turns {
+        if deadline::reached() || ledger::ceiling_reached() {
+            return Ok(DebateOutcome::Interrupted);
+        }
+        if budget::exhausted(repo_info) {
+            return Ok(DebateOutcome::OverBudget);
+        }
+
+        info!(
+            "[{}] ⚖️ A/B 比較実行中: {}/{} ({}) - ターン {}",
+            endpoint.name, repo_info.owner, repo_info.repo, job.debate_type, turn
+        );
+        openai_client.reset_metrics();
+        let started = Instant::now();
+        let (a, b) = tokio::join!(
+            answer(openai_client, config, &messages_a, &generation_a),
+            answer(openai_client, config, &messages_b, &generation_b),
+        );
+        let (a, b) = match (a, b) {
+            (Ok(a), Ok(b)) => (a, b),
+            (Err(e), _) | (_, Err(e)) => {
+                error!(
+                    "[{}] OpenAI API エラー: {}/{} - ターン {} - {}",
+                    endpoint.name, repo_info.owner, repo_info.repo, turn, e
+                );
+                if BurnerError::of(&e).is_some_and(|error| !error.is_retryable()) {
+                    return Err(e);
+                }
+                consecutive_errors += 1;
+                if consecutive_errors >= 3 {
+                    bail!("OpenAI API 呼び出しに複数回失敗しました。終了します。");
+                }
+                time::sleep(Duration::from_secs(1)).await;
+                continue;
+            }
+        };
+        consecutive_errors = 0;
+        let tokens_used = a.tokens_used + b.tokens_used;
+        scheduler.record(endpoint_index, tokens_used, started.elapsed());
+        budget::record(repo_info, tokens_used);
+
+        let question = messages_a
+            .last()
+            .map(|message| message.content.clone())
+            .unwrap_or_default();
+        let verdict = match judge(openai_client, compare, &question, &a.content, &b.content).await {
+            Ok(verdict) => {
+                let index = match verdict.winner.as_str() {
+                    "a" => 0,
+                    "b" => 1,
+                    _ => 2,
+                };
+                wins[index] += 1;
+                Some(verdict)
+            }
+            Err(e) => {
+                warn!(
+                    "[{}] ⚠️ 判定エラー: {}/{} - ターン {} - {}",
+                    endpoint.name, repo_info.owner, repo_info.repo, turn, e
+                );
+                None
+            }
+        };
+
+        messages_a.push(ChatMessage {
+            role: "assistant".to_string(),
+            content: a.content.clone(),
+            ..Default::default()
+        });
+        messages_b.push(ChatMessage {
+            role: "assistant".to_string(),
+            content: b.content.clone(),
+            ..Default::default()
+        });
+
+        let record = ComparisonRecord {
+            repo: format!("{}/{}", repo_info.owner, repo_info.repo),
+            debate_type: job.debate_type.clone(),
+            turn,
+            timestamp: Utc::now().to_rfc3339(),
+            endpoint: endpoint.name.clone(),
+            category: category.clone(),
+            question,
+            a,
+            b,
+            verdict,
+            run_id: runs::current_id(),
+            task_id: job.task_id.clone(),
+        };
+        match save_record(&config.output_dir, job, &record).await {
+            Ok(filename) => info!(
+                "[{}] 保存完了: {} (トークン数: {}, 判定: {})",
+                endpoint.name,
+                filename,
+                tokens_used,
+                record
+                    .verdict
+                    .as_ref()
+                    .map_or("なし", |verdict| verdict.winner.as_str())
+            ),
+            Err(e) => error!(
+                "[{}] 保存エラー: {}/{} - ターン {} - {}",
+                endpoint.name, repo_info.owner, repo_info.repo, turn, e
+            ),
+        }
+
+        // 次の質問は2つの会話で同じにする
+        let (next_category, next_question) = get_next_question(repo_info, &deep_questions, turn);
+        category = next_category;
+        for messages in [&mut messages_a, &mut messages_b] {
+            messages.push(ChatMessage {
+                role: "user".to_string(),
+                content: next_question.clone(),
+                ..Default::default()
+            });
+        }
+        turn += 1;
+    }
+
+    info!(
+        "[{}] ⚖️ A/B 比較完了: {}/{} ({}) - {} の勝ち {} / {} の勝ち {} / 引き分け {}",
+        endpoint.name,
+        repo_info.owner,
+        repo_info.repo,
+        job.debate_type,
+        model_a,
+        wins[0],
+        model_b,
+        wins[1],
+        wins[2]
+    );
+
+    Ok(DebateOutcome::Completed)
+}
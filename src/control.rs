@@ -0,0 +1,316 @@
+// 実行中の操作モジュール
+// 長時間の実行やデーモンモードで、ローカルの Unix ソケット（Windows では名前付きパイプ）からコマンドを受け付け、
+// 再起動せずに同時実行数の変更・エンドポイントの一時停止・リポジトリのスキップを行う
+// コマンドは1行に1つで、結果を1行で返す（例: `set concurrency 16`、`pause endpoint west-us`、`skip repo org/foo`）
+
+use anyhow::{Result, anyhow, bail};
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::task::JoinHandle;
+
+use crate::llm::schemas::{github_response::RepoInfo, openai_response::Endpoint};
+
+// 実行中の操作の設定
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ControlConfig {
+    /// コマンドを受け付けるか
+    pub enabled: bool,
+    /// Unix ソケットのパス（省略時は <output_dir>/control.sock）。Windows では名前付きパイプの名前（省略時は \\.\pipe\azure-credit-burner）
+    pub path: Option<String>,
+    /// set concurrency で指定できる同時実行数の上限（起動時にこの数のワーカーを用意する。省略時は concurrency）
+    pub max_concurrency: Option<usize>,
+}
+
+impl ControlConfig {
+    // コマンドを受け付けるソケット（名前付きパイプ）のパス
+    pub fn path(&self, output_dir: &str) -> String {
+        match &self.path {
+            Some(path) => path.clone(),
+            None if cfg!(windows) => r"\\.\pipe\azure-credit-burner".to_string(),
+            None => format!("{}/control.sock", output_dir),
+        }
+    }
+}
+
+// 同時に実行する議論の数の上限（0 なら制限しない）と、実行中の議論の数
+static LIMIT: AtomicUsize = AtomicUsize::new(0);
+static RUNNING: AtomicUsize = AtomicUsize::new(0);
+
+// 起動時に用意したワーカーの数（set concurrency で指定できる上限）
+static WORKERS: AtomicUsize = AtomicUsize::new(0);
+
+// 一時停止できるエンドポイント、一時停止したエンドポイント、スキップするリポジトリ（owner/repo、小文字）
+static ENDPOINTS: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+static PAUSED: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+static SKIPPED: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+// 起動するワーカーの数（set concurrency で増やせるよう max_concurrency まで用意する）
+pub fn worker_count(config: &ControlConfig, concurrency: usize) -> usize {
+    match config.max_concurrency {
+        Some(max_concurrency) if config.enabled => max_concurrency.max(concurrency),
+        _ => concurrency,
+    }
+}
+
+// 議論の実行枠（破棄すると枠を返す）
+pub struct Slot;
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        RUNNING.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+// 同時実行数の上限に達していなければ議論の実行枠を取る
+pub fn slot() -> Option<Slot> {
+    let limit = LIMIT.load(Ordering::SeqCst);
+    RUNNING
+        .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |running| {
+            (limit == 0 || running < limit).then_some(running + 1)
+        })
+        .ok()
+        .map(|_| Slot)
+}
+
+// 実行中に追加されたエンドポイントも一時停止できるようにする
+pub fn add_endpoint(endpoint: &str) {
+    ENDPOINTS.lock().unwrap().insert(endpoint.to_string());
+}
+
+// 一時停止したエンドポイントか（ワーカーは再開されるまで新しい議論を取らない）
+pub fn is_paused(endpoint: &str) -> bool {
+    PAUSED.lock().unwrap().contains(endpoint)
+}
+
+// スキップするリポジトリか（まだ始まっていない議論は実行せず、次回の実行に回す）
+pub fn is_skipped(repo_info: &RepoInfo) -> bool {
+    let repo = format!("{}/{}", repo_info.owner, repo_info.repo).to_lowercase();
+    SKIPPED.lock().unwrap().contains(&repo)
+}
+
+// 1行のコマンドを実行し、結果を返す
+fn execute(line: &str) -> Result<String> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    match words.as_slice() {
+        ["set", "concurrency", value] => {
+            let value: usize = value
+                .parse()
+                .map_err(|_| anyhow!("同時実行数は整数で指定してください: {}", value))?;
+            if value == 0 {
+                bail!("同時実行数は1以上で指定してください");
+            }
+            let workers = WORKERS.load(Ordering::SeqCst);
+            if value > workers {
+                bail!(
+                    "同時実行数は起動時に用意したワーカーの数（{}）以下で指定してください（増やすには control.max_concurrency を設定して再起動してください）",
+                    workers
+                );
+            }
+            LIMIT.store(value, Ordering::SeqCst);
+            info!("🎛️ 同時実行数を変更しました: {}", value);
+            Ok(format!("concurrency = {}", value))
+        }
+        ["pause", "endpoint", name] => {
+            if !ENDPOINTS.lock().unwrap().contains(*name) {
+                bail!("エンドポイントが見つかりません: {}", name);
+            }
+            PAUSED.lock().unwrap().insert(name.to_string());
+            info!(
+                "[{}] ⏸️ エンドポイントを一時停止しました（実行中の議論は最後まで進めます）",
+                name
+            );
+            Ok(format!("paused {}", name))
+        }
+        ["resume", "endpoint", name] => {
+            if !PAUSED.lock().unwrap().remove(*name) {
+                bail!("一時停止していないエンドポイントです: {}", name);
+            }
+            info!("[{}] ▶️ エンドポイントを再開しました", name);
+            Ok(format!("resumed {}", name))
+        }
+        ["skip", "repo", repo] => {
+            if !repo.contains('/') {
+                bail!("リポジトリは owner/repo の形式で指定してください: {}", repo);
+            }
+            SKIPPED.lock().unwrap().insert(repo.to_lowercase());
+            info!(
+                "⏭️ リポジトリをスキップします（まだ始まっていない議論は次回の実行に回します）: {}",
+                repo
+            );
+            Ok(format!("skipped {}", repo))
+        }
+        ["unskip", "repo", repo] => {
+            if !SKIPPED.lock().unwrap().remove(&repo.to_lowercase()) {
+                bail!("スキップしていないリポジトリです: {}", repo);
+            }
+            info!("⏭️ リポジトリのスキップを取り消しました: {}", repo);
+            Ok(format!("unskipped {}", repo))
+        }
+        ["status"] => {
+            let limit = match LIMIT.load(Ordering::SeqCst) {
+                0 => "unlimited".to_string(),
+                limit => limit.to_string(),
+            };
+            let join = |set: &Mutex<BTreeSet<String>>| {
+                set.lock()
+                    .unwrap()
+                    .iter()
+                    .cloned()
+                    .collect::<Vec<_>>()
+                    .join(",")
+            };
+            Ok(format!(
+                "concurrency = {}, workers = {}, running = {}, paused = [{}], skipped = [{}]",
+                limit,
+                WORKERS.load(Ordering::SeqCst),
+                RUNNING.load(Ordering::SeqCst),
+                join(&PAUSED),
+                join(&SKIPPED)
+            ))
+        }
+        _ => bail!(
+            "不明なコマンドです: {}（使えるコマンド: set concurrency <数>, pause endpoint <名前>, resume endpoint <名前>, skip repo <owner/repo>, unskip repo <owner/repo>, status）",
+            line.trim()
+        ),
+    }
+}
+
+// 1つの接続のコマンドを処理する
+async fn handle<S: AsyncRead + AsyncWrite + Unpin>(stream: S) {
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut lines = BufReader::new(reader).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match execute(&line) {
+            Ok(message) => format!("ok: {}\n", message),
+            Err(e) => {
+                warn!("⚠️ 操作コマンドのエラー: {}", e);
+                format!("error: {}\n", e)
+            }
+        };
+        if writer.write_all(response.as_bytes()).await.is_err() {
+            break;
+        }
+    }
+}
+
+// コマンドの受け付けを始める（無効なら None）
+pub fn start(
+    config: &ControlConfig,
+    output_dir: &str,
+    concurrency: usize,
+    endpoints: &[Endpoint],
+) -> Option<JoinHandle<()>> {
+    if !config.enabled {
+        return None;
+    }
+    LIMIT.store(concurrency, Ordering::SeqCst);
+    WORKERS.store(worker_count(config, concurrency), Ordering::SeqCst);
+    for endpoint in endpoints {
+        add_endpoint(&endpoint.name);
+    }
+    let path = config.path(output_dir);
+    match listen(path.clone()) {
+        Ok(task) => {
+            info!("🎛️ 操作コマンドを受け付けます: {}", path);
+            Some(task)
+        }
+        Err(e) => {
+            error!(
+                "❌ 操作コマンドの受け付けを開始できません: {} - {}",
+                path, e
+            );
+            None
+        }
+    }
+}
+
+// コマンドの受け付けを止め、ソケットファイルを片付ける
+pub fn stop(task: JoinHandle<()>, config: &ControlConfig, output_dir: &str) {
+    task.abort();
+    if cfg!(unix) {
+        let _ = std::fs::remove_file(config.path(output_dir));
+    }
+}
+
+#[cfg(unix)]
+fn listen(path: String) -> Result<JoinHandle<()>> {
+    // 前回の実行で残ったソケットファイルは作り直す
+    let _ = std::fs::remove_file(&path);
+    let listener = tokio::net::UnixListener::bind(&path)?;
+    Ok(tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    tokio::spawn(handle(stream));
+                }
+                Err(e) => error!("❌ 操作コマンドの接続エラー: {}", e),
+            }
+        }
+    }))
+}
+
+#[cfg(windows)]
+fn listen(path: String) -> Result<JoinHandle<()>> {
+    use tokio::net::windows::named_pipe::ServerOptions;
+
+    let mut server = ServerOptions::new()
+        .first_pipe_instance(true)
+        .create(&path)?;
+    Ok(tokio::spawn(async move {
+        loop {
+            if let Err(e) = server.connect().await {
+                error!("❌ 操作コマンドの接続エラー: {}", e);
+                continue;
+            }
+            // 次の接続を待つインスタンスを作ってから、接続済みのインスタンスを処理する
+            let connected = match ServerOptions::new().create(&path) {
+                Ok(next) => std::mem::replace(&mut server, next),
+                Err(e) => {
+                    error!("❌ 名前付きパイプを作成できません: {} - {}", path, e);
+                    break;
+                }
+            };
+            tokio::spawn(handle(connected));
+        }
+    }))
+}
+
+// 実行中のプロセスにコマンドを送り、結果を返す
+pub async fn send(config: &ControlConfig, output_dir: &str, command: &str) -> Result<String> {
+    let path = config.path(output_dir);
+    let stream = connect(&path)
+        .await
+        .map_err(|e| anyhow!("操作コマンドのソケットに接続できません: {} - {}", path, e))?;
+    let (reader, mut writer) = tokio::io::split(stream);
+    writer
+        .write_all(format!("{}\n", command.trim()).as_bytes())
+        .await?;
+    let mut response = String::new();
+    BufReader::new(reader).read_line(&mut response).await?;
+    let response = response.trim_end().to_string();
+    match response.strip_prefix("error: ") {
+        Some(message) => Err(anyhow!("{}", message)),
+        None => Ok(response
+            .strip_prefix("ok: ")
+            .unwrap_or(&response)
+            .to_string()),
+    }
+}
+
+#[cfg(unix)]
+async fn connect(path: &str) -> std::io::Result<tokio::net::UnixStream> {
+    tokio::net::UnixStream::connect(path).await
+}
+
+#[cfg(windows)]
+async fn connect(path: &str) -> std::io::Result<tokio::net::windows::named_pipe::NamedPipeClient> {
+    tokio::net::windows::named_pipe::ClientOptions::new().open(path)
+}
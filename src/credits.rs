@@ -0,0 +1,66 @@
+// クレジット枯渇モジュール
+// 使用上限に達した・無効化されたサブスクリプションのエンドポイントを記録し、
+// すべてのエンドポイントが使えなくなったら何時間もリトライを続けずに実行全体を止める
+
+use log::{error, warn};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use crate::llm::schemas::openai_response::Endpoint;
+
+// クレジットが枯渇したエンドポイントとそのエラー
+static EXHAUSTED: Mutex<BTreeMap<String, String>> = Mutex::new(BTreeMap::new());
+
+// エンドポイントのクレジット枯渇を記録（初回だけログに出力）
+pub fn record_exhausted(endpoint: &str, message: &str) {
+    let mut exhausted = EXHAUSTED.lock().unwrap();
+    if exhausted
+        .insert(endpoint.to_string(), message.to_string())
+        .is_none()
+    {
+        warn!(
+            "[{}] 💸 クレジットが枯渇したため、このエンドポイントでは新しい議論を開始しません: {}",
+            endpoint, message
+        );
+    }
+}
+
+// エンドポイントのクレジットが枯渇しているか
+pub fn is_exhausted(endpoint: &str) -> bool {
+    EXHAUSTED.lock().unwrap().contains_key(endpoint)
+}
+
+// すべてのエンドポイントのクレジットが枯渇したか
+fn all_exhausted(exhausted: &BTreeMap<String, String>, endpoints: &[Endpoint]) -> bool {
+    !endpoints.is_empty()
+        && endpoints
+            .iter()
+            .all(|endpoint| exhausted.contains_key(&endpoint.name))
+}
+
+// クレジットが枯渇したエンドポイントをログに出力
+pub fn log_summary(endpoints: &[Endpoint]) {
+    let exhausted = EXHAUSTED.lock().unwrap();
+    if exhausted.is_empty() {
+        return;
+    }
+
+    if all_exhausted(&exhausted, endpoints) {
+        error!(
+            "💸 すべてのエンドポイント ({} 件) でクレジットが枯渇したため実行を停止しました",
+            endpoints.len()
+        );
+    } else {
+        warn!(
+            "💸 クレジットが枯渇したエンドポイント: {} / {} 件",
+            exhausted.len(),
+            endpoints.len()
+        );
+    }
+    for (endpoint, message) in exhausted.iter() {
+        warn!("   {} - {}", endpoint, message);
+    }
+    warn!(
+        "💸 未完了の議論はジャーナルに残っています。クレジットを追加するかサブスクリプションを有効にしてから再実行すると続きから再開します"
+    );
+}
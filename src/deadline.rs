@@ -0,0 +1,169 @@
+// 実行期限モジュール
+// --max-duration で指定した時間が近づいたら新しいターンの開始を止め、実行中の処理と後処理を期限内に終わらせる
+// ターンごと・議論ごとの時間制限で、応答が返ってこない接続が同時実行枠を占有し続けないようにする
+
+use anyhow::{Result, anyhow};
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::error::BurnerError;
+
+// 実行中のターンと後処理のために残しておく時間（秒、指定時間の1/10が上限）
+const DEADLINE_MARGIN_SECS: u64 = 600;
+
+struct Deadline {
+    // 新しいターンを開始しない時刻
+    stop_at: Instant,
+    // 期限のために中断した議論の数
+    interrupted: AtomicUsize,
+}
+
+static DEADLINE: OnceLock<Deadline> = OnceLock::new();
+
+// ターン・議論ごとの時間制限の設定
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct TimeoutConfig {
+    /// 1ターン（APIの呼び出しとリトライ）の時間制限（秒、0で無制限）
+    pub turn_secs: u64,
+    /// 1議論全体の時間制限（秒、0で無制限）
+    pub debate_secs: u64,
+    /// 推論モデル（o1 / o3 など）の1ターンの時間制限（秒、0で無制限）。推論に時間がかかるため turn_secs より長くする
+    pub reasoning_turn_secs: u64,
+}
+
+impl Default for TimeoutConfig {
+    fn default() -> Self {
+        TimeoutConfig {
+            turn_secs: 600,
+            debate_secs: 4 * 3600,
+            reasoning_turn_secs: 1800,
+        }
+    }
+}
+
+impl TimeoutConfig {
+    pub fn turn(&self) -> Option<Duration> {
+        (self.turn_secs > 0).then(|| Duration::from_secs(self.turn_secs))
+    }
+
+    // モデルに応じた1ターンの時間制限（推論モデルは reasoning_turn_secs）
+    pub fn turn_for(&self, reasoning: bool) -> Option<Duration> {
+        if !reasoning {
+            return self.turn();
+        }
+        (self.reasoning_turn_secs > 0).then(|| Duration::from_secs(self.reasoning_turn_secs))
+    }
+
+    pub fn debate(&self) -> Option<Duration> {
+        (self.debate_secs > 0).then(|| Duration::from_secs(self.debate_secs))
+    }
+}
+
+// 時間制限付きで実行し、制限を超えたら打ち切ってエラーにする（None なら制限なし）
+pub async fn with_timeout<T>(
+    limit: Option<Duration>,
+    what: &str,
+    future: impl Future<Output = Result<T>>,
+) -> Result<T> {
+    let Some(limit) = limit else {
+        return future.await;
+    };
+    match tokio::time::timeout(limit, future).await {
+        Ok(result) => result,
+        Err(_) => Err(BurnerError::TimedOut {
+            what: what.to_string(),
+            limit,
+        }
+        .into()),
+    }
+}
+
+// "72h" "1h30m" "2d" "90s" 形式の時間を解析（単位は d / h / m / s）
+pub fn parse_duration(value: &str) -> Result<Duration> {
+    let mut total = 0u64;
+    let mut number = String::new();
+    for c in value.trim().chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let unit = match c {
+            'd' => 86400,
+            'h' => 3600,
+            'm' => 60,
+            's' => 1,
+            _ => return Err(anyhow!("時間の単位が正しくありません: {}", value)),
+        };
+        let amount: u64 = number
+            .parse()
+            .map_err(|_| anyhow!("時間の形式が正しくありません: {}", value))?;
+        // 桁の大きすぎる指定はあふれる前にエラーにする
+        total = amount
+            .checked_mul(unit)
+            .and_then(|seconds| total.checked_add(seconds))
+            .ok_or_else(|| anyhow!("時間が長すぎます: {}", value))?;
+        number.clear();
+    }
+    if !number.is_empty() || total == 0 {
+        return Err(anyhow!(
+            "時間の形式が正しくありません（例: 72h, 1h30m）: {}",
+            value
+        ));
+    }
+    Ok(Duration::from_secs(total))
+}
+
+// 実行期限を設定（設定しなければ期限なし）
+pub fn start(max_duration: Duration) {
+    let margin = Duration::from_secs(DEADLINE_MARGIN_SECS).min(max_duration / 10);
+    let Some(stop_at) = Instant::now().checked_add(max_duration - margin) else {
+        info!("⏳ 実行期限が長すぎるため、期限なしで実行します");
+        return;
+    };
+    if DEADLINE
+        .set(Deadline {
+            stop_at,
+            interrupted: AtomicUsize::new(0),
+        })
+        .is_ok()
+    {
+        info!(
+            "⏳ 実行期限: {} 秒後（残り {} 秒を切ったら新しいターンを開始しません）",
+            max_duration.as_secs(),
+            margin.as_secs()
+        );
+    }
+}
+
+// 新しいターンを開始すべきでない時刻を過ぎたか
+pub fn reached() -> bool {
+    DEADLINE
+        .get()
+        .is_some_and(|deadline| Instant::now() >= deadline.stop_at)
+}
+
+// 期限のために議論を中断したことを記録
+pub fn note_interrupted() {
+    if let Some(deadline) = DEADLINE.get() {
+        deadline.interrupted.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+// 期限のために中断した議論の数をログに出力
+pub fn log_summary() {
+    let Some(deadline) = DEADLINE.get() else {
+        return;
+    };
+    let interrupted = deadline.interrupted.load(Ordering::Relaxed);
+    if interrupted > 0 {
+        info!(
+            "⏳ 実行期限のため {} 件の議論を中断しました（次回の実行で続きから再開します）",
+            interrupted
+        );
+    }
+}
@@ -0,0 +1,185 @@
+// 応答の重複検出モジュール
+// アシスタントの応答を正規化してハッシュを求め、同じリポジトリの議論で以前に保存した応答と同じなら
+// 重複として記録する（設定によっては保存しない）。長い実行で繰り返される定型文の保存とレビューの手間を減らす
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Mutex, OnceLock};
+
+use crate::llm::schemas::{github_response::RepoInfo, openai_response::ResponseData};
+use crate::{outputs, runs};
+
+// 重複検出の設定
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct DedupeConfig {
+    /// 応答のハッシュを記録して重複を検出するか
+    pub enabled: bool,
+    /// 重複した応答を保存しないか（無効なら duplicate_of を記録して保存する）
+    pub skip_duplicates: bool,
+    /// 重複を検出する応答の最小文字数（短い応答は偶然一致しやすいため対象外）
+    pub min_chars: usize,
+}
+
+impl Default for DedupeConfig {
+    fn default() -> Self {
+        DedupeConfig {
+            enabled: false,
+            skip_duplicates: false,
+            min_chars: 40,
+        }
+    }
+}
+
+// リポジトリごとの重複の件数
+#[derive(Default)]
+struct DuplicateCount {
+    flagged: usize,
+    skipped: usize,
+    tokens: usize,
+}
+
+static DEDUPE: OnceLock<DedupeConfig> = OnceLock::new();
+// リポジトリごとの、応答のハッシュと最初に保存したファイル
+static SEEN: Mutex<BTreeMap<String, HashMap<String, String>>> = Mutex::new(BTreeMap::new());
+static COUNTS: Mutex<BTreeMap<String, DuplicateCount>> = Mutex::new(BTreeMap::new());
+
+// 重複検出を設定
+pub fn configure(config: &DedupeConfig) {
+    let _ = DEDUPE.set(config.clone());
+}
+
+fn config() -> &'static DedupeConfig {
+    DEDUPE.get_or_init(DedupeConfig::default)
+}
+
+// 空白・記号・数字の違いだけの応答を同じとみなすため、文字だけを小文字にして残す
+fn normalize(content: &str) -> String {
+    content
+        .chars()
+        .filter(|c| c.is_alphabetic())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+// 応答のハッシュ（正規化した内容の SHA-256）
+pub fn content_hash(content: &str) -> String {
+    hex::encode(Sha256::digest(normalize(content).as_bytes()))
+}
+
+// 重複を検出する応答のハッシュ（短すぎる応答は None）
+pub fn duplicate_key(content: &str) -> Option<String> {
+    if content.trim().chars().count() < config().min_chars {
+        return None;
+    }
+    Some(content_hash(content))
+}
+
+// ターンのアシスタントの応答（最後のメッセージ）
+pub fn assistant_content(response_data: &ResponseData) -> Option<&str> {
+    response_data
+        .messages
+        .last()
+        .filter(|message| message.role == "assistant")
+        .map(|message| message.content.as_str())
+}
+
+// リポジトリの保存済みの応答のハッシュを読み込む（ドライランと本番の結果は混ぜない）
+fn load_seen(base_dir: &str, repo_info: &RepoInfo) -> HashMap<String, String> {
+    let repo = format!("{}/{}", repo_info.owner, repo_info.repo);
+    let dry_run = runs::is_dry_run_id(runs::current_id().as_deref());
+    let mut seen = HashMap::new();
+    let dirs = outputs::repo_dirs(base_dir, &repo_info.owner, &repo_info.repo);
+    let responses = dirs.iter().flat_map(|dir| outputs::load_responses(dir));
+    for (path, response) in responses {
+        if response.repo != repo || runs::is_dry_run_id(response.run_id.as_deref()) != dry_run {
+            continue;
+        }
+        let Some(hash) = response
+            .content_hash
+            .clone()
+            .or_else(|| assistant_content(&response).and_then(duplicate_key))
+        else {
+            continue;
+        };
+        seen.entry(hash)
+            .or_insert_with(|| path.to_string_lossy().to_string());
+    }
+    seen
+}
+
+// 保存する前に応答のハッシュを記録し、重複なら元のファイルを記録する（保存しない場合は true）
+pub fn check(base_dir: &str, repo_info: &RepoInfo, response_data: &mut ResponseData) -> bool {
+    let config = config();
+    if !config.enabled {
+        return false;
+    }
+    let Some(hash) = assistant_content(response_data).and_then(duplicate_key) else {
+        return false;
+    };
+
+    let original = {
+        let mut seen = SEEN.lock().unwrap();
+        seen.entry(response_data.repo.clone())
+            .or_insert_with(|| load_seen(base_dir, repo_info))
+            .get(&hash)
+            .cloned()
+    };
+    response_data.content_hash = Some(hash);
+    let Some(original) = original else {
+        return false;
+    };
+
+    let skip = config.skip_duplicates;
+    {
+        let mut counts = COUNTS.lock().unwrap();
+        let count = counts.entry(response_data.repo.clone()).or_default();
+        if skip {
+            count.skipped += 1;
+        } else {
+            count.flagged += 1;
+        }
+        count.tokens += response_data.tokens_used;
+    }
+    warn!(
+        "[{}] 🪞 以前の応答と重複しています{}: {} ({}) - ターン {} - {}",
+        response_data.endpoint,
+        if skip { "（保存しません）" } else { "" },
+        response_data.repo,
+        response_data.debate_type,
+        response_data.turn,
+        original
+    );
+    response_data.duplicate_of = Some(original);
+    skip
+}
+
+// 保存した応答のハッシュを記録する（重複した応答は最初のファイルを残す）
+pub fn remember(response_data: &ResponseData, filename: &str) {
+    let Some(hash) = &response_data.content_hash else {
+        return;
+    };
+    SEEN.lock()
+        .unwrap()
+        .entry(response_data.repo.clone())
+        .or_default()
+        .entry(hash.clone())
+        .or_insert_with(|| filename.to_string());
+}
+
+// 実行全体の重複の件数をログに出力
+pub fn log_summary() {
+    let counts = COUNTS.lock().unwrap();
+    for (repo, count) in counts.iter() {
+        info!(
+            "🪞 {}: 重複した応答 {} 件（記録 {} 件, 保存せず {} 件, {} トークン）",
+            repo,
+            count.flagged + count.skipped,
+            count.flagged,
+            count.skipped,
+            count.tokens
+        );
+    }
+}
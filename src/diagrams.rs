@@ -0,0 +1,167 @@
+// アーキテクチャ図の生成モジュール
+// モデルにリポジトリのアーキテクチャを説明させ、その説明から画像生成APIで図を作成して保存する
+
+use anyhow::Result;
+use chrono::prelude::*;
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::fs;
+
+use crate::llm::schemas::{
+    github_response::RepoInfo,
+    openai_response::{ChatMessage, Endpoint},
+};
+use crate::{
+    GitHubClient, LlmClient, budget, deadline, encryption, generate_repo_debate_prompt, generation,
+    readme, stats,
+};
+
+// アーキテクチャ図生成の設定
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct DiagramConfig {
+    /// アーキテクチャ図の生成を有効にするか
+    pub enabled: bool,
+    /// 画像生成モデルのデプロイメント名
+    pub deployment: String,
+    /// 画像サイズ
+    pub size: String,
+    /// リポジトリあたりの生成枚数
+    pub count: usize,
+}
+
+impl Default for DiagramConfig {
+    fn default() -> Self {
+        DiagramConfig {
+            enabled: false,
+            deployment: "dall-e-3".to_string(),
+            size: "1792x1024".to_string(),
+            count: 1,
+        }
+    }
+}
+
+// 画像生成プロンプトの最大文字数（DALL-E 3の上限は4000文字）
+const MAX_IMAGE_PROMPT_CHARS: usize = 3500;
+
+// 図の説明を依頼するメッセージ
+const DESCRIBE_REQUEST: &str = "このリポジトリのアーキテクチャ図を画像生成モデルで描くための説明を英語で書いてください。\
+主要コンポーネント、外部サービス、データストア、それらの間のデータの流れを箱と矢印で表現できるように、\
+レイアウトとラベルを具体的に指定してください。説明文のみを出力してください。";
+
+// リポジトリのアーキテクチャ図を生成
+pub async fn diagram_runner(
+    github_client: Arc<GitHubClient>,
+    endpoints: Arc<Vec<Endpoint>>,
+    repo_info: RepoInfo,
+    endpoint_index: usize,
+    base_dir: String,
+    config: DiagramConfig,
+) -> Result<()> {
+    let endpoint = &endpoints[endpoint_index % endpoints.len()];
+    let openai_client = LlmClient::new(endpoint.clone());
+
+    info!(
+        "[{}] アーキテクチャ図の生成開始: {}/{}",
+        endpoint.name, repo_info.owner, repo_info.repo
+    );
+
+    let repo_files = github_client.fetch_repo_files(&repo_info).await?;
+    let code_stats = stats::collect(&github_client, &repo_info)
+        .await
+        .unwrap_or_default();
+    let readme = readme::resolve(&openai_client, &github_client, &repo_info, &repo_files).await;
+    let (system_prompt, _) = generate_repo_debate_prompt(
+        &repo_info,
+        &repo_files,
+        "アーキテクチャ図の作成",
+        code_stats.as_deref(),
+        &readme,
+    );
+
+    let messages = vec![
+        ChatMessage {
+            role: "system".to_string(),
+            content: system_prompt,
+            ..Default::default()
+        },
+        ChatMessage {
+            role: "user".to_string(),
+            content: DESCRIBE_REQUEST.to_string(),
+            ..Default::default()
+        },
+    ];
+
+    // 実行期限が近ければ新しい生成を開始しない
+    if deadline::reached() {
+        info!(
+            "[{}] ⏳ 実行期限が近いためアーキテクチャ図を生成しません: {}/{}",
+            endpoint.name, repo_info.owner, repo_info.repo
+        );
+        return Ok(());
+    }
+
+    // まずモデルにアーキテクチャを説明させる
+    let (description, tokens_used) = openai_client
+        .chat_completion(&messages, generation::default_model(), 2000, 0.7)
+        .await?;
+    budget::record(&repo_info, tokens_used);
+
+    info!(
+        "[{}] アーキテクチャの説明を取得: {}/{} (トークン数: {})",
+        endpoint.name, repo_info.owner, repo_info.repo, tokens_used
+    );
+
+    let image_prompt: String = format!(
+        "A clean, professional software architecture diagram on a white background with labeled boxes and arrows. {}",
+        description
+    )
+    .chars()
+    .take(MAX_IMAGE_PROMPT_CHARS)
+    .collect();
+
+    let repo_dir = format!("{}/{}_{}", base_dir, repo_info.owner, repo_info.repo);
+    fs::create_dir_all(&repo_dir).await?;
+
+    let timestamp = Utc::now().format("%Y%m%d_%H%M%S");
+
+    // 説明文も画像と一緒に保存
+    encryption::write(
+        format!("{}/architecture_diagram_{}.md", repo_dir, timestamp),
+        description.as_str(),
+    )
+    .await?;
+
+    for n in 1..=config.count {
+        if deadline::reached() {
+            info!(
+                "[{}] ⏳ 実行期限が近いため残りのアーキテクチャ図を生成しません: {}/{}",
+                endpoint.name, repo_info.owner, repo_info.repo
+            );
+            break;
+        }
+        match openai_client
+            .generate_image(&image_prompt, &config.deployment, &config.size)
+            .await
+        {
+            Ok(png) => {
+                let filename = format!("{}/architecture_diagram_{}_{}.png", repo_dir, timestamp, n);
+                fs::write(&filename, png).await?;
+                encryption::warn_plaintext("アーキテクチャ図の画像", &filename);
+                info!(
+                    "[{}] 🖼️ アーキテクチャ図を保存: {}",
+                    endpoint.name, filename
+                );
+            }
+            Err(e) => {
+                error!(
+                    "[{}] 画像生成エラー: {}/{} - {}",
+                    endpoint.name, repo_info.owner, repo_info.repo, e
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,361 @@
+// 分析結果の差分モジュール
+// 同じリポジトリ・議論タイプの2回の実行結果を比較し、ターンごとの回答の変化と評価の変化をMarkdownのレポートにまとめる
+
+use anyhow::{Result, anyhow, bail};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use log::{error, info};
+use similar::TextDiff;
+use std::collections::BTreeMap;
+use tokio::fs;
+
+use crate::journal::{Journal, TaskState};
+use crate::llm::schemas::github_response::RepoInfo;
+use crate::llm::schemas::openai_response::{ChatMessage, Endpoint, ResponseData};
+use crate::{LlmClient, encryption, generation, routing};
+
+// 差分の前後に表示する行数
+const CONTEXT_LINES: usize = 2;
+
+// 評価の変化の要約に渡す1回分の回答の最大文字数
+const MAX_SUMMARY_INPUT_CHARS: usize = 30000;
+
+// 評価の変化の要約の指示
+const CHANGE_SUMMARY_PROMPT: &str = "あなたはコードレビューの結果を比較するシニアエンジニアです。\
+同じリポジトリについて、前回と今回の2回の実行でAIが行った分析結果を示します。\
+評価がどう変わったか（改善された点、新たに指摘された問題、解消された問題、評価が変わらない点）を\
+Markdownの箇条書きで簡潔にまとめてください。";
+
+// diff コマンドのオプション
+pub struct DiffOptions {
+    pub repo: String,
+    pub branch: Option<String>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub output: Option<String>,
+    pub summarize: bool,
+}
+
+// 会話履歴からアシスタントの回答だけを順に取り出す
+fn answers(response_data: &ResponseData) -> Vec<&str> {
+    response_data
+        .messages
+        .iter()
+        .filter(|message| message.role == "assistant")
+        .map(|message| message.content.as_str())
+        .collect()
+}
+
+// 2回の実行結果の差分レポートを作成（level は見出しの階層）
+pub fn render_report(previous: &ResponseData, current: &ResponseData, level: usize) -> String {
+    let heading = "#".repeat(level);
+    let previous_answers = answers(previous);
+    let current_answers = answers(current);
+
+    let mut sections = Vec::new();
+    let (mut added, mut removed) = (0, 0);
+    for turn in 0..previous_answers.len().max(current_answers.len()) {
+        let before = previous_answers.get(turn).copied().unwrap_or_default();
+        let after = current_answers.get(turn).copied().unwrap_or_default();
+        if before == after {
+            continue;
+        }
+
+        let diff = TextDiff::from_lines(before, after);
+        for change in diff.iter_all_changes() {
+            match change.tag() {
+                similar::ChangeTag::Insert => added += 1,
+                similar::ChangeTag::Delete => removed += 1,
+                similar::ChangeTag::Equal => {}
+            }
+        }
+        sections.push(format!(
+            "{}## ターン {}\n\n```diff\n{}```\n",
+            heading,
+            turn + 1,
+            diff.unified_diff().context_radius(CONTEXT_LINES)
+        ));
+    }
+
+    let mut report = format!(
+        "{} 分析結果の差分: {} ({})\n\n\
+         | | 前回 | 今回 |\n\
+         |---|---|---|\n\
+         | 日時 | {} | {} |\n\
+         | エンドポイント | {} | {} |\n\
+         | ターン数 | {} | {} |\n\n\
+         変更のあったターン: {} / 追加 {} 行 / 削除 {} 行\n\n",
+        heading,
+        current.repo,
+        current.debate_type,
+        previous.timestamp,
+        current.timestamp,
+        previous.endpoint,
+        current.endpoint,
+        previous.turn,
+        current.turn,
+        sections.len(),
+        added,
+        removed
+    );
+    if sections.is_empty() {
+        report.push_str("回答に変化はありませんでした。\n");
+    } else {
+        report.push_str(&sections.join("\n"));
+    }
+    report
+}
+
+// 差分レポートをリポジトリの出力ディレクトリに保存
+pub async fn save_report(
+    base_dir: &str,
+    repo_info: &RepoInfo,
+    debate_type: &str,
+    report: &str,
+) -> Result<String> {
+    let diff_dir = format!("{}/{}_{}/diffs", base_dir, repo_info.owner, repo_info.repo);
+    fs::create_dir_all(&diff_dir).await?;
+
+    let filename = format!(
+        "{}/{}_{}.md",
+        diff_dir,
+        debate_type.replace(" ", "_"),
+        Utc::now().format("%Y%m%d_%H%M%S")
+    );
+    encryption::write(&filename, report).await?;
+    Ok(filename)
+}
+
+// 日時の指定（RFC3339 または YYYY-MM-DD、日付はその日の終わりまで）を解析
+fn parse_until(value: &str) -> Result<DateTime<Utc>> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|_| anyhow!("日時の形式が正しくありません: {}", value))?;
+    Ok(date
+        .and_hms_opt(23, 59, 59)
+        .ok_or_else(|| anyhow!("日時の形式が正しくありません: {}", value))?
+        .and_utc())
+}
+
+// 保存日時
+fn saved_at(response_data: &ResponseData) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(&response_data.timestamp)
+        .ok()
+        .map(|time| time.with_timezone(&Utc))
+}
+
+// 完了した1回分の実行
+struct CompletedRun {
+    result: ResponseData,
+    // 分析したコミット（解決できなかった場合は None）
+    commit: Option<String>,
+    // 実行を始めた日時（実行IDがなければ最後のターンの保存日時）
+    started_at: Option<DateTime<Utc>>,
+}
+
+// 実行の開始日時（実行IDは開始日時の YYYYMMDD_HHMMSS）
+fn started_at(response_data: &ResponseData) -> Option<DateTime<Utc>> {
+    response_data
+        .run_id
+        .as_deref()
+        .and_then(|run_id| NaiveDateTime::parse_from_str(run_id, "%Y%m%d_%H%M%S").ok())
+        .map(|time| time.and_utc())
+        .or_else(|| saved_at(response_data))
+}
+
+// 比較の単位（議論タイプ・観点・ファイル単位のレビューの対象ファイル）
+type RunKey = (String, Option<usize>, Option<String>);
+
+// 比較の単位の見出し
+fn heading(key: &RunKey) -> String {
+    let (debate_type, perspective, file) = key;
+    let mut heading = debate_type.clone();
+    if let Some(perspective) = perspective {
+        heading.push_str(&format!(" (観点 {})", perspective + 1));
+    }
+    if let Some(file) = file {
+        heading.push_str(&format!(" - {}", file));
+    }
+    heading
+}
+
+// 完了した実行の最終結果を、議論タイプ・観点ごとに実行の古い順で集める
+// 同じ実行の別の観点どうしを前回・今回として比較しないようにする
+fn completed_runs(journal: &Journal, options: &DiffOptions) -> BTreeMap<RunKey, Vec<CompletedRun>> {
+    let mut runs: BTreeMap<RunKey, Vec<CompletedRun>> = BTreeMap::new();
+
+    for task in journal.list() {
+        if task.state != TaskState::Done
+            || task.repo != options.repo
+            || (options.branch.is_some() && task.branch != options.branch)
+        {
+            continue;
+        }
+        match journal.load_latest(&task.task_id) {
+            Ok(Some(response_data)) => {
+                let key = (
+                    task.debate_type.clone(),
+                    task.perspective,
+                    response_data.file.clone(),
+                );
+                runs.entry(key).or_default().push(CompletedRun {
+                    commit: task.resolved_commit.clone().or(task.commit.clone()),
+                    started_at: started_at(&response_data),
+                    result: response_data,
+                })
+            }
+            Ok(None) => {}
+            Err(e) => error!("❌ 実行結果を読み込めません: {} - {}", task.task_id, e),
+        }
+    }
+
+    for results in runs.values_mut() {
+        results.sort_by_key(|run| (run.started_at, saved_at(&run.result)));
+    }
+    runs
+}
+
+// 指定日時までに始めた最後の実行（指定がなければ除外する実行以外の最後）
+fn pick_run<'a>(
+    runs: &'a [CompletedRun],
+    until: Option<DateTime<Utc>>,
+    before: Option<&CompletedRun>,
+) -> Option<&'a CompletedRun> {
+    runs.iter().rev().find(|run| {
+        until.is_none_or(|until| run.started_at.is_some_and(|time| time <= until))
+            && before.is_none_or(|before| {
+                (run.started_at, saved_at(&run.result))
+                    < (before.started_at, saved_at(&before.result))
+            })
+    })
+}
+
+// 回答を連結して要約の入力を作る
+fn joined_answers(response_data: &ResponseData) -> String {
+    answers(response_data)
+        .join("\n\n")
+        .chars()
+        .take(MAX_SUMMARY_INPUT_CHARS)
+        .collect()
+}
+
+// 2回の実行で評価がどう変わったかをモデルに要約させる
+async fn summarize_change(
+    client: &LlmClient,
+    previous: &ResponseData,
+    current: &ResponseData,
+) -> Result<String> {
+    let messages = vec![
+        ChatMessage {
+            role: "system".to_string(),
+            content: CHANGE_SUMMARY_PROMPT.to_string(),
+            ..Default::default()
+        },
+        ChatMessage {
+            role: "user".to_string(),
+            content: format!(
+                "リポジトリ: {}\n議論タイプ: {}\n\n## 前回 ({})\n{}\n\n## 今回 ({})\n{}",
+                current.repo,
+                current.debate_type,
+                previous.timestamp,
+                joined_answers(previous),
+                current.timestamp,
+                joined_answers(current)
+            ),
+            ..Default::default()
+        },
+    ];
+
+    let (summary, tokens_used) = client
+        .chat_completion(&messages, generation::default_model(), 2000, 0.3)
+        .await?;
+    info!(
+        "📝 評価の変化を要約しました: {} (トークン数: {})",
+        current.debate_type, tokens_used
+    );
+    Ok(summary)
+}
+
+// 2回の実行を議論タイプごとに比較してレポートを保存
+pub async fn run_diff(
+    journal: &Journal,
+    endpoints: &[Endpoint],
+    output_dir: &str,
+    options: &DiffOptions,
+) -> Result<String> {
+    let (owner, repo) = options
+        .repo
+        .split_once('/')
+        .ok_or_else(|| anyhow!("--repo は owner/repo の形式で指定してください"))?;
+    let from = options.from.as_deref().map(parse_until).transpose()?;
+    let to = options.to.as_deref().map(parse_until).transpose()?;
+    // 要約は結果を待つ対話的な処理のため、近い（遅延の小さい）エンドポイントを使う
+    let client = match (options.summarize, routing::interactive_endpoint(endpoints)) {
+        (true, Some(endpoint)) => Some(LlmClient::new(endpoint.clone())),
+        _ => None,
+    };
+
+    let mut sections = Vec::new();
+    for (key, runs) in completed_runs(journal, options) {
+        let heading = heading(&key);
+        let Some(current) = pick_run(&runs, to, None) else {
+            continue;
+        };
+        let Some(previous) = pick_run(&runs, from, Some(current)) else {
+            info!("ℹ️ 比較できる前回の実行がありません: {}", heading);
+            continue;
+        };
+        // 同じコミットの分析どうしでは、コードの変化ではなくモデルの揺らぎしか比較できない
+        if let (Some(previous_commit), Some(current_commit)) = (&previous.commit, &current.commit)
+            && previous_commit == current_commit
+        {
+            info!(
+                "ℹ️ 前回と今回が同じコミットのため比較しません: {} ({})",
+                heading,
+                &current_commit[..current_commit.len().min(12)]
+            );
+            continue;
+        }
+        let (previous, current) = (&previous.result, &current.result);
+
+        let mut section = format!("## {}\n\n", heading);
+        if let Some(client) = &client {
+            match summarize_change(client, previous, current).await {
+                Ok(summary) => section.push_str(&format!("### 評価の変化\n\n{}\n\n", summary)),
+                Err(e) => error!("❌ 評価の変化の要約エラー: {} - {}", heading, e),
+            }
+        }
+        section.push_str(&render_report(previous, current, 3));
+        sections.push(section);
+    }
+
+    if sections.is_empty() {
+        bail!(
+            "比較できる実行が2回分ありません（同じコミットの実行どうしは比較しません）: {}",
+            options.repo
+        );
+    }
+
+    let report = format!(
+        "# 分析結果の比較: {}\n\n{}",
+        options.repo,
+        sections.join("\n")
+    );
+    let path = match &options.output {
+        Some(path) => {
+            encryption::write(path, report).await?;
+            path.clone()
+        }
+        None => {
+            let repo_info = RepoInfo {
+                owner: owner.to_string(),
+                repo: repo.to_string(),
+                ..Default::default()
+            };
+            save_report(output_dir, &repo_info, "runs", &report).await?
+        }
+    };
+    info!("🔀 比較レポートを保存しました: {}", path);
+    Ok(path)
+}
@@ -0,0 +1,323 @@
+// 埋め込みベクトル生成モジュール
+// リポジトリの全ファイルをチャンクに分割し、Azure OpenAIの埋め込みデプロイメントでベクトル化して保存する
+
+use anyhow::Result;
+use chrono::prelude::*;
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::{path::Path, sync::Arc};
+use tokio::fs;
+
+use crate::llm::schemas::{github_response::RepoInfo, openai_response::Endpoint};
+use crate::{GitHubClient, LlmClient, budget, deadline, encryption};
+
+// 埋め込み生成の設定
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct EmbeddingsConfig {
+    /// 埋め込み生成を有効にするか
+    pub enabled: bool,
+    /// 埋め込みモデルのデプロイメント名
+    pub deployment: String,
+    /// チャンクあたりの最大文字数
+    pub chunk_chars: usize,
+    /// 隣接チャンク間で重複させる文字数
+    pub chunk_overlap: usize,
+    /// 1リクエストにまとめるチャンク数
+    pub batch_size: usize,
+    /// リポジトリあたりの最大ファイル数
+    pub max_files: usize,
+    /// 追加質問に付加する関連チャンク数（0で無効）
+    pub rag_top_k: usize,
+}
+
+impl Default for EmbeddingsConfig {
+    fn default() -> Self {
+        EmbeddingsConfig {
+            enabled: false,
+            deployment: "text-embedding-3-large".to_string(),
+            chunk_chars: 2000,
+            chunk_overlap: 200,
+            batch_size: 16,
+            max_files: 10000,
+            rag_top_k: 0,
+        }
+    }
+}
+
+// 埋め込み済みのチャンク
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EmbeddedChunk {
+    pub path: String,
+    pub chunk_index: usize,
+    pub content: String,
+    pub embedding: Vec<f32>,
+}
+
+// リポジトリ単位の埋め込みインデックス
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EmbeddingIndex {
+    pub repo: String,
+    pub deployment: String,
+    pub timestamp: String,
+    pub tokens_used: usize,
+    pub chunks: Vec<EmbeddedChunk>,
+}
+
+// ファイル内容を文字単位でチャンクに分割する
+pub fn chunk_content(content: &str, chunk_chars: usize, overlap: usize) -> Vec<String> {
+    let chars: Vec<char> = content.chars().collect();
+    let chunk_chars = chunk_chars.max(1);
+    let step = chunk_chars.saturating_sub(overlap).max(1);
+
+    let mut chunks = Vec::new();
+    let mut start = 0;
+
+    while start < chars.len() {
+        let end = (start + chunk_chars).min(chars.len());
+        chunks.push(chars[start..end].iter().collect());
+
+        if end == chars.len() {
+            break;
+        }
+        start += step;
+    }
+
+    chunks
+}
+
+// 埋め込みインデックスの保存先パス
+pub fn index_path(base_dir: &str, repo_info: &RepoInfo) -> String {
+    format!(
+        "{}/{}_{}/embeddings.json",
+        base_dir, repo_info.owner, repo_info.repo
+    )
+}
+
+// リポジトリの埋め込み生成を実行
+pub async fn embedding_runner(
+    github_client: Arc<GitHubClient>,
+    endpoints: Arc<Vec<Endpoint>>,
+    repo_info: RepoInfo,
+    endpoint_index: usize,
+    base_dir: String,
+    config: EmbeddingsConfig,
+) -> Result<()> {
+    let endpoint = &endpoints[endpoint_index % endpoints.len()];
+    let openai_client = LlmClient::new(endpoint.clone());
+
+    info!(
+        "[{}] 埋め込み生成開始: {}/{} ({})",
+        endpoint.name, repo_info.owner, repo_info.repo, config.deployment
+    );
+
+    // 全ファイルを取得
+    let files = github_client
+        .collect_repo_files(&repo_info, config.max_files)
+        .await?;
+
+    // (パス, チャンク番号, 内容) の一覧を作成
+    let pending: Vec<(String, usize, String)> = files
+        .iter()
+        .flat_map(|file| {
+            chunk_content(&file.content, config.chunk_chars, config.chunk_overlap)
+                .into_iter()
+                .enumerate()
+                .map(|(i, chunk)| (file.path.clone(), i, chunk))
+        })
+        .collect();
+
+    info!(
+        "[{}] 🧩 チャンク数: {} ({}/{})",
+        endpoint.name,
+        pending.len(),
+        repo_info.owner,
+        repo_info.repo
+    );
+
+    let mut chunks = Vec::with_capacity(pending.len());
+    let mut tokens_used = 0;
+
+    for batch in pending.chunks(config.batch_size.max(1)) {
+        // 実行期限が近ければ途中までのインデックスは保存せず、次回の実行で作り直す
+        if deadline::reached() {
+            info!(
+                "[{}] ⏳ 実行期限が近いため埋め込みの生成を中断します: {}/{}",
+                endpoint.name, repo_info.owner, repo_info.repo
+            );
+            return Ok(());
+        }
+        let inputs: Vec<String> = batch.iter().map(|(_, _, chunk)| chunk.clone()).collect();
+
+        match openai_client.embeddings(&inputs, &config.deployment).await {
+            Ok((vectors, tokens)) => {
+                tokens_used += tokens;
+                budget::record(&repo_info, tokens);
+                for ((path, chunk_index, content), embedding) in batch.iter().cloned().zip(vectors)
+                {
+                    chunks.push(EmbeddedChunk {
+                        path,
+                        chunk_index,
+                        content,
+                        embedding,
+                    });
+                }
+            }
+            Err(e) => {
+                error!(
+                    "[{}] 埋め込みAPI エラー: {}/{} - {}",
+                    endpoint.name, repo_info.owner, repo_info.repo, e
+                );
+            }
+        }
+    }
+
+    // インデックスを保存
+    let index = EmbeddingIndex {
+        repo: format!("{}/{}", repo_info.owner, repo_info.repo),
+        deployment: config.deployment.clone(),
+        timestamp: Utc::now().to_rfc3339(),
+        tokens_used,
+        chunks,
+    };
+
+    let path = index_path(&base_dir, &repo_info);
+    if let Some(parent) = Path::new(&path).parent() {
+        fs::create_dir_all(parent).await?;
+    }
+    encryption::write(&path, serde_json::to_string(&index)?).await?;
+
+    info!(
+        "[{}] 埋め込み保存完了: {} (チャンク数: {}, トークン数: {})",
+        endpoint.name,
+        path,
+        index.chunks.len(),
+        tokens_used
+    );
+
+    Ok(())
+}
+
+impl EmbeddingIndex {
+    // 保存済みのインデックスを読み込む
+    pub async fn load(path: &str) -> Result<Self> {
+        let content = encryption::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    // クエリベクトルとのコサイン類似度が高い順にチャンクを返す
+    pub fn search(&self, query: &[f32], top_k: usize) -> Vec<&EmbeddedChunk> {
+        let mut scored: Vec<(f32, &EmbeddedChunk)> = self
+            .chunks
+            .iter()
+            .map(|chunk| (cosine_similarity(query, &chunk.embedding), chunk))
+            .collect();
+
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+        scored
+            .into_iter()
+            .take(top_k)
+            .map(|(_, chunk)| chunk)
+            .collect()
+    }
+}
+
+// コサイン類似度
+fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a: f32 = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b: f32 = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a * norm_b)
+    }
+}
+
+// 追加質問に関連コードを付加する検索器
+pub struct RagRetriever {
+    repo_info: RepoInfo,
+    index_path: String,
+    deployment: String,
+    top_k: usize,
+    index: Option<EmbeddingIndex>,
+}
+
+impl RagRetriever {
+    pub fn new(base_dir: &str, repo_info: &RepoInfo, config: &EmbeddingsConfig) -> Self {
+        RagRetriever {
+            repo_info: repo_info.clone(),
+            index_path: index_path(base_dir, repo_info),
+            deployment: config.deployment.clone(),
+            top_k: config.rag_top_k,
+            index: None,
+        }
+    }
+
+    // インデックスが未読み込みなら読み込みを試みる（埋め込み生成と並行実行のため毎回確認）
+    async fn ensure_loaded(&mut self) -> bool {
+        if self.index.is_none() && Path::new(&self.index_path).exists() {
+            match EmbeddingIndex::load(&self.index_path).await {
+                Ok(index) => {
+                    info!(
+                        "📚 埋め込みインデックスを読み込みました: {} (チャンク数: {})",
+                        self.index_path,
+                        index.chunks.len()
+                    );
+                    self.index = Some(index);
+                }
+                Err(e) => {
+                    warn!(
+                        "⚠️ 埋め込みインデックスの読み込みに失敗: {} - {}",
+                        self.index_path, e
+                    );
+                }
+            }
+        }
+
+        self.index.is_some()
+    }
+
+    // カテゴリと質問文に関連するコードチャンクを質問の末尾に付加する
+    pub async fn augment_question(
+        &mut self,
+        client: &LlmClient,
+        category: &str,
+        question: String,
+    ) -> String {
+        if self.top_k == 0 || !self.ensure_loaded().await {
+            return question;
+        }
+
+        let query = format!("{}: {}", category, question);
+        let query_vector = match client.embeddings(&[query], &self.deployment).await {
+            Ok((mut vectors, tokens_used)) => {
+                budget::record(&self.repo_info, tokens_used);
+                if vectors.is_empty() {
+                    return question;
+                }
+                vectors.swap_remove(0)
+            }
+            Err(e) => {
+                error!("⚠️ RAG用の埋め込み取得エラー: {}", e);
+                return question;
+            }
+        };
+
+        let Some(index) = &self.index else {
+            return question;
+        };
+
+        let mut augmented = question;
+        augmented.push_str("\n\n【関連コード】");
+        for chunk in index.search(&query_vector, self.top_k) {
+            augmented.push_str(&format!(
+                "\n--- {} (チャンク {}) ---\n{}",
+                chunk.path, chunk.chunk_index, chunk.content
+            ));
+        }
+
+        augmented
+    }
+}
@@ -0,0 +1,220 @@
+// 保存結果の暗号化モジュール
+// 議論の結果にはプライベートリポジトリのソースコードがそのまま含まれるため、
+// 共有ディスクや Blob Storage に置く場合に備えて AES-256-GCM で暗号化して保存する
+// （鍵は環境変数か Key Vault のシークレットから読み込む。暗号化したファイルも拡張子は変えない）
+
+use anyhow::{Result, anyhow, bail};
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use log::{info, warn};
+use ring::aead::{AES_256_GCM, Aad, LessSafeKey, NONCE_LEN, Nonce, UnboundKey};
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use crate::arm::{self, DiscoveryConfig};
+use crate::redact;
+
+// Key Vault のデータプレーンのスコープとAPIバージョン
+const KEY_VAULT_SCOPE: &str = "https://vault.azure.net/.default";
+const KEY_VAULT_API_VERSION: &str = "7.4";
+
+// 暗号化したファイルの先頭に付ける目印（このあとにナンス、暗号文と認証タグが続く）
+const MAGIC: &[u8] = b"ACB-ENC1";
+
+// 暗号化の設定
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct EncryptionConfig {
+    /// 議論の結果を暗号化して保存するか
+    pub enabled: bool,
+    /// 鍵（base64 でエンコードした32バイト）を読み込む環境変数
+    pub key_env: String,
+    /// 鍵を保存した Key Vault（例: https://myvault.vault.azure.net、設定すると環境変数より優先する）
+    pub key_vault_url: Option<String>,
+    /// 鍵を保存した Key Vault のシークレット名
+    pub key_vault_secret: String,
+}
+
+impl Default for EncryptionConfig {
+    fn default() -> Self {
+        EncryptionConfig {
+            enabled: false,
+            key_env: "BURNER_ENCRYPTION_KEY".to_string(),
+            key_vault_url: None,
+            key_vault_secret: "burner-encryption-key".to_string(),
+        }
+    }
+}
+
+static KEY: OnceLock<Option<LessSafeKey>> = OnceLock::new();
+static ENABLED: OnceLock<bool> = OnceLock::new();
+
+// Key Vault のシークレットを読み込む（認証は管理APIと同じサービスプリンシパル）
+async fn fetch_secret(vault_url: &str, name: &str, discovery: &DiscoveryConfig) -> Result<String> {
+    let client = reqwest::Client::new();
+    let token = arm::access_token(&client, discovery, KEY_VAULT_SCOPE).await?;
+    let url = format!("{}/secrets/{}", vault_url.trim_end_matches('/'), name);
+    let response = client
+        .get(&url)
+        .query(&[("api-version", KEY_VAULT_API_VERSION)])
+        .bearer_auth(token)
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_text = response.text().await?;
+        bail!(
+            "Key Vault のシークレットを読み込めません: {} - ステータス {}, レスポンス: {}",
+            url,
+            status,
+            error_text
+        );
+    }
+    let body: Value = response.json().await?;
+    body["value"]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| anyhow!("Key Vault のレスポンスに value がありません: {}", url))
+}
+
+// base64 の鍵を読み込む
+fn parse_key(encoded: &str) -> Result<LessSafeKey> {
+    let bytes = BASE64
+        .decode(encoded.trim())
+        .map_err(|e| anyhow!("暗号化の鍵を base64 として読み込めません: {}", e))?;
+    let key = UnboundKey::new(&AES_256_GCM, &bytes).map_err(|_| {
+        anyhow!(
+            "暗号化の鍵は32バイトにしてください（{} バイト）",
+            bytes.len()
+        )
+    })?;
+    Ok(LessSafeKey::new(key))
+}
+
+// 鍵を読み込む（暗号化が無効でも、環境変数に鍵があれば暗号化済みの結果を読み込めるようにする）
+pub async fn configure(config: &EncryptionConfig, discovery: &DiscoveryConfig) -> Result<()> {
+    let _ = ENABLED.set(config.enabled);
+    let encoded = if !config.enabled {
+        std::env::var(&config.key_env).ok()
+    } else if let Some(vault_url) = &config.key_vault_url {
+        info!(
+            "🔏 Key Vault から暗号化の鍵を読み込みます: {} ({})",
+            vault_url, config.key_vault_secret
+        );
+        Some(fetch_secret(vault_url, &config.key_vault_secret, discovery).await?)
+    } else {
+        Some(std::env::var(&config.key_env).map_err(|_| {
+            anyhow!(
+                "暗号化が有効ですが、鍵が設定されていません（{} または key_vault_url）",
+                config.key_env
+            )
+        })?)
+    };
+    if let Some(encoded) = &encoded {
+        redact::add_secrets([encoded]);
+    }
+    let key = encoded.as_deref().map(parse_key).transpose()?;
+    if config.enabled {
+        info!("🔏 議論の結果を暗号化して保存します（AES-256-GCM）");
+    }
+    let _ = KEY.set(key);
+    Ok(())
+}
+
+// 保存する結果を暗号化するか
+pub fn enabled() -> bool {
+    ENABLED.get().copied().unwrap_or(false)
+}
+
+// 暗号化せずに書き出す成果物（エクスポート・音声・画像）を保存するときに警告する
+pub fn warn_plaintext(kind: &str, path: &str) {
+    if enabled() {
+        warn!(
+            "⚠️ {}は暗号化せずに保存します（暗号化の対象外です）: {}",
+            kind, path
+        );
+    }
+}
+
+fn key() -> Option<&'static LessSafeKey> {
+    KEY.get().and_then(Option::as_ref)
+}
+
+// 保存する内容を暗号化する（暗号化が無効ならそのまま返す）
+pub fn seal(plaintext: String) -> Result<Vec<u8>> {
+    if !enabled() {
+        return Ok(plaintext.into_bytes());
+    }
+    let key = key().ok_or_else(|| anyhow!("暗号化の鍵が読み込まれていません"))?;
+
+    let mut nonce = [0u8; NONCE_LEN];
+    SystemRandom::new()
+        .fill(&mut nonce)
+        .map_err(|_| anyhow!("ナンスを生成できません"))?;
+    let mut in_out = plaintext.into_bytes();
+    key.seal_in_place_append_tag(
+        Nonce::assume_unique_for_key(nonce),
+        Aad::empty(),
+        &mut in_out,
+    )
+    .map_err(|_| anyhow!("暗号化に失敗しました"))?;
+
+    let mut sealed = Vec::with_capacity(MAGIC.len() + NONCE_LEN + in_out.len());
+    sealed.extend_from_slice(MAGIC);
+    sealed.extend_from_slice(&nonce);
+    sealed.extend_from_slice(&in_out);
+    Ok(sealed)
+}
+
+// 保存された内容を読み込む（暗号化されていなければそのまま文字列にする）
+pub fn open(bytes: Vec<u8>) -> Result<String> {
+    let Some(rest) = bytes.strip_prefix(MAGIC) else {
+        return String::from_utf8(bytes).map_err(|e| anyhow!("UTF-8 として読み込めません: {}", e));
+    };
+    let key = key().ok_or_else(|| anyhow!("暗号化された結果ですが、鍵が設定されていません"))?;
+    if rest.len() < NONCE_LEN {
+        bail!("暗号化された結果が壊れています");
+    }
+    let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+    let nonce = Nonce::try_assume_unique_for_key(nonce)
+        .map_err(|_| anyhow!("暗号化された結果が壊れています"))?;
+    let mut in_out = ciphertext.to_vec();
+    let plaintext = key
+        .open_in_place(nonce, Aad::empty(), &mut in_out)
+        .map_err(|_| anyhow!("復号できません（鍵が違うか、ファイルが壊れています）"))?;
+    String::from_utf8(plaintext.to_vec()).map_err(|e| anyhow!("UTF-8 として読み込めません: {}", e))
+}
+
+// 1行ずつ追記するファイルの行を暗号化する（暗号化が有効なら base64 の1行にする）
+pub fn seal_line(line: String) -> Result<String> {
+    if !enabled() {
+        return Ok(line);
+    }
+    Ok(BASE64.encode(seal(line)?))
+}
+
+// 1行ずつ追記するファイルの行を読み込む（暗号化されていなければそのまま返す）
+pub fn open_line(line: &str) -> Result<String> {
+    match BASE64.decode(line.trim()) {
+        Ok(bytes) if bytes.starts_with(MAGIC) => open(bytes),
+        _ => Ok(line.to_string()),
+    }
+}
+
+// 結果のファイルを保存する（暗号化が有効なら暗号化する）
+pub async fn write(path: impl AsRef<Path>, contents: impl Into<String>) -> Result<()> {
+    let path = path.as_ref();
+    tokio::fs::write(path, seal(contents.into())?)
+        .await
+        .map_err(|e| anyhow!("ファイルを保存できません: {} - {}", path.display(), e))
+}
+
+// 保存された結果のファイルを読み込む
+pub fn read_to_string(path: impl AsRef<Path>) -> Result<String> {
+    let path = path.as_ref();
+    let bytes = std::fs::read(path)
+        .map_err(|e| anyhow!("ファイルを読み込めません: {} - {}", path.display(), e))?;
+    open(bytes).map_err(|e| anyhow!("{} - {}", path.display(), e))
+}
@@ -0,0 +1,168 @@
+// エラー分類モジュール
+// APIの呼び出しやリポジトリの取得の失敗を種類ごとに分類し、
+// 再試行・エンドポイントの待機・ジャーナルへの記録がエラーの文字列ではなく種類で判断できるようにする
+
+use serde_json::Value;
+use std::fmt;
+use std::time::Duration;
+
+#[derive(Debug)]
+pub enum BurnerError {
+    /// レート制限（429）
+    RateLimited {
+        retry_after: Option<u64>,
+        message: String,
+    },
+    /// クォータ・クレジットの枯渇（使用上限への到達やサブスクリプションの無効化）
+    QuotaExhausted { message: String },
+    /// コンテンツフィルターによる拒否（同じ入力では何度送っても通らない）
+    ContentFiltered { message: String },
+    /// 認証エラー（401 / 403）
+    AuthFailed { message: String },
+    /// リポジトリをクローン・取得できない
+    RepoUnavailable { repo: String, message: String },
+    /// 時間制限を超えた
+    TimedOut { what: String, limit: Duration },
+    /// 台帳の推定使用額が --max-usd の上限に達した（単価がないモデルを呼び出した場合を含む）
+    SpendCeiling { max_usd: f64 },
+    /// その他のAPIエラー
+    Api { status: u16, message: String },
+}
+
+// クレジット枯渇・サブスクリプション無効化を示すエラーコード
+const CREDIT_ERROR_CODES: [&str; 5] = [
+    "insufficient_quota",
+    "QuotaExceeded",
+    "SubscriptionDisabled",
+    "ReadOnlyDisabledSubscription",
+    "SpendingLimitReached",
+];
+
+// 403 のうちクレジット枯渇を示すメッセージ（小文字で比較）
+const CREDIT_ERROR_MESSAGES: [&str; 4] = [
+    "quota",
+    "spending limit",
+    "subscription has been disabled",
+    "subscription is disabled",
+];
+
+// "Please retry after X seconds" から待機時間を抽出
+fn retry_after(body: &str) -> Option<u64> {
+    let start = body.find("retry after ")? + "retry after ".len();
+    let rest = &body[start..];
+    rest[..rest.find(" seconds")?].parse().ok()
+}
+
+impl BurnerError {
+    // エラーレスポンスのステータスと本文から分類する
+    pub fn from_response(status: u16, body: &str) -> Self {
+        let code = serde_json::from_str::<Value>(body)
+            .ok()
+            .and_then(|value| value["error"]["code"].as_str().map(str::to_string))
+            .unwrap_or_default();
+        let message = format!("ステータス {}, レスポンス: {}", status, body);
+
+        // 使用上限に達した・無効化されたサブスクリプション（認証エラーと同じ 403 で返ることがある）
+        let body_lower = body.to_lowercase();
+        if CREDIT_ERROR_CODES.contains(&code.as_str())
+            || (status == 403
+                && CREDIT_ERROR_MESSAGES
+                    .iter()
+                    .any(|pattern| body_lower.contains(pattern)))
+        {
+            return BurnerError::QuotaExhausted { message };
+        }
+        match status {
+            401 | 403 => BurnerError::AuthFailed { message },
+            429 => BurnerError::RateLimited {
+                retry_after: retry_after(body),
+                message,
+            },
+            400 if code == "content_filter" || body.contains("ResponsibleAIPolicyViolation") => {
+                BurnerError::ContentFiltered { message }
+            }
+            _ => BurnerError::Api { status, message },
+        }
+    }
+
+    // 成功（2xx）した補完の応答の終了理由から分類する（コンテンツフィルターで打ち切られた場合だけエラー）
+    pub fn from_finish_reason(finish_reason: &str) -> Option<Self> {
+        (finish_reason == "content_filter").then(|| BurnerError::ContentFiltered {
+            message: "ステータス 200, 回答がコンテンツフィルターで打ち切られました (finish_reason: content_filter)"
+                .to_string(),
+        })
+    }
+
+    // anyhow のエラーから分類済みのエラーを取り出す
+    pub fn of(error: &anyhow::Error) -> Option<&BurnerError> {
+        error.downcast_ref::<BurnerError>()
+    }
+
+    // ジャーナルや集計で使う種類名
+    pub fn kind(&self) -> &'static str {
+        match self {
+            BurnerError::RateLimited { .. } => "rate_limited",
+            BurnerError::QuotaExhausted { .. } => "quota_exhausted",
+            BurnerError::ContentFiltered { .. } => "content_filtered",
+            BurnerError::AuthFailed { .. } => "auth_failed",
+            BurnerError::RepoUnavailable { .. } => "repo_unavailable",
+            BurnerError::TimedOut { .. } => "timed_out",
+            BurnerError::SpendCeiling { .. } => "spend_ceiling",
+            BurnerError::Api { .. } => "api",
+        }
+    }
+
+    // 同じエンドポイントで待ってから再試行すれば成功する見込みがあるか
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            BurnerError::RateLimited { .. } | BurnerError::TimedOut { .. } => true,
+            // サーバー側のエラーとリクエストのタイムアウトだけ再試行する
+            BurnerError::Api { status, .. } => *status >= 500 || *status == 408,
+            BurnerError::QuotaExhausted { .. }
+            | BurnerError::ContentFiltered { .. }
+            | BurnerError::AuthFailed { .. }
+            | BurnerError::RepoUnavailable { .. }
+            | BurnerError::SpendCeiling { .. } => false,
+        }
+    }
+
+    // 別のエンドポイントで実行し直せば成功する見込みがあるか
+    pub fn is_retryable_elsewhere(&self) -> bool {
+        !matches!(
+            self,
+            BurnerError::ContentFiltered { .. }
+                | BurnerError::RepoUnavailable { .. }
+                | BurnerError::SpendCeiling { .. }
+        )
+    }
+}
+
+impl fmt::Display for BurnerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BurnerError::RateLimited { message, .. } => write!(f, "レート制限: {}", message),
+            BurnerError::QuotaExhausted { message, .. } => write!(f, "クレジット枯渇: {}", message),
+            BurnerError::ContentFiltered { message, .. } => {
+                write!(f, "コンテンツフィルター: {}", message)
+            }
+            BurnerError::AuthFailed { message, .. } => write!(f, "認証エラー: {}", message),
+            BurnerError::RepoUnavailable { repo, message } => {
+                write!(f, "リポジトリを取得できません: {} - {}", repo, message)
+            }
+            BurnerError::TimedOut { what, limit } => write!(
+                f,
+                "{}が {} 秒以内に終わらなかったため打ち切りました",
+                what,
+                limit.as_secs()
+            ),
+            BurnerError::SpendCeiling { max_usd } => write!(
+                f,
+                "台帳の推定使用額が上限（{:.2} USD）に達したか、料金表に単価がないモデルを呼び出したため、リクエストを止めました",
+                max_usd
+            ),
+            BurnerError::Api { message, .. } => write!(f, "OpenAI API エラー: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for BurnerError {}
@@ -0,0 +1,422 @@
+// 保存済みの回答の評価モジュール
+// 各ターンで保存した回答を判定用モデルに渡して評価基準（ルーブリック）ごとに採点し直し、
+// 採点結果を eval/scores.json に、モデル・エンドポイントごとの平均点を eval/report.md にまとめる
+// （どのリージョン・モデルの分析が良かったかを比べられるようにする）
+
+use anyhow::{Result, anyhow, bail};
+use chrono::prelude::*;
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::collections::BTreeMap;
+use tokio::fs;
+
+use crate::llm::schemas::openai_response::{ChatMessage, Endpoint, ResponseData};
+use crate::{LlmClient, encryption, generation, outputs};
+
+// 回答の評価の設定
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct EvalConfig {
+    /// 採点に使うデプロイメント名（省略時は議論と同じモデル。安いモデルを指定してもよい）
+    pub deployment: Option<String>,
+    /// 採点の最大出力トークン数
+    pub max_tokens: usize,
+    /// 採点に渡す回答の最大文字数
+    pub max_answer_chars: usize,
+    /// 評価基準（省略時は具体性・正確さ・実用性）
+    pub rubrics: Vec<Rubric>,
+}
+
+impl Default for EvalConfig {
+    fn default() -> Self {
+        EvalConfig {
+            deployment: None,
+            max_tokens: 1000,
+            max_answer_chars: 20000,
+            rubrics: vec![
+                Rubric {
+                    name: "具体性".to_string(),
+                    description: "コードや設計の具体的な箇所に言及しているか".to_string(),
+                },
+                Rubric {
+                    name: "正確さ".to_string(),
+                    description: "技術的に正確で、誤りや根拠のない断定がないか".to_string(),
+                },
+                Rubric {
+                    name: "実用性".to_string(),
+                    description: "すぐに取り組める実用的な提案を含むか".to_string(),
+                },
+            ],
+        }
+    }
+}
+
+// 評価基準
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Rubric {
+    pub name: String,
+    /// 判定用モデルに伝える採点の観点
+    pub description: String,
+}
+
+// 評価のオプション
+pub struct EvalOptions {
+    // 採点に使うモデル（設定ファイルの eval.deployment より優先）
+    pub judge_model: Option<String>,
+    // 対象リポジトリ（owner/repo、省略時はすべて）
+    pub repo: Option<String>,
+    // 対象の議論タイプ（名前の一部、省略時はすべて）
+    pub debate: Option<String>,
+    // 採点済みの回答も採点し直す
+    pub rescore: bool,
+}
+
+// 採点結果の保存先（出力ディレクトリ直下）
+const EVAL_DIR: &str = "eval";
+const SCORES_FILE: &str = "scores.json";
+const REPORT_FILE: &str = "report.md";
+
+// 1つの回答の採点結果
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EvalScore {
+    pub repo: String,
+    pub debate_type: String,
+    pub turn: usize,
+    pub endpoint: String,
+    // 回答したモデル（記録がなければ None）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    pub judge_model: String,
+    // 評価基準ごとの点数（1〜10）
+    pub scores: BTreeMap<String, u8>,
+    pub comment: String,
+    pub timestamp: String,
+}
+
+impl EvalScore {
+    // 評価基準の平均点
+    fn average(&self) -> f64 {
+        if self.scores.is_empty() {
+            return 0.0;
+        }
+        self.scores
+            .values()
+            .map(|&score| f64::from(score))
+            .sum::<f64>()
+            / self.scores.len() as f64
+    }
+}
+
+// 採点の指示
+const EVAL_PROMPT: &str = "あなたはコードレビューの品質を評価する審査員です。\
+以下の質問に対する回答を、指定された評価基準ごとに1〜10点（10が最良）で採点し、採点の根拠を1文で添えてください。\
+回答の長さではなく内容で評価してください。";
+
+// 構造化出力の JSON スキーマ（strict モードではすべてのプロパティを必須にする）
+fn schema(rubrics: &[Rubric]) -> Value {
+    let names: Vec<&str> = rubrics.iter().map(|rubric| rubric.name.as_str()).collect();
+    json!({
+        "type": "object",
+        "properties": {
+            "scores": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "rubric": { "type": "string", "enum": names },
+                        "score": { "type": "integer" },
+                    },
+                    "required": ["rubric", "score"],
+                    "additionalProperties": false,
+                },
+            },
+            "comment": { "type": "string" },
+        },
+        "required": ["scores", "comment"],
+        "additionalProperties": false,
+    })
+}
+
+#[derive(Deserialize)]
+struct EvalResponse {
+    scores: Vec<RubricScore>,
+    comment: String,
+}
+
+#[derive(Deserialize)]
+struct RubricScore {
+    rubric: String,
+    score: i64,
+}
+
+// ターンのファイルで、そのターンに保存した回答とその質問（最後のアシスタントのメッセージと直前のユーザーのメッセージ）
+fn last_answer(messages: &[ChatMessage]) -> Option<(&ChatMessage, &ChatMessage)> {
+    let (index, answer) =
+        messages.iter().enumerate().rev().find(|(_, message)| {
+            message.role == "assistant" && !message.content.trim().is_empty()
+        })?;
+    let question = messages[..index]
+        .iter()
+        .rev()
+        .find(|message| message.role == "user")?;
+    Some((question, answer))
+}
+
+// 回答したモデル（古い結果はAPI呼び出しの記録から補う）
+fn answered_model(response: &ResponseData) -> Option<String> {
+    response.model.clone().or_else(|| {
+        response
+            .metrics
+            .as_ref()
+            .and_then(|metrics| metrics.calls.last())
+            .map(|call| call.model.clone())
+    })
+}
+
+// 1つの回答を評価基準ごとに採点する
+async fn score(
+    client: &LlmClient,
+    config: &EvalConfig,
+    judge_model: &str,
+    question: &str,
+    answer: &str,
+) -> Result<(BTreeMap<String, u8>, String)> {
+    let rubrics = config
+        .rubrics
+        .iter()
+        .map(|rubric| format!("- {}: {}", rubric.name, rubric.description))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let answer: String = answer.chars().take(config.max_answer_chars).collect();
+    let messages = vec![
+        ChatMessage {
+            role: "system".to_string(),
+            content: EVAL_PROMPT.to_string(),
+            ..Default::default()
+        },
+        ChatMessage {
+            role: "user".to_string(),
+            content: format!(
+                "【評価基準】\n{}\n\n【質問】\n{}\n\n【回答】\n{}",
+                rubrics, question, answer
+            ),
+            ..Default::default()
+        },
+    ];
+    let (response, _) = client
+        .structured_completion(
+            &messages,
+            judge_model,
+            config.max_tokens,
+            "eval",
+            schema(&config.rubrics),
+        )
+        .await?;
+    let parsed: EvalResponse = serde_json::from_str(&response)
+        .map_err(|e| anyhow!("採点結果のJSONを解析できません: {} - {}", e, response))?;
+
+    // 同じ評価基準が重複した場合は最初の点数を使う
+    let mut scores = BTreeMap::new();
+    for rubric_score in parsed.scores {
+        scores
+            .entry(rubric_score.rubric)
+            .or_insert(rubric_score.score.clamp(1, 10) as u8);
+    }
+    Ok((scores, parsed.comment))
+}
+
+// 採点結果を読み込む（キーは出力ディレクトリ以下のターンのファイルのパス）
+async fn load_scores(path: &str) -> BTreeMap<String, EvalScore> {
+    match encryption::read_to_string(path) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+            warn!("⚠️ 採点結果を読み込めません: {} - {}", path, e);
+            BTreeMap::new()
+        }),
+        Err(_) => BTreeMap::new(),
+    }
+}
+
+// 採点済みの回答をそのまま使えるか（同じ判定用モデルで、すべての評価基準を採点している）
+fn is_scored(score: &EvalScore, judge_model: &str, rubrics: &[Rubric]) -> bool {
+    score.judge_model == judge_model
+        && rubrics
+            .iter()
+            .all(|rubric| score.scores.contains_key(&rubric.name))
+}
+
+// 平均点の表示
+fn format_average(total: f64, count: usize) -> String {
+    if count == 0 {
+        "-".to_string()
+    } else {
+        format!("{:.1}", total / count as f64)
+    }
+}
+
+// モデル・エンドポイントごとの平均点のレポートを作る
+fn render_report(scores: &[&EvalScore], rubrics: &[Rubric]) -> String {
+    // (モデル, エンドポイント) → 採点結果
+    let mut groups: BTreeMap<(String, String), Vec<&EvalScore>> = BTreeMap::new();
+    for score in scores {
+        let model = score.model.clone().unwrap_or_else(|| "不明".to_string());
+        groups
+            .entry((model, score.endpoint.clone()))
+            .or_default()
+            .push(score);
+    }
+    // 平均点の高い順に並べる
+    let mut rows: Vec<_> = groups.into_iter().collect();
+    let average = |scores: &[&EvalScore]| {
+        scores.iter().map(|score| score.average()).sum::<f64>() / scores.len() as f64
+    };
+    rows.sort_by(|(_, a), (_, b)| average(b).total_cmp(&average(a)));
+
+    let mut report = format!(
+        "# 回答の評価レポート\n\n作成日時: {}\n採点した回答: {} 件\n\n",
+        Local::now().format("%Y-%m-%d %H:%M"),
+        scores.len()
+    );
+    report.push_str("| モデル | エンドポイント | 回答数 |");
+    for rubric in rubrics {
+        report.push_str(&format!(" {} |", rubric.name));
+    }
+    report.push_str(" 平均 |\n|---|---|---:|");
+    report.push_str(&"---:|".repeat(rubrics.len() + 1));
+    report.push('\n');
+
+    for ((model, endpoint), group) in &rows {
+        report.push_str(&format!("| {} | {} | {} |", model, endpoint, group.len()));
+        for rubric in rubrics {
+            let values: Vec<f64> = group
+                .iter()
+                .filter_map(|score| score.scores.get(&rubric.name))
+                .map(|&score| f64::from(score))
+                .collect();
+            report.push_str(&format!(
+                " {} |",
+                format_average(values.iter().sum(), values.len())
+            ));
+        }
+        report.push_str(&format!(" {:.1} |\n", average(group)));
+    }
+    report
+}
+
+// 保存済みの回答を採点し、採点結果とレポートを保存する
+pub async fn run_eval(
+    endpoints: &[Endpoint],
+    base_dir: &str,
+    config: &EvalConfig,
+    options: &EvalOptions,
+    dry_run: bool,
+) -> Result<String> {
+    if config.rubrics.is_empty() {
+        bail!("評価基準がありません（eval.rubrics）");
+    }
+    let Some(endpoint) = endpoints.first() else {
+        bail!("採点に使用できるエンドポイントがありません");
+    };
+    let judge_model = options
+        .judge_model
+        .clone()
+        .or_else(|| config.deployment.clone())
+        .unwrap_or_else(|| generation::default_model().to_string());
+
+    let eval_dir = format!("{}/{}", base_dir, EVAL_DIR);
+    let scores_path = format!("{}/{}", eval_dir, SCORES_FILE);
+    let mut scores = load_scores(&scores_path).await;
+
+    // 対象のターンのファイル（採点済みの回答は採点し直さない）
+    let mut targets = Vec::new();
+    let mut selected = Vec::new();
+    for (path, response) in outputs::load_responses(base_dir) {
+        if options
+            .repo
+            .as_ref()
+            .is_some_and(|repo| !response.repo.eq_ignore_ascii_case(repo))
+            || options
+                .debate
+                .as_ref()
+                .is_some_and(|debate| !response.debate_type.contains(debate.as_str()))
+        {
+            continue;
+        }
+        let key = outputs::slash_path(path.strip_prefix(base_dir).unwrap_or(&path));
+        selected.push(key.clone());
+        let scored = scores
+            .get(&key)
+            .is_some_and(|score| is_scored(score, &judge_model, &config.rubrics));
+        if options.rescore || !scored {
+            targets.push((key, response));
+        }
+    }
+    if selected.is_empty() {
+        bail!("評価する分析結果がありません: {}", base_dir);
+    }
+
+    info!(
+        "🧪 回答を採点します: {} 件（採点済み: {} 件、判定用モデル: {}）",
+        targets.len(),
+        selected.len() - targets.len(),
+        judge_model
+    );
+    if dry_run {
+        info!("🔍 ドライランのため採点しません");
+        return Ok(scores_path);
+    }
+
+    fs::create_dir_all(&eval_dir).await?;
+    let client = LlmClient::new(endpoint.clone());
+    let mut failed = 0;
+    for (key, response) in targets {
+        let Some((question, answer)) = last_answer(&response.messages) else {
+            continue;
+        };
+        match score(
+            &client,
+            config,
+            &judge_model,
+            &question.content,
+            &answer.content,
+        )
+        .await
+        {
+            Ok((rubric_scores, comment)) => {
+                scores.insert(
+                    key,
+                    EvalScore {
+                        repo: response.repo.clone(),
+                        debate_type: response.debate_type.clone(),
+                        turn: response.turn,
+                        endpoint: response.endpoint.clone(),
+                        model: answered_model(&response),
+                        judge_model: judge_model.clone(),
+                        scores: rubric_scores,
+                        comment,
+                        timestamp: Utc::now().to_rfc3339(),
+                    },
+                );
+                // 途中で止めても採点済みの回答を失わないよう毎回保存する
+                encryption::write(&scores_path, serde_json::to_string_pretty(&scores)?).await?;
+            }
+            Err(e) => {
+                error!("⚠️ 採点エラー: {} - {}", key, e);
+                failed += 1;
+            }
+        }
+    }
+
+    // レポートは今回の対象の回答だけで作る
+    let selected: Vec<&EvalScore> = selected.iter().filter_map(|key| scores.get(key)).collect();
+    let report_path = format!("{}/{}", eval_dir, REPORT_FILE);
+    encryption::write(&report_path, render_report(&selected, &config.rubrics)).await?;
+
+    info!(
+        "✅ 評価レポートを保存しました: {} ({} 件, 採点エラー: {} 件)",
+        report_path,
+        selected.len(),
+        failed
+    );
+    Ok(report_path)
+}
@@ -0,0 +1,419 @@
+// 分析結果のエクスポートモジュール
+// 保存済みの議論を他のツールで扱える形式に変換して書き出す
+
+use anyhow::{Result, bail};
+use chrono::prelude::*;
+use clap::ValueEnum;
+use log::{error, info};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use tokio::fs;
+
+use crate::judge::{self, ScoreCache};
+use crate::llm::schemas::openai_response::{ChatMessage, Endpoint, ResponseData};
+use crate::{LlmClient, encryption, outputs};
+
+// エクスポート形式
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum ExportFormat {
+    /// OpenAIのファインチューニング用JSONL（system/user/assistantの組）
+    Finetune,
+    /// mdBook のソース（リポジトリごとのサマリーと議論タイプごとの章）
+    Mdbook,
+    /// ChatML のテキスト（議論ごとに1行の JSONL の text）
+    Chatml,
+    /// OpenAI のチャット形式の JSONL（議論ごとに1行の messages）
+    OpenaiJsonl,
+    /// 会話をそのまま読める1つの Markdown
+    Markdown,
+}
+
+// エクスポートのオプション
+pub struct ExportOptions {
+    pub format: ExportFormat,
+    pub output: Option<String>,
+    pub min_score: Option<f32>,
+    pub judge_model: String,
+    // 対象リポジトリ（owner/repo、省略時はすべて）
+    pub repo: Option<String>,
+    // 対象の議論タイプ（名前の一部、省略時はすべて）
+    pub debate: Option<String>,
+}
+
+// エクスポートする議論（議論ごとの最終ターン。リポジトリと議論タイプで絞り込む）
+fn select_debates(base_dir: &str, options: &ExportOptions) -> Vec<(PathBuf, ResponseData)> {
+    let responses = outputs::load_responses(base_dir)
+        .into_iter()
+        .filter(|(_, response)| {
+            options
+                .repo
+                .as_ref()
+                .is_none_or(|repo| response.repo.eq_ignore_ascii_case(repo))
+                && options
+                    .debate
+                    .as_ref()
+                    .is_none_or(|debate| response.debate_type.contains(debate.as_str()))
+        })
+        .collect();
+    outputs::latest_per_debate(responses)
+}
+
+// 会話履歴から (system, user, assistant) の組を取り出す
+fn extract_triples(messages: &[ChatMessage]) -> Vec<(usize, &ChatMessage, &ChatMessage)> {
+    messages
+        .iter()
+        .enumerate()
+        .skip(1)
+        .filter(|(i, message)| message.role == "assistant" && messages[i - 1].role == "user")
+        .map(|(i, message)| (i, &messages[i - 1], message))
+        .collect()
+}
+
+// ファインチューニング用JSONLを書き出す
+async fn export_finetune(
+    base_dir: &str,
+    endpoints: &[Endpoint],
+    options: &ExportOptions,
+) -> Result<String> {
+    let output = options
+        .output
+        .clone()
+        .unwrap_or_else(|| format!("{}/finetune.jsonl", base_dir));
+
+    // スコアで絞り込む場合は判定用モデルを使う
+    let judge_client = match (options.min_score, endpoints.first()) {
+        (Some(_), Some(endpoint)) => Some(LlmClient::new(endpoint.clone())),
+        (Some(_), None) => bail!("採点に使用できるエンドポイントがありません"),
+        (None, _) => None,
+    };
+    let mut score_cache = ScoreCache::load(base_dir).await;
+
+    let mut lines = Vec::new();
+    let mut skipped = 0;
+
+    for (path, response) in select_debates(base_dir, options) {
+        let Some(system) = response.messages.iter().find(|m| m.role == "system") else {
+            continue;
+        };
+
+        for (index, question, answer) in extract_triples(&response.messages) {
+            if let (Some(min_score), Some(client)) = (options.min_score, &judge_client) {
+                let key = ScoreCache::key(&path, index);
+                let score = match score_cache.get(&key) {
+                    Some(score) => score,
+                    None => {
+                        match judge::score_answer(
+                            client,
+                            &options.judge_model,
+                            &question.content,
+                            &answer.content,
+                        )
+                        .await
+                        {
+                            Ok(score) => {
+                                score_cache.insert(key, score);
+                                score
+                            }
+                            Err(e) => {
+                                error!("⚠️ 採点エラー: {} - {}", path.display(), e);
+                                continue;
+                            }
+                        }
+                    }
+                };
+
+                if score < min_score {
+                    skipped += 1;
+                    continue;
+                }
+            }
+
+            let example = json!({
+                "messages": [
+                    { "role": "system", "content": system.content },
+                    { "role": "user", "content": question.content },
+                    { "role": "assistant", "content": answer.content },
+                ]
+            });
+            lines.push(serde_json::to_string(&example)?);
+        }
+    }
+
+    if options.min_score.is_some() {
+        score_cache.save().await?;
+    }
+
+    fs::write(&output, lines.join("\n") + "\n").await?;
+
+    info!(
+        "✅ ファインチューニング用データを書き出しました: {} ({}件, スコア不足で除外: {}件)",
+        output,
+        lines.len(),
+        skipped
+    );
+
+    Ok(output)
+}
+
+// Markdown の引用にする
+fn quote(text: &str) -> String {
+    text.lines()
+        .map(|line| format!("> {}", line).trim_end().to_string())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// 1つの議論を章の節にする（質問は引用、回答はそのまま）
+fn render_debate(response: &ResponseData) -> String {
+    let mut heading = response.endpoint.clone();
+    if let Some(file) = &response.file {
+        heading = format!("{}（{}）", file, response.endpoint);
+    }
+    let timestamp = DateTime::parse_from_rfc3339(&response.timestamp)
+        .map(|t| t.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_else(|_| response.timestamp.clone());
+    let mut section = format!(
+        "## {}\n\n{} ターン・{}{}\n",
+        heading,
+        response.turn,
+        timestamp,
+        response
+            .run_id
+            .as_ref()
+            .map(|run_id| format!("・実行 `{}`", run_id))
+            .unwrap_or_default()
+    );
+
+    let mut turn = 1;
+    let mut question: Option<&str> = None;
+    for message in &response.messages {
+        match message.role.as_str() {
+            "user" => question = Some(&message.content),
+            "assistant" => {
+                section.push_str(&format!("\n### ターン {}\n\n", turn));
+                if let Some(question) = question.take() {
+                    section.push_str(&format!("{}\n\n", quote(question)));
+                }
+                section.push_str(message.content.trim());
+                section.push('\n');
+                turn += 1;
+            }
+            _ => {}
+        }
+    }
+    section
+}
+
+// mdBook のソースを書き出す
+// src/SUMMARY.md にリポジトリごとの部を作り、サマリーを部の最初のページ、議論タイプを章にする
+async fn export_mdbook(base_dir: &str, options: &ExportOptions) -> Result<String> {
+    let output = options
+        .output
+        .clone()
+        .unwrap_or_else(|| format!("{}/book", base_dir));
+    let src_dir = format!("{}/src", output);
+
+    // リポジトリ → 議論タイプ → 議論
+    let mut repos: BTreeMap<String, BTreeMap<String, Vec<ResponseData>>> = BTreeMap::new();
+    for (_, response) in select_debates(base_dir, options) {
+        repos
+            .entry(response.repo.clone())
+            .or_default()
+            .entry(response.debate_type.clone())
+            .or_default()
+            .push(response);
+    }
+    if repos.is_empty() {
+        bail!("エクスポートする分析結果がありません: {}", base_dir);
+    }
+
+    let mut summary = String::from("# Summary\n\n[はじめに](README.md)\n");
+    let mut introduction = String::from(
+        "# 分析結果\n\nAzure Credit Burner で保存した議論の結果です。\n\n| リポジトリ | 議論タイプ | 議論数 |\n|---|---:|---:|\n",
+    );
+    let mut chapters = 0;
+    for (repo, debates) in &repos {
+        let repo_dir = outputs::path_segment(repo);
+        fs::create_dir_all(format!("{}/{}", src_dir, repo_dir)).await?;
+
+        // サマリーがあれば部の最初のページにする
+        let summary_file = format!("{}/{}/summary.md", base_dir, repo_dir);
+        let mut index = match encryption::read_to_string(&summary_file) {
+            Ok(content) => format!("# {}\n\n{}\n", repo, content.trim()),
+            Err(_) => format!(
+                "# {}\n\nサマリーはまだ生成されていません（`summarize`）。\n",
+                repo
+            ),
+        };
+        index.push_str("\n## 議論タイプ\n\n");
+        summary.push_str(&format!(
+            "\n# {}\n\n- [サマリー]({}/README.md)\n",
+            repo, repo_dir
+        ));
+
+        for (debate_type, responses) in debates {
+            let chapter = format!("{}.md", outputs::path_segment(debate_type));
+            let mut content = format!("# {}\n", debate_type);
+            for response in responses {
+                content.push('\n');
+                content.push_str(&render_debate(response));
+            }
+            fs::write(format!("{}/{}/{}", src_dir, repo_dir, chapter), content).await?;
+
+            index.push_str(&format!("- [{}]({})\n", debate_type, chapter));
+            summary.push_str(&format!(
+                "  - [{}]({}/{})\n",
+                debate_type, repo_dir, chapter
+            ));
+            chapters += 1;
+        }
+        fs::write(format!("{}/{}/README.md", src_dir, repo_dir), index).await?;
+
+        let count: usize = debates.values().map(Vec::len).sum();
+        introduction.push_str(&format!(
+            "| [{}]({}/README.md) | {} | {} |\n",
+            repo,
+            repo_dir,
+            debates.len(),
+            count
+        ));
+    }
+
+    fs::write(format!("{}/SUMMARY.md", src_dir), summary).await?;
+    fs::write(format!("{}/README.md", src_dir), introduction).await?;
+    // 既存の book.toml（テーマなどの設定）は上書きしない
+    let book_toml = format!("{}/book.toml", output);
+    if fs::metadata(&book_toml).await.is_err() {
+        fs::write(
+            &book_toml,
+            "[book]\ntitle = \"Azure Credit Burner 分析結果\"\nlanguage = \"ja\"\nsrc = \"src\"\n",
+        )
+        .await?;
+    }
+
+    info!(
+        "📚 mdBook のソースを書き出しました: {} ({} リポジトリ, {} 章)",
+        output,
+        repos.len(),
+        chapters
+    );
+
+    Ok(output)
+}
+
+// 会話を ChatML のテキストにする（ツールの呼び出しは引数の JSON を本文にする）
+fn render_chatml(messages: &[ChatMessage]) -> String {
+    messages
+        .iter()
+        .map(|message| {
+            let mut content = message.content.clone();
+            for call in &message.tool_calls {
+                if !content.is_empty() {
+                    content.push('\n');
+                }
+                content.push_str(&serde_json::to_string(call).unwrap_or_default());
+            }
+            format!("<|im_start|>{}\n{}<|im_end|>", message.role, content)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// 議論ごとに1行の JSONL を書き出す（ChatML のテキストまたは OpenAI のチャット形式）
+async fn export_conversations(base_dir: &str, options: &ExportOptions) -> Result<String> {
+    let (default_name, label) = match options.format {
+        ExportFormat::Chatml => ("chatml.jsonl", "ChatML"),
+        _ => ("conversations.jsonl", "OpenAI のチャット形式"),
+    };
+    let output = options
+        .output
+        .clone()
+        .unwrap_or_else(|| format!("{}/{}", base_dir, default_name));
+
+    let mut lines = Vec::new();
+    for (_, response) in select_debates(base_dir, options) {
+        let line = match options.format {
+            ExportFormat::Chatml => json!({ "text": render_chatml(&response.messages) }),
+            _ => json!({ "messages": response.messages }),
+        };
+        lines.push(serde_json::to_string(&line)?);
+    }
+    if lines.is_empty() {
+        bail!("エクスポートする分析結果がありません: {}", base_dir);
+    }
+
+    fs::write(&output, lines.join("\n") + "\n").await?;
+
+    info!(
+        "✅ 会話を{}で書き出しました: {} ({}件)",
+        label,
+        output,
+        lines.len()
+    );
+
+    Ok(output)
+}
+
+// 会話を1つの Markdown に書き出す（リポジトリと議論タイプごとの見出しの下に議論を並べる）
+async fn export_markdown(base_dir: &str, options: &ExportOptions) -> Result<String> {
+    let output = options
+        .output
+        .clone()
+        .unwrap_or_else(|| format!("{}/conversations.md", base_dir));
+
+    let debates = select_debates(base_dir, options);
+    if debates.is_empty() {
+        bail!("エクスポートする分析結果がありません: {}", base_dir);
+    }
+
+    // (リポジトリ, 議論タイプ) → 議論（議論の見出しより1つ上の見出しにする）
+    let mut groups: BTreeMap<(&str, &str), Vec<&ResponseData>> = BTreeMap::new();
+    for (_, response) in &debates {
+        groups
+            .entry((&response.repo, &response.debate_type))
+            .or_default()
+            .push(response);
+    }
+
+    let mut content = String::new();
+    for ((repo, debate_type), responses) in &groups {
+        if !content.is_empty() {
+            content.push('\n');
+        }
+        content.push_str(&format!("# {} / {}\n", repo, debate_type));
+        for response in responses {
+            content.push('\n');
+            content.push_str(&render_debate(response));
+        }
+    }
+
+    fs::write(&output, content).await?;
+
+    info!(
+        "📝 会話を Markdown で書き出しました: {} ({}件)",
+        output,
+        debates.len()
+    );
+
+    Ok(output)
+}
+
+// 指定された形式でエクスポート
+pub async fn run_export(
+    base_dir: &str,
+    endpoints: &[Endpoint],
+    options: &ExportOptions,
+) -> Result<String> {
+    let output = match options.format {
+        ExportFormat::Finetune => export_finetune(base_dir, endpoints, options).await?,
+        ExportFormat::Mdbook => export_mdbook(base_dir, options).await?,
+        ExportFormat::Chatml | ExportFormat::OpenaiJsonl => {
+            export_conversations(base_dir, options).await?
+        }
+        ExportFormat::Markdown => export_markdown(base_dir, options).await?,
+    };
+    // エクスポートは他のツールで読むためのものなので、暗号化が有効でも平文で書き出す
+    encryption::warn_plaintext("エクスポート", &output);
+    Ok(output)
+}
@@ -0,0 +1,284 @@
+// ファイル内容のキャッシュモジュール
+// 読み込んだファイルの内容とトークン数を Git の blob の SHA ごとに file_cache.jsonl に残し、
+// 実行をまたいで同じリポジトリを分析するときに、変わっていないファイルの読み込みとトークン数の計算を省く
+// （blob の SHA は内容だけで決まるため、別のリポジトリやブランチの同じ内容のファイルにも使える）
+// ファイルの内容をそのまま含むため、暗号化が有効なら行ごとに暗号化し、保持期間の整理の対象にもする
+
+use anyhow::Result;
+use chrono::prelude::*;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tokio::process::Command;
+
+use crate::encryption;
+use crate::llm::schemas::github_response::{FileInfo, FileLimits};
+
+// ファイル内容のキャッシュの設定
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct FileCacheConfig {
+    /// 読み込んだファイルの内容を blob の SHA ごとにキャッシュするか
+    pub enabled: bool,
+    /// キャッシュのファイル（省略時は <output_dir>/file_cache.jsonl）
+    pub path: Option<String>,
+}
+
+// キャッシュの1行（1つの blob）
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct CacheEntry {
+    blob: String,
+    // 読み込んだときのファイルの読み込みの制限（切り詰め方が変わったら使わない）
+    limits: FileLimits,
+    content: String,
+    tokens: usize,
+    // キャッシュした日時（保持期間を過ぎたら整理で取り除く。以前の行にはない）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    cached_at: Option<String>,
+}
+
+type CacheKey = (String, FileLimits);
+
+// キャッシュ（blob の SHA と読み込みの制限ごとの内容とトークン数）
+struct FileCache {
+    path: PathBuf,
+    entries: HashMap<CacheKey, CacheEntry>,
+}
+
+static CACHE: Mutex<Option<FileCache>> = Mutex::new(None);
+
+// 読み込んだキャッシュのファイル
+struct Loaded {
+    entries: HashMap<CacheKey, CacheEntry>,
+    // 空でない行の数（同じ blob の行や読み込めない行があれば entries より多い）
+    lines: usize,
+    // 暗号化していない行の数
+    plaintext: usize,
+}
+
+// キャッシュのファイルのパス
+fn cache_path(config: &FileCacheConfig, output_dir: &str) -> PathBuf {
+    PathBuf::from(
+        config
+            .path
+            .clone()
+            .unwrap_or_else(|| format!("{}/file_cache.jsonl", output_dir)),
+    )
+}
+
+// キャッシュのファイルを読み込む（同じ blob の行は最後の行を使う）
+fn load(path: &Path) -> Loaded {
+    let mut loaded = Loaded {
+        entries: HashMap::new(),
+        lines: 0,
+        plaintext: 0,
+    };
+    let Ok(content) = fs::read_to_string(path) else {
+        return loaded;
+    };
+    let now = Utc::now().to_rfc3339();
+    for line in content.lines().filter(|line| !line.trim().is_empty()) {
+        loaded.lines += 1;
+        if line.starts_with('{') {
+            loaded.plaintext += 1;
+        }
+        // 書き込み中に止まった行や復号できない行は読み飛ばす（その blob は読み込み直す）
+        let Ok(mut entry) = encryption::open_line(line)
+            .and_then(|line| serde_json::from_str::<CacheEntry>(&line).map_err(Into::into))
+        else {
+            continue;
+        };
+        entry.cached_at.get_or_insert_with(|| now.clone());
+        loaded
+            .entries
+            .insert((entry.blob.clone(), entry.limits), entry);
+    }
+    loaded
+}
+
+// キャッシュのファイルを書き直す（一時ファイルに書いてから置き換える）
+fn rewrite<'a>(path: &Path, entries: impl Iterator<Item = &'a CacheEntry>) -> Result<()> {
+    let temp = path.with_extension("jsonl.tmp");
+    let mut writer = BufWriter::new(fs::File::create(&temp)?);
+    for entry in entries {
+        writeln!(
+            writer,
+            "{}",
+            encryption::seal_line(serde_json::to_string(entry)?)?
+        )?;
+    }
+    writer.into_inner()?.sync_all()?;
+    fs::rename(&temp, path)?;
+    Ok(())
+}
+
+// キャッシュを開き、これまでの内容を読み込む
+// 同じ blob の行や読み込めない行が残っている場合と、暗号化が有効で暗号化していない行がある場合は書き直す
+pub fn configure(config: &FileCacheConfig, output_dir: &str) {
+    if !config.enabled {
+        return;
+    }
+    let path = cache_path(config, output_dir);
+    let loaded = load(&path);
+    let entries = loaded.entries;
+    if loaded.lines > entries.len() || (encryption::enabled() && loaded.plaintext > 0) {
+        match rewrite(&path, entries.values()) {
+            Ok(()) => info!(
+                "💾 ファイル内容のキャッシュを整理しました: {} 行 → {} 件",
+                loaded.lines,
+                entries.len()
+            ),
+            Err(e) => warn!(
+                "⚠️ ファイル内容のキャッシュを整理できません: {} - {}",
+                path.display(),
+                e
+            ),
+        }
+    }
+    info!(
+        "💾 ファイル内容のキャッシュ: {} ({} 件)",
+        path.display(),
+        entries.len()
+    );
+    *CACHE.lock().unwrap() = Some(FileCache { path, entries });
+}
+
+// 保持期間を過ぎたキャッシュの内容を取り除く（キャッシュのファイルがなければ何もしない）
+pub fn prune(
+    config: &FileCacheConfig,
+    output_dir: &str,
+    cutoff: DateTime<Utc>,
+    dry_run: bool,
+) -> Result<()> {
+    let path = cache_path(config, output_dir);
+    if !path.exists() {
+        return Ok(());
+    }
+    let loaded = load(&path);
+    let kept: Vec<&CacheEntry> = loaded
+        .entries
+        .values()
+        .filter(|entry| {
+            entry
+                .cached_at
+                .as_deref()
+                .and_then(|cached_at| DateTime::parse_from_rfc3339(cached_at).ok())
+                .is_some_and(|cached_at| cached_at >= cutoff)
+        })
+        .collect();
+    let removed = loaded.entries.len() - kept.len();
+    if removed == 0 && loaded.lines == kept.len() {
+        return Ok(());
+    }
+    if !dry_run {
+        rewrite(&path, kept.iter().copied())?;
+    }
+    info!(
+        "🧹 ファイル内容のキャッシュから古い内容 {} 件を削除{}（残り {} 件）: {}",
+        removed,
+        if dry_run { "します" } else { "しました" },
+        kept.len(),
+        path.display()
+    );
+    Ok(())
+}
+
+// クローンしたリポジトリのファイルの blob の SHA（リポジトリのルートからの相対パスごと。無効なら空）
+pub async fn blob_shas(repo_dir: &Path) -> HashMap<String, String> {
+    if CACHE.lock().unwrap().is_none() {
+        return HashMap::new();
+    }
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo_dir)
+        .args(["ls-files", "--stage", "-z"])
+        .output()
+        .await;
+    match output {
+        // 各行は「<モード> <SHA> <ステージ>\t<パス>」
+        Ok(output) if output.status.success() => String::from_utf8_lossy(&output.stdout)
+            .split('\0')
+            .filter_map(|record| {
+                let (info, path) = record.split_once('\t')?;
+                let mut fields = info.split(' ');
+                let mode = fields.next()?;
+                let sha = fields.next()?;
+                // サブモジュール（コミット）は除く
+                (mode != "160000").then(|| (path.to_string(), sha.to_string()))
+            })
+            .collect(),
+        Ok(output) => {
+            warn!(
+                "⚠️ blob の SHA を取得できません: {} - {}",
+                repo_dir.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+            HashMap::new()
+        }
+        Err(e) => {
+            warn!(
+                "⚠️ blob の SHA を取得できません: {} - {}",
+                repo_dir.display(),
+                e
+            );
+            HashMap::new()
+        }
+    }
+}
+
+// キャッシュしたファイルの内容（なければ None）
+pub fn get(blob: &str, rel_path: &str, limits: &FileLimits) -> Option<FileInfo> {
+    let cache = CACHE.lock().unwrap();
+    let entry = cache.as_ref()?.entries.get(&(blob.to_string(), *limits))?;
+    Some(FileInfo {
+        path: rel_path.to_string(),
+        content: entry.content.clone(),
+        tokens: entry.tokens,
+    })
+}
+
+// 読み込んだファイルの内容をキャッシュに加える（暗号化が有効なら行ごとに暗号化する）
+pub fn put(blob: &str, file: &FileInfo, limits: &FileLimits) {
+    let mut cache = CACHE.lock().unwrap();
+    let Some(cache) = cache.as_mut() else {
+        return;
+    };
+    let key = (blob.to_string(), *limits);
+    if cache.entries.contains_key(&key) {
+        return;
+    }
+    let entry = CacheEntry {
+        blob: blob.to_string(),
+        limits: *limits,
+        content: file.content.clone(),
+        tokens: file.tokens,
+        cached_at: Some(Utc::now().to_rfc3339()),
+    };
+    let result = serde_json::to_string(&entry)
+        .map_err(anyhow::Error::from)
+        .and_then(encryption::seal_line)
+        .and_then(|line| {
+            if let Some(dir) = cache.path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            let mut writer = fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&cache.path)?;
+            writeln!(writer, "{}", line)?;
+            Ok(())
+        });
+    match result {
+        Ok(()) => {
+            cache.entries.insert(key, entry);
+        }
+        Err(e) => warn!(
+            "⚠️ ファイル内容をキャッシュできません: {} - {}",
+            file.path, e
+        ),
+    }
+}
@@ -0,0 +1,112 @@
+// ファイル内容の検査モジュール
+// 読み込むファイルがバイナリ（NUL バイトを含む）か、1行が極端に長いファイル（ミニファイされたバンドルなど）かを調べる
+// バイナリは読み飛ばし、1行が長いファイルは読み飛ばすか短い要約に置き換える
+// （UTF-8 として読めずにエラーになったり、役に立たない内容でプロンプトが膨らんだりしないようにする）
+
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+// 1行が長いファイルの扱い
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LongLinePolicy {
+    /// 読み飛ばす
+    Skip,
+    /// 行数・最長の行の長さと先頭部分だけの要約に置き換える
+    #[default]
+    Summarize,
+}
+
+// ファイル内容の検査の設定
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct FileGuardConfig {
+    /// NUL バイトを探す先頭のバイト数（0 ならファイル全体）
+    pub binary_check_bytes: usize,
+    /// 1行の最大文字数（これを超える行があるファイルを1行が長いファイルとみなす）
+    pub max_line_chars: usize,
+    /// 1行が長いファイルの扱い（skip または summarize）
+    pub long_lines: LongLinePolicy,
+    /// 要約に含める先頭部分の文字数
+    pub summary_chars: usize,
+}
+
+impl Default for FileGuardConfig {
+    fn default() -> Self {
+        FileGuardConfig {
+            binary_check_bytes: 8000,
+            max_line_chars: 2000,
+            long_lines: LongLinePolicy::Summarize,
+            summary_chars: 300,
+        }
+    }
+}
+
+// ファイル内容の検査の結果
+pub enum Verdict {
+    /// そのまま使う
+    Keep(String),
+    /// 読み飛ばす（「〜のため」の形の理由）
+    Skip(String),
+    /// 要約に置き換える
+    Summarize(String),
+}
+
+static CONFIG: OnceLock<FileGuardConfig> = OnceLock::new();
+
+// ファイル内容の検査の設定を登録する
+pub fn configure(config: &FileGuardConfig) {
+    let _ = CONFIG.set(config.clone());
+}
+
+fn config() -> FileGuardConfig {
+    CONFIG.get().cloned().unwrap_or_default()
+}
+
+// バイナリか（先頭に NUL バイトを含む）
+pub fn is_binary(bytes: &[u8]) -> bool {
+    let check_bytes = match config().binary_check_bytes {
+        0 => bytes.len(),
+        check_bytes => check_bytes.min(bytes.len()),
+    };
+    bytes[..check_bytes].contains(&0)
+}
+
+// 読み込んだファイルの内容を検査する
+pub fn inspect(rel_path: &str, bytes: Vec<u8>) -> Verdict {
+    let config = config();
+    if is_binary(&bytes) {
+        return Verdict::Skip("バイナリのため".to_string());
+    }
+    let content = match String::from_utf8(bytes) {
+        Ok(content) => content,
+        Err(_) => return Verdict::Skip("UTF-8 のテキストではないため".to_string()),
+    };
+
+    let longest = content
+        .lines()
+        .map(|line| line.chars().count())
+        .max()
+        .unwrap_or_default();
+    if config.max_line_chars == 0 || longest <= config.max_line_chars {
+        return Verdict::Keep(content);
+    }
+    match config.long_lines {
+        LongLinePolicy::Skip => Verdict::Skip(format!("1行が長いため（最長 {} 文字）", longest)),
+        LongLinePolicy::Summarize => {
+            info!(
+                "✂️ 1行が長いため要約に置き換え: {} (最長 {} 文字)",
+                rel_path, longest
+            );
+            let head: String = content.chars().take(config.summary_chars).collect();
+            Verdict::Summarize(format!(
+                "(1行が長いファイル（ミニファイされたコードや生成されたファイルなど）のため内容を省略: {} 行, {} 文字, 最長の行 {} 文字)\n先頭部分:\n{}...",
+                content.lines().count(),
+                content.chars().count(),
+                longest,
+                head
+            ))
+        }
+    }
+}
@@ -0,0 +1,333 @@
+// ファイル単位のレビューモジュール
+// 選択したファイルごとに短いレビューの会話を行い、エンドポイントをまたいで並行に実行する
+// （リポジトリ全体の議論より細かいレビューが得られ、リクエスト数もファイル数に比例して増える）
+
+use anyhow::{Result, anyhow, bail};
+use chrono::prelude::*;
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::{fs, time};
+
+use crate::error::BurnerError;
+use crate::generation::Generation;
+use crate::journal::Journal;
+use crate::llm::schemas::{
+    github_response::RepoInfo,
+    openai_response::{ChatMessage, RESPONSE_SCHEMA_VERSION, ResponseData},
+};
+use crate::scheduler::Scheduler;
+use crate::work_queue::DebateJob;
+use crate::{
+    Config, DebateOutcome, GitHubClient, LlmClient, budget, deadline, dedupe, encryption, findings,
+    generation, ledger, outputs, runs,
+};
+
+// ファイル単位のレビューの設定
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct FileReviewConfig {
+    /// ファイル単位のレビューを有効にするか（リポジトリ全体の議論に加えて実行する）
+    pub enabled: bool,
+    /// 1ファイルあたりのターン数
+    pub turns: usize,
+    /// リポジトリあたりのレビューするファイル数（省略時はリポジトリの max_files）
+    pub max_files: Option<usize>,
+    /// プロンプトに含めるファイル内容の最大文字数
+    pub max_chars: usize,
+    /// 1ターンの最大出力トークン数
+    pub max_tokens: usize,
+}
+
+impl Default for FileReviewConfig {
+    fn default() -> Self {
+        FileReviewConfig {
+            enabled: false,
+            turns: 2,
+            max_files: None,
+            max_chars: 12000,
+            max_tokens: 2000,
+        }
+    }
+}
+
+// ファイル単位のレビューの議論タイプ（保存する結果とジャーナルに記録する）
+pub const FILE_REVIEW_TYPE: &str = "ファイル単位レビュー";
+
+// 2ターン目以降の質問（順に出題する）
+const FOLLOW_UP_QUESTIONS: [&str; 3] = [
+    "指摘した問題のうち最も重要なものについて、修正後のコード例を示してください。",
+    "このファイルに追加すべきテストケースを、入力と期待する結果を含めて具体的に挙げてください。",
+    "このファイルを読みやすく保守しやすくするためのリファクタリング案を、手順を追って提案してください。",
+];
+
+// リポジトリごとに、レビューするファイルの議論を作成
+pub async fn plan_jobs(
+    github_client: &GitHubClient,
+    repos: &[RepoInfo],
+    config: &FileReviewConfig,
+) -> Vec<DebateJob> {
+    let mut jobs = Vec::new();
+    for repo_info in repos {
+        let max_files = config.max_files.unwrap_or(repo_info.max_files);
+        match github_client.collect_repo_files(repo_info, max_files).await {
+            Ok(files) => {
+                info!(
+                    "📄 ファイル単位のレビューを追加します: {}/{} - {} ファイル",
+                    repo_info.owner,
+                    repo_info.repo,
+                    files.len()
+                );
+                jobs.extend(
+                    files
+                        .into_iter()
+                        .map(|file| DebateJob::file_review(repo_info.clone(), file.path)),
+                );
+            }
+            Err(e) => {
+                error!(
+                    "❌ ファイル単位のレビューのファイル一覧を取得できません: {}/{} - {}",
+                    repo_info.owner, repo_info.repo, e
+                );
+            }
+        }
+    }
+    jobs
+}
+
+// レビューを依頼するシステムプロンプトと最初の質問
+fn review_prompt(
+    repo_info: &RepoInfo,
+    path: &str,
+    content: &str,
+    max_chars: usize,
+) -> (String, String) {
+    let content = if content.chars().count() > max_chars {
+        let truncated: String = content.chars().take(max_chars).collect();
+        format!("{}...\n(省略)...", truncated)
+    } else {
+        content.to_string()
+    };
+
+    let system_prompt = format!(
+        r#"あなたは経験豊富なソフトウェアエンジニアとして、GitHubリポジトリ「{}/{}」のファイル「{}」をレビューします。
+このファイルだけに集中し、行や関数を具体的に引用しながら指摘してください。
+
+【ファイル内容】
+--- {} ---
+{}"#,
+        repo_info.owner, repo_info.repo, path, path, content
+    );
+    let initial_message = format!(
+        "「{}」をレビューしてください。このファイルの役割を簡潔にまとめたうえで、バグや潜在的な不具合、セキュリティ上の問題、パフォーマンスの問題を重要度の高い順に挙げてください。",
+        path
+    );
+    (system_prompt, initial_message)
+}
+
+// ターンの次の質問
+fn follow_up_question(turn: usize) -> String {
+    FOLLOW_UP_QUESTIONS[(turn - 1) % FOLLOW_UP_QUESTIONS.len()].to_string()
+}
+
+// レビュー結果を保存（リポジトリの出力ディレクトリの files/ 以下）
+async fn save_review(
+    base_dir: &str,
+    repo_info: &RepoInfo,
+    path: &str,
+    response_data: &mut ResponseData,
+) -> Result<Option<String>> {
+    // リポジトリの議論の応答と合わせて重複を検出する
+    if dedupe::check(base_dir, repo_info, response_data) {
+        return Ok(None);
+    }
+    let review_dir = format!(
+        "{}/files",
+        outputs::repo_dirs(base_dir, &repo_info.owner, &repo_info.repo).remove(0)
+    );
+    fs::create_dir_all(&review_dir).await?;
+
+    let filename = format!(
+        "{}/{}_{}_turn{}_{}.json",
+        review_dir,
+        outputs::path_segment(path),
+        outputs::path_segment(&response_data.endpoint),
+        response_data.turn,
+        Utc::now().format("%Y%m%d_%H%M%S")
+    );
+    let json_data = encryption::seal(serde_json::to_string_pretty(response_data)?)?;
+    fs::write(&filename, json_data).await?;
+    dedupe::remember(response_data, &filename);
+
+    Ok(Some(filename))
+}
+
+// 1ファイルのレビューを実行
+#[allow(clippy::too_many_arguments)]
+pub async fn review_runner(
+    github_client: &GitHubClient,
+    openai_client: &LlmClient,
+    job: &DebateJob,
+    path: &str,
+    endpoint_index: usize,
+    config: &Config,
+    scheduler: &Scheduler,
+    journal: &Journal,
+) -> Result<DebateOutcome> {
+    let endpoint_name = &openai_client.endpoint.name;
+    let repo_info = &job.repo_info;
+    let turns = config.file_review.turns.max(1);
+
+    // 途中まで保存されたレビューがあれば、その続きから再開する
+    let (mut messages, mut turn) = match journal.load_progress(&job.task_id, &job.repo_info) {
+        Some(saved) if saved.turn >= turns => return Ok(DebateOutcome::Completed),
+        Some(saved) => {
+            let mut messages = saved.messages;
+            messages.push(ChatMessage {
+                role: "user".to_string(),
+                content: follow_up_question(saved.turn),
+                ..Default::default()
+            });
+            (messages, saved.turn + 1)
+        }
+        None => {
+            let repo_dir = github_client.clone_repository(repo_info).await?;
+            let content = fs::read_to_string(repo_dir.join(path))
+                .await
+                .map_err(|e| anyhow!("レビューするファイルを読み込めません: {} - {}", path, e))?;
+            let (system_prompt, initial_message) =
+                review_prompt(repo_info, path, &content, config.file_review.max_chars);
+            let messages = vec![
+                ChatMessage {
+                    role: "system".to_string(),
+                    content: system_prompt,
+                    ..Default::default()
+                },
+                ChatMessage {
+                    role: "user".to_string(),
+                    content: initial_message,
+                    ..Default::default()
+                },
+            ];
+            (messages, 1)
+        }
+    };
+
+    info!(
+        "[{}] 📄 ファイルレビュー開始: {}/{} - {}",
+        endpoint_name, repo_info.owner, repo_info.repo, path
+    );
+
+    let mut consecutive_errors = 0;
+    while turn <= turns {
+        // 実行期限が近いか使用額の上限に達していれば新しいターンを開始しない（保存済みのターンから次回再開する）
+        if deadline::reached() || ledger::ceiling_reached() {
+            return Ok(DebateOutcome::Interrupted);
+        }
+        if budget::exhausted(repo_info) {
+            return Ok(DebateOutcome::OverBudget);
+        }
+
+        openai_client.reset_metrics();
+        let started = std::time::Instant::now();
+        let generation = Generation {
+            max_tokens: config.file_review.max_tokens,
+            ..generation::resolve(&openai_client.endpoint, &job.debate_type)
+        };
+        let completion = openai_client.chat_completion_with_fallback(&messages, &generation, &[]);
+        let limit = config
+            .timeouts
+            .turn_for(generation::may_reason(&openai_client.endpoint, &generation));
+        match deadline::with_timeout(limit, "ターン", completion).await {
+            Ok((message, tokens_used, answered)) => {
+                let response = message.content;
+                consecutive_errors = 0;
+                scheduler.record(endpoint_index, tokens_used, started.elapsed());
+                budget::record(repo_info, tokens_used);
+
+                messages.push(ChatMessage {
+                    role: "assistant".to_string(),
+                    content: response,
+                    ..Default::default()
+                });
+
+                let mut response_data = ResponseData {
+                    schema_version: RESPONSE_SCHEMA_VERSION,
+                    repo: format!("{}/{}", repo_info.owner, repo_info.repo),
+                    debate_type: FILE_REVIEW_TYPE.to_string(),
+                    turn,
+                    timestamp: Utc::now().to_rfc3339(),
+                    endpoint: endpoint_name.clone(),
+                    category: Some(FILE_REVIEW_TYPE.to_string()),
+                    messages: messages.clone(),
+                    tokens_used,
+                    context_summary: None,
+                    run_id: runs::current_id(),
+                    file: Some(path.to_string()),
+                    metrics: Some(openai_client.take_metrics(started.elapsed())),
+                    task_id: Some(job.task_id.clone()),
+                    rejected: Vec::new(),
+                    content_hash: None,
+                    duplicate_of: None,
+                    model: Some(answered.model),
+                    draft: None,
+                };
+                match save_review(&config.output_dir, repo_info, path, &mut response_data).await {
+                    Ok(Some(filename)) => {
+                        info!(
+                            "[{}] 保存完了: {} (トークン数: {}{})",
+                            endpoint_name,
+                            filename,
+                            tokens_used,
+                            ledger::cost_note(&response_data)
+                        );
+                        journal.record_turn(&job.task_id, &response_data, &filename);
+                    }
+                    Ok(None) => {}
+                    Err(e) => {
+                        error!(
+                            "[{}] 保存エラー: {}/{} - {} - ターン {} - {}",
+                            endpoint_name, repo_info.owner, repo_info.repo, path, turn, e
+                        );
+                    }
+                }
+
+                messages.push(ChatMessage {
+                    role: "user".to_string(),
+                    content: follow_up_question(turn),
+                    ..Default::default()
+                });
+                turn += 1;
+            }
+            Err(e) => {
+                warn!(
+                    "[{}] OpenAI API エラー: {}/{} - {} - ターン {} - {}",
+                    endpoint_name, repo_info.owner, repo_info.repo, path, turn, e
+                );
+                if BurnerError::of(&e).is_some_and(|error| !error.is_retryable()) {
+                    return Err(e);
+                }
+                consecutive_errors += 1;
+                if consecutive_errors >= 3 {
+                    bail!("OpenAI API 呼び出しに複数回失敗しました。終了します。");
+                }
+                time::sleep(Duration::from_secs(1)).await;
+            }
+        }
+    }
+
+    if config.findings.enabled {
+        findings::extract_and_save(
+            openai_client,
+            &config.output_dir,
+            &config.findings,
+            repo_info,
+            FILE_REVIEW_TYPE,
+            Some(path),
+            &messages,
+        )
+        .await;
+    }
+
+    Ok(DebateOutcome::Completed)
+}
@@ -0,0 +1,231 @@
+// 指摘事項の抽出モジュール
+// 完了した議論の会話から、重要度・ファイル・内容・推奨対応を持つ指摘事項の一覧を
+// JSON スキーマを指定した構造化出力で取り出し、議論の結果と並べて保存する（他のツールで機械的に扱えるようにする）
+
+use anyhow::{Result, anyhow};
+use chrono::prelude::*;
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use tokio::fs;
+
+use crate::llm::schemas::{github_response::RepoInfo, openai_response::ChatMessage};
+use crate::{LlmClient, budget, encryption, generation, outputs, runs};
+
+// 指摘事項の抽出の設定
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct FindingsConfig {
+    /// 議論の完了後に指摘事項を抽出するか
+    pub enabled: bool,
+    /// 抽出に使うデプロイメント名（省略時は議論と同じモデル）
+    pub deployment: Option<String>,
+    /// 抽出の最大出力トークン数
+    pub max_tokens: usize,
+}
+
+impl Default for FindingsConfig {
+    fn default() -> Self {
+        FindingsConfig {
+            enabled: false,
+            deployment: None,
+            max_tokens: 4000,
+        }
+    }
+}
+
+// 指摘事項の重要度
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Critical,
+    High,
+    Medium,
+    Low,
+    Info,
+}
+
+// 指摘事項
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Finding {
+    pub severity: Severity,
+    // 指摘の対象のファイル（リポジトリ全体に関する指摘では null）
+    pub file: Option<String>,
+    pub description: String,
+    pub recommendation: String,
+}
+
+// 保存する指摘事項の一覧
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FindingsReport {
+    pub repo: String,
+    pub debate_type: String,
+    // ファイル単位のレビューで対象にしたファイルのパス
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_file: Option<String>,
+    pub timestamp: String,
+    pub endpoint: String,
+    pub findings: Vec<Finding>,
+    pub tokens_used: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run_id: Option<String>,
+}
+
+// 抽出の指示
+const EXTRACT_PROMPT: &str = "あなたはコードレビューの記録を整理するアシスタントです。\
+以下のリポジトリ分析の会話から、具体的な問題点や改善提案を指摘事項として抜き出してください。\
+重要度は critical（重大な不具合・セキュリティ上の問題）、high、medium、low、info（参考情報）のいずれかとし、\
+対象のファイルが特定できる場合はリポジトリ内のパスを、できない場合は null を指定してください。\
+会話に含まれない指摘を作らないでください。";
+
+// 構造化出力の JSON スキーマ（strict モードではすべてのプロパティを必須にする）
+fn schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "findings": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "severity": {
+                            "type": "string",
+                            "enum": ["critical", "high", "medium", "low", "info"],
+                        },
+                        "file": { "type": ["string", "null"] },
+                        "description": { "type": "string" },
+                        "recommendation": { "type": "string" },
+                    },
+                    "required": ["severity", "file", "description", "recommendation"],
+                    "additionalProperties": false,
+                },
+            },
+        },
+        "required": ["findings"],
+        "additionalProperties": false,
+    })
+}
+
+// 構造化出力の応答
+#[derive(Deserialize)]
+struct ExtractResponse {
+    findings: Vec<Finding>,
+}
+
+// 会話をシステムプロンプトを除いたテキストにまとめる（回答のない最後の質問は含めない）
+fn transcript(messages: &[ChatMessage]) -> String {
+    let mut messages: Vec<&ChatMessage> = messages
+        .iter()
+        .filter(|message| message.role != "system")
+        .collect();
+    if messages
+        .last()
+        .is_some_and(|message| message.role == "user")
+    {
+        messages.pop();
+    }
+    messages
+        .iter()
+        .map(|message| match message.role.as_str() {
+            "user" => format!("【質問】\n{}", message.content),
+            _ => format!("【回答】\n{}", message.content),
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+// 会話から指摘事項を抽出
+async fn extract(
+    client: &LlmClient,
+    config: &FindingsConfig,
+    messages: &[ChatMessage],
+) -> Result<(Vec<Finding>, usize)> {
+    let request = vec![
+        ChatMessage {
+            role: "system".to_string(),
+            content: EXTRACT_PROMPT.to_string(),
+            ..Default::default()
+        },
+        ChatMessage {
+            role: "user".to_string(),
+            content: transcript(messages),
+            ..Default::default()
+        },
+    ];
+    let model = config
+        .deployment
+        .as_deref()
+        .unwrap_or(generation::default_model());
+    let (response, tokens_used) = client
+        .structured_completion(&request, model, config.max_tokens, "findings", schema())
+        .await?;
+    let parsed: ExtractResponse = serde_json::from_str(&response)
+        .map_err(|e| anyhow!("指摘事項のJSONを解析できません: {} - {}", e, response))?;
+    Ok((parsed.findings, tokens_used))
+}
+
+// 指摘事項を抽出して保存（失敗しても議論の結果には影響させない）
+pub async fn extract_and_save(
+    client: &LlmClient,
+    base_dir: &str,
+    config: &FindingsConfig,
+    repo_info: &RepoInfo,
+    debate_type: &str,
+    source_file: Option<&str>,
+    messages: &[ChatMessage],
+) {
+    let (findings, tokens_used) = match extract(client, config, messages).await {
+        Ok(extracted) => extracted,
+        Err(e) => {
+            error!(
+                "[{}] 指摘事項の抽出エラー: {}/{} ({}) - {}",
+                client.endpoint.name, repo_info.owner, repo_info.repo, debate_type, e
+            );
+            return;
+        }
+    };
+    budget::record(repo_info, tokens_used);
+
+    let report = FindingsReport {
+        repo: format!("{}/{}", repo_info.owner, repo_info.repo),
+        debate_type: debate_type.to_string(),
+        source_file: source_file.map(str::to_string),
+        timestamp: Utc::now().to_rfc3339(),
+        endpoint: client.endpoint.name.clone(),
+        findings,
+        tokens_used,
+        run_id: runs::current_id(),
+    };
+    match save(base_dir, repo_info, &report).await {
+        Ok(filename) => info!(
+            "[{}] 🔎 指摘事項を保存しました: {} ({} 件)",
+            client.endpoint.name,
+            filename,
+            report.findings.len()
+        ),
+        Err(e) => error!(
+            "[{}] 指摘事項の保存エラー: {}/{} ({}) - {}",
+            client.endpoint.name, repo_info.owner, repo_info.repo, debate_type, e
+        ),
+    }
+}
+
+// リポジトリの出力ディレクトリの findings/ 以下に保存
+async fn save(base_dir: &str, repo_info: &RepoInfo, report: &FindingsReport) -> Result<String> {
+    let findings_dir = format!(
+        "{}/findings",
+        outputs::repo_dirs(base_dir, &repo_info.owner, &repo_info.repo).remove(0)
+    );
+    fs::create_dir_all(&findings_dir).await?;
+
+    let name = report.source_file.as_ref().unwrap_or(&report.debate_type);
+    let filename = format!(
+        "{}/{}_{}_{}.json",
+        findings_dir,
+        outputs::path_segment(name),
+        outputs::path_segment(&report.endpoint),
+        Utc::now().format("%Y%m%d_%H%M%S")
+    );
+    encryption::write(&filename, serde_json::to_string_pretty(report)?).await?;
+    Ok(filename)
+}
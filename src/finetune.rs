@@ -0,0 +1,143 @@
+// Azure OpenAI ファインチューニングモジュール
+// エクスポートした学習データをアップロードし、ファインチューニングジョブを作成・監視する
+
+use anyhow::{Result, anyhow, bail};
+use log::{error, info};
+use serde::Deserialize;
+use serde_json::json;
+use std::path::Path;
+use std::time::Duration;
+use tokio::{fs, time};
+
+use crate::LlmClient;
+
+// ファインチューニングのオプション
+pub struct FinetuneOptions {
+    pub training_file: String,
+    pub model: String,
+    pub suffix: Option<String>,
+    pub epochs: Option<u32>,
+    pub wait: bool,
+    pub job_id: Option<String>,
+}
+
+// ファインチューニングジョブの情報
+#[derive(Deserialize, Debug)]
+struct FineTuningJob {
+    id: String,
+    status: String,
+    fine_tuned_model: Option<String>,
+    error: Option<serde_json::Value>,
+}
+
+// ジョブの状態確認の間隔
+const POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+impl LlmClient {
+    // ファインチューニングジョブを作成
+    async fn create_fine_tuning_job(
+        &self,
+        training_file_id: &str,
+        options: &FinetuneOptions,
+    ) -> Result<FineTuningJob> {
+        let url = self.provider.resource_url("fine_tuning/jobs");
+
+        let mut request_body = json!({
+            "model": options.model,
+            "training_file": training_file_id,
+        });
+        if let Some(suffix) = &options.suffix {
+            request_body["suffix"] = json!(suffix);
+        }
+        if let Some(epochs) = options.epochs {
+            request_body["hyperparameters"] = json!({ "n_epochs": epochs });
+        }
+
+        self.post_with_retry(&url, &request_body).await
+    }
+
+    // ファインチューニングジョブの状態を取得
+    async fn get_fine_tuning_job(&self, job_id: &str) -> Result<FineTuningJob> {
+        let url = self
+            .provider
+            .resource_url(&format!("fine_tuning/jobs/{}", job_id));
+        self.get_with_retry(&url).await
+    }
+}
+
+// ジョブが終了するまで監視
+async fn monitor_job(client: &LlmClient, job_id: &str) -> Result<FineTuningJob> {
+    let mut last_status = String::new();
+
+    loop {
+        let job = client.get_fine_tuning_job(job_id).await?;
+        if job.status != last_status {
+            info!("🧪 ファインチューニングジョブ {}: {}", job.id, job.status);
+            last_status = job.status.clone();
+        }
+
+        match job.status.as_str() {
+            "succeeded" | "failed" | "cancelled" => return Ok(job),
+            _ => time::sleep(POLL_INTERVAL).await,
+        }
+    }
+}
+
+// ファインチューニングを実行
+pub async fn run_finetune(client: &LlmClient, options: &FinetuneOptions) -> Result<()> {
+    let job_id = match &options.job_id {
+        // 既存ジョブの監視のみ
+        Some(job_id) => job_id.clone(),
+        None => {
+            if !Path::new(&options.training_file).exists() {
+                bail!(
+                    "学習データが見つかりません: {} (先に export --format finetune を実行してください)",
+                    options.training_file
+                );
+            }
+
+            info!("📤 学習データをアップロード中: {}", options.training_file);
+            let content = fs::read(&options.training_file).await?;
+            let filename = Path::new(&options.training_file)
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_else(|| "training.jsonl".to_string());
+            let file = client.upload_file(content, &filename, "fine-tune").await?;
+            info!("✅ アップロード完了: {} ({})", file.id, file.status);
+
+            client.wait_for_file_processed(&file.id).await?;
+
+            let job = client.create_fine_tuning_job(&file.id, options).await?;
+            info!(
+                "🚀 ファインチューニングジョブを作成しました: {} (モデル: {})",
+                job.id, options.model
+            );
+            job.id
+        }
+    };
+
+    if !options.wait {
+        info!("ℹ️ ジョブの完了は待たずに終了します: {}", job_id);
+        return Ok(());
+    }
+
+    let job = monitor_job(client, &job_id).await?;
+    match job.status.as_str() {
+        "succeeded" => {
+            info!(
+                "🎉 ファインチューニング完了: {}",
+                job.fine_tuned_model.unwrap_or_default()
+            );
+            Ok(())
+        }
+        status => {
+            error!("❌ ファインチューニングが終了しました: {}", status);
+            Err(anyhow!(
+                "ファインチューニングジョブ {} が {} で終了しました: {}",
+                job.id,
+                status,
+                job.error.unwrap_or_default()
+            ))
+        }
+    }
+}
@@ -0,0 +1,430 @@
+// 生成パラメーター（モデル・最大トークン数・温度）のモジュール
+// 議論で使うモデル・最大トークン数・温度を、全体の既定値 → エンドポイント → 議論タイプ → コマンドライン引数の順に上書きして決める
+// （再ビルドせずに設定ファイルや引数だけでモデルを切り替えられるようにする）
+// o1 / o3 などの推論モデルには reasoning_effort を付け、受け付けないパラメーター（temperature など）は送らない
+// デプロイメントがない（404）・レート制限が続くモデルは、フォールバックのモデルで実行し直す
+
+use anyhow::{Result, bail};
+use clap::ValueEnum;
+use log::info;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::error::BurnerError;
+use crate::llm::schemas::openai_response::Endpoint;
+use crate::{CHAT_MODEL, DEBATE_TYPE_ALIASES};
+
+// 推論モデルが受け付けないパラメーター
+const UNSUPPORTED_REASONING_PARAMS: [&str; 6] = [
+    "temperature",
+    "top_p",
+    "presence_penalty",
+    "frequency_penalty",
+    "logprobs",
+    "top_logprobs",
+];
+
+// レート制限が続いたモデルを再び最初に試すまでの時間
+const RATE_LIMITED_COOLDOWN_SECS: u64 = 300;
+
+// 推論の労力（推論モデルの reasoning_effort）
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ReasoningEffort {
+    Low,
+    Medium,
+    High,
+}
+
+impl ReasoningEffort {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReasoningEffort::Low => "low",
+            ReasoningEffort::Medium => "medium",
+            ReasoningEffort::High => "high",
+        }
+    }
+}
+
+// 生成パラメーターの全体設定
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct GenerationConfig {
+    /// 議論・要約などで使うデプロイメント名（モデル）
+    pub model: String,
+    /// 議論の1回の応答の最大トークン数
+    pub max_tokens: usize,
+    /// 議論の温度（省略時はリクエストに含めず、モデルの既定値を使う）
+    pub temperature: Option<f32>,
+    /// 推論モデルの推論の労力（low / medium / high、省略時はモデルの既定値）
+    pub reasoning_effort: Option<ReasoningEffort>,
+    /// 推論モデルとみなすモデル名の接頭辞（デプロイメントのモデルが分かればモデル名、なければデプロイメント名で判定する）
+    pub reasoning_models: Vec<String>,
+    /// モデルのデプロイメントがない（404）かレート制限が続く場合に、順に試すモデル
+    pub fallback_models: Vec<String>,
+    /// 議論タイプ（短い名前または議論タイプ名）ごとの上書き
+    pub debates: BTreeMap<String, GenerationOverride>,
+}
+
+impl Default for GenerationConfig {
+    fn default() -> Self {
+        GenerationConfig {
+            model: CHAT_MODEL.to_string(),
+            max_tokens: 4000,
+            temperature: None,
+            reasoning_effort: None,
+            reasoning_models: vec!["o1".to_string(), "o3".to_string(), "o4".to_string()],
+            fallback_models: Vec::new(),
+            debates: BTreeMap::new(),
+        }
+    }
+}
+
+// 生成パラメーターの上書き（指定した項目だけを上書きする）
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct GenerationOverride {
+    /// デプロイメント名（モデル）
+    pub model: Option<String>,
+    /// 1回の応答の最大トークン数
+    pub max_tokens: Option<usize>,
+    /// 温度
+    pub temperature: Option<f32>,
+    /// 推論モデルの推論の労力
+    pub reasoning_effort: Option<ReasoningEffort>,
+    /// フォールバックのモデル（指定すると全体の fallback_models を置き換える）
+    pub fallback_models: Option<Vec<String>>,
+}
+
+// 議論のリクエストに使う生成パラメーター
+#[derive(Debug, Clone)]
+pub struct Generation {
+    pub model: String,
+    pub max_tokens: usize,
+    pub temperature: Option<f32>,
+    pub reasoning_effort: Option<ReasoningEffort>,
+    // 推論モデルか（ターンの時間制限を長くする）
+    pub reasoning: bool,
+    // model で回答を得られない場合に順に試すモデル
+    pub fallback_models: Vec<String>,
+}
+
+impl Generation {
+    // モデルと最大トークン数だけを指定したパラメーター（温度・推論の労力はモデルの既定値）
+    pub fn basic(model: &str, max_tokens: usize) -> Self {
+        Generation {
+            model: model.to_string(),
+            max_tokens,
+            temperature: None,
+            reasoning_effort: None,
+            reasoning: false,
+            fallback_models: Vec::new(),
+        }
+    }
+
+    fn apply(
+        &mut self,
+        model: Option<&String>,
+        max_tokens: Option<usize>,
+        temperature: Option<f32>,
+        reasoning_effort: Option<ReasoningEffort>,
+    ) {
+        if let Some(model) = model {
+            self.model = model.clone();
+        }
+        if let Some(max_tokens) = max_tokens {
+            self.max_tokens = max_tokens;
+        }
+        if temperature.is_some() {
+            self.temperature = temperature;
+        }
+        if reasoning_effort.is_some() {
+            self.reasoning_effort = reasoning_effort;
+        }
+    }
+}
+
+// 使えないと判断したモデル（エンドポイント名, モデル）と、再び試す時刻（None なら実行中は試さない）
+static UNAVAILABLE: Mutex<BTreeMap<(String, String), Option<Instant>>> =
+    Mutex::new(BTreeMap::new());
+
+struct Settings {
+    config: GenerationConfig,
+    // コマンドライン引数（設定ファイルのすべての値より優先する）
+    cli: GenerationOverride,
+}
+
+static GENERATION: OnceLock<Settings> = OnceLock::new();
+// パラメーターを省いたことをログに出力済みのモデル
+static REPORTED: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+// 温度は API が受け付ける範囲（0〜2）だけを許可する
+fn check_temperature(temperature: Option<f32>, source: &str) -> Result<()> {
+    match temperature {
+        Some(temperature) if !(0.0..=2.0).contains(&temperature) => bail!(
+            "{} は 0〜2 の範囲で指定してください: {}",
+            source,
+            temperature
+        ),
+        _ => Ok(()),
+    }
+}
+
+// 生成パラメーターを設定（温度が範囲外ならエラー）
+pub fn configure(
+    config: &GenerationConfig,
+    endpoints: &[Endpoint],
+    cli: GenerationOverride,
+) -> Result<()> {
+    check_temperature(config.temperature, "generation.temperature")?;
+    check_temperature(cli.temperature, "--temperature")?;
+    for (debate_type, debate) in &config.debates {
+        check_temperature(
+            debate.temperature,
+            &format!("generation.debates.{}.temperature", debate_type),
+        )?;
+    }
+    for endpoint in endpoints {
+        check_temperature(
+            endpoint.temperature,
+            &format!("エンドポイント {} の temperature", endpoint.name),
+        )?;
+    }
+
+    let settings = Settings {
+        config: config.clone(),
+        cli,
+    };
+    let defaults = settings.resolve_with(None, None);
+    info!(
+        "🧠 生成パラメーター: モデル {}{}, 最大トークン数 {}, 温度 {}",
+        defaults.model,
+        match (defaults.reasoning, defaults.reasoning_effort) {
+            (true, Some(effort)) => format!("（推論モデル, 推論の労力 {}）", effort.as_str()),
+            (true, None) => "（推論モデル）".to_string(),
+            (false, _) => String::new(),
+        },
+        defaults.max_tokens,
+        defaults
+            .temperature
+            .map(|temperature| temperature.to_string())
+            .unwrap_or_else(|| "モデルの既定値".to_string())
+    );
+    if !defaults.fallback_models.is_empty() {
+        info!(
+            "🔀 フォールバックのモデル: {}",
+            defaults.fallback_models.join(" → ")
+        );
+    }
+    for (debate_type, debate) in &config.debates {
+        if let Some(model) = &debate.model {
+            info!("🧠 議論タイプ {} のモデル: {}", debate_type, model);
+        }
+        if let Some(fallback_models) = &debate.fallback_models {
+            info!(
+                "🔀 議論タイプ {} のフォールバックのモデル: {}",
+                debate_type,
+                fallback_models.join(" → ")
+            );
+        }
+    }
+    let _ = GENERATION.set(settings);
+    Ok(())
+}
+
+impl Settings {
+    fn resolve_with(
+        &self,
+        endpoint: Option<&Endpoint>,
+        debate: Option<&GenerationOverride>,
+    ) -> Generation {
+        let mut generation = Generation {
+            model: self.config.model.clone(),
+            max_tokens: self.config.max_tokens,
+            temperature: self.config.temperature,
+            reasoning_effort: self.config.reasoning_effort,
+            reasoning: false,
+            fallback_models: self.config.fallback_models.clone(),
+        };
+        if let Some(endpoint) = endpoint {
+            generation.apply(
+                endpoint.model.as_ref(),
+                endpoint.max_tokens,
+                endpoint.temperature,
+                None,
+            );
+        }
+        for layer in debate.into_iter().chain([&self.cli]) {
+            generation.apply(
+                layer.model.as_ref(),
+                layer.max_tokens,
+                layer.temperature,
+                layer.reasoning_effort,
+            );
+            if let Some(fallback_models) = &layer.fallback_models {
+                generation.fallback_models = fallback_models.clone();
+            }
+        }
+        generation.reasoning = self.is_reasoning(endpoint, &generation.model);
+        generation
+    }
+
+    // デプロイメントのモデル（分からなければデプロイメント名）が推論モデルの接頭辞で始まるか
+    fn is_reasoning(&self, endpoint: Option<&Endpoint>, model: &str) -> bool {
+        let name = endpoint
+            .and_then(|endpoint| {
+                endpoint
+                    .deployments
+                    .iter()
+                    .find(|deployment| deployment.name == model)
+            })
+            .map_or(model, |deployment| deployment.model.as_str())
+            .to_lowercase();
+        self.config
+            .reasoning_models
+            .iter()
+            .any(|prefix| name.starts_with(&prefix.to_lowercase()))
+    }
+}
+
+// 議論タイプの上書き（議論タイプ名、なければ短い名前で探す）
+fn debate_override<'a>(
+    config: &'a GenerationConfig,
+    debate_type: &str,
+) -> Option<&'a GenerationOverride> {
+    config.debates.get(debate_type).or_else(|| {
+        DEBATE_TYPE_ALIASES
+            .iter()
+            .find(|(_, name)| *name == debate_type)
+            .and_then(|(alias, _)| config.debates.get(*alias))
+    })
+}
+
+// エンドポイントと議論タイプの生成パラメーター
+pub fn resolve(endpoint: &Endpoint, debate_type: &str) -> Generation {
+    let settings = settings();
+    settings.resolve_with(
+        Some(endpoint),
+        debate_override(&settings.config, debate_type),
+    )
+}
+
+fn settings() -> &'static Settings {
+    GENERATION.get_or_init(|| Settings {
+        config: GenerationConfig::default(),
+        cli: GenerationOverride::default(),
+    })
+}
+
+// エンドポイントのデプロイメントが推論モデルか
+pub fn is_reasoning(endpoint: &Endpoint, model: &str) -> bool {
+    settings().is_reasoning(Some(endpoint), model)
+}
+
+// リクエストをモデルに合わせる
+// 推論モデルでは受け付けないパラメーターを取り除き、reasoning_effort がなければ全体の設定を付ける
+// 推論モデル以外では reasoning_effort を取り除く
+pub fn adapt_request(endpoint: &Endpoint, model: &str, request_body: &mut Value) {
+    let Some(body) = request_body.as_object_mut() else {
+        return;
+    };
+    if !is_reasoning(endpoint, model) {
+        body.remove("reasoning_effort");
+        return;
+    }
+
+    let removed: Vec<&str> = UNSUPPORTED_REASONING_PARAMS
+        .into_iter()
+        .filter(|param| body.remove(*param).is_some())
+        .collect();
+    if !removed.is_empty() && REPORTED.lock().unwrap().insert(model.to_string()) {
+        info!(
+            "🧠 {} は推論モデルのため、受け付けないパラメーターを送りません: {}",
+            model,
+            removed.join(", ")
+        );
+    }
+    if !body.contains_key("reasoning_effort") {
+        let settings = settings();
+        if let Some(effort) = settings
+            .cli
+            .reasoning_effort
+            .or(settings.config.reasoning_effort)
+        {
+            body.insert("reasoning_effort".to_string(), json!(effort.as_str()));
+        }
+    }
+}
+
+// 議論以外（要約・図・差分など）で使う既定のモデル（コマンドライン引数、なければ全体設定のモデル）
+pub fn default_model() -> &'static str {
+    let settings = settings();
+    settings
+        .cli
+        .model
+        .as_deref()
+        .unwrap_or(&settings.config.model)
+}
+
+// 別のモデルで実行し直せば成功する見込みがあるエラーか（デプロイメントがない・レート制限が続く）
+pub fn is_model_unavailable(error: &anyhow::Error) -> bool {
+    matches!(
+        BurnerError::of(error),
+        Some(BurnerError::Api { status: 404, .. } | BurnerError::RateLimited { .. })
+    )
+}
+
+// モデルを使えないものとして記録する（404 は実行中は試さず、レート制限はしばらく後回しにする）
+pub fn mark_unavailable(endpoint: &Endpoint, model: &str, error: &anyhow::Error) {
+    let until = match BurnerError::of(error) {
+        Some(BurnerError::RateLimited { .. }) => {
+            Some(Instant::now() + Duration::from_secs(RATE_LIMITED_COOLDOWN_SECS))
+        }
+        _ => None,
+    };
+    UNAVAILABLE
+        .lock()
+        .unwrap()
+        .insert((endpoint.name.clone(), model.to_string()), until);
+}
+
+// 試す順のモデル（使えないと判断したモデルは後回しにし、重複は除く）
+pub fn candidates(endpoint: &Endpoint, generation: &Generation) -> Vec<Generation> {
+    let mut models: Vec<&String> = Vec::new();
+    for model in std::iter::once(&generation.model).chain(&generation.fallback_models) {
+        if !models.contains(&model) {
+            models.push(model);
+        }
+    }
+
+    let now = Instant::now();
+    let unavailable = UNAVAILABLE.lock().unwrap();
+    let is_unavailable = |model: &str| {
+        unavailable
+            .get(&(endpoint.name.clone(), model.to_string()))
+            .is_some_and(|until| until.is_none_or(|until| now < until))
+    };
+    let (available, skipped): (Vec<&String>, Vec<&String>) =
+        models.into_iter().partition(|model| !is_unavailable(model));
+
+    let settings = settings();
+    available
+        .into_iter()
+        .chain(skipped)
+        .map(|model| Generation {
+            model: model.clone(),
+            reasoning: settings.is_reasoning(Some(endpoint), model),
+            ..generation.clone()
+        })
+        .collect()
+}
+
+// 試すモデルに推論モデルが含まれるか（ターンの時間制限を推論モデルに合わせる）
+pub fn may_reason(endpoint: &Endpoint, generation: &Generation) -> bool {
+    candidates(endpoint, generation)
+        .iter()
+        .any(|candidate| candidate.reasoning)
+}
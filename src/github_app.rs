@@ -0,0 +1,217 @@
+// GitHub App 認証モジュール
+// App ID と秘密鍵から署名した JWT でインストールのアクセストークンを取得し、
+// 期限が近づいたら取り直す（長期間有効な個人のアクセストークンを使わずにプライベートリポジトリを取得する）
+
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Duration, Utc};
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use log::info;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use tokio::sync::Mutex;
+
+// GitHub App の設定
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct GitHubAppConfig {
+    /// App ID（未指定なら GitHub App 認証を使わない）
+    pub app_id: Option<u64>,
+    /// インストールID（省略時はリポジトリごとに調べる）
+    pub installation_id: Option<u64>,
+    /// 秘密鍵（PEM）のファイルパス
+    pub private_key_path: Option<String>,
+    /// 秘密鍵（PEM）の内容（環境変数の参照を想定）
+    pub private_key: Option<String>,
+    /// GitHub API の URL（GitHub Enterprise Server では変更する）
+    pub api_url: String,
+}
+
+impl Default for GitHubAppConfig {
+    fn default() -> Self {
+        GitHubAppConfig {
+            app_id: None,
+            installation_id: None,
+            private_key_path: None,
+            private_key: None,
+            api_url: "https://api.github.com".to_string(),
+        }
+    }
+}
+
+// 期限のこの時間前にトークンを取り直す
+const REFRESH_MARGIN_MINUTES: i64 = 5;
+
+// JWT のクレーム
+#[derive(Serialize)]
+struct Claims {
+    iat: i64,
+    exp: i64,
+    iss: String,
+}
+
+// インストールのアクセストークン
+struct InstallationToken {
+    token: String,
+    expires_at: DateTime<Utc>,
+}
+
+#[derive(Default)]
+struct TokenCache {
+    // owner ごとのインストールID
+    installations: HashMap<String, u64>,
+    // インストールIDごとのトークン
+    tokens: HashMap<u64, InstallationToken>,
+}
+
+// GitHub App としてトークンを発行するクライアント
+pub struct GitHubApp {
+    app_id: u64,
+    installation_id: Option<u64>,
+    api_url: String,
+    key: EncodingKey,
+    client: reqwest::Client,
+    cache: Mutex<TokenCache>,
+}
+
+impl GitHubApp {
+    // 設定から作成（App ID が未指定なら None）
+    pub fn from_config(config: &GitHubAppConfig) -> Result<Option<Self>> {
+        let Some(app_id) = config.app_id else {
+            return Ok(None);
+        };
+
+        let pem = match (&config.private_key, &config.private_key_path) {
+            (Some(pem), _) if !pem.trim().is_empty() => pem.replace("\\n", "\n"),
+            (_, Some(path)) => std::fs::read_to_string(path)
+                .map_err(|e| anyhow!("GitHub App の秘密鍵を読み込めません: {} - {}", path, e))?,
+            _ => {
+                return Err(anyhow!(
+                    "GitHub App の private_key または private_key_path を指定してください"
+                ));
+            }
+        };
+        let key = EncodingKey::from_rsa_pem(pem.as_bytes())
+            .map_err(|e| anyhow!("GitHub App の秘密鍵の形式が正しくありません: {}", e))?;
+
+        info!("🔐 GitHub App で認証します (App ID: {})", app_id);
+        Ok(Some(GitHubApp {
+            app_id,
+            installation_id: config.installation_id,
+            api_url: config.api_url.trim_end_matches('/').to_string(),
+            key,
+            client: reqwest::Client::new(),
+            cache: Mutex::new(TokenCache::default()),
+        }))
+    }
+
+    // App として署名した JWT（有効期限は GitHub の上限の10分より短くする）
+    fn jwt(&self) -> Result<String> {
+        let now = Utc::now();
+        let claims = Claims {
+            // 時計のずれを見込んで1分前から有効にする
+            iat: (now - Duration::seconds(60)).timestamp(),
+            exp: (now + Duration::minutes(9)).timestamp(),
+            iss: self.app_id.to_string(),
+        };
+        jsonwebtoken::encode(&Header::new(Algorithm::RS256), &claims, &self.key)
+            .map_err(|e| anyhow!("GitHub App の JWT を作成できません: {}", e))
+    }
+
+    // App として GitHub API を呼び出す
+    async fn request(&self, method: reqwest::Method, path: &str) -> Result<Value> {
+        let response = self
+            .client
+            .request(method, format!("{}{}", self.api_url, path))
+            .bearer_auth(self.jwt()?)
+            .header("Accept", "application/vnd.github+json")
+            .header("User-Agent", "azure-credit-burner")
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await?;
+            return Err(anyhow!(
+                "GitHub App API エラー: {} - ステータス {}, レスポンス: {}",
+                path,
+                status,
+                error_text
+            ));
+        }
+        Ok(response.json().await?)
+    }
+
+    // リポジトリにアクセスできるインストールのID
+    async fn installation_for(
+        &self,
+        cache: &mut TokenCache,
+        owner: &str,
+        repo: &str,
+    ) -> Result<u64> {
+        if let Some(id) = self.installation_id {
+            return Ok(id);
+        }
+        if let Some(id) = cache.installations.get(owner) {
+            return Ok(*id);
+        }
+
+        let body = self
+            .request(
+                reqwest::Method::GET,
+                &format!("/repos/{}/{}/installation", owner, repo),
+            )
+            .await?;
+        let id = body["id"].as_u64().ok_or_else(|| {
+            anyhow!(
+                "GitHub App がインストールされていません: {}/{}",
+                owner,
+                repo
+            )
+        })?;
+        cache.installations.insert(owner.to_string(), id);
+        Ok(id)
+    }
+
+    // リポジトリを取得するためのアクセストークン（期限が近ければ取り直す）
+    pub async fn token(&self, owner: &str, repo: &str) -> Result<String> {
+        let mut cache = self.cache.lock().await;
+        let installation_id = self.installation_for(&mut cache, owner, repo).await?;
+
+        let refresh_at = Utc::now() + Duration::minutes(REFRESH_MARGIN_MINUTES);
+        match cache.tokens.get(&installation_id) {
+            Some(cached) if cached.expires_at > refresh_at => return Ok(cached.token.clone()),
+            _ => {}
+        }
+
+        let body = self
+            .request(
+                reqwest::Method::POST,
+                &format!("/app/installations/{}/access_tokens", installation_id),
+            )
+            .await?;
+        let token = body["token"]
+            .as_str()
+            .ok_or_else(|| anyhow!("アクセストークンのレスポンスに token がありません"))?
+            .to_string();
+        let expires_at = body["expires_at"]
+            .as_str()
+            .and_then(|value| DateTime::parse_from_rfc3339(value).ok())
+            .map(|value| value.with_timezone(&Utc))
+            .unwrap_or_else(|| Utc::now() + Duration::hours(1));
+
+        info!(
+            "🔐 GitHub App のアクセストークンを取得しました (インストール: {}, 期限: {})",
+            installation_id,
+            expires_at.to_rfc3339()
+        );
+        cache.tokens.insert(
+            installation_id,
+            InstallationToken {
+                token: token.clone(),
+                expires_at,
+            },
+        );
+        Ok(token)
+    }
+}
@@ -0,0 +1,125 @@
+// エンドポイントのグループモジュール
+// 別々に付与された複数のクレジットを1つの実行で使い切るため、エンドポイントにサブスクリプションとテナントを付けて
+// グループごとの使用額を台帳に記録し、予算に達したグループのエンドポイントでは新しいリクエストを送らない
+// （クレジットが枯渇したエンドポイントと同じく、残りの議論は他のグループのエンドポイントが引き継ぐ）
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Mutex, OnceLock};
+
+use crate::llm::schemas::openai_response::Endpoint;
+use crate::{ledger, pricing};
+
+// エンドポイントのグループの設定
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct GroupsConfig {
+    /// グループ（エンドポイントの subscription または tenant の値）ごとの予算
+    pub budgets: BTreeMap<String, GroupBudget>,
+}
+
+// グループの予算（台帳の累計で判定するため、過去の実行の使用額も含む）
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct GroupBudget {
+    /// 推定使用額の上限（料金表の通貨）
+    pub max_cost: Option<f64>,
+    /// トークン数の上限
+    pub max_tokens: Option<usize>,
+}
+
+static BUDGETS: OnceLock<BTreeMap<String, GroupBudget>> = OnceLock::new();
+// 予算到達をログに出力済みのグループ
+static REPORTED: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+// グループの予算を設定（台帳を開いてから呼び出す）
+pub fn configure(config: &GroupsConfig, endpoints: &[Endpoint]) {
+    for (group, budget) in &config.budgets {
+        let members = endpoints
+            .iter()
+            .filter(|endpoint| endpoint.groups().contains(group))
+            .count();
+        if members == 0 {
+            warn!(
+                "⚠️ グループ {} に属するエンドポイントがありません（subscription または tenant を確認してください）",
+                group
+            );
+        }
+        info!(
+            "🏷️ グループの予算: {} - 推定使用額 {}, トークン数 {}（エンドポイント {} 件）",
+            group,
+            budget
+                .max_cost
+                .map(|max| pricing::format_amount(max, pricing::currency()))
+                .unwrap_or_else(|| "上限なし".to_string()),
+            budget
+                .max_tokens
+                .map(|max| max.to_string())
+                .unwrap_or_else(|| "上限なし".to_string()),
+            members
+        );
+    }
+    let _ = BUDGETS.set(config.budgets.clone());
+}
+
+// エンドポイントが属するグループのうち予算に達したものがあれば、その理由を返す（グループごとに初回だけログに出力）
+pub fn exhausted(endpoint: &Endpoint) -> Option<String> {
+    let budgets = BUDGETS.get().filter(|budgets| !budgets.is_empty())?;
+    let spend = ledger::spend()?;
+    for group in endpoint.groups() {
+        let Some(budget) = budgets.get(&group) else {
+            continue;
+        };
+        let used = spend.groups.get(&group).cloned().unwrap_or_default();
+        let over_cost = budget.max_cost.is_some_and(|max| used.total >= max);
+        let over_tokens = budget.max_tokens.is_some_and(|max| used.tokens >= max);
+        if !over_cost && !over_tokens {
+            continue;
+        }
+
+        let message = format!(
+            "グループ {} の予算に達しました（推定使用額 {}, {} トークン）",
+            group,
+            pricing::format_amount(used.total, &spend.currency),
+            used.tokens
+        );
+        if REPORTED.lock().unwrap().insert(group) {
+            warn!(
+                "🏷️ {} - このグループのエンドポイントでは新しいリクエストを送りません",
+                message
+            );
+        }
+        return Some(message);
+    }
+    None
+}
+
+// グループごとの使用額をログに出力
+pub fn log_summary() {
+    let Some(spend) = ledger::spend() else {
+        return;
+    };
+    let budgets = BUDGETS.get();
+    for (group, used) in &spend.groups {
+        let (run, currency) = pricing::convert(used.run, &spend.currency);
+        let (total, _) = pricing::convert(used.total, &spend.currency);
+        let budget = budgets
+            .and_then(|budgets| budgets.get(group))
+            .and_then(|budget| budget.max_cost)
+            .map(|max| {
+                let (max, currency) = pricing::convert(max, &spend.currency);
+                format!(" / 予算 {}", pricing::format_amount(max, &currency))
+            })
+            .unwrap_or_default();
+        info!(
+            "🏷️ {}: 今回 {}, 累計 {}{}（{} 回の呼び出し, {} トークン）",
+            group,
+            pricing::format_amount(run, &currency),
+            pricing::format_amount(total, &currency),
+            budget,
+            used.calls,
+            used.tokens
+        );
+    }
+}
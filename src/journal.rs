@@ -0,0 +1,547 @@
+// タスクジャーナルモジュール
+// 議論タスクごとに決定的なIDを付け、状態の遷移をディスクに追記する
+// 再実行時は完了済みの議論をスキップし、途中まで進んだ議論は最後に保存したターンから再開する
+
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io::Write;
+use std::sync::Mutex;
+
+use crate::error::BurnerError;
+use crate::llm::schemas::github_response::RepoInfo;
+use crate::llm::schemas::openai_response::ResponseData;
+use crate::{encryption, outputs, redact, runs};
+
+// タスクの状態
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TaskState {
+    Queued,
+    Running,
+    Done,
+    Failed,
+}
+
+// ジャーナルの1行
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct JournalEntry {
+    task_id: String,
+    state: TaskState,
+    repo: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    branch: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    commit: Option<String>,
+    debate_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    endpoint: Option<String>,
+    // 保存済みのターン番号と保存先ファイル
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    turn: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    file: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    // エラーの種類（BurnerError の種類名）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error_kind: Option<String>,
+    // 分析したコミット（クローンしたディレクトリの HEAD）と使用したモデル
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    resolved_commit: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    perspective: Option<usize>,
+    // 同じ分析が完了済みのためスキップした場合、その議論のタスクID
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    duplicate_of: Option<String>,
+    // 失敗した議論を再試行する場合、それまでの試行回数と再試行する日時
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    attempts: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    retry_at: Option<String>,
+    timestamp: String,
+}
+
+// タスクの現在の状態（ジャーナルの記録を集約したもの）
+#[derive(Serialize, Debug, Clone)]
+pub struct TaskStatus {
+    pub task_id: String,
+    pub state: TaskState,
+    pub repo: String,
+    pub branch: Option<String>,
+    pub commit: Option<String>,
+    pub debate_type: String,
+    pub endpoint: Option<String>,
+    // 最後に保存したターンと保存先ファイル
+    pub turn: Option<usize>,
+    pub file: Option<String>,
+    pub error: Option<String>,
+    pub error_kind: Option<String>,
+    pub resolved_commit: Option<String>,
+    pub model: Option<String>,
+    pub perspective: Option<usize>,
+    pub duplicate_of: Option<String>,
+    pub attempts: Option<usize>,
+    // 再試行を待っている場合、再試行する日時
+    pub retry_at: Option<String>,
+    pub updated_at: String,
+}
+
+impl TaskStatus {
+    // ジャーナルの1行を反映
+    fn apply(&mut self, entry: JournalEntry) {
+        self.state = entry.state;
+        if entry.endpoint.is_some() {
+            self.endpoint = entry.endpoint;
+        }
+        if let (Some(turn), Some(file)) = (entry.turn, entry.file) {
+            self.turn = Some(turn);
+            self.file = Some(file);
+        }
+        self.error = entry.error;
+        self.error_kind = entry.error_kind;
+        if entry.resolved_commit.is_some() {
+            self.resolved_commit = entry.resolved_commit;
+        }
+        if entry.model.is_some() {
+            self.model = entry.model;
+        }
+        if entry.perspective.is_some() {
+            self.perspective = entry.perspective;
+        }
+        if entry.duplicate_of.is_some() {
+            self.duplicate_of = entry.duplicate_of;
+        }
+        if entry.attempts.is_some() {
+            self.attempts = entry.attempts;
+        }
+        self.retry_at = entry.retry_at;
+        self.updated_at = entry.timestamp;
+    }
+}
+
+impl From<&JournalEntry> for TaskStatus {
+    fn from(entry: &JournalEntry) -> Self {
+        TaskStatus {
+            task_id: entry.task_id.clone(),
+            state: entry.state,
+            repo: entry.repo.clone(),
+            branch: entry.branch.clone(),
+            commit: entry.commit.clone(),
+            debate_type: entry.debate_type.clone(),
+            endpoint: None,
+            turn: None,
+            file: None,
+            error: None,
+            error_kind: None,
+            resolved_commit: None,
+            model: None,
+            perspective: None,
+            duplicate_of: None,
+            attempts: None,
+            retry_at: None,
+            updated_at: String::new(),
+        }
+    }
+}
+
+// ジャーナルファイルのパス（ドライランは本番の記録と混ざらないよう別ファイル）
+pub fn journal_path(output_dir: &str, dry_run: bool) -> String {
+    if dry_run {
+        format!("{}/journal.dry-run.jsonl", output_dir)
+    } else {
+        format!("{}/journal.jsonl", output_dir)
+    }
+}
+
+// 議論タスクのIDを作成（リポジトリ・ブランチ・コミット・議論タイプ・実行の区別から決まる）
+// 同じリポジトリと議論タイプを複数回実行する場合は variant（視点の番号や定期実行の時刻）で区別する
+pub fn task_id(repo_info: &RepoInfo, debate_type: &str, variant: &str) -> String {
+    let mut repo = format!("{}/{}", repo_info.owner, repo_info.repo);
+    if let Some(branch) = &repo_info.branch {
+        repo.push_str(&format!("@{}", branch));
+    }
+    if let Some(commit) = &repo_info.commit {
+        repo.push_str(&format!("#{}", commit));
+    }
+
+    let mut hasher = Sha256::new();
+    hasher.update(format!("{}\n{}\n{}", repo, debate_type, variant));
+    hex::encode(&hasher.finalize()[..8])
+}
+
+// 失敗の記録
+fn failure_entry(
+    task_id: &str,
+    repo_info: &RepoInfo,
+    debate_type: &str,
+    endpoint: Option<&str>,
+    error: &anyhow::Error,
+) -> JournalEntry {
+    JournalEntry {
+        task_id: task_id.to_string(),
+        state: TaskState::Failed,
+        repo: format!("{}/{}", repo_info.owner, repo_info.repo),
+        branch: repo_info.branch.clone(),
+        commit: repo_info.commit.clone(),
+        debate_type: debate_type.to_string(),
+        endpoint: endpoint.map(|endpoint| endpoint.to_string()),
+        turn: None,
+        file: None,
+        error: Some(redact::scrub(&error.to_string())),
+        error_kind: BurnerError::of(error).map(|error| error.kind().to_string()),
+        resolved_commit: None,
+        model: None,
+        perspective: None,
+        duplicate_of: None,
+        attempts: None,
+        retry_at: None,
+        timestamp: Utc::now().to_rfc3339(),
+    }
+}
+
+// 状態の遷移を追記していくジャーナル
+pub struct Journal {
+    file: Mutex<std::fs::File>,
+    tasks: Mutex<HashMap<String, TaskStatus>>,
+    // ジャーナルを置いた出力ディレクトリ
+    output_dir: String,
+}
+
+impl Journal {
+    // ジャーナルを開き、既存の記録から各タスクの状態を復元
+    pub fn open(path: &str) -> Result<Self> {
+        if let Some(parent) = std::path::Path::new(path).parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let mut tasks: HashMap<String, TaskStatus> = HashMap::new();
+        if let Ok(content) = std::fs::read_to_string(path) {
+            for line in content.lines().filter(|line| !line.trim().is_empty()) {
+                // 書き込み途中で止まった行は読み飛ばす
+                let entry: JournalEntry = match serde_json::from_str(line) {
+                    Ok(entry) => entry,
+                    Err(e) => {
+                        warn!("⚠️ ジャーナルの行を読み飛ばします: {} - {}", path, e);
+                        continue;
+                    }
+                };
+
+                tasks
+                    .entry(entry.task_id.clone())
+                    .or_insert_with(|| TaskStatus::from(&entry))
+                    .apply(entry);
+            }
+        }
+
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| anyhow!("ジャーナルを開けません: {} - {}", path, e))?;
+
+        let output_dir = std::path::Path::new(path)
+            .parent()
+            .map(|parent| parent.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        Ok(Journal {
+            file: Mutex::new(file),
+            tasks: Mutex::new(tasks),
+            output_dir,
+        })
+    }
+
+    // 完了済みのタスクか
+    pub fn is_done(&self, task_id: &str) -> bool {
+        self.tasks
+            .lock()
+            .unwrap()
+            .get(task_id)
+            .is_some_and(|status| status.state == TaskState::Done)
+    }
+
+    // タスクの状態を取得
+    pub fn status(&self, task_id: &str) -> Option<TaskStatus> {
+        self.tasks.lock().unwrap().get(task_id).cloned()
+    }
+
+    // すべてのタスクの状態を取得（更新日時の新しい順）
+    pub fn list(&self) -> Vec<TaskStatus> {
+        let mut tasks: Vec<TaskStatus> = self.tasks.lock().unwrap().values().cloned().collect();
+        tasks.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
+        tasks
+    }
+
+    // 同じリポジトリ・コミット・議論タイプ・モデル・視点で完了済みの別の議論
+    pub fn find_duplicate(
+        &self,
+        task_id: &str,
+        repo: &str,
+        resolved_commit: &str,
+        debate_type: &str,
+        model: &str,
+        perspective: usize,
+    ) -> Option<TaskStatus> {
+        self.list().into_iter().find(|task| {
+            task.task_id != task_id
+                && task.state == TaskState::Done
+                && task.duplicate_of.is_none()
+                && task.repo == repo
+                && task.resolved_commit.as_deref() == Some(resolved_commit)
+                && task.debate_type == debate_type
+                && task.model.as_deref() == Some(model)
+                && task.perspective.unwrap_or_default() == perspective
+        })
+    }
+
+    // タスクの最後に保存したターンの内容を読み込む（未保存ならNone）
+    // 重複としてスキップしたタスクは、完了済みの元の議論の内容を返す
+    pub fn load_latest(&self, task_id: &str) -> Result<Option<ResponseData>> {
+        let Some(status) = self.status(task_id) else {
+            return Ok(None);
+        };
+        if let Some(original) = &status.duplicate_of {
+            return self.load_latest(original);
+        }
+        let Some((turn, file)) = status.turn.zip(status.file) else {
+            return Ok(None);
+        };
+
+        let content = encryption::read_to_string(&file)
+            .map_err(|e| anyhow!("保存結果を読み込めません: {}", e))?;
+        let response_data = outputs::parse_response(&content)?;
+        if response_data.turn != turn {
+            return Err(anyhow!("保存結果のターンが一致しません: {}", file));
+        }
+        Ok(Some(response_data))
+    }
+
+    // 途中まで進んだタスクの最後の保存内容を読み込む（読み込めなければ最初からやり直す）
+    // ジャーナルに途中経過が残っていない場合は、リポジトリの出力ディレクトリの保存結果から復元する
+    pub fn load_progress(&self, task_id: &str, repo_info: &RepoInfo) -> Option<ResponseData> {
+        match self.load_latest(task_id) {
+            Ok(Some(saved)) => return Some(saved),
+            Ok(None) => {}
+            Err(e) => warn!("⚠️ ジャーナルに記録した途中経過を読み込めません: {}", e),
+        }
+        if self
+            .status(task_id)
+            .is_some_and(|status| status.duplicate_of.is_some())
+        {
+            return None;
+        }
+
+        let dry_run = runs::is_dry_run_id(runs::current_id().as_deref());
+        let (path, saved) = outputs::find_latest_turn(
+            &self.output_dir,
+            &repo_info.owner,
+            &repo_info.repo,
+            task_id,
+            dry_run,
+        )?;
+        let file = path.to_string_lossy().to_string();
+        info!(
+            "🩹 保存結果から途中経過を復元しました: {}/{} ({}) - ターン {} ({})",
+            repo_info.owner, repo_info.repo, saved.debate_type, saved.turn, file
+        );
+        self.record_turn(task_id, &saved, &file);
+        Some(saved)
+    }
+
+    // 状態の遷移を追記（プロセスが落ちても残るようにディスクへ同期する）
+    fn append(&self, entry: JournalEntry) -> TaskStatus {
+        let line = serde_json::to_string(&entry);
+        let status = {
+            let mut tasks = self.tasks.lock().unwrap();
+            let status = tasks
+                .entry(entry.task_id.clone())
+                .or_insert_with(|| TaskStatus::from(&entry));
+            status.apply(entry);
+            status.clone()
+        };
+
+        let result = line.map_err(anyhow::Error::from).and_then(|line| {
+            let mut file = self.file.lock().unwrap();
+            writeln!(file, "{}", line)?;
+            file.sync_data()?;
+            Ok(())
+        });
+        if let Err(e) = result {
+            warn!("⚠️ ジャーナルの書き込みエラー: {}", e);
+        }
+        status
+    }
+
+    // 状態を記録
+    pub fn record(
+        &self,
+        task_id: &str,
+        repo_info: &RepoInfo,
+        debate_type: &str,
+        state: TaskState,
+        endpoint: Option<&str>,
+        error: Option<String>,
+    ) -> TaskStatus {
+        self.append(JournalEntry {
+            task_id: task_id.to_string(),
+            state,
+            repo: format!("{}/{}", repo_info.owner, repo_info.repo),
+            branch: repo_info.branch.clone(),
+            commit: repo_info.commit.clone(),
+            debate_type: debate_type.to_string(),
+            endpoint: endpoint.map(|endpoint| endpoint.to_string()),
+            turn: None,
+            file: None,
+            error,
+            error_kind: None,
+            resolved_commit: None,
+            model: None,
+            perspective: None,
+            duplicate_of: None,
+            attempts: None,
+            retry_at: None,
+            timestamp: Utc::now().to_rfc3339(),
+        })
+    }
+
+    // 失敗を記録（分類済みのエラーはその種類も残す）
+    pub fn record_failure(
+        &self,
+        task_id: &str,
+        repo_info: &RepoInfo,
+        debate_type: &str,
+        endpoint: Option<&str>,
+        error: &anyhow::Error,
+    ) -> TaskStatus {
+        self.append(failure_entry(
+            task_id,
+            repo_info,
+            debate_type,
+            endpoint,
+            error,
+        ))
+    }
+
+    // 失敗と再試行の予定を記録（再実行時も予定の日時まで待ってから再試行する）
+    #[allow(clippy::too_many_arguments)]
+    pub fn record_retry(
+        &self,
+        task_id: &str,
+        repo_info: &RepoInfo,
+        debate_type: &str,
+        endpoint: &str,
+        error: &anyhow::Error,
+        attempts: usize,
+        retry_at: DateTime<Utc>,
+    ) -> TaskStatus {
+        self.append(JournalEntry {
+            attempts: Some(attempts),
+            retry_at: Some(retry_at.to_rfc3339()),
+            ..failure_entry(task_id, repo_info, debate_type, Some(endpoint), error)
+        })
+    }
+
+    // ターンの保存を記録
+    pub fn record_turn(
+        &self,
+        task_id: &str,
+        response_data: &ResponseData,
+        file: &str,
+    ) -> TaskStatus {
+        outputs::index_turn(
+            &self.output_dir,
+            &response_data.repo,
+            task_id,
+            response_data.turn,
+            file,
+        );
+        self.append(JournalEntry {
+            task_id: task_id.to_string(),
+            state: TaskState::Running,
+            repo: response_data.repo.clone(),
+            branch: None,
+            commit: None,
+            debate_type: response_data.debate_type.clone(),
+            endpoint: Some(response_data.endpoint.clone()),
+            turn: Some(response_data.turn),
+            file: Some(file.to_string()),
+            error: None,
+            error_kind: None,
+            resolved_commit: None,
+            model: None,
+            perspective: None,
+            duplicate_of: None,
+            attempts: None,
+            retry_at: None,
+            timestamp: Utc::now().to_rfc3339(),
+        })
+    }
+
+    // 分析するコミット・モデル・視点を記録
+    pub fn record_source(
+        &self,
+        task_id: &str,
+        repo_info: &RepoInfo,
+        debate_type: &str,
+        resolved_commit: &str,
+        model: &str,
+        perspective: usize,
+    ) -> TaskStatus {
+        self.append(JournalEntry {
+            task_id: task_id.to_string(),
+            state: TaskState::Running,
+            repo: format!("{}/{}", repo_info.owner, repo_info.repo),
+            branch: repo_info.branch.clone(),
+            commit: repo_info.commit.clone(),
+            debate_type: debate_type.to_string(),
+            endpoint: None,
+            turn: None,
+            file: None,
+            error: None,
+            error_kind: None,
+            resolved_commit: Some(resolved_commit.to_string()),
+            model: Some(model.to_string()),
+            perspective: Some(perspective),
+            duplicate_of: None,
+            attempts: None,
+            retry_at: None,
+            timestamp: Utc::now().to_rfc3339(),
+        })
+    }
+
+    // 同じ分析が完了済みのためスキップしたことを記録（完了扱い）
+    pub fn record_duplicate(
+        &self,
+        task_id: &str,
+        repo_info: &RepoInfo,
+        debate_type: &str,
+        duplicate_of: &str,
+    ) -> TaskStatus {
+        self.append(JournalEntry {
+            task_id: task_id.to_string(),
+            state: TaskState::Done,
+            repo: format!("{}/{}", repo_info.owner, repo_info.repo),
+            branch: repo_info.branch.clone(),
+            commit: repo_info.commit.clone(),
+            debate_type: debate_type.to_string(),
+            endpoint: None,
+            turn: None,
+            file: None,
+            error: None,
+            error_kind: None,
+            resolved_commit: None,
+            model: None,
+            perspective: None,
+            duplicate_of: Some(duplicate_of.to_string()),
+            attempts: None,
+            retry_at: None,
+            timestamp: Utc::now().to_rfc3339(),
+        })
+    }
+}
@@ -0,0 +1,83 @@
+// 回答品質の採点モジュール
+// 判定用モデルに質問と回答を渡し、1〜10点で採点させる
+
+use anyhow::{Result, anyhow};
+use regex::Regex;
+use std::collections::BTreeMap;
+use std::path::Path;
+use tokio::fs;
+
+use crate::LlmClient;
+use crate::llm::schemas::openai_response::ChatMessage;
+
+// 採点の指示
+const JUDGE_PROMPT: &str = "あなたはコードレビューの品質を評価する審査員です。\
+以下の質問に対する回答を、具体性（コードや設計への言及）、技術的な正確さ、実用的な提案の有無の観点から1〜10点で採点してください。\
+出力は {\"score\": <点数>} というJSONのみとしてください。";
+
+// 判定用モデルに回答を採点させる
+pub async fn score_answer(
+    client: &LlmClient,
+    model: &str,
+    question: &str,
+    answer: &str,
+) -> Result<f32> {
+    let messages = vec![
+        ChatMessage {
+            role: "system".to_string(),
+            content: JUDGE_PROMPT.to_string(),
+            ..Default::default()
+        },
+        ChatMessage {
+            role: "user".to_string(),
+            content: format!("【質問】\n{}\n\n【回答】\n{}", question, answer),
+            ..Default::default()
+        },
+    ];
+
+    let (response, _) = client.chat_completion(&messages, model, 100, 0.0).await?;
+    parse_score(&response)
+}
+
+// 採点結果から点数を取り出す
+fn parse_score(response: &str) -> Result<f32> {
+    let score_regex = Regex::new(r#""score"\s*:\s*([0-9]+(?:\.[0-9]+)?)"#).unwrap();
+    score_regex
+        .captures(response)
+        .and_then(|captures| captures[1].parse().ok())
+        .ok_or_else(|| anyhow!("採点結果を解析できません: {}", response))
+}
+
+// 採点結果のキャッシュ（キーは「ファイルパス#メッセージ番号」）
+pub struct ScoreCache {
+    path: String,
+    scores: BTreeMap<String, f32>,
+}
+
+impl ScoreCache {
+    pub async fn load(base_dir: &str) -> Self {
+        let path = format!("{}/judge_scores.json", base_dir);
+        let scores = match fs::read_to_string(&path).await {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_default(),
+            Err(_) => BTreeMap::new(),
+        };
+        ScoreCache { path, scores }
+    }
+
+    pub fn key(file: &Path, message_index: usize) -> String {
+        format!("{}#{}", file.display(), message_index)
+    }
+
+    pub fn get(&self, key: &str) -> Option<f32> {
+        self.scores.get(key).copied()
+    }
+
+    pub fn insert(&mut self, key: String, score: f32) {
+        self.scores.insert(key, score);
+    }
+
+    pub async fn save(&self) -> Result<()> {
+        fs::write(&self.path, serde_json::to_string_pretty(&self.scores)?).await?;
+        Ok(())
+    }
+}
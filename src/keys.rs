@@ -0,0 +1,133 @@
+// APIキーのローテーションモジュール
+// エンドポイントごとに複数のAPIキーを持ち、401 / 403 が返ったら次のキーに切り替える
+// SIGHUP で設定ファイルのキーを読み込み直し、再起動せずにキーを入れ替えられるようにする
+
+use log::{error, info, warn};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+
+use crate::llm::schemas::openai_response::Endpoint;
+use crate::redact;
+
+// 1エンドポイント分のキー
+pub struct KeyRing {
+    endpoint: String,
+    keys: RwLock<Vec<String>>,
+    current: AtomicUsize,
+}
+
+impl KeyRing {
+    fn new(endpoint: &Endpoint) -> Self {
+        KeyRing {
+            endpoint: endpoint.name.clone(),
+            keys: RwLock::new(endpoint.all_keys()),
+            current: AtomicUsize::new(0),
+        }
+    }
+
+    // 使用中のキー（キーが設定されていなければ空文字）
+    pub fn current(&self) -> String {
+        let keys = self.keys.read().unwrap();
+        if keys.is_empty() {
+            return String::new();
+        }
+        keys[self.current.load(Ordering::Relaxed) % keys.len()].clone()
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.read().unwrap().len()
+    }
+
+    // 認証に失敗したキーから次のキーに切り替える
+    // 他のリクエストがすでに切り替えていれば何もしない（切り替え後のキーで再試行する）
+    pub fn rotate(&self, failed_key: &str) {
+        let len = self.len();
+        if len < 2 || self.current() != failed_key {
+            return;
+        }
+        let next = (self.current.fetch_add(1, Ordering::Relaxed) + 1) % len;
+        warn!(
+            "[{}] 🔑 認証に失敗したため次のAPIキーに切り替えます ({}/{})",
+            self.endpoint,
+            next + 1,
+            len
+        );
+    }
+
+    // キーを入れ替える（使用中のキーが残っていればそのまま使い続ける）
+    fn replace(&self, keys: Vec<String>) {
+        let current = self.current();
+        let index = keys.iter().position(|key| *key == current).unwrap_or(0);
+        let count = keys.len();
+        *self.keys.write().unwrap() = keys;
+        self.current.store(index, Ordering::Relaxed);
+        info!(
+            "[{}] 🔑 APIキーを読み込み直しました ({} 件)",
+            self.endpoint, count
+        );
+    }
+}
+
+// エンドポイント名ごとのキー（同じエンドポイントのクライアント間で共有する）
+static KEY_RINGS: OnceLock<Mutex<HashMap<String, Arc<KeyRing>>>> = OnceLock::new();
+
+// エンドポイントのキーを取得
+pub fn key_ring(endpoint: &Endpoint) -> Arc<KeyRing> {
+    KEY_RINGS
+        .get_or_init(|| Mutex::new(HashMap::new()))
+        .lock()
+        .unwrap()
+        .entry(endpoint.name.clone())
+        .or_insert_with(|| Arc::new(KeyRing::new(endpoint)))
+        .clone()
+}
+
+// 設定ファイルのエンドポイントのキーで、使用中のキーを入れ替える
+pub fn reload(endpoints: &[Endpoint]) {
+    let Some(rings) = KEY_RINGS.get() else {
+        return;
+    };
+    let rings = rings.lock().unwrap();
+    for endpoint in endpoints {
+        let keys = endpoint.all_keys();
+        redact::add_secrets(&keys);
+        match rings.get(&endpoint.name) {
+            Some(ring) if !keys.is_empty() => ring.replace(keys),
+            Some(_) => warn!(
+                "[{}] ⚠️ 設定ファイルにAPIキーがないため、使用中のキーのままにします",
+                endpoint.name
+            ),
+            None => {}
+        }
+    }
+}
+
+// SIGHUP を受けたら設定ファイルを読み込み直してキーを入れ替える
+#[cfg(unix)]
+pub fn watch_sighup(config_path: String) {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    let mut hangup = match signal(SignalKind::hangup()) {
+        Ok(hangup) => hangup,
+        Err(e) => {
+            warn!("⚠️ SIGHUP を監視できません: {}", e);
+            return;
+        }
+    };
+    tokio::spawn(async move {
+        while hangup.recv().await.is_some() {
+            info!(
+                "🔑 SIGHUP を受信しました。設定ファイルからAPIキーを読み込み直します: {}",
+                config_path
+            );
+            match crate::load_config(&config_path).await {
+                Ok(config) => reload(&config.endpoints),
+                Err(e) => error!("❌ 設定ファイルの読み込みエラー: {}", e),
+            }
+        }
+    });
+}
+
+#[cfg(not(unix))]
+pub fn watch_sighup(_config_path: String) {}
@@ -0,0 +1,225 @@
+// 言語ごとの分析モジュール
+// リポジトリのファイルの拡張子から主な言語を判定し、言語ごとのテンプレート（llm/templates/<言語>/）の
+// システムプロンプトの追記と深掘り質問を使う（Rust なら unsafe の使い方、Java なら GC の負荷など）
+
+use anyhow::{Result, anyhow};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+use crate::GitHubClient;
+use crate::llm::schemas::github_response::RepoInfo;
+
+// 言語ごとの分析の設定
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct LanguagesConfig {
+    /// 主な言語のテンプレートを使うか
+    pub enabled: bool,
+    /// 言語ごとのテンプレート（<言語>/system.md と <言語>/questions.json）を置くディレクトリ
+    pub dir: String,
+    /// 主な言語とみなす、言語のファイルに占める割合（0〜1）
+    pub min_share: f64,
+    /// 使う言語の最大数（割合の高い順）
+    pub max_languages: usize,
+}
+
+impl Default for LanguagesConfig {
+    fn default() -> Self {
+        LanguagesConfig {
+            enabled: false,
+            dir: "llm/templates".to_string(),
+            min_share: 0.25,
+            max_languages: 2,
+        }
+    }
+}
+
+// 言語のキー（テンプレートのディレクトリ名）・表示名・拡張子
+const LANGUAGES: [(&str, &str, &[&str]); 13] = [
+    ("rust", "Rust", &["rs"]),
+    ("java", "Java", &["java"]),
+    ("kotlin", "Kotlin", &["kt", "kts"]),
+    ("python", "Python", &["py"]),
+    ("go", "Go", &["go"]),
+    ("typescript", "TypeScript", &["ts", "tsx"]),
+    ("javascript", "JavaScript", &["js", "jsx", "mjs", "cjs"]),
+    ("cpp", "C/C++", &["c", "h", "cc", "cpp", "hpp", "cxx"]),
+    ("csharp", "C#", &["cs"]),
+    ("ruby", "Ruby", &["rb"]),
+    ("php", "PHP", &["php"]),
+    ("swift", "Swift", &["swift"]),
+    ("scala", "Scala", &["scala"]),
+];
+
+// 言語の深掘り質問（<言語>/questions.json の形式）
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LanguageQuestions {
+    /// 質問のカテゴリ名（省略時は「<表示名>固有」）
+    #[serde(default)]
+    pub name: Option<String>,
+    pub questions: Vec<String>,
+}
+
+// リポジトリの主な言語とそのテンプレート
+#[derive(Debug, Clone)]
+pub struct LanguageProfile {
+    pub name: String,
+    // 言語のファイルに占める割合
+    pub share: f64,
+    // システムプロンプトに加える言語ごとの観点
+    pub system: Option<String>,
+    // 深掘り質問のカテゴリ名と質問
+    pub category: String,
+    pub questions: Vec<String>,
+}
+
+static CONFIG: OnceLock<LanguagesConfig> = OnceLock::new();
+
+// リポジトリ（owner/repo）ごとの判定結果（議論タイプごとにファイルを数え直さない）
+static PROFILES: OnceLock<Mutex<HashMap<String, Vec<LanguageProfile>>>> = OnceLock::new();
+
+// 言語ごとの分析の設定を登録する
+pub fn configure(config: &LanguagesConfig) {
+    if config.enabled {
+        info!("🗣️ 言語ごとのテンプレート: {}", config.dir);
+        if !Path::new(&config.dir).is_dir() {
+            warn!(
+                "⚠️ 言語ごとのテンプレートのディレクトリがありません: {}",
+                config.dir
+            );
+        }
+    }
+    let _ = CONFIG.set(config.clone());
+}
+
+// 拡張子から言語を数え、割合の高い順に並べる
+fn count_languages(paths: &[PathBuf]) -> Vec<(&'static str, &'static str, f64)> {
+    let mut counts: BTreeMap<usize, usize> = BTreeMap::new();
+    for path in paths {
+        let Some(extension) = path
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+        else {
+            continue;
+        };
+        if let Some(index) = LANGUAGES
+            .iter()
+            .position(|(_, _, extensions)| extensions.contains(&extension.as_str()))
+        {
+            *counts.entry(index).or_default() += 1;
+        }
+    }
+    let total: usize = counts.values().sum();
+    let mut languages: Vec<_> = counts
+        .into_iter()
+        .map(|(index, count)| {
+            let (key, name, _) = LANGUAGES[index];
+            (key, name, count as f64 / total as f64)
+        })
+        .collect();
+    languages.sort_by(|(_, _, a), (_, _, b)| b.total_cmp(a));
+    languages
+}
+
+// 言語のテンプレートを読み込む（どちらのファイルもなければ None）
+fn load_profile(dir: &str, key: &str, name: &str, share: f64) -> Option<LanguageProfile> {
+    let language_dir = Path::new(dir).join(key);
+    let system = std::fs::read_to_string(language_dir.join("system.md")).ok();
+    let questions = match std::fs::read_to_string(language_dir.join("questions.json")) {
+        Ok(content) => match serde_json::from_str::<LanguageQuestions>(&content) {
+            Ok(questions) => Some(questions),
+            Err(e) => {
+                warn!(
+                    "⚠️ 言語の深掘り質問の形式が正しくありません: {} - {}",
+                    language_dir.join("questions.json").display(),
+                    e
+                );
+                None
+            }
+        },
+        Err(_) => None,
+    };
+    if system.is_none() && questions.is_none() {
+        return None;
+    }
+    let (category, questions) = match questions {
+        Some(questions) => (
+            questions.name.unwrap_or_else(|| format!("{}固有", name)),
+            questions.questions,
+        ),
+        None => (format!("{}固有", name), Vec::new()),
+    };
+    Some(LanguageProfile {
+        name: name.to_string(),
+        share,
+        system,
+        category,
+        questions,
+    })
+}
+
+// リポジトリの主な言語のうち、テンプレートがあるもの（無効なら空）
+pub async fn detect(
+    github_client: &GitHubClient,
+    repo_info: &RepoInfo,
+) -> Result<Vec<LanguageProfile>> {
+    let Some(config) = CONFIG.get().filter(|config| config.enabled) else {
+        return Ok(Vec::new());
+    };
+    let repo = format!("{}/{}", repo_info.owner, repo_info.repo);
+    let profiles = PROFILES.get_or_init(|| Mutex::new(HashMap::new()));
+    if let Some(cached) = profiles.lock().unwrap().get(&repo) {
+        return Ok(cached.clone());
+    }
+
+    let (_, paths) = github_client
+        .list_code_files(repo_info)
+        .await
+        .map_err(|e| anyhow!("言語を判定できません: {} - {}", repo, e))?;
+    let detected: Vec<LanguageProfile> = count_languages(&paths)
+        .into_iter()
+        .filter(|(_, _, share)| *share >= config.min_share)
+        .take(config.max_languages)
+        .filter_map(|(key, name, share)| load_profile(&config.dir, key, name, share))
+        .collect();
+    if !detected.is_empty() {
+        info!(
+            "🗣️ 主な言語: {} - {}",
+            repo,
+            detected
+                .iter()
+                .map(|profile| format!("{} ({:.0}%)", profile.name, profile.share * 100.0))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+    profiles.lock().unwrap().insert(repo, detected.clone());
+    Ok(detected)
+}
+
+// システムプロンプトに加える言語ごとの観点（テンプレートがなければ None）
+pub fn prompt_section(profiles: &[LanguageProfile]) -> Option<String> {
+    let sections: Vec<String> = profiles
+        .iter()
+        .filter_map(|profile| {
+            profile.system.as_ref().map(|system| {
+                format!(
+                    "\n### {}（ファイルの {:.0}%）\n{}\n",
+                    profile.name,
+                    profile.share * 100.0,
+                    system.trim()
+                )
+            })
+        })
+        .collect();
+    if sections.is_empty() {
+        return None;
+    }
+    Some(format!(
+        "\n\n【言語ごとの観点】\nこのリポジトリの主な言語に特有の観点にも注目してください。\n{}",
+        sections.concat()
+    ))
+}
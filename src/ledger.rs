@@ -0,0 +1,430 @@
+// 使用量の台帳モジュール
+// 完了したAPI呼び出しごとにトークン数と推定コストを ledger.jsonl に追記し、実行をまたいだ累計の使用額を残す
+// 追記は1行ずつ書き足すだけにし、APIを呼び出すワーカーを止めないよう別スレッドで行う
+// （書き込み中に落ちて残った書きかけの行は読み込み時に読み飛ばす。一定のサイズを超えたら日時付きのファイルに切り替える）
+// レポートと実行状況ファイルの使用額（エンドポイントのグループごとの使用額を含む）はこの台帳から求める
+// --max-usd を指定した場合は、台帳の累計が上限に達したら新しいリクエストをすべて止める
+// （単価が分からないと使用額を数えられないため、料金表に単価がないモデルを呼び出した場合も止める）
+
+use anyhow::{Result, bail};
+use chrono::prelude::*;
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+
+use crate::llm::schemas::openai_response::{Endpoint, ResponseData};
+use crate::{generation, pricing, runs};
+
+// 台帳を切り替えるサイズ
+const SEGMENT_BYTES: u64 = 16 * 1024 * 1024;
+
+// 台帳の1行（1回のAPI呼び出し）
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LedgerEntry {
+    pub timestamp: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run_id: Option<String>,
+    pub endpoint: String,
+    // エンドポイントのサブスクリプションとテナント（グループごとの使用額の集計に使う）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subscription: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+    pub model: String,
+    pub prompt_tokens: usize,
+    pub completion_tokens: usize,
+    // 推論トークン数（completion_tokens に含まれる。推論モデル以外は省略）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning_tokens: Option<usize>,
+    pub total_tokens: usize,
+    // 料金表に単価がないモデルは null
+    pub estimated_cost: Option<f64>,
+    pub currency: String,
+}
+
+// 台帳の使用額（料金表の通貨）
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct Spend {
+    // 今回の実行（プロセス）の使用額
+    pub run: f64,
+    // 台帳全体の使用額
+    pub total: f64,
+    pub calls: usize,
+    pub tokens: usize,
+    pub currency: String,
+    // グループ（サブスクリプション・テナント）ごとの使用額
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub groups: BTreeMap<String, GroupSpend>,
+}
+
+// グループの使用額（料金表の通貨）
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct GroupSpend {
+    pub run: f64,
+    pub total: f64,
+    pub calls: usize,
+    pub tokens: usize,
+}
+
+impl LedgerEntry {
+    // 使用額を集計するグループ
+    pub fn groups(&self) -> impl Iterator<Item = &String> {
+        self.subscription
+            .iter()
+            .chain(&self.tenant)
+            .filter(|group| !group.is_empty())
+    }
+}
+
+struct Ledger {
+    path: PathBuf,
+    spend: Spend,
+}
+
+static LEDGER: Mutex<Option<Ledger>> = Mutex::new(None);
+
+// 台帳への追記（切り替えを含む）を1つずつ行うためのロック
+static APPEND: Mutex<()> = Mutex::new(());
+
+// 使用額の上限
+struct Ceiling {
+    max_usd: f64,
+    // 料金表の通貨での上限
+    limit: f64,
+    // 上限に達したことをログに出力したか
+    reached: AtomicBool,
+    // 呼び出した、料金表に単価がないモデル
+    unpriced: Mutex<Option<String>>,
+}
+
+static CEILING: OnceLock<Ceiling> = OnceLock::new();
+
+// 台帳のパス（ドライランは本番の台帳と混ざらないよう別ファイル）
+pub fn ledger_path(output_dir: &str, dry_run: bool) -> String {
+    if dry_run {
+        format!("{}/ledger.dry-run.jsonl", output_dir)
+    } else {
+        format!("{}/ledger.jsonl", output_dir)
+    }
+}
+
+// 台帳と、切り替えた古い台帳（ledger.<日時>.jsonl）のファイル
+fn segments(path: &Path) -> Vec<PathBuf> {
+    let (Some(dir), Some(stem)) = (
+        path.parent(),
+        path.file_stem().and_then(|stem| stem.to_str()),
+    ) else {
+        return Vec::new();
+    };
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|file| {
+            let Some(name) = file.file_name().and_then(|name| name.to_str()) else {
+                return false;
+            };
+            // ledger.jsonl の古い台帳に ledger.dry-run.jsonl などを含めない
+            let segment = name
+                .strip_prefix(stem)
+                .and_then(|rest| rest.strip_prefix('.'))
+                .and_then(|rest| rest.strip_suffix(".jsonl"))
+                .is_some_and(|stamp| {
+                    !stamp.is_empty() && stamp.chars().all(|c| c.is_ascii_digit() || c == '_')
+                });
+            segment || file.as_path() == path
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+// 台帳をすべて読み込む（書きかけの行は読み飛ばす）
+pub fn load(output_dir: &str, dry_run: bool) -> Vec<LedgerEntry> {
+    let path = ledger_path(output_dir, dry_run);
+    let mut entries = Vec::new();
+    for file in segments(Path::new(&path)) {
+        let content = match fs::read_to_string(&file) {
+            Ok(content) => content,
+            Err(e) => {
+                warn!("⚠️ 台帳を読み込めません: {} - {}", file.display(), e);
+                continue;
+            }
+        };
+        for (i, line) in content.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str::<LedgerEntry>(line) {
+                Ok(entry) => entries.push(entry),
+                Err(e) => warn!(
+                    "⚠️ 台帳の {} 行目を読み込めません: {} - {}",
+                    i + 1,
+                    file.display(),
+                    e
+                ),
+            }
+        }
+    }
+    entries
+}
+
+// 台帳を開き、これまでの使用額を読み込む
+pub fn open(output_dir: &str, dry_run: bool) {
+    let path = ledger_path(output_dir, dry_run);
+    let currency = pricing::currency().to_string();
+    let mut spend = Spend {
+        currency: currency.clone(),
+        ..Default::default()
+    };
+    let mut other_currency = 0;
+    for entry in load(output_dir, dry_run) {
+        let cost = match entry.estimated_cost {
+            Some(cost) if entry.currency == currency => cost,
+            Some(_) => {
+                other_currency += 1;
+                0.0
+            }
+            None => 0.0,
+        };
+        spend.calls += 1;
+        spend.tokens += entry.total_tokens;
+        spend.total += cost;
+        for group in entry.groups() {
+            let group = spend.groups.entry(group.clone()).or_default();
+            group.calls += 1;
+            group.tokens += entry.total_tokens;
+            group.total += cost;
+        }
+    }
+    if other_currency > 0 {
+        warn!(
+            "⚠️ 料金表と異なる通貨で記録した {} 件の使用額は累計に含めません",
+            other_currency
+        );
+    }
+    *LEDGER.lock().unwrap() = Some(Ledger {
+        path: PathBuf::from(path),
+        spend,
+    });
+}
+
+// 台帳の末尾に1行を追記する
+fn append(path: &Path, line: &str) -> Result<()> {
+    let _guard = APPEND.lock().unwrap();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    // 大きくなった台帳は日時付きの名前に切り替え、新しい台帳に書き始める
+    if fs::metadata(path).is_ok_and(|metadata| metadata.len() >= SEGMENT_BYTES) {
+        let segment = path.with_extension(format!("{}.jsonl", Utc::now().format("%Y%m%d_%H%M%S")));
+        fs::rename(path, segment)?;
+    }
+
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    file.write_all(format!("{}\n", line).as_bytes())?;
+    file.sync_data()?;
+    Ok(())
+}
+
+// 台帳への追記を別スレッドで行う（非同期のランタイムの外では、その場で書き込む）
+fn append_in_background(path: PathBuf, line: String) {
+    let write = move || {
+        if let Err(e) = append(&path, &line) {
+            warn!("⚠️ 台帳に記録できません: {} - {}", path.display(), e);
+        }
+    };
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) => {
+            handle.spawn_blocking(write);
+        }
+        Err(_) => write(),
+    }
+}
+
+// 完了したAPI呼び出しを台帳に記録し、記録後の使用額を返す（台帳を開いていなければ何もしない）
+pub fn record(
+    endpoint: &Endpoint,
+    model: &str,
+    prompt_tokens: usize,
+    completion_tokens: usize,
+    reasoning_tokens: Option<usize>,
+    estimated_cost: Option<f64>,
+) -> Option<Spend> {
+    if estimated_cost.is_none()
+        && let Some(ceiling) = CEILING.get()
+    {
+        ceiling
+            .unpriced
+            .lock()
+            .unwrap()
+            .get_or_insert_with(|| model.to_string());
+    }
+    let mut ledger = LEDGER.lock().unwrap();
+    let ledger = ledger.as_mut()?;
+    let entry = LedgerEntry {
+        timestamp: Utc::now().to_rfc3339(),
+        run_id: runs::current_id(),
+        endpoint: endpoint.name.clone(),
+        subscription: endpoint.subscription.clone(),
+        tenant: endpoint.tenant.clone(),
+        model: model.to_string(),
+        prompt_tokens,
+        completion_tokens,
+        reasoning_tokens,
+        total_tokens: prompt_tokens + completion_tokens,
+        estimated_cost,
+        currency: pricing::currency().to_string(),
+    };
+    match serde_json::to_string(&entry) {
+        Ok(line) => append_in_background(ledger.path.clone(), line),
+        Err(e) => warn!("⚠️ 台帳の行を作成できません: {}", e),
+    }
+
+    let spend = &mut ledger.spend;
+    let cost = estimated_cost.unwrap_or_default();
+    spend.calls += 1;
+    spend.tokens += entry.total_tokens;
+    spend.run += cost;
+    spend.total += cost;
+    for group in entry.groups() {
+        let group = spend.groups.entry(group.clone()).or_default();
+        group.calls += 1;
+        group.tokens += entry.total_tokens;
+        group.run += cost;
+        group.total += cost;
+    }
+    Some(spend.clone())
+}
+
+// 現在の使用額（台帳を開いていなければ None）
+pub fn spend() -> Option<Spend> {
+    LEDGER
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|ledger| ledger.spend.clone())
+}
+
+// 保存完了のログに添える、ターンの推定コストと台帳の累計（例: ", ~$3.42, 累計 $1,204.55"）
+pub fn cost_note(response_data: &ResponseData) -> String {
+    let turn_cost = response_data
+        .metrics
+        .as_ref()
+        .and_then(|metrics| metrics.estimated_cost);
+    let mut note = String::new();
+    if let Some(cost) = turn_cost {
+        let (cost, currency) = pricing::convert(cost, pricing::currency());
+        note.push_str(&format!(", ~{}", pricing::format_amount(cost, &currency)));
+    }
+    // 単価が分からない呼び出しだけなら累計も表示しない
+    if let Some(spend) = spend().filter(|spend| turn_cost.is_some() || spend.total > 0.0) {
+        let (total, currency) = pricing::convert(spend.total, &spend.currency);
+        note.push_str(&format!(
+            ", 累計 {}",
+            pricing::format_amount(total, &currency)
+        ));
+    }
+    note
+}
+
+// 台帳の使用額をログに出力
+pub fn log_summary() {
+    let Some(spend) = spend() else {
+        return;
+    };
+    if spend.calls == 0 {
+        return;
+    }
+    let (run, currency) = pricing::convert(spend.run, &spend.currency);
+    let (total, _) = pricing::convert(spend.total, &spend.currency);
+    info!(
+        "📒 台帳の推定使用額: 今回 {:.2} {}, 累計 {:.2} {}（{} 回の呼び出し, {} トークン）",
+        run, currency, total, currency, spend.calls, spend.tokens
+    );
+}
+
+// 使用額の上限（USD）を設定する（台帳を開いてから呼び出す）
+pub fn set_ceiling(max_usd: f64) -> Result<()> {
+    if max_usd <= 0.0 {
+        bail!("--max-usd には正の値を指定してください: {}", max_usd);
+    }
+    // 台帳の使用額は料金表の通貨で記録するため、USD の上限をその通貨に換算する
+    let currency = pricing::currency();
+    let limit = if currency.eq_ignore_ascii_case("USD") {
+        max_usd
+    } else if pricing::display_currency().eq_ignore_ascii_case("USD") {
+        max_usd / pricing::convert(1.0, currency).0
+    } else {
+        bail!(
+            "--max-usd を使うには pricing.currency か pricing.report_currency を USD にしてください（現在: {}）",
+            currency
+        );
+    };
+    // 単価がないモデルの使用額は数えられないため、上限を守れない設定では起動しない
+    let model = generation::default_model();
+    if !pricing::has_price(model) {
+        bail!(
+            "--max-usd を指定した場合は、料金表に単価があるモデルを使ってください（pricing.models に {} がありません）",
+            model
+        );
+    }
+    let _ = CEILING.set(Ceiling {
+        max_usd,
+        limit,
+        reached: AtomicBool::new(false),
+        unpriced: Mutex::new(None),
+    });
+
+    let total = spend().map(|spend| spend.total).unwrap_or_default();
+    info!(
+        "🛑 使用額の上限: {:.2} USD（台帳の累計: {:.2} {}）",
+        max_usd, total, currency
+    );
+    Ok(())
+}
+
+// 使用額の上限（USD、未設定なら None）
+pub fn ceiling() -> Option<f64> {
+    CEILING.get().map(|ceiling| ceiling.max_usd)
+}
+
+// 台帳の累計が使用額の上限に達したか、単価がないモデルを呼び出したか（初めて止めるときにログに出力する）
+pub fn ceiling_reached() -> bool {
+    let Some(ceiling) = CEILING.get() else {
+        return false;
+    };
+    if let Some(model) = ceiling.unpriced.lock().unwrap().as_deref() {
+        if !ceiling.reached.swap(true, Ordering::Relaxed) {
+            error!(
+                "🛑 料金表に単価がないモデルを呼び出したため、使用額の上限（{:.2} USD）を守れません。新しいリクエストを止めます: {}",
+                ceiling.max_usd, model
+            );
+        }
+        return true;
+    }
+    let Some(spend) = spend() else {
+        return false;
+    };
+    if spend.total < ceiling.limit {
+        return false;
+    }
+    if !ceiling.reached.swap(true, Ordering::Relaxed) {
+        let (total, currency) = pricing::convert(spend.total, &spend.currency);
+        error!(
+            "🛑 台帳の推定使用額が上限（{:.2} USD）に達したため、新しいリクエストを止めます（累計 {:.2} {}）",
+            ceiling.max_usd, total, currency
+        );
+    }
+    true
+}
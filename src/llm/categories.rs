@@ -1,93 +1,363 @@
 // リポジトリ分析用の質問カテゴリと質問文を管理するモジュール
+// 組み込みの質問に、質問バンクのディレクトリのカテゴリごとの JSON ファイル（{category}.json）を重ねて使う
+// （同じカテゴリのファイルは組み込みの質問を置き換え、新しいカテゴリのファイルは出題するカテゴリに加える）
 use anyhow::{Result, anyhow};
-use std::collections::HashMap;
-
-// カテゴリごとの質問を定義
-type QuestionMap = HashMap<String, Vec<String>>;
-
-// 質問マップを初期化
-fn init_questions() -> QuestionMap {
-    let mut questions = HashMap::new();
-
-    // アーキテクチャに関する質問
-    questions.insert(
-        "architecture".to_string(), 
-        vec![
-            "このリポジトリのアーキテクチャについて詳細に分析してください。主要なコンポーネントとその関係性、設計パターン、アーキテクチャの強みと弱みについて議論してください。".to_string(),
-            "このプロジェクトのアーキテクチャスタイル（マイクロサービス、モノリス、イベント駆動など）について分析してください。なぜこのスタイルが選ばれたと思いますか？別のアーキテクチャスタイルの方が適している可能性はありますか？".to_string(),
-            "このシステムのスケーラビリティに関するアーキテクチャ上の考慮点を分析してください。将来の拡張や変更に対してどのように設計されていますか？".to_string(),
-        ]
-    );
-
-    // パフォーマンスに関する質問
-    questions.insert(
-        "performance".to_string(), 
-        vec![
-            "このリポジトリのコードでパフォーマンスに影響する可能性のある部分を特定し、改善策を提案してください。特にボトルネックになりそうな処理や最適化できる部分に焦点を当ててください。".to_string(),
-            "このシステムのリソース使用状況（CPU、メモリ、I/Oなど）について分析してください。効率的にリソースを使用していますか？最適化の余地はありますか？".to_string(),
-            "このコードベースのデータ処理パターンを分析してください。大量のデータを扱う場合のパフォーマンス特性はどうでしょうか？".to_string(),
-        ]
-    );
-
-    // セキュリティに関する質問
-    questions.insert(
-        "security".to_string(), 
-        vec![
-            "このリポジトリのコードにおける潜在的なセキュリティ脆弱性を特定し、それらのリスクレベルと対応策について議論してください。".to_string(),
-            "このシステムの認証と認可のメカニズムについて評価してください。セキュリティのベストプラクティスに従っていますか？改善の余地はありますか？".to_string(),
-            "このコードベースでデータの検証と入力サニタイズはどのように行われていますか？潜在的な脆弱性や改善点はありますか？".to_string(),
-        ]
-    );
-
-    // テスト品質に関する質問
-    questions.insert(
-        "testing".to_string(), 
-        vec![
-            "このリポジトリのテスト戦略と品質保証の仕組みを分析してください。テストカバレッジ、テストの種類、自動化の程度はどうでしょうか？改善点はありますか？".to_string(),
-            "このプロジェクトの単体テスト、統合テスト、エンドツーエンドテストのアプローチについて評価してください。テストピラミッドのバランスは適切ですか？".to_string(),
-            "このコードベースのエラーハンドリングとエッジケースの処理を分析してください。例外処理とバリデーションは十分に行われていますか？".to_string(),
-        ]
-    );
-
-    // ドメイン分析に関する質問
-    questions.insert(
-        "domain".to_string(), 
-        vec![
-            "このリポジトリが解決しようとしているドメイン問題について詳細に分析してください。ドメインモデルの設計とビジネスロジックの表現方法は適切でしょうか？".to_string(),
-            "このプロジェクトのドメイン駆動設計の原則の適用状況を評価してください。境界づけられたコンテキスト、エンティティ、値オブジェクトなどの概念はどのように表現されていますか？".to_string(),
-            "このコードベースのビジネスルールとドメインロジックのカプセル化方法を分析してください。ドメインの変更に対してどの程度柔軟に対応できますか？".to_string(),
-        ]
-    );
-
-    // 分散システムに関する質問
-    questions.insert(
-        "distributed".to_string(), 
-        vec![
-            "このリポジトリが分散システムとして動作する場合、その特性と課題について分析してください。ネットワーク障害、レイテンシ、データ一貫性などにどう対応していますか？".to_string(),
-            "このプロジェクトにおける非同期処理とメッセージパッシングのパターンを評価してください。分散システムのベストプラクティスに従っていますか？".to_string(),
-            "このコードベースのCAP定理（一貫性、可用性、分断耐性）におけるトレードオフを分析してください。システム設計でどのような選択がされていますか？".to_string(),
-        ]
-    );
-
-    // コード保守性に関する質問
-    questions.insert(
-        "maintainability".to_string(), 
-        vec![
-            "このリポジトリのコード保守性と読みやすさを評価してください。命名規則、コメント、関数の長さ、複雑性などの面で改善点はありますか？".to_string(),
-            "このプロジェクトのコード構造と依存関係の管理を分析してください。依存性注入、モジュール化、関心の分離などの原則はどの程度適用されていますか？".to_string(),
-            "このコードベースの技術的負債と将来のリファクタリングの機会を特定してください。コードの品質を向上させるための具体的な提案はありますか？".to_string(),
-        ]
-    );
-
-    questions
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::OnceLock;
+
+// 質問バンクの設定
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct QuestionsConfig {
+    /// 質問バンクのディレクトリ（カテゴリごとの {category}.json）
+    pub dir: String,
+    /// 組み込み以外に出題するカテゴリ（質問バンクのファイルがなければ questions bootstrap で生成する）
+    pub categories: Vec<CategoryDefinition>,
+    /// questions bootstrap で1カテゴリあたりに生成する質問数
+    pub generate_count: usize,
+    /// 質問の生成に使うデプロイメント名（省略時は議論と同じモデル）
+    pub deployment: Option<String>,
+    /// 質問の生成の最大出力トークン数
+    pub max_tokens: usize,
+}
+
+impl Default for QuestionsConfig {
+    fn default() -> Self {
+        QuestionsConfig {
+            dir: "categories".to_string(),
+            categories: Vec::new(),
+            generate_count: 5,
+            deployment: None,
+            max_tokens: 4000,
+        }
+    }
+}
+
+// 設定で追加するカテゴリ
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CategoryDefinition {
+    /// カテゴリのキー（質問バンクのファイル名）
+    pub category: String,
+    /// カテゴリの日本語名
+    pub name: String,
+    /// 質問の生成時にモデルに伝えるカテゴリの説明
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+// 1つのカテゴリの質問（質問バンクのファイルの形式）
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct QuestionCategory {
+    /// カテゴリのキー（英小文字・数字・_ ・-、ファイル名と同じ）
+    pub category: String,
+    /// カテゴリの日本語名（保存する結果に記録する）
+    pub name: String,
+    /// 質問文（順に出題する）
+    pub questions: Vec<String>,
+}
+
+// 組み込みの質問
+fn builtin_categories() -> Vec<QuestionCategory> {
+    [
+        // アーキテクチャに関する質問
+        (
+            "architecture",
+            "アーキテクチャ",
+            vec![
+                "このリポジトリのアーキテクチャについて詳細に分析してください。主要なコンポーネントとその関係性、設計パターン、アーキテクチャの強みと弱みについて議論してください。",
+                "このプロジェクトのアーキテクチャスタイル（マイクロサービス、モノリス、イベント駆動など）について分析してください。なぜこのスタイルが選ばれたと思いますか？別のアーキテクチャスタイルの方が適している可能性はありますか？",
+                "このシステムのスケーラビリティに関するアーキテクチャ上の考慮点を分析してください。将来の拡張や変更に対してどのように設計されていますか？",
+            ],
+        ),
+        // パフォーマンスに関する質問
+        (
+            "performance",
+            "パフォーマンス",
+            vec![
+                "このリポジトリのコードでパフォーマンスに影響する可能性のある部分を特定し、改善策を提案してください。特にボトルネックになりそうな処理や最適化できる部分に焦点を当ててください。",
+                "このシステムのリソース使用状況（CPU、メモリ、I/Oなど）について分析してください。効率的にリソースを使用していますか？最適化の余地はありますか？",
+                "このコードベースのデータ処理パターンを分析してください。大量のデータを扱う場合のパフォーマンス特性はどうでしょうか？",
+            ],
+        ),
+        // セキュリティに関する質問
+        (
+            "security",
+            "セキュリティ",
+            vec![
+                "このリポジトリのコードにおける潜在的なセキュリティ脆弱性を特定し、それらのリスクレベルと対応策について議論してください。",
+                "このシステムの認証と認可のメカニズムについて評価してください。セキュリティのベストプラクティスに従っていますか？改善の余地はありますか？",
+                "このコードベースでデータの検証と入力サニタイズはどのように行われていますか？潜在的な脆弱性や改善点はありますか？",
+            ],
+        ),
+        // テスト品質に関する質問
+        (
+            "testing",
+            "テスト品質",
+            vec![
+                "このリポジトリのテスト戦略と品質保証の仕組みを分析してください。テストカバレッジ、テストの種類、自動化の程度はどうでしょうか？改善点はありますか？",
+                "このプロジェクトの単体テスト、統合テスト、エンドツーエンドテストのアプローチについて評価してください。テストピラミッドのバランスは適切ですか？",
+                "このコードベースのエラーハンドリングとエッジケースの処理を分析してください。例外処理とバリデーションは十分に行われていますか？",
+            ],
+        ),
+        // ドメイン分析に関する質問
+        (
+            "domain",
+            "ドメイン分析",
+            vec![
+                "このリポジトリが解決しようとしているドメイン問題について詳細に分析してください。ドメインモデルの設計とビジネスロジックの表現方法は適切でしょうか？",
+                "このプロジェクトのドメイン駆動設計の原則の適用状況を評価してください。境界づけられたコンテキスト、エンティティ、値オブジェクトなどの概念はどのように表現されていますか？",
+                "このコードベースのビジネスルールとドメインロジックのカプセル化方法を分析してください。ドメインの変更に対してどの程度柔軟に対応できますか？",
+            ],
+        ),
+        // 分散システムに関する質問
+        (
+            "distributed",
+            "分散システム",
+            vec![
+                "このリポジトリが分散システムとして動作する場合、その特性と課題について分析してください。ネットワーク障害、レイテンシ、データ一貫性などにどう対応していますか？",
+                "このプロジェクトにおける非同期処理とメッセージパッシングのパターンを評価してください。分散システムのベストプラクティスに従っていますか？",
+                "このコードベースのCAP定理（一貫性、可用性、分断耐性）におけるトレードオフを分析してください。システム設計でどのような選択がされていますか？",
+            ],
+        ),
+        // コード保守性に関する質問
+        (
+            "maintainability",
+            "コード保守性",
+            vec![
+                "このリポジトリのコード保守性と読みやすさを評価してください。命名規則、コメント、関数の長さ、複雑性などの面で改善点はありますか？",
+                "このプロジェクトのコード構造と依存関係の管理を分析してください。依存性注入、モジュール化、関心の分離などの原則はどの程度適用されていますか？",
+                "このコードベースの技術的負債と将来のリファクタリングの機会を特定してください。コードの品質を向上させるための具体的な提案はありますか？",
+            ],
+        ),
+    ]
+    .into_iter()
+    .map(|(category, name, questions)| QuestionCategory {
+        category: category.to_string(),
+        name: name.to_string(),
+        questions: questions.into_iter().map(str::to_string).collect(),
+    })
+    .collect()
+}
+
+// カテゴリのキーとして使える文字列か
+fn is_valid_key(key: &str) -> bool {
+    !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-')
+}
+
+// 質問の比較用に空白をまとめる
+fn normalize(question: &str) -> String {
+    question.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+impl QuestionCategory {
+    // 空の質問と重複する質問を取り除く（取り除いた数を返す）
+    pub fn dedupe(&mut self) -> usize {
+        let before = self.questions.len();
+        let mut seen = HashSet::new();
+        self.questions = std::mem::take(&mut self.questions)
+            .into_iter()
+            .map(|question| question.trim().to_string())
+            .filter(|question| !question.is_empty() && seen.insert(normalize(question)))
+            .collect();
+        before - self.questions.len()
+    }
+
+    // 形式の問題を一覧にする（ファイル名と一致するかも確認する）
+    pub fn validate(&self, file_stem: Option<&str>) -> Vec<String> {
+        let mut problems = Vec::new();
+        if !is_valid_key(&self.category) {
+            problems.push(format!(
+                "category は英小文字・数字・_ ・- で指定してください: {:?}",
+                self.category
+            ));
+        }
+        if let Some(stem) = file_stem.filter(|stem| *stem != self.category) {
+            problems.push(format!(
+                "category ({}) がファイル名 ({}) と一致しません",
+                self.category, stem
+            ));
+        }
+        if self.name.trim().is_empty() {
+            problems.push("name が空です".to_string());
+        }
+        if self.questions.is_empty() {
+            problems.push("questions が空です".to_string());
+        }
+        let mut seen = HashSet::new();
+        for (i, question) in self.questions.iter().enumerate() {
+            if question.trim().is_empty() {
+                problems.push(format!("questions[{}] が空です", i));
+            } else if !seen.insert(normalize(question)) {
+                problems.push(format!("questions[{}] は重複しています", i));
+            }
+        }
+        problems
+    }
+}
+
+// 質問バンクのファイルのパス
+pub fn file_path(dir: &str, category: &str) -> String {
+    format!("{}/{}.json", dir, category)
+}
+
+// 質問バンクのファイルを読み込む
+pub fn load_file(path: &Path) -> Result<QuestionCategory> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| anyhow!("質問バンクを読み込めません: {} - {}", path.display(), e))?;
+    serde_json::from_str(&content).map_err(|e| {
+        anyhow!(
+            "質問バンクの形式が正しくありません: {} - {}",
+            path.display(),
+            e
+        )
+    })
+}
+
+// 質問バンクのファイルを保存する
+pub fn save_file(dir: &str, category: &QuestionCategory) -> Result<String> {
+    std::fs::create_dir_all(dir)
+        .map_err(|e| anyhow!("質問バンクのディレクトリを作成できません: {} - {}", dir, e))?;
+    let path = file_path(dir, &category.category);
+    std::fs::write(&path, serde_json::to_string_pretty(category)? + "\n")
+        .map_err(|e| anyhow!("質問バンクを保存できません: {} - {}", path, e))?;
+    Ok(path)
+}
+
+// ディレクトリの質問バンクのファイルを読み込む（ファイル名の順）
+pub fn load_dir(dir: &str) -> Vec<(String, Result<QuestionCategory>)> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<_> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "json")
+        })
+        .collect();
+    paths.sort();
+    paths
+        .into_iter()
+        .map(|path| {
+            let stem = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let category = load_file(&path).and_then(|category| {
+                let problems = category.validate(Some(&stem));
+                if problems.is_empty() {
+                    Ok(category)
+                } else {
+                    Err(anyhow!("{}", problems.join(", ")))
+                }
+            });
+            (path.display().to_string(), category)
+        })
+        .collect()
+}
+
+// 組み込みの質問に質問バンクのファイルを重ねる（形式が正しくないファイルは使わない）
+fn merge(dir: &str, verbose: bool) -> Vec<QuestionCategory> {
+    let mut categories = builtin_categories();
+    for (path, loaded) in load_dir(dir) {
+        match loaded {
+            Ok(category) => {
+                if verbose {
+                    info!(
+                        "📚 質問バンクを読み込みました: {} ({} 問)",
+                        path,
+                        category.questions.len()
+                    );
+                }
+                match categories
+                    .iter_mut()
+                    .find(|existing| existing.category == category.category)
+                {
+                    Some(existing) => *existing = category,
+                    None => categories.push(category),
+                }
+            }
+            Err(e) => {
+                if verbose {
+                    warn!("⚠️ 質問バンクを使用しません: {} - {}", path, e);
+                }
+            }
+        }
+    }
+    categories
+}
+
+static CATEGORIES: OnceLock<Vec<QuestionCategory>> = OnceLock::new();
+
+// 質問バンクのディレクトリを読み込む
+pub fn configure(config: &QuestionsConfig) {
+    let categories = merge(&config.dir, true);
+    for definition in missing(config, &categories) {
+        warn!(
+            "⚠️ カテゴリ {} ({}) の質問バンクがないため出題しません。questions bootstrap で生成できます",
+            definition.category, definition.name
+        );
+    }
+    let _ = CATEGORIES.set(categories);
+}
+
+// 設定で追加したカテゴリのうち、質問バンクのファイルがないもの
+pub fn missing<'a>(
+    config: &'a QuestionsConfig,
+    categories: &[QuestionCategory],
+) -> Vec<&'a CategoryDefinition> {
+    config
+        .categories
+        .iter()
+        .filter(|definition| {
+            !categories
+                .iter()
+                .any(|category| category.category == definition.category)
+        })
+        .collect()
+}
+
+// 出題するカテゴリ（組み込みのカテゴリの後に質問バンクで追加したカテゴリが続く）
+pub fn categories() -> &'static [QuestionCategory] {
+    CATEGORIES.get_or_init(builtin_categories)
+}
+
+// 質問バンクのファイルを重ねたカテゴリを読み直す（質問バンクの管理用）
+pub fn reload(config: &QuestionsConfig) -> Vec<QuestionCategory> {
+    merge(&config.dir, false)
+}
+
+// 組み込みのカテゴリか
+pub fn is_builtin(category: &str) -> bool {
+    builtin_categories()
+        .iter()
+        .any(|builtin| builtin.category == category)
+}
+
+// 日本語のカテゴリ名からカテゴリのキーを取得する
+pub fn key_for_name(name: &str) -> Option<&'static str> {
+    categories()
+        .iter()
+        .find(|category| category.name == name)
+        .map(|category| category.category.as_str())
 }
 
 // 特定のカテゴリから質問を取得する
 pub fn get_question(category: &str, index: usize) -> Result<String> {
-    let questions = init_questions();
-
     // カテゴリが存在するか確認
-    if let Some(category_questions) = questions.get(category) {
+    if let Some(category_questions) = categories()
+        .iter()
+        .find(|existing| existing.category == category)
+        .map(|existing| &existing.questions)
+        .filter(|questions| !questions.is_empty())
+    {
         // インデックスが範囲内かチェック
         let actual_index = index % category_questions.len();
 
@@ -99,16 +369,11 @@ pub fn get_question(category: &str, index: usize) -> Result<String> {
 }
 
 // カテゴリの日本語名を取得
+#[allow(dead_code)]
 pub fn get_category_japanese(category_en: &str) -> String {
-    match category_en {
-        "architecture" => "アーキテクチャ",
-        "performance" => "パフォーマンス",
-        "security" => "セキュリティ",
-        "testing" => "テスト品質",
-        "domain" => "ドメイン分析",
-        "distributed" => "分散システム",
-        "maintainability" => "コード保守性",
-        _ => "不明なカテゴリ",
-    }
-    .to_string()
+    categories()
+        .iter()
+        .find(|category| category.category == category_en)
+        .map(|category| category.name.clone())
+        .unwrap_or_else(|| "不明なカテゴリ".to_string())
 }
@@ -1,12 +1,90 @@
 // プロンプトテンプレートを管理するモジュール
 use anyhow::{Result, anyhow};
-use std::collections::HashMap;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use std::path::Path;
+use std::sync::OnceLock;
 
 // テンプレート変数のタイプ
 pub type TemplateVariables = Vec<(String, String)>;
 
+// 議論タイプごとのシステムプロンプトの設定
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct SystemPromptsConfig {
+    /// システムプロンプトのファイル（{name}.md）を置くディレクトリ
+    pub dir: String,
+    /// 議論タイプ（短い名前または議論タイプ名）ごとのシステムプロンプトのファイル名（拡張子なし、省略時は短い名前）
+    pub prompts: BTreeMap<String, String>,
+}
+
+impl Default for SystemPromptsConfig {
+    fn default() -> Self {
+        SystemPromptsConfig {
+            dir: "llm/system_prompts".to_string(),
+            prompts: BTreeMap::from([("review".to_string(), "code_review".to_string())]),
+        }
+    }
+}
+
+static SYSTEM_PROMPTS: OnceLock<SystemPromptsConfig> = OnceLock::new();
+
+// システムプロンプトの設定を登録
+pub fn configure(config: &SystemPromptsConfig) {
+    let _ = SYSTEM_PROMPTS.set(config.clone());
+    for (debate_type, name) in &config.prompts {
+        let path = Path::new(&config.dir).join(format!("{}.md", name));
+        if !path.exists() {
+            warn!(
+                "⚠️ 議論タイプ {} のシステムプロンプトがありません: {}",
+                debate_type,
+                path.display()
+            );
+        }
+    }
+}
+
+// システムプロンプトをファイルから読み込む
+pub fn load_system_prompt(prompt_name: &str) -> Result<String> {
+    let config = SYSTEM_PROMPTS.get_or_init(SystemPromptsConfig::default);
+    let prompt_path = Path::new(&config.dir).join(format!("{}.md", prompt_name));
+    fs::read_to_string(&prompt_path).map_err(|e| {
+        anyhow!(
+            "システムプロンプト {} の読み込みに失敗: {}",
+            prompt_path.display(),
+            e
+        )
+    })
+}
+
+// 議論タイプのシステムプロンプト（設定したファイル名、なければ短い名前のファイル。どちらもなければ None）
+pub fn system_prompt_for(debate_type: &str, alias: Option<&str>) -> Option<String> {
+    let config = SYSTEM_PROMPTS.get_or_init(SystemPromptsConfig::default);
+    let configured = config
+        .prompts
+        .get(debate_type)
+        .or_else(|| alias.and_then(|alias| config.prompts.get(alias)));
+    let name = configured.map(String::as_str).or(alias)?;
+    match load_system_prompt(name) {
+        Ok(prompt) => {
+            info!(
+                "📝 議論タイプ {} のシステムプロンプト: {}",
+                debate_type, name
+            );
+            Some(prompt)
+        }
+        Err(e) => {
+            // 設定で指定したファイルが読めない場合だけ警告する
+            if configured.is_some() {
+                warn!("⚠️ {}", e);
+            }
+            None
+        }
+    }
+}
+
 // テンプレートをファイルから読み込む
 pub fn load_template(template_name: &str) -> Result<String> {
     // テンプレートディレクトリのパス
@@ -49,6 +127,7 @@ pub fn render_template(template: &str, variables: &TemplateVariables) -> String
 }
 
 // デフォルトテンプレートのマップを取得
+#[allow(dead_code)]
 pub fn get_default_templates() -> HashMap<String, String> {
     let mut templates = HashMap::new();
 
@@ -87,6 +166,7 @@ pub fn get_default_templates() -> HashMap<String, String> {
 }
 
 // テンプレートをファイルシステムに保存
+#[allow(dead_code)]
 pub fn save_default_templates() -> Result<()> {
     let templates = get_default_templates();
 
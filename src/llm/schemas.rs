@@ -5,11 +5,93 @@ pub mod github_response {
     use serde::{Deserialize, Serialize};
 
     // リポジトリ情報
-    #[derive(Clone, Debug, Deserialize, Serialize)]
+    #[derive(Clone, Debug, Default, Deserialize, Serialize)]
     pub struct RepoInfo {
         pub owner: String,
         pub repo: String,
         pub max_files: usize,
+        // ブランチ（省略時はデフォルトブランチ）
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub branch: Option<String>,
+        // 分析するコミット（省略時はブランチの最新）
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub commit: Option<String>,
+        // 優先度（高いリポジトリから分析し、追加の視点でも分析する）
+        #[serde(default)]
+        pub priority: Priority,
+        // 実行する議論タイプ（短い名前または議論タイプ名。省略時はすべて）
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        pub debate_types: Vec<String>,
+        // 実行しない議論タイプ（社内リポジトリのライセンス分析などを省く）
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        pub skip_debate_types: Vec<String>,
+        // 読み込むファイルの最大サイズ（バイト。省略時は設定全体の max_file_bytes）
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub max_file_bytes: Option<usize>,
+        // ファイルの内容を切り詰める文字数（省略時は設定全体の truncate_chars）
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub truncate_chars: Option<usize>,
+        // ファイルの内容を切り詰めるおおよそのトークン数（省略時は設定全体の truncate_tokens）
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub truncate_tokens: Option<usize>,
+    }
+
+    // ファイルの読み込みの制限
+    #[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq, Hash)]
+    pub struct FileLimits {
+        // これを超えるサイズ（バイト）のファイルは読み飛ばす
+        pub max_file_bytes: usize,
+        // 読み込んだ内容をこの文字数で切り詰める（None なら切り詰めない）
+        pub truncate_chars: Option<usize>,
+        // 読み込んだ内容をこのおおよそのトークン数で切り詰める（4文字を1トークンと見積もる）
+        pub truncate_tokens: Option<usize>,
+    }
+
+    impl FileLimits {
+        // リポジトリごとの上書きを反映した制限
+        pub fn for_repo(&self, repo_info: &RepoInfo) -> FileLimits {
+            FileLimits {
+                max_file_bytes: repo_info.max_file_bytes.unwrap_or(self.max_file_bytes),
+                truncate_chars: repo_info.truncate_chars.or(self.truncate_chars),
+                truncate_tokens: repo_info.truncate_tokens.or(self.truncate_tokens),
+            }
+        }
+
+        // 切り詰める文字数（文字数とトークン数の小さい方。どちらもなければ None）
+        pub fn truncate_at(&self) -> Option<usize> {
+            [
+                self.truncate_chars,
+                self.truncate_tokens.map(|tokens| tokens * 4),
+            ]
+            .into_iter()
+            .flatten()
+            .min()
+        }
+
+        // 長すぎる内容は先頭部分のみにする（文字単位で安全に切り取る）
+        pub fn truncate(&self, content: String) -> String {
+            match self.truncate_at() {
+                Some(limit) if content.chars().count() > limit => {
+                    let truncated: String = content.chars().take(limit).collect();
+                    format!("{}...\n(内容省略)...", truncated)
+                }
+                _ => content,
+            }
+        }
+    }
+
+    // リポジトリの優先度
+    #[derive(
+        Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord,
+    )]
+    #[serde(rename_all = "lowercase")]
+    pub enum Priority {
+        /// 予算や時間が残っている場合だけ分析する
+        Low,
+        #[default]
+        Normal,
+        /// 最初に分析し、追加の視点でも分析する
+        High,
     }
 
     // ファイル情報
@@ -17,6 +99,9 @@ pub mod github_response {
     pub struct FileInfo {
         pub path: String,
         pub content: String,
+        // 内容のおおよそのトークン数
+        #[serde(default)]
+        pub tokens: usize,
     }
 }
 
@@ -24,19 +109,157 @@ pub mod github_response {
 pub mod openai_response {
     use serde::{Deserialize, Serialize};
 
-    // Azureエンドポイント設定
-    #[derive(Clone, Debug, Deserialize, Serialize)]
+    // エンドポイント設定
+    #[derive(Clone, Debug, Default, Deserialize, Serialize)]
     pub struct Endpoint {
         pub name: String,
         pub key: String,
+        /// 予備のAPIキー（401 / 403 が返ったら key から順に切り替える）
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        pub keys: Vec<String>,
         pub endpoint: String,
+        #[serde(default)]
+        pub provider: ProviderKind,
+        /// APIバージョン（省略時は全体設定、それもなければプロバイダーの既定値）
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub api_version: Option<String>,
+        /// モデル単位のAPIのパステンプレート（{deployment}, {operation} を置換）
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub deployment_path: Option<String>,
+        /// リソース単位のAPIのパステンプレート（{path} を置換）
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub resource_path: Option<String>,
+        /// タスク割り当ての重み（省略時は1。クォータ取得時は容量から設定）
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub weight: Option<f64>,
+        /// 1分あたりの最大リクエスト数（省略時は制限なし）
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub rpm_limit: Option<u32>,
+        /// 同時に送信するリクエストの上限（省略時は制限なし。ワーカー数もこの値までに抑える）
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub max_concurrent: Option<usize>,
+        /// PTU デプロイメントの割り当て数（PTU モードで使用。クォータ取得時は Provisioned SKU の容量から設定）
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub provisioned_units: Option<u64>,
+        /// リソース上のデプロイメント一覧（ARMから自動検出した場合に設定）
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        pub deployments: Vec<DeploymentInfo>,
+        /// エンドポイントのサブスクリプション（使用額をグループごとに集計する。ARMから自動検出した場合に設定）
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub subscription: Option<String>,
+        /// エンドポイントのテナント（使用額をグループごとに集計する。ARMから自動検出した場合に設定）
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub tenant: Option<String>,
+        /// このエンドポイントで使うデプロイメント名（省略時は generation.model）
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub model: Option<String>,
+        /// このエンドポイントでの議論の最大トークン数（省略時は generation.max_tokens）
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub max_tokens: Option<usize>,
+        /// このエンドポイントでの議論の温度（省略時は generation.temperature）
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub temperature: Option<f32>,
+    }
+
+    impl Endpoint {
+        // key と予備のキーを重複と空文字を除いて並べる
+        pub fn all_keys(&self) -> Vec<String> {
+            let mut keys: Vec<String> = Vec::new();
+            for key in std::iter::once(&self.key).chain(&self.keys) {
+                if !key.is_empty() && !keys.contains(key) {
+                    keys.push(key.clone());
+                }
+            }
+            keys
+        }
+
+        // 使用額を集計するグループ（サブスクリプションとテナント）
+        pub fn groups(&self) -> Vec<String> {
+            self.subscription
+                .iter()
+                .chain(&self.tenant)
+                .filter(|group| !group.is_empty())
+                .cloned()
+                .collect()
+        }
+    }
+
+    // デプロイメント情報
+    #[derive(Clone, Debug, Default, Deserialize, Serialize)]
+    pub struct DeploymentInfo {
+        pub name: String,
+        pub model: String,
+        pub model_version: String,
+        /// SKU（Standard, GlobalStandard, GlobalBatch, ProvisionedManaged など）
+        pub sku: String,
+        /// 割り当て容量（Standard系は1単位 = 1,000 TPM）
+        pub capacity: u64,
+    }
+
+    // エンドポイントのAPI形式
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+    #[serde(rename_all = "lowercase")]
+    pub enum ProviderKind {
+        /// Azure OpenAI（デプロイメント単位のURLとapi-keyヘッダー）
+        #[default]
+        Azure,
+        /// OpenAI互換API（api.openai.com, vLLM, LM Studio など）
+        OpenAI,
+        /// ダミーの応答を返すモック（ドライラン用、通信しない）
+        Mock,
     }
 
     // チャットメッセージ
-    #[derive(Clone, Debug, Deserialize, Serialize)]
+    #[derive(Clone, Debug, Default, Deserialize, Serialize)]
     pub struct ChatMessage {
         pub role: String,
+        // ツールを呼び出すアシスタントのメッセージでは null が返るため空文字列として扱う
+        #[serde(default, deserialize_with = "null_as_empty")]
         pub content: String,
+        // アシスタントが要求したツールの呼び出し
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        pub tool_calls: Vec<ToolCall>,
+        // ツールの実行結果のメッセージ（role が tool）が応答する呼び出しのID
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub tool_call_id: Option<String>,
+    }
+
+    fn null_as_empty<'de, D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<String, D::Error> {
+        Option::<String>::deserialize(deserializer).map(Option::unwrap_or_default)
+    }
+
+    // ツールの呼び出し
+    #[derive(Clone, Debug, Deserialize, Serialize)]
+    pub struct ToolCall {
+        pub id: String,
+        #[serde(rename = "type")]
+        pub kind: String,
+        pub function: FunctionCall,
+    }
+
+    // 呼び出す関数と引数（引数はJSON文字列）
+    #[derive(Clone, Debug, Deserialize, Serialize)]
+    pub struct FunctionCall {
+        pub name: String,
+        pub arguments: String,
+    }
+
+    // モデルに渡すツールの定義
+    #[derive(Clone, Debug, Deserialize, Serialize)]
+    pub struct ToolDefinition {
+        #[serde(rename = "type")]
+        pub kind: String,
+        pub function: FunctionDefinition,
+    }
+
+    // 関数の名前・説明・引数の JSON スキーマ
+    #[derive(Clone, Debug, Deserialize, Serialize)]
+    pub struct FunctionDefinition {
+        pub name: String,
+        pub description: String,
+        pub parameters: serde_json::Value,
     }
 
     // OpenAI APIレスポンス
@@ -64,17 +287,193 @@ pub mod openai_response {
         pub prompt_tokens: usize,
         pub completion_tokens: usize,
         pub total_tokens: usize,
+        // 出力トークンの内訳（推論モデルは推論トークンを返す）
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub completion_tokens_details: Option<CompletionTokensDetails>,
+    }
+
+    impl OpenAIUsage {
+        // 推論トークン数（推論モデル以外は None）
+        pub fn reasoning_tokens(&self) -> Option<usize> {
+            self.completion_tokens_details
+                .as_ref()
+                .and_then(|details| details.reasoning_tokens)
+                .filter(|tokens| *tokens > 0)
+        }
+    }
+
+    // 出力トークンの内訳
+    #[derive(Clone, Debug, Default, Deserialize, Serialize)]
+    pub struct CompletionTokensDetails {
+        #[serde(default)]
+        pub reasoning_tokens: Option<usize>,
+    }
+
+    // 埋め込みAPIレスポンス
+    #[derive(Clone, Debug, Deserialize, Serialize)]
+    pub struct EmbeddingResponse {
+        pub data: Vec<EmbeddingData>,
+        pub model: String,
+        pub usage: EmbeddingUsage,
+    }
+
+    // 埋め込みベクトル
+    #[derive(Clone, Debug, Deserialize, Serialize)]
+    pub struct EmbeddingData {
+        pub index: usize,
+        pub embedding: Vec<f32>,
+    }
+
+    // 埋め込みAPIトークン使用量
+    #[derive(Clone, Debug, Deserialize, Serialize)]
+    pub struct EmbeddingUsage {
+        pub prompt_tokens: usize,
+        pub total_tokens: usize,
+    }
+
+    // 画像生成APIレスポンス
+    #[derive(Clone, Debug, Deserialize, Serialize)]
+    pub struct ImageGenerationResponse {
+        pub created: u64,
+        pub data: Vec<ImageData>,
+    }
+
+    // 生成された画像
+    #[derive(Clone, Debug, Deserialize, Serialize)]
+    pub struct ImageData {
+        pub b64_json: Option<String>,
+        pub revised_prompt: Option<String>,
+    }
+
+    // 音声書き起こしAPIレスポンス
+    #[derive(Clone, Debug, Deserialize, Serialize)]
+    pub struct TranscriptionResponse {
+        pub text: String,
+        // トークン数で課金するモデルだけが返す（whisper は返さない）
+        #[serde(default)]
+        pub usage: Option<TranscriptionUsage>,
+    }
+
+    // 音声書き起こしAPIトークン使用量
+    #[derive(Clone, Debug, Deserialize, Serialize)]
+    pub struct TranscriptionUsage {
+        #[serde(default)]
+        pub total_tokens: usize,
     }
 
+    // ファイルAPIのファイル情報
+    #[derive(Clone, Debug, Deserialize, Serialize)]
+    pub struct FileObject {
+        pub id: String,
+        pub status: String,
+        pub filename: Option<String>,
+        pub purpose: Option<String>,
+    }
+
+    // 保存する ResponseData の形式のバージョン
+    // 1: schema_version・category のない初期の形式
+    // 2: schema_version・category・context_summary・run_id を追加
+    pub const RESPONSE_SCHEMA_VERSION: u32 = 2;
+
     // レスポンスデータ保存用
     #[derive(Clone, Debug, Deserialize, Serialize)]
     pub struct ResponseData {
+        pub schema_version: u32,
         pub repo: String,
         pub debate_type: String,
         pub turn: usize,
         pub timestamp: String,
         pub endpoint: String,
+        #[serde(default)]
+        pub category: Option<String>,
         pub messages: Vec<ChatMessage>,
         pub tokens_used: usize,
+        // 古いターンの要約（messages は要約せずにすべて保存する）
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub context_summary: Option<ContextSummary>,
+        // 結果を作った実行のID（runs/<run_id>/manifest.json）
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub run_id: Option<String>,
+        // ファイル単位のレビューで対象にしたファイルのパス
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub file: Option<String>,
+        // このターンのAPI呼び出しの所要時間とリトライ
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub metrics: Option<TurnMetrics>,
+        // 議論のタスクID（ジャーナルが失われても保存結果から途中経過を復元できるようにする）
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub task_id: Option<String>,
+        // 品質チェックで不採用にした応答（聞き直す前の応答）
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        pub rejected: Vec<RejectedResponse>,
+        // 応答のハッシュ（空白・記号・数字を除いて求める）
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub content_hash: Option<String>,
+        // 同じリポジトリで以前に保存した、同じ応答のファイル
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub duplicate_of: Option<String>,
+        // 回答したモデル（デプロイメント名。フォールバックした場合は最初に試したモデルと異なる）
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub model: Option<String>,
+        // 自己改善の前の応答（messages には改善版を残す）
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub draft: Option<String>,
+    }
+
+    // 品質チェックで不採用にした応答
+    #[derive(Clone, Debug, Default, Deserialize, Serialize)]
+    pub struct RejectedResponse {
+        pub endpoint: String,
+        pub question: String,
+        pub content: String,
+        pub reason: String,
+        pub tokens_used: usize,
+    }
+
+    // 1ターンのAPI呼び出しの計測値
+    #[derive(Clone, Debug, Default, Deserialize, Serialize)]
+    pub struct TurnMetrics {
+        // 応答が返るまでの時間（リトライの待機を含む）
+        pub latency_ms: u64,
+        // 送信したリクエスト数（ツールの呼び出しとリトライを含む）
+        pub requests: usize,
+        // リトライした回数
+        pub retries: usize,
+        // 受け取ったHTTPステータスの履歴
+        pub http_statuses: Vec<u16>,
+        // 成功したAPI呼び出しごとの記録
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        pub calls: Vec<ApiCall>,
+        // 料金表から求めた推定コストの合計（単価が分かる呼び出しがない場合は省略）
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub estimated_cost: Option<f64>,
+        // 推定コストの通貨
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub currency: Option<String>,
+    }
+
+    // 成功した1回のAPI呼び出し
+    #[derive(Clone, Debug, Default, Deserialize, Serialize)]
+    pub struct ApiCall {
+        pub timestamp: String,
+        pub model: String,
+        pub prompt_tokens: usize,
+        pub completion_tokens: usize,
+        // 推論トークン数（completion_tokens に含まれる。推論モデル以外は省略）
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub reasoning_tokens: Option<usize>,
+        // リトライを含めて応答が返るまでの時間
+        pub latency_ms: u64,
+        // 料金表から求めた推定コスト
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub estimated_cost: Option<f64>,
+    }
+
+    // 会話履歴の古いターンをまとめた要約
+    #[derive(Clone, Debug, Deserialize, Serialize)]
+    pub struct ContextSummary {
+        // 要約に含めたターン数（1ターン目から）
+        pub turns: usize,
+        pub content: String,
     }
 }
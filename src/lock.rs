@@ -0,0 +1,126 @@
+// ロックファイルモジュール
+// 出力ディレクトリにPIDを書いたロックファイルを置き、同じ出力ディレクトリに対して
+// 2つのインスタンスが同時にクローンや状態ファイルを書き換えないようにする
+
+use anyhow::{Result, bail};
+use log::{info, warn};
+use std::fs::OpenOptions;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+// ロックファイル名
+const LOCK_FILE: &str = ".azure-credit-burner.lock";
+
+// 取得したロック（破棄時にロックファイルを削除する）
+pub struct OutputLock {
+    path: PathBuf,
+}
+
+impl Drop for OutputLock {
+    fn drop(&mut self) {
+        // 別のインスタンスに作り直されたロックは残す
+        let owner = std::fs::read_to_string(&self.path).unwrap_or_default();
+        if owner.trim() != std::process::id().to_string() {
+            return;
+        }
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            warn!(
+                "⚠️ ロックファイルを削除できませんでした: {} - {}",
+                self.path.display(),
+                e
+            );
+        }
+    }
+}
+
+// プロセスが動いているか（確認できない環境では None）
+fn process_alive(pid: u32) -> Option<bool> {
+    let proc_dir = Path::new("/proc");
+    if proc_dir.exists() {
+        return Some(proc_dir.join(pid.to_string()).exists());
+    }
+    // /proc がない Unix（macOS など）ではシグナル 0 を送って確認する
+    if cfg!(unix) {
+        return std::process::Command::new("kill")
+            .args(["-0", &pid.to_string()])
+            .stderr(std::process::Stdio::null())
+            .status()
+            .ok()
+            .map(|status| status.success());
+    }
+    None
+}
+
+// 出力ディレクトリのロックを取得
+// 終了したプロセスと自分と同じPIDのロック（コンテナの再起動で同じPIDが振られた場合など）は削除する。
+// force_lock は、動いているか確認できないプロセスやPIDを読めないロックに加えて、
+// 動いているように見えるプロセスのロックも削除する（PIDが別のプロセスに再利用された場合に備える）
+pub fn acquire(output_dir: &str, force_lock: bool) -> Result<OutputLock> {
+    let path = Path::new(output_dir).join(LOCK_FILE);
+    let own_pid = std::process::id();
+
+    loop {
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(mut file) => {
+                writeln!(file, "{}", own_pid)?;
+                info!(
+                    "🔒 出力ディレクトリをロックしました: {} (PID: {})",
+                    path.display(),
+                    own_pid
+                );
+                return Ok(OutputLock { path });
+            }
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        // 既存のロックの持ち主を確認
+        // （PIDを読めないロックは、別のインスタンスが作成してまだPIDを書き込んでいない可能性があるため使用中として扱う）
+        let content = std::fs::read_to_string(&path).unwrap_or_default();
+        let pid = content.trim().parse::<u32>().ok();
+        let alive = pid.map(|pid| {
+            if pid == own_pid {
+                Some(false)
+            } else {
+                process_alive(pid)
+            }
+        });
+        match (pid, alive.flatten()) {
+            (_, Some(false)) => warn!(
+                "🧹 終了したインスタンスのロックファイルを削除します: {} ({})",
+                path.display(),
+                content.trim()
+            ),
+            (Some(pid), Some(true)) if !force_lock => bail!(
+                "出力ディレクトリ {} は別のインスタンス (PID: {}) が使用中です。\
+                 PIDが別のプロセスに再利用されているだけの場合は --force-lock を指定すると続行できます（ロックファイル: {}）",
+                output_dir,
+                pid,
+                path.display()
+            ),
+            (Some(pid), None) if !force_lock => bail!(
+                "出力ディレクトリ {} は別のインスタンス (PID: {}) が使用中の可能性があります。\
+                 そのインスタンスが終了していることを確認したうえで --force-lock を指定すると続行できます（ロックファイル: {}）",
+                output_dir,
+                pid,
+                path.display()
+            ),
+            (None, _) if !force_lock => bail!(
+                "出力ディレクトリ {} のロックファイルからPIDを読み込めません（別のインスタンスが作成中の可能性があります）。\
+                 そのインスタンスが終了していることを確認したうえで --force-lock を指定すると続行できます（ロックファイル: {}）",
+                output_dir,
+                path.display()
+            ),
+            _ => warn!(
+                "⚠️ 使用中の可能性があるロックを --force-lock で削除します: {} ({})",
+                path.display(),
+                content.trim()
+            ),
+        }
+        match std::fs::remove_file(&path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
@@ -2,31 +2,165 @@
 // Rust版実装
 
 use chrono::prelude::*;
-use reqwest::{self, header};
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
 use serde_json::json;
-use std::{path::Path, sync::Arc, time::Duration};
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 use tokio::{fs, process::Command, time};
 
-use anyhow::{anyhow, bail, Result};
-use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
-use clap::Parser;
+use anyhow::{Result, anyhow, bail};
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use clap::{Parser, Subcommand};
 use dotenv::dotenv;
-use futures::{stream, StreamExt};
-use ignore::{Walk, WalkBuilder};
-use log::{error, info};
-use simple_logger::SimpleLogger;
-use std::collections::HashMap;
+use ignore::WalkBuilder;
+use log::{error, info, warn};
 use std::env;
-use walkdir::WalkDir;
 
 // llmディレクトリのスキーマを利用
+mod ai_search;
+mod arm;
+mod batch;
+mod budget;
+mod cassette;
+mod chunking;
+mod ci;
+mod clone;
+mod compaction;
+mod compare;
+mod control;
+mod credits;
+mod deadline;
+mod dedupe;
+mod diagrams;
+mod diff;
+mod embeddings;
+mod encryption;
+mod error;
+mod eval;
+mod export;
+mod file_cache;
+mod file_guard;
+mod file_review;
+mod findings;
+mod finetune;
+mod generation;
+mod github_app;
+mod groups;
+mod journal;
+mod judge;
+mod keys;
+mod languages;
+mod ledger;
 mod llm;
-use llm::categories::{self, get_category_japanese};
+mod lock;
+mod media;
+mod org_report;
+mod outputs;
+mod pricing;
+mod provider;
+mod provision;
+mod ptu;
+mod quality;
+mod questions;
+mod quota;
+mod readme;
+mod recurring;
+mod redact;
+mod refine;
+mod reload;
+mod repo_list;
+mod report;
+mod retention;
+mod roleplay;
+mod routing;
+mod runs;
+mod scheduler;
+mod scorecard;
+mod search;
+mod seed;
+mod serve;
+mod ssh;
+mod stats;
+mod status;
+mod summary;
+mod tools;
+mod translator;
+mod tts;
+mod vision;
+mod webhook;
+mod work_queue;
+use ai_search::AiSearchConfig;
+use arm::DiscoveryConfig;
+use batch::BatchConfig;
+use chunking::ChunkingConfig;
+use ci::CiConfig;
+use clone::{CloneConfig, OversizePolicy};
+use compaction::{CompactionConfig, Compactor};
+use compare::CompareConfig;
+use control::ControlConfig;
+use deadline::TimeoutConfig;
+use dedupe::DedupeConfig;
+use diagrams::DiagramConfig;
+use diff::DiffOptions;
+use embeddings::{EmbeddingsConfig, RagRetriever};
+use encryption::EncryptionConfig;
+use error::BurnerError;
+use eval::{EvalConfig, EvalOptions};
+use export::{ExportFormat, ExportOptions};
+use file_cache::FileCacheConfig;
+use file_guard::{FileGuardConfig, Verdict};
+use file_review::FileReviewConfig;
+use findings::FindingsConfig;
+use finetune::FinetuneOptions;
+use generation::{Generation, GenerationConfig, GenerationOverride, ReasoningEffort};
+use github_app::{GitHubApp, GitHubAppConfig};
+use groups::GroupsConfig;
+use journal::{Journal, TaskState, TaskStatus};
+use languages::{LanguageProfile, LanguagesConfig};
+use llm::categories::{self, QuestionsConfig};
+use llm::prompts::SystemPromptsConfig;
 use llm::schemas::{
-    github_response::{FileInfo, RepoInfo},
-    openai_response::{ChatMessage, Endpoint, OpenAIResponse, ResponseData},
+    github_response::{FileInfo, FileLimits, RepoInfo},
+    openai_response::{
+        ApiCall, ChatMessage, EmbeddingResponse, Endpoint, FileObject, ImageGenerationResponse,
+        OpenAIChoice, OpenAIResponse, OpenAIUsage, ProviderKind, RESPONSE_SCHEMA_VERSION,
+        RejectedResponse, ResponseData, ToolDefinition, TranscriptionResponse, TurnMetrics,
+    },
 };
+use media::TranscriptionConfig;
+use org_report::OrgReportConfig;
+use pricing::PricingConfig;
+use provider::{ApiConfig, LlmProvider};
+use provision::{DeprovisionOptions, ProvisionConfig, ProvisionOptions};
+use ptu::PtuConfig;
+use quality::QualityConfig;
+use questions::QuestionsCommand;
+use quota::{QuotaConfig, RateLimiter};
+use readme::ReadmeConfig;
+use redact::RedactionConfig;
+use refine::RefineConfig;
+use reload::ReloadConfig;
+use report::ReportFormat;
+use retention::RetentionConfig;
+use roleplay::{ROLEPLAY_TYPE, RolePlayConfig};
+use routing::RoutingConfig;
+use scheduler::{Scheduler, SchedulerConfig};
+use scorecard::ScorecardConfig;
+use search::SearchOptions;
+use serve::ServeConfig;
+use ssh::SshConfig;
+use stats::StatsConfig;
+use status::StatusConfig;
+use summary::SummaryConfig;
+use tools::{RepoSandbox, ToolsConfig};
+use translator::TranslatorConfig;
+use tts::TtsConfig;
+use vision::VisionConfig;
+use work_queue::{DebateJob, RetryConfig, WorkQueue};
 
 // コマンドライン引数の定義
 #[derive(Parser, Debug)]
@@ -52,63 +186,424 @@ struct Args {
     #[clap(long)]
     max_files: Option<usize>,
 
-    /// 最大ファイルサイズ（バイト）
+    /// 読み込むファイルの最大サイズ（バイト）。これを超えるファイルは読み飛ばす
+    #[clap(long, alias = "max-file-size")]
+    max_file_bytes: Option<usize>,
+
+    /// ファイルの内容を切り詰める文字数
+    #[clap(long)]
+    truncate_chars: Option<usize>,
+
+    /// ファイルの内容を切り詰めるおおよそのトークン数
+    #[clap(long)]
+    truncate_tokens: Option<usize>,
+
+    /// 使用するデプロイメント名（モデル）。設定ファイルのエンドポイント・議論タイプごとの指定より優先する
+    #[clap(long)]
+    model: Option<String>,
+
+    /// 議論の1回の応答の最大トークン数。設定ファイルのエンドポイント・議論タイプごとの指定より優先する
     #[clap(long)]
-    max_file_size: Option<usize>,
+    max_tokens: Option<usize>,
+
+    /// 議論の温度（0〜2）。設定ファイルのエンドポイント・議論タイプごとの指定より優先する
+    #[clap(long)]
+    temperature: Option<f32>,
+
+    /// 推論モデル（o1 / o3 など）の推論の労力。設定ファイルの指定より優先する
+    #[clap(long, value_enum)]
+    reasoning_effort: Option<ReasoningEffort>,
+
+    /// モデルのデプロイメントがない（404）かレート制限が続く場合に順に試すモデル（複数指定可）。設定ファイルの指定より優先する
+    #[clap(long = "fallback-model")]
+    fallback_models: Vec<String>,
 
     /// 設定ファイルのパス
     #[clap(long, default_value = "config.json")]
     config_file: String,
+
+    /// 埋め込みベクトル生成を有効にする
+    #[clap(long)]
+    embeddings: bool,
+
+    /// Azure OpenAI Batch APIで議論を実行する
+    #[clap(long)]
+    batch: bool,
+
+    /// APIを呼び出さずにダミーの応答で実行する（プロンプトやファイル選択の確認用）
+    #[clap(long)]
+    dry_run: bool,
+
+    /// APIのレスポンスを指定したカセットファイルに記録する
+    #[clap(long, conflicts_with = "replay")]
+    record: Option<String>,
+
+    /// 記録したカセットファイルのレスポンスを再生する（APIは呼び出さない）
+    #[clap(long)]
+    replay: Option<String>,
+
+    /// 実行時間の上限（例: 72h, 1h30m）。期限が近づくと新しいターンを開始せず、後処理を実行して終了する
+    #[clap(long, value_parser = deadline::parse_duration)]
+    max_duration: Option<Duration>,
+
+    /// 使用額の上限（USD）。台帳の推定使用額が上限に達すると、後処理を含めて新しいリクエストをすべて止める
+    #[clap(long)]
+    max_usd: Option<f64>,
+
+    /// 分析の終了後に、provision で作成した（タグの付いた）リソースを削除する
+    #[clap(long)]
+    deprovision_after: bool,
+
+    /// 質問の出題順とAPIの seed パラメーターを固定するシード（実行記録 runs.jsonl に残る）
+    #[clap(long)]
+    seed: Option<u64>,
+
+    /// 分析するリポジトリの一覧ファイル（1行に1つの owner/repo、または JSON の配列）。- なら標準入力から読み込む。設定ファイルの repos の代わりに使う
+    #[clap(long)]
+    repos_file: Option<String>,
+
+    /// 同じコミット・議論タイプ・モデルで完了済みの議論があっても再実行する
+    #[clap(long)]
+    force: bool,
+
+    /// 出力ディレクトリのロックの持ち主が動いているか確認できない場合や、PIDが別のプロセスに再利用されている場合に、終了したものとしてロックを削除する
+    #[clap(long)]
+    force_lock: bool,
+
+    /// サブコマンド（省略時は分析を実行）
+    #[clap(subcommand)]
+    command: Option<Commands>,
 }
 
-// 深掘り質問カテゴリ
-struct DeepQuestions;
+// サブコマンドの定義
+#[derive(Subcommand, Debug)]
+enum Commands {
+    /// 保存済みの分析結果をAzure AI Searchにインデックス登録する
+    SearchIndex,
+    /// 管理APIでAzure OpenAIのエンドポイントとデプロイメントを検出し、設定用のJSONを出力する（APIキーは環境変数の参照に置き換える）
+    Discover,
+    /// 指定したリージョンに Azure OpenAI アカウントとモデルのデプロイメントを作成し、設定ファイルのエンドポイントに追加する
+    Provision {
+        /// リソースを作成するサブスクリプションID（省略時は discovery.subscription_ids の最初）
+        #[clap(long)]
+        subscription: Option<String>,
+        /// アカウントを作成するリージョン（複数指定可）
+        #[clap(long = "region", required = true)]
+        regions: Vec<String>,
+        /// アカウントを作成するリソースグループ（なければ作成する。省略時は provision.resource_group）
+        #[clap(long)]
+        resource_group: Option<String>,
+        /// アカウント名の接頭辞（<接頭辞>-<リージョン>-<サブスクリプションIDの先頭> の名前で作成する。省略時は provision.prefix）
+        #[clap(long)]
+        prefix: Option<String>,
+        /// デプロイするモデル
+        #[clap(long, default_value = CHAT_MODEL)]
+        model: String,
+        /// モデルのバージョン（省略時はモデルの既定のバージョン）
+        #[clap(long)]
+        model_version: Option<String>,
+        /// デプロイメント名（省略時はモデル名）
+        #[clap(long)]
+        deployment: Option<String>,
+        /// デプロイメントの SKU
+        #[clap(long, default_value = "GlobalStandard")]
+        sku: String,
+        /// デプロイメントの容量（Standard系は1単位 = 1,000 TPM）
+        #[clap(long, default_value_t = 100)]
+        capacity: u64,
+    },
+    /// provision で作成した（タグの付いた）Azure OpenAI アカウントとリソースグループを削除し、設定ファイルから取り除く
+    Deprovision {
+        /// リソースを削除するサブスクリプションID（複数指定可。省略時はエンドポイントの subscription と discovery.subscription_ids）
+        #[clap(long = "subscription")]
+        subscriptions: Vec<String>,
+        /// アカウントを削除するリソースグループ（省略時は provision.resource_group）
+        #[clap(long)]
+        resource_group: Option<String>,
+        /// 削除するアカウント名の接頭辞（省略時は provision.prefix）
+        #[clap(long)]
+        prefix: Option<String>,
+        /// 削除するリソースを確認せずに削除する
+        #[clap(long)]
+        yes: bool,
+        /// 削除したアカウントを完全に削除する（同じ名前で作り直せるようにする）
+        #[clap(long)]
+        purge: bool,
+        /// 設定ファイルのエンドポイントを残す
+        #[clap(long)]
+        keep_config: bool,
+    },
+    /// 保存済みの分析結果からサマリーを生成し、後処理を実行する
+    Summarize,
+    /// 保存済みの分析結果を指定形式でエクスポートする
+    Export {
+        /// 出力形式
+        #[clap(long, value_enum, default_value = "finetune")]
+        format: ExportFormat,
+        /// 出力先ファイル
+        #[clap(long)]
+        output: Option<String>,
+        /// 判定用モデルの採点がこの値未満の回答を除外する（1〜10）
+        #[clap(long)]
+        min_score: Option<f32>,
+        /// 採点に使用するモデルのデプロイメント名
+        #[clap(long, default_value = CHAT_MODEL)]
+        judge_model: String,
+        /// 対象リポジトリ（owner/repo、省略時はすべて）
+        #[clap(long)]
+        repo: Option<String>,
+        /// 対象の議論タイプ（名前の一部、省略時はすべて）
+        #[clap(long)]
+        debate: Option<String>,
+    },
+    /// 保存済みの回答を判定用モデルで評価基準ごとに採点し直し、モデル・エンドポイントごとの評価レポートを作成する
+    Eval {
+        /// 採点に使用するモデルのデプロイメント名（省略時は設定ファイルの eval.deployment）
+        #[clap(long)]
+        judge_model: Option<String>,
+        /// 対象リポジトリ（owner/repo、省略時はすべて）
+        #[clap(long)]
+        repo: Option<String>,
+        /// 対象の議論タイプ（名前の一部、省略時はすべて）
+        #[clap(long)]
+        debate: Option<String>,
+        /// 採点済みの回答も採点し直す
+        #[clap(long)]
+        rescore: bool,
+    },
+    /// エクスポートした学習データでファインチューニングジョブを作成・監視する
+    Finetune {
+        /// 学習データ（省略時は出力ディレクトリの finetune.jsonl）
+        #[clap(long)]
+        training_file: Option<String>,
+        /// ベースモデル
+        #[clap(long, default_value = "gpt-4o-mini-2024-07-18")]
+        model: String,
+        /// ジョブを作成するエンドポイント名（省略時は最初のエンドポイント）
+        #[clap(long)]
+        endpoint: Option<String>,
+        /// ファインチューニング済みモデル名に付けるサフィックス
+        #[clap(long)]
+        suffix: Option<String>,
+        /// エポック数（省略時は自動）
+        #[clap(long)]
+        epochs: Option<u32>,
+        /// ジョブの完了を待たずに終了する
+        #[clap(long)]
+        no_wait: bool,
+        /// 新規作成せず既存のジョブを監視する
+        #[clap(long)]
+        job_id: Option<String>,
+    },
+    /// HTTP APIで議論の登録・状態確認・結果取得を受け付けるデーモンとして起動する
+    Serve {
+        /// 待ち受けるアドレス（省略時は設定ファイルの serve.bind）
+        #[clap(long)]
+        bind: Option<String>,
+    },
+    /// 設定ファイルの repos の代わりに1つのリポジトリだけを分析する
+    Analyze {
+        /// 対象リポジトリ（owner/repo）
+        repo: String,
+        /// 議論タイプ（review, architecture, alternatives, security, performance, api, roadmap, license, roleplay。省略時はすべて。roleplay は roleplay.enabled の場合のみ）
+        #[clap(long)]
+        debate: Vec<String>,
+        /// 対象ブランチ
+        #[clap(long)]
+        branch: Option<String>,
+        /// 対象コミット
+        #[clap(long)]
+        commit: Option<String>,
+    },
+    /// 同じリポジトリの2回の実行結果を議論タイプごとに比較し、評価の変化をレポートにまとめる
+    Diff {
+        /// 対象リポジトリ（owner/repo）
+        #[clap(long)]
+        repo: String,
+        /// 対象ブランチ（省略時はすべての実行）
+        #[clap(long)]
+        branch: Option<String>,
+        /// 比較元: この日時までに完了した最後の実行（RFC3339 または YYYY-MM-DD、省略時は比較先の1つ前）
+        #[clap(long)]
+        from: Option<String>,
+        /// 比較先: この日時までに完了した最後の実行（省略時は最新）
+        #[clap(long)]
+        to: Option<String>,
+        /// 出力先ファイル（省略時はリポジトリの diffs ディレクトリ）
+        #[clap(long)]
+        output: Option<String>,
+        /// モデルによる評価の変化の要約を省略する
+        #[clap(long)]
+        no_summary: bool,
+    },
+    /// 保存済みの分析結果からエンドポイントごとの応答時間（p50 / p99）とリトライ回数を集計して表示する
+    Report {
+        /// 出力形式（csv / parquet はAPI呼び出しごとの使用量を1行ずつ書き出す）
+        #[clap(long, value_enum, default_value = "table")]
+        format: ReportFormat,
+        /// 出力先ファイル（csv の省略時は標準出力、parquet の省略時は出力ディレクトリの usage.parquet）
+        #[clap(long)]
+        output: Option<String>,
+    },
+    /// 深掘り質問の質問バンク（カテゴリごとの JSON ファイル）を管理する
+    Questions {
+        #[clap(subcommand)]
+        action: QuestionsCommand,
+    },
+    /// 保存済みの分析結果の会話からキーワードを検索し、リポジトリ・議論タイプ・ターンと抜粋を表示する
+    Search {
+        /// 検索するキーワード
+        query: String,
+        /// 対象リポジトリ（owner/repo）
+        #[clap(long)]
+        repo: Option<String>,
+        /// 対象の議論タイプ（名前の一部）
+        #[clap(long)]
+        debate: Option<String>,
+        /// キーワードを正規表現として扱う
+        #[clap(long)]
+        regex: bool,
+        /// 大文字と小文字を区別する
+        #[clap(long)]
+        case_sensitive: bool,
+        /// システムプロンプト（リポジトリのファイル内容）も検索する
+        #[clap(long)]
+        include_system: bool,
+        /// 抜粋に含める前後の文字数
+        #[clap(long, default_value_t = 60)]
+        context: usize,
+        /// 表示する最大件数
+        #[clap(long, default_value_t = 50)]
+        limit: usize,
+    },
+    /// 設定ファイルの retention に従って出力ディレクトリを整理する（--dry-run で削除するファイルの確認だけ行う）
+    Clean {
+        /// 保持期間を過ぎたターンのファイルを削除する（議論の最終ターン・サマリー・実行記録は残す）
+        #[clap(long)]
+        outputs: bool,
+    },
+    /// 実行中のプロセス（長時間の実行やデーモンモード）に操作コマンドを送る（例: set concurrency 16 / pause endpoint west-us / skip repo org/foo / status）
+    Control {
+        /// 操作コマンド
+        #[clap(required = true, num_args = 1..)]
+        command: Vec<String>,
+    },
+}
+
+// 分析に使用するチャットモデルのデプロイメント名
+const CHAT_MODEL: &str = "gpt-4.5-preview";
+
+// 組み込みの深掘り質問カテゴリ（初期の形式で保存された結果の移行に使う）
+const QUESTION_CATEGORIES: [&str; 7] = [
+    "アーキテクチャ",
+    "パフォーマンス",
+    "セキュリティ",
+    "テスト品質",
+    "ドメイン分析",
+    "分散システム",
+    "コード保守性",
+];
+
+// 深掘り質問の出題順
+struct DeepQuestions {
+    // 出題するカテゴリの日本語名
+    categories: Vec<String>,
+    // 各カテゴリで最初に出題する質問の位置
+    question_offset: usize,
+    // リポジトリの主な言語の深掘り質問（カテゴリ名と質問）
+    language_questions: Vec<(String, Vec<String>)>,
+    // 「テスト品質」の質問に添えるテストと CI の要約
+    test_context: Option<String>,
+}
 
 impl DeepQuestions {
-    fn new() -> Self {
-        DeepQuestions {}
+    // シードを指定するとカテゴリの順番と最初の質問をシードから決める
+    fn new(seed: Option<u64>) -> Self {
+        let mut categories: Vec<String> = categories::categories()
+            .iter()
+            .map(|category| category.name.clone())
+            .collect();
+        let mut question_offset = 0;
+        if let Some(seed) = seed {
+            let mut rng = seed::SeededRng::new(seed);
+            rng.shuffle(&mut categories);
+            question_offset = rng.below(64);
+        }
+        DeepQuestions {
+            categories,
+            question_offset,
+            language_questions: Vec::new(),
+            test_context: None,
+        }
+    }
+
+    // 議論の質問（リポジトリの主な言語の深掘り質問も出題し、テストの質問にはテストと CI の要約を添える）
+    async fn for_debate(github_client: &GitHubClient, repo_info: &RepoInfo, task_id: &str) -> Self {
+        let mut deep_questions = DeepQuestions::new(seed::for_task(task_id));
+        match languages::detect(github_client, repo_info).await {
+            Ok(profiles) => deep_questions.add_languages(&profiles),
+            Err(e) => warn!("⚠️ {}", e),
+        }
+        match ci::test_context(github_client, repo_info).await {
+            Ok(test_context) => deep_questions.test_context = test_context,
+            Err(e) => warn!("⚠️ {}", e),
+        }
+        deep_questions
+    }
+
+    // 言語の深掘り質問をカテゴリとして加える
+    fn add_languages(&mut self, profiles: &[LanguageProfile]) {
+        for profile in profiles
+            .iter()
+            .filter(|profile| !profile.questions.is_empty())
+        {
+            self.categories.push(profile.category.clone());
+            self.language_questions
+                .push((profile.category.clone(), profile.questions.clone()));
+        }
     }
 
     fn get_question(&self, category: &str, index: usize) -> String {
-        // 日本語カテゴリ名から英語カテゴリ名に変換
-        let category_en = match category {
-            "アーキテクチャ" => "architecture",
-            "パフォーマンス" => "performance",
-            "セキュリティ" => "security",
-            "テスト品質" => "testing",
-            "ドメイン分析" => "domain",
-            "分散システム" => "distributed",
-            "コード保守性" => "maintainability",
-            _ => "architecture", // デフォルトはアーキテクチャ
-        };
+        // エラー時のフォールバック質問
+        let fallback = "このリポジトリについて、さらに詳細な分析を行ってください。コードの品質や設計について特に重要な点は何でしょうか？";
+
+        // 言語の深掘り質問
+        if let Some((_, questions)) = self
+            .language_questions
+            .iter()
+            .find(|(name, _)| name == category)
+        {
+            return questions[(index + self.question_offset) % questions.len()].clone();
+        }
 
-        // カテゴリファイルから質問を取得
-        match categories::get_question(category_en, index) {
-            Ok(question) => question,
-            Err(_) => {
-                // エラー時のフォールバック質問
-                "このリポジトリについて、さらに詳細な分析を行ってください。コードの品質や設計について特に重要な点は何でしょうか？".to_string()
+        // 日本語カテゴリ名から英語カテゴリ名に変換し、質問バンクから質問を取得
+        let question = categories::key_for_name(category)
+            .ok_or_else(|| anyhow!("カテゴリ名 '{}' は定義されていません", category))
+            .and_then(|category_en| {
+                categories::get_question(category_en, index + self.question_offset)
+            });
+        match question {
+            Ok(question) => match &self.test_context {
+                Some(test_context) if category == ci::TEST_CATEGORY => {
+                    format!("{}\n\n{}", question, test_context)
+                }
+                _ => question,
+            },
+            Err(e) => {
+                warn!("⚠️ 質問を取得できないため汎用の質問を使います: {}", e);
+                fallback.to_string()
             }
         }
     }
 
     fn get_category(&self, turn: usize) -> String {
-        let categories = vec![
-            "アーキテクチャ",
-            "パフォーマンス",
-            "セキュリティ",
-            "テスト品質",
-            "ドメイン分析",
-            "分散システム",
-            "コード保守性",
-        ];
-        categories[turn % categories.len()].to_string()
+        self.categories[turn % self.categories.len()].to_string()
     }
 }
 
 // 分析タイプの定義
-fn get_debate_types() -> Vec<String> {
-    vec![
+fn get_debate_types(config: &Config) -> Vec<String> {
+    let mut debate_types = vec![
         "コードレビュー・分析".to_string(),
         "アーキテクチャの強み・弱み評価".to_string(),
         "実装の代替アプローチ提案".to_string(),
@@ -117,63 +612,432 @@ fn get_debate_types() -> Vec<String> {
         "APIデザインの批評".to_string(),
         "プロジェクトのロードマップ予測".to_string(),
         "ライセンスとオープンソースコミュニティへの影響分析".to_string(),
-    ]
+    ];
+    // ロールプレイ討論はすべてのリポジトリで実行する設定の場合だけ加える
+    if config.roleplay.enabled {
+        debate_types.push(ROLEPLAY_TYPE.to_string());
+    }
+    debate_types
+}
+
+// コマンドラインで指定する議論タイプの短い名前
+const DEBATE_TYPE_ALIASES: [(&str, &str); 9] = [
+    ("review", "コードレビュー・分析"),
+    ("architecture", "アーキテクチャの強み・弱み評価"),
+    ("alternatives", "実装の代替アプローチ提案"),
+    ("security", "セキュリティ脆弱性の検出"),
+    ("performance", "パフォーマンス最適化の提案"),
+    ("api", "APIデザインの批評"),
+    ("roadmap", "プロジェクトのロードマップ予測"),
+    (
+        "license",
+        "ライセンスとオープンソースコミュニティへの影響分析",
+    ),
+    ("roleplay", ROLEPLAY_TYPE),
+];
+
+// 短い名前または議論タイプ名から議論タイプを決める
+fn resolve_debate_type(name: &str) -> Result<String> {
+    DEBATE_TYPE_ALIASES
+        .iter()
+        .find(|(alias, debate_type)| alias.eq_ignore_ascii_case(name) || *debate_type == name)
+        .map(|(_, debate_type)| debate_type.to_string())
+        .ok_or_else(|| {
+            let aliases: Vec<&str> = DEBATE_TYPE_ALIASES
+                .iter()
+                .map(|(alias, _)| *alias)
+                .collect();
+            anyhow!(
+                "不明な議論タイプです: {}（指定できる値: {}）",
+                name,
+                aliases.join(", ")
+            )
+        })
 }
 
 // GitHubクライアント
 struct GitHubClient {
     token: String,
+    // GitHub App 認証（設定されていればトークンより優先する）
+    app: Option<GitHubApp>,
+    // GitHub API の URL（github_app.api_url。GitHub Enterprise Server では変更する）
+    api_url: String,
+    // SSH でのクローン（有効ならどちらよりも優先する）
+    ssh: SshConfig,
+    // サブモジュールと Git LFS の取得
+    clone: CloneConfig,
     output_dir: String,
-    max_file_size: usize,
+    // ファイルの読み込みの制限（設定全体の値。リポジトリごとの上書きは読み込むときに反映する）
+    file_limits: FileLimits,
+    // クローン先ごとのロック（同じディレクトリのクローンと更新を1つずつ行う）
+    clone_locks: Mutex<HashMap<PathBuf, Arc<tokio::sync::Mutex<()>>>>,
+    // この実行で最新に更新したクローン先（議論の途中でファイルが変わらないよう、同じ実行では更新し直さない）
+    updated: Mutex<HashSet<PathBuf>>,
 }
 
 impl GitHubClient {
-    fn new(token: String, output_dir: String, max_file_size: usize) -> Self {
-        GitHubClient {
+    fn new(config: &Config) -> Result<Self> {
+        let token = config.github_token.trim().to_string();
+        let app = GitHubApp::from_config(&config.github_app)?;
+        config.ssh.validate()?;
+        if config.ssh.enabled {
+            info!("🔑 SSH でリポジトリをクローンします: {}", config.ssh.host);
+        } else if token.is_empty() && app.is_none() {
+            warn!(
+                "⚠️ GitHubトークンが設定されていないため、公開リポジトリだけを認証なしで取得します"
+            );
+        }
+        Ok(GitHubClient {
             token,
-            output_dir,
-            max_file_size,
+            app,
+            api_url: config.github_app.api_url.clone(),
+            ssh: config.ssh.clone(),
+            clone: config.clone.clone(),
+            output_dir: config.output_dir.clone(),
+            file_limits: config.file_limits(),
+            clone_locks: Mutex::new(HashMap::new()),
+            updated: Mutex::new(HashSet::new()),
+        })
+    }
+
+    // GitHub にアクセスするトークン（GitHub App → トークン → 認証なしの順）
+    async fn access_token(&self, repo_info: &RepoInfo) -> Result<Option<String>> {
+        Ok(match &self.app {
+            Some(app) => Some(app.token(&repo_info.owner, &repo_info.repo).await?),
+            None if self.token.is_empty() => None,
+            None => Some(self.token.clone()),
+        })
+    }
+
+    // github.com への HTTPS のリクエストに付ける Authorization ヘッダー（トークンがなければ None）
+    // トークンは URL に含めず、.git/config やログ・エラーメッセージに残らないようにする
+    async fn authorization(&self, repo_info: &RepoInfo) -> Result<Option<String>> {
+        Ok(self.access_token(repo_info).await?.map(|token| {
+            format!(
+                "Authorization: Basic {}",
+                BASE64.encode(format!("x-access-token:{}", token))
+            )
+        }))
+    }
+
+    // クローンする前にリポジトリのサイズを確認する（確認できなければそのままクローンする）
+    async fn check_repo_size(&self, repo_info: &RepoInfo) -> Result<()> {
+        let Some(max_size_mb) = self.clone.max_repo_size_mb else {
+            return Ok(());
+        };
+        let name = format!("{}/{}", repo_info.owner, repo_info.repo);
+
+        let token = self.access_token(repo_info).await.ok().flatten();
+        let size_kb = match clone::repo_size_kb(
+            &self.api_url,
+            &repo_info.owner,
+            &repo_info.repo,
+            token.as_deref(),
+        )
+        .await
+        {
+            Ok(size_kb) => size_kb,
+            Err(e) => {
+                warn!("⚠️ リポジトリのサイズを確認できません: {} - {}", name, e);
+                return Ok(());
+            }
+        };
+        let size_mb = size_kb / 1024;
+        if size_mb <= max_size_mb {
+            return Ok(());
+        }
+
+        match self.clone.oversize {
+            OversizePolicy::Skip => Err(BurnerError::RepoUnavailable {
+                repo: name,
+                message: format!(
+                    "リポジトリが大きすぎるためスキップします ({} MB > {} MB)",
+                    size_mb, max_size_mb
+                ),
+            }
+            .into()),
+            OversizePolicy::Warn => {
+                warn!(
+                    "🐘 リポジトリが大きいため、クローンに時間がかかる可能性があります: {} ({} MB > {} MB)",
+                    name, size_mb, max_size_mb
+                );
+                Ok(())
+            }
         }
     }
 
-    // リポジトリをクローンする
-    async fn clone_repository(&self, repo_info: &RepoInfo) -> Result<String> {
-        let repo_dir = format!(
-            "{}/repos/{}_{}",
-            self.output_dir, repo_info.owner, repo_info.repo
+    // クローンに使う URL（SSH が有効なら SSH。認証情報は含めない）
+    fn clone_url(&self, repo_info: &RepoInfo) -> String {
+        if self.ssh.enabled {
+            return self.ssh.clone_url(&repo_info.owner, &repo_info.repo);
+        }
+        format!(
+            "https://github.com/{}/{}.git",
+            repo_info.owner, repo_info.repo
+        )
+    }
+
+    // サブモジュールと Git LFS のオブジェクトを取得する（取得しない場合はその旨をログに残す）
+    // 取得に失敗しても、本体のリポジトリだけで分析を続ける
+    async fn fetch_submodules_and_lfs(&self, repo_info: &RepoInfo, repo_dir: &Path) {
+        let name = format!("{}/{}", repo_info.owner, repo_info.repo);
+
+        let submodules = clone::submodule_paths(repo_dir).await;
+        if !submodules.is_empty() && !self.clone.submodules {
+            warn!(
+                "📦 サブモジュールは取得していません（clone.submodules で取得できます）: {} - {}",
+                name,
+                submodules.join(", ")
+            );
+        } else if !submodules.is_empty() {
+            // 絶対 URL のサブモジュールも本体と同じ認証で取得する（HTTPS は Authorization ヘッダーで認証する）
+            let mut command = self.authorized_git(repo_info).await;
+            command.arg("-C").arg(repo_dir);
+            if self.ssh.enabled {
+                command.args([
+                    "-c",
+                    &format!("url.git@{}:.insteadOf=https://github.com/", self.ssh.host),
+                ]);
+            }
+            let output = command
+                .args([
+                    "submodule",
+                    "update",
+                    "--init",
+                    "--recursive",
+                    "--depth",
+                    "1",
+                ])
+                .output()
+                .await;
+            match output {
+                Ok(output) if output.status.success() => info!(
+                    "📦 サブモジュールを取得しました: {} - {}",
+                    name,
+                    submodules.join(", ")
+                ),
+                Ok(output) => warn!(
+                    "⚠️ サブモジュールの取得に失敗しました: {} - {}",
+                    name,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ),
+                Err(e) => warn!("⚠️ サブモジュールの取得に失敗しました: {} - {}", name, e),
+            }
+        }
+
+        if !clone::uses_lfs(repo_dir).await {
+            return;
+        }
+        if !self.clone.lfs {
+            warn!(
+                "📦 Git LFS のオブジェクトは取得していません（clone.lfs で取得できます。ポインターファイルは分析対象から外します）: {}",
+                name
+            );
+            return;
+        }
+        let output = self
+            .authorized_git(repo_info)
+            .await
+            .arg("-C")
+            .arg(repo_dir)
+            .args(["lfs", "pull"])
+            .output()
+            .await;
+        match output {
+            Ok(output) if output.status.success() => {
+                info!("📦 Git LFS のオブジェクトを取得しました: {}", name)
+            }
+            Ok(output) => warn!(
+                "⚠️ Git LFS のオブジェクトの取得に失敗しました（git-lfs がインストールされているか確認してください）: {} - {}",
+                name,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+            Err(e) => warn!(
+                "⚠️ Git LFS のオブジェクトの取得に失敗しました: {} - {}",
+                name, e
+            ),
+        }
+    }
+
+    // リモートにアクセスする git コマンド
+    // 認証が必要なリポジトリで git がユーザー名やパスフレーズを尋ねて止まらないようにする
+    fn git(&self) -> Command {
+        let mut command = Command::new("git");
+        // 時間制限で打ち切ったときに git のプロセスも止める
+        command.kill_on_drop(true);
+        command.env("GIT_TERMINAL_PROMPT", "0");
+        // LFS のオブジェクトはクローン時には取得せず、必要なら後から git lfs pull で取得する
+        command.env("GIT_LFS_SKIP_SMUDGE", "1");
+        if self.ssh.enabled {
+            command.env("GIT_SSH_COMMAND", self.ssh.command());
+        }
+        command
+    }
+
+    // github.com の HTTPS に認証を付けた git コマンド
+    // ヘッダーはコマンドライン引数ではなく環境変数の設定（GIT_CONFIG_*）で渡し、プロセス一覧にも出さない
+    // トークンを取得できなければ認証なしの git コマンドを返す（公開リポジトリはそのまま取得できる）
+    async fn authorized_git(&self, repo_info: &RepoInfo) -> Command {
+        let mut command = self.git();
+        if self.ssh.enabled {
+            return command;
+        }
+        match self.authorization(repo_info).await {
+            Ok(Some(header)) => {
+                command
+                    .env("GIT_CONFIG_COUNT", "1")
+                    .env("GIT_CONFIG_KEY_0", "http.https://github.com/.extraheader")
+                    .env("GIT_CONFIG_VALUE_0", header);
+            }
+            Ok(None) => {}
+            Err(e) => warn!(
+                "⚠️ GitHub のトークンを取得できないため、認証なしで取得します: {}/{} - {}",
+                repo_info.owner, repo_info.repo, e
+            ),
+        }
+        command
+    }
+
+    // クローン先のディレクトリ（ブランチやコミットごとに分ける）
+    fn clone_dir(&self, repo_info: &RepoInfo) -> PathBuf {
+        let mut name = format!(
+            "{}_{}",
+            outputs::path_segment(&repo_info.owner),
+            outputs::path_segment(&repo_info.repo)
         );
+        if let Some(commit) = &repo_info.commit {
+            name.push_str(&format!(
+                "@{}",
+                outputs::path_segment(&commit[..commit.len().min(12)])
+            ));
+        } else if let Some(branch) = &repo_info.branch {
+            name.push_str(&format!("@{}", outputs::path_segment(branch)));
+        }
+        Path::new(&self.output_dir).join("repos").join(name)
+    }
+
+    // クローン済みのリポジトリの HEAD のコミット
+    async fn head_commit(&self, repo_info: &RepoInfo) -> Option<String> {
+        // 親ディレクトリのリポジトリのコミットを拾わないよう、クローンしたディレクトリだけを見る
+        let repo_dir = self.clone_dir(repo_info);
+        if !repo_dir.join(".git").exists() {
+            return None;
+        }
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(&repo_dir)
+            .args(["rev-parse", "HEAD"])
+            .output()
+            .await
+            .ok()?;
+        output
+            .status
+            .success()
+            .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    // 次にクローンを使うときに、リモートの最新に更新し直す（serve で新しく議論を登録したとき）
+    fn refresh_clone(&self, repo_info: &RepoInfo) {
+        self.updated
+            .lock()
+            .unwrap()
+            .remove(&self.clone_dir(repo_info));
+    }
+
+    // リポジトリをクローンする
+    // ブランチやコミットが指定されている場合は、それぞれ別のディレクトリに取得する
+    async fn clone_repository(&self, repo_info: &RepoInfo) -> Result<PathBuf> {
+        let repo_dir = self.clone_dir(repo_info);
+
+        // 同じディレクトリを読み込み中の議論があるため、クローンと更新は1つずつ、更新は実行ごとに1回だけ行う
+        let lock = self
+            .clone_locks
+            .lock()
+            .unwrap()
+            .entry(repo_dir.clone())
+            .or_default()
+            .clone();
+        let _guard = lock.lock().await;
 
         // すでにクローン済みかチェック
-        if Path::new(&repo_dir).exists() {
-            info!(
-                "🔄 リポジトリはすでにクローン済み: {}/{}",
-                repo_info.owner, repo_info.repo
-            );
+        if repo_dir.exists() {
+            // コミットを固定していなければ、前回のクローン以降の変更を取り込んでからコミットを決める
+            if repo_info.commit.is_none() && !self.updated.lock().unwrap().contains(&repo_dir) {
+                info!(
+                    "🔄 リポジトリはすでにクローン済みのため、最新に更新します: {}/{}",
+                    repo_info.owner, repo_info.repo
+                );
+                self.update_clone(repo_info, &repo_dir).await?;
+                self.updated.lock().unwrap().insert(repo_dir.clone());
+            }
         } else {
+            // 巨大なリポジトリのクローンでワーカーが止まらないよう、先にサイズを確認する
+            self.check_repo_size(repo_info).await?;
+
             // ディレクトリ作成
-            fs::create_dir_all(Path::new(&repo_dir).parent().unwrap()).await?;
+            if let Some(parent) = repo_dir.parent() {
+                fs::create_dir_all(parent).await?;
+            }
 
-            // git clone コマンド実行
-            let clone_url = format!(
-                "https://{}@github.com/{}/{}.git",
-                self.token, repo_info.owner, repo_info.repo
-            );
+            // git clone コマンド実行（トークンがなければ認証なしで公開リポジトリを取得）
+            let clone_url = self.clone_url(repo_info);
 
             info!(
                 "🔽 リポジトリをクローン中: {}/{}",
                 repo_info.owner, repo_info.repo
             );
 
-            let output = Command::new("git")
-                .args(["clone", "--depth", "1", &clone_url, &repo_dir])
-                .output()
-                .await?;
+            let mut args = vec!["clone", "--depth", "1"];
+            if let Some(branch) = &repo_info.branch {
+                args.extend(["--branch", branch.as_str()]);
+            }
+            args.push(clone_url.as_str());
+            let mut command = self.authorized_git(repo_info).await;
+            let clone = command.args(&args).arg(&repo_dir).output();
+            let output = match self.clone.timeout() {
+                Some(limit) => match time::timeout(limit, clone).await {
+                    Ok(output) => output?,
+                    Err(_) => {
+                        fs::remove_dir_all(&repo_dir).await.ok();
+                        return Err(BurnerError::RepoUnavailable {
+                            repo: format!("{}/{}", repo_info.owner, repo_info.repo),
+                            message: format!(
+                                "クローンが {} 秒以内に終わらなかったため打ち切りました",
+                                limit.as_secs()
+                            ),
+                        }
+                        .into());
+                    }
+                },
+                None => clone.await?,
+            };
 
             if !output.status.success() {
                 let error = String::from_utf8_lossy(&output.stderr);
-                return Err(anyhow!("リポジトリのクローンに失敗: {}", error));
+                let hint = if self.token.is_empty() && self.app.is_none() && !self.ssh.enabled {
+                    "（GitHubトークンが未設定のため、プライベートリポジトリは取得できません）"
+                } else {
+                    ""
+                };
+                return Err(BurnerError::RepoUnavailable {
+                    repo: format!("{}/{}", repo_info.owner, repo_info.repo),
+                    message: format!("リポジトリのクローンに失敗{}: {}", hint, error.trim()),
+                }
+                .into());
             }
 
+            // 指定されたコミットを取得してチェックアウト
+            if let Some(commit) = &repo_info.commit {
+                let result = self.checkout_commit(repo_info, &repo_dir, commit).await;
+                if let Err(e) = result {
+                    fs::remove_dir_all(&repo_dir).await.ok();
+                    return Err(e);
+                }
+            }
+
+            self.fetch_submodules_and_lfs(repo_info, &repo_dir).await;
+            self.updated.lock().unwrap().insert(repo_dir.clone());
+
             info!(
                 "✅ リポジトリのクローン成功: {}/{}",
                 repo_info.owner, repo_info.repo
@@ -183,6 +1047,82 @@ impl GitHubClient {
         Ok(repo_dir)
     }
 
+    // クローン済みのリポジトリをリモートのブランチ（指定がなければデフォルトブランチ）の最新に合わせる
+    async fn update_clone(&self, repo_info: &RepoInfo, repo_dir: &Path) -> Result<()> {
+        let name = format!("{}/{}", repo_info.owner, repo_info.repo);
+        let unavailable = |message: String| BurnerError::RepoUnavailable {
+            repo: name.clone(),
+            message,
+        };
+
+        // 以前のバージョンで origin に残したトークンを取り除き、認証はヘッダーで付ける
+        let clone_url = self.clone_url(repo_info);
+        let refspec = repo_info.branch.as_deref().unwrap_or("HEAD");
+        for args in [
+            vec!["remote", "set-url", "origin", clone_url.as_str()],
+            vec!["fetch", "--depth", "1", "origin", refspec],
+            vec!["reset", "--hard", "FETCH_HEAD"],
+        ] {
+            let mut command = self.authorized_git(repo_info).await;
+            let git = command.arg("-C").arg(repo_dir).args(&args).output();
+            let output = match self.clone.timeout() {
+                Some(limit) => match time::timeout(limit, git).await {
+                    Ok(output) => output?,
+                    Err(_) => {
+                        return Err(unavailable(format!(
+                            "リポジトリの更新が {} 秒以内に終わらなかったため打ち切りました",
+                            limit.as_secs()
+                        ))
+                        .into());
+                    }
+                },
+                None => git.await?,
+            };
+            if !output.status.success() {
+                return Err(unavailable(format!(
+                    "リポジトリの更新に失敗: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ))
+                .into());
+            }
+        }
+
+        self.fetch_submodules_and_lfs(repo_info, repo_dir).await;
+        info!("🔄 リポジトリを最新に更新しました: {} ({})", name, refspec);
+        Ok(())
+    }
+
+    // クローンしたリポジトリで指定のコミットをチェックアウトする
+    async fn checkout_commit(
+        &self,
+        repo_info: &RepoInfo,
+        repo_dir: &Path,
+        commit: &str,
+    ) -> Result<()> {
+        for args in [
+            vec!["fetch", "--depth", "1", "origin", commit],
+            vec!["checkout", "--detach", "FETCH_HEAD"],
+        ] {
+            let output = self
+                .authorized_git(repo_info)
+                .await
+                .arg("-C")
+                .arg(repo_dir)
+                .args(&args)
+                .output()
+                .await?;
+            if !output.status.success() {
+                let error = String::from_utf8_lossy(&output.stderr);
+                return Err(anyhow!(
+                    "コミットのチェックアウトに失敗: {} - {}",
+                    commit,
+                    error
+                ));
+            }
+        }
+        Ok(())
+    }
+
     // コードファイルを判定する関数
     fn is_code_file(path: &str) -> bool {
         let code_extensions = [
@@ -195,6 +1135,40 @@ impl GitHubClient {
         code_extensions.iter().any(|&ext| path.ends_with(ext))
     }
 
+    // 議論を始める前にリポジトリを並行してクローンする
+    // API のワーカーがクローンを待たずに済み、ネットワークのエラーも最初にわかる
+    async fn prefetch(&self, repos: &[RepoInfo]) {
+        use futures::StreamExt;
+
+        let concurrency = self.clone.prefetch_concurrency.max(1);
+        info!(
+            "📥 リポジトリを事前にクローンします: {} 件（同時実行数: {}）",
+            repos.len(),
+            concurrency
+        );
+        let results: Vec<(&RepoInfo, Result<PathBuf>)> = futures::stream::iter(repos)
+            .map(|repo_info| async move { (repo_info, self.clone_repository(repo_info).await) })
+            .buffer_unordered(concurrency)
+            .collect()
+            .await;
+
+        let mut failed = 0;
+        for (repo_info, result) in &results {
+            if let Err(e) = result {
+                error!(
+                    "❌ 事前クローンの失敗: {}/{} - {}",
+                    repo_info.owner, repo_info.repo, e
+                );
+                failed += 1;
+            }
+        }
+        info!(
+            "📥 事前クローン完了: 成功 {} 件, 失敗 {} 件",
+            results.len() - failed,
+            failed
+        );
+    }
+
     // 除外すべきディレクトリを判定する関数
     fn is_excluded_dir(path: &str) -> bool {
         let excluded_dirs = [
@@ -219,17 +1193,15 @@ impl GitHubClient {
 
     // リポジトリファイルを取得
     async fn fetch_repo_files(&self, repo_info: &RepoInfo) -> Result<Vec<FileInfo>> {
-        info!(
-            "⬇️ リポジトリからファイル取得中: {}/{}",
-            repo_info.owner, repo_info.repo
-        );
+        self.collect_repo_files(repo_info, repo_info.max_files)
+            .await
+    }
 
+    // クローンしたディレクトリと、分析対象のコードファイル（優先度の高いファイルが先頭）をすべて列挙する
+    async fn list_code_files(&self, repo_info: &RepoInfo) -> Result<(PathBuf, Vec<PathBuf>)> {
         // リポジトリをクローン
         let repo_dir = self.clone_repository(repo_info).await?;
 
-        // ファイル一覧を取得
-        let mut files = Vec::new();
-
         // ignoreクレートを使ってgitignoreなどを考慮したファイル走査
         let walker = WalkBuilder::new(&repo_dir)
             .standard_filters(true) // .gitignoreを考慮
@@ -244,7 +1216,7 @@ impl GitHubClient {
                 Ok(entry) => {
                     let path = entry.path();
                     if path.is_file() {
-                        let path_str = path.to_string_lossy().to_string();
+                        let path_str = outputs::slash_path(path);
 
                         // コードファイルかつ除外対象でないファイルのみ
                         if Self::is_code_file(&path_str) && !Self::is_excluded_dir(&path_str) {
@@ -260,8 +1232,8 @@ impl GitHubClient {
 
         // 優先度の高いファイルを先頭に
         all_files.sort_by(|a, b| {
-            let a_str = a.to_string_lossy();
-            let b_str = b.to_string_lossy();
+            let a_str = outputs::slash_path(a);
+            let b_str = outputs::slash_path(b);
             let a_priority = is_priority_file(&a_str);
             let b_priority = is_priority_file(&b_str);
 
@@ -274,24 +1246,41 @@ impl GitHubClient {
             }
         });
 
-        // ファイル数を制限
-        let max_files = repo_info.max_files.min(all_files.len());
-        let selected_files = all_files.into_iter().take(max_files);
+        Ok((repo_dir, all_files))
+    }
+
+    // ファイルの内容を読み込む（大きすぎるファイルや取得していない Git LFS のオブジェクトは読み飛ばす）
+    // 内容が変わっていないファイル（blob の SHA が同じファイル）はキャッシュから読む
+    async fn read_files(
+        &self,
+        repo_info: &RepoInfo,
+        repo_dir: &Path,
+        paths: impl Iterator<Item = PathBuf>,
+    ) -> Result<Vec<FileInfo>> {
+        let limits = self.file_limits.for_repo(repo_info);
+        let mut files = Vec::new();
+        let blobs = file_cache::blob_shas(repo_dir).await;
 
         // ファイル内容を読み込む
-        for path in selected_files {
-            // 相対パスを取得
-            let rel_path = path
-                .strip_prefix(&repo_dir)
-                .map_err(|e| anyhow!("パス変換エラー: {}", e))?
-                .to_string_lossy()
-                .to_string();
+        for path in paths {
+            // 相対パスを取得（OS によらず / 区切り）
+            let rel_path = outputs::slash_path(
+                path.strip_prefix(repo_dir)
+                    .map_err(|e| anyhow!("パス変換エラー: {}", e))?,
+            );
+
+            let blob = blobs.get(&rel_path);
+            if let Some(file) = blob.and_then(|blob| file_cache::get(blob, &rel_path, &limits)) {
+                info!("♻️ キャッシュから読み込み: {}", rel_path);
+                files.push(file);
+                continue;
+            }
 
             // ファイルサイズをチェック
             match fs::metadata(&path).await {
                 Ok(metadata) => {
                     // 大きすぎるファイルはスキップ
-                    if metadata.len() > self.max_file_size as u64 {
+                    if metadata.len() > limits.max_file_bytes as u64 {
                         info!(
                             "⏩ サイズが大きいためスキップ: {} ({} bytes)",
                             rel_path,
@@ -307,31 +1296,80 @@ impl GitHubClient {
             }
 
             // ファイル内容を読み込む
-            match fs::read_to_string(&path).await {
-                Ok(content) => {
+            let bytes = match fs::read(&path).await {
+                Ok(bytes) => bytes,
+                Err(e) => {
+                    error!("⚠️ ファイル読み込みエラー: {} - {}", rel_path, e);
+                    continue;
+                }
+            };
+
+            // バイナリや1行が長いファイルは読み飛ばすか要約に置き換える
+            match file_guard::inspect(&rel_path, bytes) {
+                Verdict::Skip(reason) => {
+                    info!("⏩ {}スキップ: {}", reason, rel_path);
+                }
+                Verdict::Keep(content) if clone::is_lfs_pointer(&content) => {
+                    info!(
+                        "⏩ Git LFS のオブジェクトを取得していないためスキップ: {}",
+                        rel_path
+                    );
+                }
+                Verdict::Keep(content) => {
                     info!("✅ ファイル読み込み成功: {}", rel_path);
 
                     // 長すぎるファイルは先頭部分のみ
-                    let content = if content.len() > self.max_file_size {
-                        // 文字単位で処理して安全に切り取る
-                        let truncated: String = content.chars().take(self.max_file_size).collect();
-                        format!("{}...\n(内容省略)...", truncated)
-                    } else {
-                        content
-                    };
+                    let content = limits.truncate(content);
 
-                    files.push(FileInfo {
+                    let file = FileInfo {
                         path: rel_path,
+                        tokens: provider::estimate_tokens(&content),
                         content,
-                    });
+                    };
+                    if let Some(blob) = blob {
+                        file_cache::put(blob, &file, &limits);
+                    }
+                    files.push(file);
                 }
-                Err(e) => {
-                    error!("⚠️ ファイル読み込みエラー: {} - {}", rel_path, e);
+                // 要約は設定によって変わるためキャッシュしない
+                Verdict::Summarize(summary) => {
+                    files.push(FileInfo {
+                        path: rel_path,
+                        tokens: provider::estimate_tokens(&summary),
+                        content: summary,
+                    });
                 }
             }
         }
 
-        info!("🗂️ 取得ファイル数: {}/{}", files.len(), max_files);
+        Ok(files)
+    }
+
+    // 最大ファイル数を指定してリポジトリファイルを取得
+    async fn collect_repo_files(
+        &self,
+        repo_info: &RepoInfo,
+        max_files: usize,
+    ) -> Result<Vec<FileInfo>> {
+        info!(
+            "⬇️ リポジトリからファイル取得中: {}/{}",
+            repo_info.owner, repo_info.repo
+        );
+
+        let (repo_dir, all_files) = self.list_code_files(repo_info).await?;
+
+        // ファイル数を制限
+        let max_files = max_files.min(all_files.len());
+        let files = self
+            .read_files(repo_info, &repo_dir, all_files.into_iter().take(max_files))
+            .await?;
+
+        info!(
+            "🗂️ 取得ファイル数: {}/{} (約 {} トークン)",
+            files.len(),
+            max_files,
+            files.iter().map(|file| file.tokens).sum::<usize>()
+        );
 
         if files.is_empty() {
             bail!("リポジトリからファイルを取得できませんでした");
@@ -350,114 +1388,560 @@ fn is_priority_file(path: &str) -> bool {
             && (path.contains("mod.rs") || path.contains("lib.rs") || path.contains("index."))
 }
 
-// Azure OpenAI クライアント
-struct AzureOpenAIClient {
+// LLM APIクライアント（URLと認証はエンドポイントのプロバイダーに従う）
+struct LlmClient {
     client: reqwest::Client,
     endpoint: Endpoint,
-    api_version: String,
+    provider: Box<dyn LlmProvider>,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    concurrency_limit: Option<Arc<tokio::sync::Semaphore>>,
+    // 前回 take_metrics を呼んでからのリクエストの記録
+    request_log: std::sync::Mutex<TurnMetrics>,
 }
 
-impl AzureOpenAIClient {
+// 補完の応答の最初の選択肢（コンテンツフィルターで回答が打ち切られた場合は分類済みのエラー）
+fn first_choice(openai_response: &OpenAIResponse) -> Result<&OpenAIChoice> {
+    let choice = openai_response
+        .choices
+        .first()
+        .ok_or_else(|| anyhow!("チャット補完の応答に choices がありません"))?;
+    if let Some(error) = BurnerError::from_finish_reason(&choice.finish_reason) {
+        return Err(error.into());
+    }
+    Ok(choice)
+}
+
+impl LlmClient {
     fn new(endpoint: Endpoint) -> Self {
         let client = reqwest::Client::new();
+        let provider = provider::from_endpoint(&endpoint);
+        // PTU は時間単位の課金のため、リクエストの間隔を空けずに容量を使い切る
+        let rate_limiter = if ptu::is_provisioned(&endpoint) {
+            None
+        } else {
+            quota::rate_limiter(&endpoint)
+        };
+        let concurrency_limit = scheduler::concurrency_limit(&endpoint);
 
-        AzureOpenAIClient {
+        LlmClient {
             client,
             endpoint,
-            api_version: "2024-12-01-preview".to_string(),
+            provider,
+            rate_limiter,
+            concurrency_limit,
+            request_log: std::sync::Mutex::new(TurnMetrics::default()),
+        }
+    }
+
+    // 計測を始める（それまでの要約や検索のリクエストの記録を捨てる）
+    fn reset_metrics(&self) {
+        *self.request_log.lock().unwrap() = TurnMetrics::default();
+    }
+
+    // 別のクライアントで送信したリクエストの記録を、このクライアントの記録に加える
+    fn absorb_metrics(&self, other: &LlmClient) {
+        let other = std::mem::take(&mut *other.request_log.lock().unwrap());
+        let mut request_log = self.request_log.lock().unwrap();
+        request_log.requests += other.requests;
+        request_log.retries += other.retries;
+        request_log.http_statuses.extend(other.http_statuses);
+        request_log.calls.extend(other.calls);
+    }
+
+    // 成功したAPI呼び出しを記録する（使用量のエクスポート用）
+    fn note_call(&self, model: &str, usage: &OpenAIUsage, elapsed: Duration) {
+        ptu::record(
+            &self.endpoint,
+            usage.prompt_tokens + usage.completion_tokens,
+        );
+        status::record_call(
+            &self.endpoint.name,
+            usage.prompt_tokens + usage.completion_tokens,
+        );
+        // 推論トークンは completion_tokens に含まれ、出力トークンの単価で課金される
+        let estimated_cost = pricing::record(model, usage.prompt_tokens, usage.completion_tokens);
+        let reasoning_tokens = usage.reasoning_tokens();
+        ledger::record(
+            &self.endpoint,
+            model,
+            usage.prompt_tokens,
+            usage.completion_tokens,
+            reasoning_tokens,
+            estimated_cost,
+        );
+        self.request_log.lock().unwrap().calls.push(ApiCall {
+            timestamp: Utc::now().to_rfc3339(),
+            model: model.to_string(),
+            prompt_tokens: usage.prompt_tokens,
+            completion_tokens: usage.completion_tokens,
+            reasoning_tokens,
+            latency_ms: elapsed.as_millis() as u64,
+            estimated_cost,
+        });
+    }
+
+    // 計測を始めてからのリクエストの記録を取り出す
+    fn take_metrics(&self, elapsed: Duration) -> TurnMetrics {
+        let mut metrics = std::mem::take(&mut *self.request_log.lock().unwrap());
+        metrics.latency_ms = elapsed.as_millis() as u64;
+        let costs: Vec<f64> = metrics
+            .calls
+            .iter()
+            .filter_map(|call| call.estimated_cost)
+            .collect();
+        if !costs.is_empty() {
+            metrics.estimated_cost = Some(costs.iter().sum());
+            metrics.currency = Some(pricing::currency().to_string());
         }
+        metrics
+    }
+
+    // OpenAI互換APIの場合はリクエストボディにモデル名を追加する
+    fn with_model(&self, mut request_body: serde_json::Value, model: &str) -> serde_json::Value {
+        runs::note_model(model);
+        if self.provider.model_in_body() {
+            request_body["model"] = json!(model);
+        }
+        generation::adapt_request(&self.endpoint, model, &mut request_body);
+        request_body
+    }
+
+    // リトライ付きでPOSTリクエストを送信し、成功時のレスポンスをデシリアライズする
+    async fn post_with_retry<T: DeserializeOwned>(
+        &self,
+        url: &str,
+        request_body: &serde_json::Value,
+    ) -> Result<T> {
+        self.send_with_retry(|| self.client.post(url).json(request_body))
+            .await
+    }
+
+    // リトライ付きでGETリクエストを送信し、レスポンスをデシリアライズする
+    async fn get_with_retry<T: DeserializeOwned>(&self, url: &str) -> Result<T> {
+        self.send_with_retry(|| self.client.get(url)).await
     }
 
-    /// エラーレスポンスから待機時間を抽出する
-    fn extract_retry_delay(&self, error_message: &str) -> Option<u64> {
-        // "Please retry after X seconds" というパターンを探す
-        if let Some(start_idx) = error_message.find("retry after ") {
-            let after_text = &error_message[start_idx + "retry after ".len()..];
-            if let Some(end_idx) = after_text.find(" seconds") {
-                let delay_str = &after_text[..end_idx];
-                if let Ok(delay) = delay_str.parse::<u64>() {
-                    return Some(delay);
+    // リクエストを組み立てて送信し、成功時のレスポンスをデシリアライズする
+    async fn send_with_retry<T: DeserializeOwned>(
+        &self,
+        build_request: impl Fn() -> reqwest::RequestBuilder,
+    ) -> Result<T> {
+        Ok(self
+            .send_raw_with_retry(build_request)
+            .await?
+            .json()
+            .await?)
+    }
+
+    // リクエストを組み立てて送信し、失敗時はリトライする
+    // （multipartのようにボディを再利用できないリクエストのため、毎回組み立て直す）
+    async fn send_raw_with_retry(
+        &self,
+        build_request: impl Fn() -> reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response> {
+        const MAX_RETRIES: usize = 5;
+        let mut retry_count = 0;
+        let mut key_rotations = 0;
+        let backoff_delay = 1; // 初期バックオフ（秒）
+
+        loop {
+            // 台帳の推定使用額が上限に達していれば、リトライを含めて新しいリクエストを送らない
+            if ledger::ceiling_reached() {
+                return Err(BurnerError::SpendCeiling {
+                    max_usd: ledger::ceiling().unwrap_or_default(),
                 }
+                .into());
             }
+            // エンドポイントのグループが予算に達していれば、クレジットの枯渇と同じく他のエンドポイントに回す
+            if let Some(message) = groups::exhausted(&self.endpoint) {
+                return Err(BurnerError::QuotaExhausted { message }.into());
+            }
+
+            // エンドポイントのRPM上限を超えないように待機
+            if let Some(rate_limiter) = &self.rate_limiter {
+                rate_limiter.acquire().await;
+            }
+
+            // エンドポイントの同時実行数の上限を超えないように、空きができるまで待機
+            let permit = match &self.concurrency_limit {
+                Some(limit) => Some(limit.clone().acquire_owned().await?),
+                None => None,
+            };
+            let key = self.provider.key_ring().map(|ring| ring.current());
+            let response = self
+                .provider
+                .send(self.provider.authorize(build_request()))
+                .await?;
+            drop(permit);
+            {
+                let mut request_log = self.request_log.lock().unwrap();
+                request_log.requests += 1;
+                request_log.http_statuses.push(response.status().as_u16());
+            }
+
+            if response.status().is_success() {
+                return Ok(response);
+            }
+
+            let status = response.status();
+            let error_text = response.text().await?;
+            let error = BurnerError::from_response(status.as_u16(), &error_text);
+            status::record_error(&self.endpoint.name, &error.to_string());
+
+            // 認証エラーは予備のキーを順に試す（すべてのキーで失敗したらエラーを返す）
+            match (&error, self.provider.key_ring(), &key) {
+                (BurnerError::AuthFailed { .. }, Some(ring), Some(key))
+                    if key_rotations + 1 < ring.len() =>
+                {
+                    ring.rotate(key);
+                    key_rotations += 1;
+                    self.request_log.lock().unwrap().retries += 1;
+                    continue;
+                }
+                _ => {}
+            }
+
+            // 最大リトライ回数に達したか、待っても成功しない種類のエラーならそのまま返す
+            if retry_count >= MAX_RETRIES || !error.is_retryable() {
+                return Err(error.into());
+            }
+
+            let wait_time = match &error {
+                // レート制限の場合、レスポンスから待機時間を抽出、失敗したら指数バックオフ
+                BurnerError::RateLimited { retry_after, .. } => retry_after.unwrap_or_else(|| {
+                    // 指数バックオフ: 2^n × ベース時間 (1, 2, 4, 8, 16...)
+                    let delay = 2_u64.pow(retry_count as u32) * backoff_delay;
+                    // 最大待機時間を120秒に制限
+                    delay.min(120)
+                }),
+                // レート制限以外のエラーでも一応リトライするが短い待機時間
+                _ => 2_u64.pow(retry_count as u32).min(30),
+            };
+
+            // エラーをログに記録
+            error!(
+                "[{}] {} (リトライ {}/{}, {}秒後)",
+                self.endpoint.name,
+                error,
+                retry_count + 1,
+                MAX_RETRIES,
+                wait_time
+            );
+
+            // 待機してリトライ
+            time::sleep(Duration::from_secs(wait_time)).await;
+            retry_count += 1;
+            self.request_log.lock().unwrap().retries += 1;
         }
-        None
     }
 
+    // チャット補完を呼び出し、回答の本文と使用トークン数を返す（温度は推論モデルには送らない）
     async fn chat_completion(
         &self,
         messages: &[ChatMessage],
         model: &str,
-        max_tokens: usize, //o1を使う場合はmax_completion_tokensに変更してね
-        _temperature: f32, //o1を使う場合はtemperatureが不要
+        max_tokens: usize,
+        temperature: f32,
     ) -> Result<(String, usize)> {
-        const MAX_RETRIES: usize = 5;
-        let mut retry_count = 0;
-        let mut backoff_delay = 1; // 初期バックオフ（秒）
+        let generation = Generation {
+            temperature: Some(temperature),
+            ..Generation::basic(model, max_tokens)
+        };
+        let (message, tokens_used) = self
+            .chat_completion_with_tools(messages, &generation, &[])
+            .await?;
+        Ok((message.content, tokens_used))
+    }
+
+    // ツールを渡してチャット補完を呼び出し、アシスタントのメッセージ（ツールの呼び出しを含む）を返す
+    async fn chat_completion_with_tools(
+        &self,
+        messages: &[ChatMessage],
+        generation: &Generation,
+        tools: &[ToolDefinition],
+    ) -> Result<(ChatMessage, usize)> {
+        let model = generation.model.as_str();
+        let url = self.provider.deployment_url(model, "chat/completions");
+
+        let mut request_body = json!({
+            "messages": messages,
+            "max_completion_tokens": generation.max_tokens,
+        });
+        // 温度と推論の労力は指定された場合だけ送る（モデルが受け付けない場合は with_model で取り除く）
+        if let Some(temperature) = generation.temperature {
+            request_body["temperature"] = json!(temperature);
+        }
+        if let Some(effort) = generation.reasoning_effort {
+            request_body["reasoning_effort"] = json!(effort.as_str());
+        }
+        if !tools.is_empty() {
+            request_body["tools"] = json!(tools);
+            request_body["tool_choice"] = json!("auto");
+        }
+        // シード指定時はサンプリングも可能な範囲で再現させる
+        if let Some(seed) = seed::get() {
+            request_body["seed"] = json!(seed);
+        }
 
+        let request_body = self.with_model(request_body, model);
+        let started = std::time::Instant::now();
+        let mut openai_response: OpenAIResponse = self.post_with_retry(&url, &request_body).await?;
+        self.note_call(model, &openai_response.usage, started.elapsed());
+        // 推論モデルは推論トークンも max_completion_tokens に含まれるため、推論だけで上限に達すると回答が空になる
+        let choice = first_choice(&openai_response)?;
+        let empty = choice.finish_reason == "length"
+            && choice.message.content.trim().is_empty()
+            && choice.message.tool_calls.is_empty();
+        if let Some(reasoning_tokens) = openai_response.usage.reasoning_tokens().filter(|_| empty) {
+            bail!(
+                "推論トークン（{}）で最大トークン数（{}）を使い切ったため回答が空でした（max_tokens を増やしてください）",
+                reasoning_tokens,
+                generation.max_tokens
+            );
+        }
+        Ok((
+            openai_response.choices.swap_remove(0).message,
+            openai_response.usage.total_tokens,
+        ))
+    }
+
+    // フォールバックのモデルを順に試してチャット補完を呼び出し、回答したモデルも返す
+    // デプロイメントがない（404）かレート制限が続くモデルだけ次のモデルで実行し直す
+    async fn chat_completion_with_fallback(
+        &self,
+        messages: &[ChatMessage],
+        generation: &Generation,
+        tools: &[ToolDefinition],
+    ) -> Result<(ChatMessage, usize, Generation)> {
+        let mut candidates = generation::candidates(&self.endpoint, generation).into_iter();
+        let mut current = candidates.next().unwrap_or_else(|| generation.clone());
         loop {
-            let url = format!(
-                "{}/openai/deployments/{}/chat/completions?api-version={}",
-                self.endpoint.endpoint, model, self.api_version
+            let error = match self
+                .chat_completion_with_tools(messages, &current, tools)
+                .await
+            {
+                Ok((message, tokens_used)) => return Ok((message, tokens_used, current)),
+                Err(e) => e,
+            };
+            if !generation::is_model_unavailable(&error) {
+                return Err(error);
+            }
+            generation::mark_unavailable(&self.endpoint, &current.model, &error);
+            let Some(next) = candidates.next() else {
+                return Err(error);
+            };
+            warn!(
+                "[{}] 🔀 {} で回答を得られないため、{} で実行し直します: {}",
+                self.endpoint.name, current.model, next.model, error
             );
+            current = next;
+        }
+    }
 
-            let request_body = json!({
-                "messages": messages,
-                "max_completion_tokens": max_tokens,
-                //"temperature": temperature, //o1を使う場合はtemperatureが不要
-            });
+    // JSON スキーマに従った応答を取得する（構造化出力）
+    async fn structured_completion(
+        &self,
+        messages: &[ChatMessage],
+        model: &str,
+        max_tokens: usize,
+        schema_name: &str,
+        schema: serde_json::Value,
+    ) -> Result<(String, usize)> {
+        let url = self.provider.deployment_url(model, "chat/completions");
+
+        let request_body = json!({
+            "messages": messages,
+            "max_completion_tokens": max_tokens,
+            "response_format": {
+                "type": "json_schema",
+                "json_schema": {
+                    "name": schema_name,
+                    "strict": true,
+                    "schema": schema,
+                },
+            },
+        });
 
-            let response = self
-                .client
-                .post(&url)
-                .header("api-key", &self.endpoint.key)
-                .json(&request_body)
-                .send()
-                .await?;
+        let request_body = self.with_model(request_body, model);
+        let started = std::time::Instant::now();
+        let openai_response: OpenAIResponse = self.post_with_retry(&url, &request_body).await?;
+        self.note_call(model, &openai_response.usage, started.elapsed());
+        Ok((
+            first_choice(&openai_response)?.message.content.clone(),
+            openai_response.usage.total_tokens,
+        ))
+    }
 
-            if response.status().is_success() {
-                let openai_response: OpenAIResponse = response.json().await?;
-                return Ok((
-                    openai_response.choices[0].message.content.clone(),
-                    openai_response.usage.total_tokens,
-                ));
-            } else {
-                let status = response.status();
-                let error_text = response.text().await?;
-                
-                // 最大リトライ回数に達したらエラーを返す
-                if retry_count >= MAX_RETRIES {
-                    return Err(anyhow!(
-                        "OpenAI API エラー: ステータス {}, レスポンス: {} (最大リトライ回数に到達)",
-                        status,
-                        error_text
-                    ));
+    // 画像をビジョンモデルに渡して説明文を取得する
+    async fn describe_image(
+        &self,
+        image: &[u8],
+        mime_type: &str,
+        prompt: &str,
+        model: &str,
+    ) -> Result<(String, usize)> {
+        let url = self.provider.deployment_url(model, "chat/completions");
+
+        let request_body = json!({
+            "messages": [{
+                "role": "user",
+                "content": [
+                    { "type": "text", "text": prompt },
+                    {
+                        "type": "image_url",
+                        "image_url": {
+                            "url": format!("data:{};base64,{}", mime_type, BASE64.encode(image)),
+                        },
+                    },
+                ],
+            }],
+            "max_completion_tokens": 1500,
+        });
+
+        let request_body = self.with_model(request_body, model);
+        let started = std::time::Instant::now();
+        let openai_response: OpenAIResponse = self.post_with_retry(&url, &request_body).await?;
+        self.note_call(model, &openai_response.usage, started.elapsed());
+        Ok((
+            first_choice(&openai_response)?.message.content.clone(),
+            openai_response.usage.total_tokens,
+        ))
+    }
+
+    // 音声ファイルを書き起こす（書き起こしとトークン数を返す）
+    async fn transcribe(
+        &self,
+        audio: &[u8],
+        filename: &str,
+        deployment: &str,
+    ) -> Result<(String, usize)> {
+        runs::note_model(deployment);
+        let url = self
+            .provider
+            .deployment_url(deployment, "audio/transcriptions");
+
+        let transcription: TranscriptionResponse = self
+            .send_with_retry(|| {
+                let part =
+                    reqwest::multipart::Part::bytes(audio.to_vec()).file_name(filename.to_string());
+                let mut form = reqwest::multipart::Form::new().part("file", part);
+                if self.provider.model_in_body() {
+                    form = form.text("model", deployment.to_string());
+                }
+                self.client.post(&url).multipart(form)
+            })
+            .await?;
+
+        let tokens_used = transcription
+            .usage
+            .map(|usage| usage.total_tokens)
+            .unwrap_or_default();
+        Ok((transcription.text, tokens_used))
+    }
+
+    // 埋め込みベクトルを取得する
+    async fn embeddings(
+        &self,
+        inputs: &[String],
+        deployment: &str,
+    ) -> Result<(Vec<Vec<f32>>, usize)> {
+        let url = self.provider.deployment_url(deployment, "embeddings");
+
+        let request_body = json!({
+            "input": inputs,
+        });
+
+        let request_body = self.with_model(request_body, deployment);
+        let mut embedding_response: EmbeddingResponse =
+            self.post_with_retry(&url, &request_body).await?;
+
+        // 入力順に並べ替えてから返す
+        embedding_response.data.sort_by_key(|data| data.index);
+        let vectors = embedding_response
+            .data
+            .into_iter()
+            .map(|data| data.embedding)
+            .collect();
+
+        Ok((vectors, embedding_response.usage.total_tokens))
+    }
+
+    // 画像を生成してPNGのバイト列を返す
+    async fn generate_image(&self, prompt: &str, deployment: &str, size: &str) -> Result<Vec<u8>> {
+        let url = self
+            .provider
+            .deployment_url(deployment, "images/generations");
+
+        let request_body = json!({
+            "prompt": prompt,
+            "size": size,
+            "n": 1,
+            "response_format": "b64_json",
+        });
+
+        let request_body = self.with_model(request_body, deployment);
+        let image_response: ImageGenerationResponse =
+            self.post_with_retry(&url, &request_body).await?;
+
+        let encoded = image_response
+            .data
+            .into_iter()
+            .find_map(|image| image.b64_json)
+            .ok_or_else(|| anyhow!("画像生成APIのレスポンスに画像データがありません"))?;
+
+        Ok(BASE64.decode(encoded)?)
+    }
+
+    // ファイルをアップロードする（purpose: fine-tune, batch など）
+    async fn upload_file(
+        &self,
+        content: Vec<u8>,
+        filename: &str,
+        purpose: &str,
+    ) -> Result<FileObject> {
+        let url = self.provider.resource_url("files");
+
+        self.send_with_retry(|| {
+            let part =
+                reqwest::multipart::Part::bytes(content.clone()).file_name(filename.to_string());
+            let form = reqwest::multipart::Form::new()
+                .text("purpose", purpose.to_string())
+                .part("file", part);
+            self.client.post(&url).multipart(form)
+        })
+        .await
+    }
+
+    // アップロードしたファイルの情報を取得する
+    async fn get_file(&self, file_id: &str) -> Result<FileObject> {
+        let url = self.provider.resource_url(&format!("files/{}", file_id));
+        self.get_with_retry(&url).await
+    }
+
+    // ファイルの中身をテキストとして取得する
+    async fn get_file_content(&self, file_id: &str) -> Result<String> {
+        let url = self
+            .provider
+            .resource_url(&format!("files/{}/content", file_id));
+        let response = self.send_raw_with_retry(|| self.client.get(&url)).await?;
+        Ok(response.text().await?)
+    }
+
+    // アップロードしたファイルの検証が終わるまで待機する
+    async fn wait_for_file_processed(&self, file_id: &str) -> Result<()> {
+        loop {
+            let file = self.get_file(file_id).await?;
+            match file.status.as_str() {
+                "processed" => return Ok(()),
+                "error" | "deleted" => bail!(
+                    "ファイルの検証に失敗しました: {} ({})",
+                    file_id,
+                    file.status
+                ),
+                status => {
+                    info!("⏳ ファイルを検証中: {} ({})", file_id, status);
+                    time::sleep(Duration::from_secs(10)).await;
                 }
-                
-                // 429エラー（レート制限）の場合、レスポンスから待機時間を抽出
-                let wait_time = if status.as_u16() == 429 {
-                    // レスポンスから待機時間を抽出、失敗したら指数バックオフ
-                    self.extract_retry_delay(&error_text).unwrap_or_else(|| {
-                        // 指数バックオフ: 2^n × ベース時間 (1, 2, 4, 8, 16...)
-                        let delay = 2_u64.pow(retry_count as u32) * backoff_delay;
-                        // 最大待機時間を120秒に制限
-                        delay.min(120)
-                    })
-                } else {
-                    // 429以外のエラーでも一応リトライするが短い待機時間
-                    2_u64.pow(retry_count as u32).min(30)
-                };
-                
-                // エラーをログに記録
-                error!(
-                    "[{}] OpenAI API エラー: ステータス {}, レスポンス: {} (リトライ {}/{}, {}秒後)",
-                    self.endpoint.name, status, error_text, retry_count + 1, MAX_RETRIES, wait_time
-                );
-                
-                // 待機してリトライ
-                time::sleep(Duration::from_secs(wait_time)).await;
-                retry_count += 1;
-                
-                // 次のループでリトライ
             }
         }
     }
@@ -468,14 +1952,9 @@ fn generate_repo_debate_prompt(
     repo_info: &RepoInfo,
     repo_files: &[FileInfo],
     debate_type: &str,
+    code_stats: Option<&str>,
+    readme_content: &str,
 ) -> (String, String) {
-    // READMEを探す
-    let readme_content = repo_files
-        .iter()
-        .find(|file| file.path.contains("README.md"))
-        .map(|file| &file.content[..])
-        .unwrap_or("README.mdが見つかりませんでした。");
-
     // ファイル一覧のサマリー
     let file_summary = repo_files
         .iter()
@@ -504,36 +1983,38 @@ fn generate_repo_debate_prompt(
         file_samples.push_str(&content);
     }
 
-    // テンプレート読み込みを試みる
-    let system_prompt = match llm::prompts::load_template("repo_analysis") {
-        Ok(template) => {
-            // テンプレート内の変数を置換
-            let variables = vec![
-                ("owner".to_string(), repo_info.owner.clone()),
-                ("repo".to_string(), repo_info.repo.clone()),
-                ("debate_type".to_string(), debate_type.to_string()),
-                ("file_count".to_string(), repo_files.len().to_string()),
-                ("file_summary".to_string(), file_summary),
-                (
-                    "readme".to_string(),
-                    readme_content.chars().take(1000).collect::<String>(),
-                ),
-                ("file_samples".to_string(), file_samples),
-            ];
-
-            llm::prompts::render_template(&template, &variables)
-        }
-        Err(_) => {
-            // テンプレート読み込みエラー時のフォールバックプロンプト
-            format!(
-                r#"あなたは高度なAIエンジニアとして、GitHubリポジトリ「{}/{}」の分析を行います。
-このリポジトリについて「{}」という観点から詳細に議論してください。
+    // テンプレート内の変数
+    let variables = vec![
+        ("owner".to_string(), repo_info.owner.clone()),
+        ("repo".to_string(), repo_info.repo.clone()),
+        ("debate_type".to_string(), debate_type.to_string()),
+        ("file_count".to_string(), repo_files.len().to_string()),
+        (
+            "code_stats".to_string(),
+            code_stats.unwrap_or(stats::NO_STATS).to_string(),
+        ),
+        ("file_summary".to_string(), file_summary.clone()),
+        (
+            "readme".to_string(),
+            readme_content.chars().take(1000).collect::<String>(),
+        ),
+        ("file_samples".to_string(), file_samples.clone()),
+    ];
 
-【リポジトリ情報】
+    // 議論タイプのシステムプロンプト、テンプレートの順に読み込みを試みる
+    let alias = DEBATE_TYPE_ALIASES
+        .iter()
+        .find(|(_, name)| *name == debate_type)
+        .map(|(alias, _)| *alias);
+    // リポジトリの情報（システムプロンプトとフォールバックのプロンプトで共通）
+    let repo_context = format!(
+        r#"【リポジトリ情報】
 所有者: {}
 リポジトリ名: {}
 ファイル数: {}
 
+【コード統計】
+{}
 【ファイル一覧】
 {}
 
@@ -541,6 +2022,41 @@ fn generate_repo_debate_prompt(
 {}
 
 【主要ファイルサンプル】
+{}"#,
+        repo_info.owner,
+        repo_info.repo,
+        repo_files.len(),
+        code_stats.unwrap_or(stats::NO_STATS),
+        file_summary,
+        &readme_content.chars().take(1000).collect::<String>(),
+        file_samples
+    );
+    let system_prompt = match llm::prompts::system_prompt_for(debate_type, alias) {
+        Some(prompt) => {
+            // システムプロンプトの指示のあとにリポジトリの情報を加える
+            format!(
+                r#"{}
+
+【分析対象】
+GitHubリポジトリ「{}/{}」について「{}」という観点から議論してください。
+
+{}"#,
+                llm::prompts::render_template(prompt.trim_end(), &variables),
+                repo_info.owner,
+                repo_info.repo,
+                debate_type,
+                repo_context
+            )
+        }
+        None => match llm::prompts::load_template("repo_analysis") {
+            // テンプレート内の変数を置換
+            Ok(template) => llm::prompts::render_template(&template, &variables),
+            Err(_) => {
+                // テンプレート読み込みエラー時のフォールバックプロンプト
+                format!(
+                    r#"あなたは高度なAIエンジニアとして、GitHubリポジトリ「{}/{}」の分析を行います。
+このリポジトリについて「{}」という観点から詳細に議論してください。
+
 {}
 
 あなたの任務:
@@ -552,18 +2068,10 @@ fn generate_repo_debate_prompt(
 5. このプロジェクトの将来性や発展方向について予測してください
 
 できるだけ具体的なコード例や技術的詳細に基づいて、深い洞察を提供してください。"#,
-                repo_info.owner,
-                repo_info.repo,
-                debate_type,
-                repo_info.owner,
-                repo_info.repo,
-                repo_files.len(),
-                file_summary,
-                &readme_content.chars().take(1000).collect::<String>(),
-                file_samples,
-                debate_type
-            )
-        }
+                    repo_info.owner, repo_info.repo, debate_type, repo_context, debate_type
+                )
+            }
+        },
     };
 
     // 初期メッセージ
@@ -575,162 +2083,613 @@ fn generate_repo_debate_prompt(
     (system_prompt, initial_message)
 }
 
-// 次の質問を取得
-fn get_next_question(repo_info: &RepoInfo, deep_questions: &DeepQuestions, turn: usize) -> String {
+// 次の質問を取得（カテゴリ名と質問文を返す）
+fn get_next_question(
+    repo_info: &RepoInfo,
+    deep_questions: &DeepQuestions,
+    turn: usize,
+) -> (String, String) {
     if turn == 1 {
-        return format!(
-            "「{}/{}」リポジトリを分析します。まず、このプロジェクトの概要と主要コンポーネントを特定しましょう。",
-            repo_info.owner, repo_info.repo
+        return (
+            "概要".to_string(),
+            format!(
+                "「{}/{}」リポジトリを分析します。まず、このプロジェクトの概要と主要コンポーネントを特定しましょう。",
+                repo_info.owner, repo_info.repo
+            ),
         );
     }
 
     let category = deep_questions.get_category(turn - 2);
-    let question_index = (turn - 2) / 7; // 7カテゴリ
+    let question_index = (turn - 2) / deep_questions.categories.len();
 
-    deep_questions.get_question(&category, question_index)
+    let question = deep_questions.get_question(&category, question_index);
+    (category, question)
 }
 
 // 保存処理
 async fn save_response(
     base_dir: &str,
     repo_info: &RepoInfo,
-    debate_type: &str,
-    endpoint_name: &str,
-    turn: usize,
-    messages: &[ChatMessage],
-    tokens_used: usize,
-) -> Result<String> {
-    let repo_dir = format!("{}/{}_{}", base_dir, repo_info.owner, repo_info.repo);
+    response_data: &mut ResponseData,
+) -> Result<Option<String>> {
+    // 以前の応答と重複していれば保存しない設定の場合は保存しない
+    if dedupe::check(base_dir, repo_info, response_data) {
+        return Ok(None);
+    }
+
+    // ファイル名を生成（output_layout の配置）
+    let path = outputs::turn_path(base_dir, repo_info, response_data);
 
     // ディレクトリがなければ作成
-    fs::create_dir_all(&repo_dir).await?;
-
-    // ファイル名を生成
-    let now = Utc::now();
-    let filename = format!(
-        "{}/{}_{}_{}_turn{}.json",
-        repo_dir,
-        debate_type.replace(" ", "_"),
-        endpoint_name,
-        turn,
-        now.format("%Y%m%d_%H%M%S")
-    );
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).await?;
+    }
+
+    // JSONにして保存
+    let json_data = encryption::seal(serde_json::to_string_pretty(response_data)?)?;
+    fs::write(&path, json_data).await?;
+    let filename = outputs::slash_path(&path);
+    dedupe::remember(response_data, &filename);
+
+    Ok(Some(filename))
+}
 
-    // 保存データを作成
-    let response_data = ResponseData {
-        repo: format!("{}/{}", repo_info.owner, repo_info.repo),
-        debate_type: debate_type.to_string(),
-        turn,
-        timestamp: now.to_rfc3339(),
-        endpoint: endpoint_name.to_string(),
-        messages: messages.to_vec(),
-        tokens_used,
+// 議論の初期メッセージ（システムプロンプトと最初の質問）を作成
+async fn prepare_debate_messages(
+    openai_client: &LlmClient,
+    github_client: &GitHubClient,
+    repo_info: &RepoInfo,
+    debate_type: &str,
+    config: &Config,
+) -> Result<Vec<ChatMessage>> {
+    // リポジトリファイルを取得
+    let repo_files = github_client.fetch_repo_files(repo_info).await?;
+
+    // コード統計（行数・ファイル数・言語の内訳）
+    let code_stats = match stats::collect(github_client, repo_info).await {
+        Ok(code_stats) => code_stats,
+        Err(e) => {
+            warn!("⚠️ {}", e);
+            None
+        }
     };
 
-    // JSONにして保存
-    let json_data = serde_json::to_string_pretty(&response_data)?;
-    fs::write(&filename, json_data).await?;
+    // README（形式によらず探し、なければ生成した概要）
+    let readme = readme::resolve(openai_client, github_client, repo_info, &repo_files).await;
+
+    // 初期プロンプト生成
+    let (mut system_prompt, initial_message) = generate_repo_debate_prompt(
+        repo_info,
+        &repo_files,
+        debate_type,
+        code_stats.as_deref(),
+        &readme,
+    );
+
+    // リポジトリの主な言語に特有の観点を付加
+    match languages::detect(github_client, repo_info).await {
+        Ok(profiles) => {
+            if let Some(section) = languages::prompt_section(&profiles) {
+                system_prompt.push_str(&section);
+            }
+        }
+        Err(e) => warn!("⚠️ {}", e),
+    }
+
+    // アーキテクチャ系の議論ではドキュメント内の画像の説明を付加
+    if config.vision.enabled && debate_type.contains("アーキテクチャ") {
+        match vision::describe_repo_images(
+            openai_client,
+            github_client,
+            repo_info,
+            &config.output_dir,
+            &config.vision,
+        )
+        .await
+        {
+            Ok(Some(descriptions)) => system_prompt.push_str(&descriptions),
+            Ok(None) => {}
+            Err(e) => {
+                error!(
+                    "[{}] 画像解析エラー: {}/{} - {}",
+                    openai_client.endpoint.name, repo_info.owner, repo_info.repo, e
+                );
+            }
+        }
+    }
+
+    // 最大ファイル数に収まらないリポジトリは、ディレクトリごとの分析の要約を付加
+    if config.chunking.enabled {
+        match chunking::analyze_chunks(
+            openai_client,
+            github_client,
+            repo_info,
+            debate_type,
+            &config.output_dir,
+            &config.chunking,
+        )
+        .await
+        {
+            Ok(Some(summaries)) => system_prompt.push_str(&summaries),
+            Ok(None) => {}
+            Err(e) => {
+                error!(
+                    "[{}] ディレクトリごとの分析エラー: {}/{} - {}",
+                    openai_client.endpoint.name, repo_info.owner, repo_info.repo, e
+                );
+            }
+        }
+    }
+
+    // メディアファイルの書き起こしを付加
+    if config.transcription.enabled {
+        match media::transcribe_repo_media(
+            openai_client,
+            github_client,
+            repo_info,
+            &config.output_dir,
+            &config.transcription,
+        )
+        .await
+        {
+            Ok(Some(transcripts)) => system_prompt.push_str(&transcripts),
+            Ok(None) => {}
+            Err(e) => {
+                error!(
+                    "[{}] 書き起こしエラー: {}/{} - {}",
+                    openai_client.endpoint.name, repo_info.owner, repo_info.repo, e
+                );
+            }
+        }
+    }
 
-    Ok(filename)
+    // 会話履歴を保持
+    Ok(vec![
+        ChatMessage {
+            role: "system".to_string(),
+            content: system_prompt,
+            ..Default::default()
+        },
+        ChatMessage {
+            role: "user".to_string(),
+            content: initial_message,
+            ..Default::default()
+        },
+    ])
+}
+
+// 議論の終わり方
+enum DebateOutcome {
+    // 最大ターンまで進んだ
+    Completed,
+    // 実行期限のため途中で止めた
+    Interrupted,
+    // トークン予算に達したため途中で止めた
+    OverBudget,
+    // 同じ分析が完了済みのため実行しなかった（完了済みの議論のタスクID）
+    Duplicate(String),
+}
+
+// 同じリポジトリ・コミット・議論タイプ・モデル・視点で完了済みの議論を探す
+// クローンしたディレクトリの HEAD を分析するコミットとしてジャーナルに記録する（--force 指定時は探さない）
+async fn find_duplicate_run(
+    github_client: &GitHubClient,
+    journal: &Journal,
+    job: &DebateJob,
+    model: &str,
+    config: &Config,
+) -> Result<Option<TaskStatus>> {
+    let repo_info = &job.repo_info;
+    github_client.clone_repository(repo_info).await?;
+    let Some(commit) = github_client.head_commit(repo_info).await else {
+        return Ok(None);
+    };
+    journal.record_source(
+        &job.task_id,
+        repo_info,
+        &job.debate_type,
+        &commit,
+        model,
+        job.perspective,
+    );
+    if config.force {
+        return Ok(None);
+    }
+
+    let duplicate = journal.find_duplicate(
+        &job.task_id,
+        &format!("{}/{}", repo_info.owner, repo_info.repo),
+        &commit,
+        &job.debate_type,
+        model,
+        job.perspective,
+    );
+    if let Some(original) = &duplicate {
+        info!(
+            "♻️ 同じコミット・議論タイプ・モデルの議論が完了済みのためスキップします: {}/{}@{} ({}) - 完了済みのタスク: {}（--force で再実行できます）",
+            repo_info.owner,
+            repo_info.repo,
+            &commit[..commit.len().min(12)],
+            job.debate_type,
+            original.task_id
+        );
+    }
+    Ok(duplicate)
 }
 
 // リポジトリ分析の実行
 async fn debate_runner(
     github_client: Arc<GitHubClient>,
     endpoints: Arc<Vec<Endpoint>>,
-    repo_info: RepoInfo,
-    debate_type: String,
+    job: &DebateJob,
     endpoint_index: usize,
-    base_dir: String,
-) -> Result<()> {
-    let endpoint = &endpoints[endpoint_index % endpoints.len()];
-    let openai_client = AzureOpenAIClient::new(endpoint.clone());
+    config: Arc<Config>,
+    scheduler: Arc<Scheduler>,
+    journal: Arc<Journal>,
+) -> Result<DebateOutcome> {
+    let endpoint_index = endpoint_index % endpoints.len();
+    let endpoint = &endpoints[endpoint_index];
+    let openai_client = LlmClient::new(endpoint.clone());
+    let repo_info = job.repo_info.clone();
+    let debate_type = job.debate_type.clone();
+
+    // ファイル単位のレビューは短い会話で別に進める
+    if let Some(path) = &job.file {
+        return file_review::review_runner(
+            &github_client,
+            &openai_client,
+            job,
+            path,
+            endpoint_index,
+            &config,
+            &scheduler,
+            &journal,
+        )
+        .await;
+    }
 
     info!(
-        "[{}] リポジトリ分析開始: {}/{} ({})",
-        endpoint.name, repo_info.owner, repo_info.repo, debate_type
+        "[{}] リポジトリ分析開始: {}/{} ({}) - モデル: {}",
+        endpoint.name,
+        repo_info.owner,
+        repo_info.repo,
+        debate_type,
+        generation::resolve(endpoint, &debate_type).model
     );
 
-    // リポジトリファイルを取得
-    let repo_files = match github_client.fetch_repo_files(&repo_info).await {
-        Ok(files) => files,
-        Err(e) => {
-            error!(
-                "[{}] リポジトリファイル取得エラー: {}/{} - {}",
-                endpoint.name, repo_info.owner, repo_info.repo, e
+    // 質問生成用（シード指定時は議論ごとに出題順が決まる）
+    let deep_questions = DeepQuestions::for_debate(&github_client, &repo_info, &job.task_id).await;
+
+    // RAG用の検索器（埋め込みインデックスが存在する場合のみ有効）
+    let mut rag = RagRetriever::new(&config.output_dir, &repo_info, &config.embeddings);
+
+    // 会話ループ
+    let mut turn = 1;
+    let mut consecutive_errors = 0; // 連続エラーカウンター
+    let mut current_category = "概要".to_string(); // 現在の質問カテゴリ
+
+    // 古いターンの要約（再開時は保存済みの要約を引き継ぐ）
+    let mut compactor = Compactor::new(&config.compaction, &debate_type, None);
+
+    // 新しく始める議論は、同じ分析が完了済みでないか確認する
+    let saved = journal.load_progress(&job.task_id, &job.repo_info);
+    if saved.is_none() {
+        // A/B 比較は同じモデルの通常の議論と区別する
+        let model = match config.compare.enabled && debate_type != ROLEPLAY_TYPE {
+            true => compare::label(&config.compare),
+            false => generation::resolve(endpoint, &debate_type).model,
+        };
+        match find_duplicate_run(&github_client, &journal, job, &model, &config).await {
+            Ok(Some(original)) => return Ok(DebateOutcome::Duplicate(original.task_id)),
+            Ok(None) => {}
+            Err(e) => {
+                error!(
+                    "[{}] リポジトリファイル取得エラー: {}/{} - {}",
+                    endpoint.name, repo_info.owner, repo_info.repo, e
+                );
+                return Err(e);
+            }
+        }
+    }
+
+    // A/B 比較では2つのモデルの会話を並行して進める
+    if config.compare.enabled && debate_type != ROLEPLAY_TYPE {
+        return compare::compare_runner(
+            &github_client,
+            &openai_client,
+            job,
+            endpoint_index,
+            &config,
+            &scheduler,
+        )
+        .await;
+    }
+
+    // ロールプレイ討論は役割ごとの会話で別に進める
+    if debate_type == ROLEPLAY_TYPE {
+        return roleplay::roleplay_runner(
+            &github_client,
+            &openai_client,
+            job,
+            endpoint_index,
+            &config,
+            &scheduler,
+            &journal,
+        )
+        .await;
+    }
+
+    // 途中まで保存された議論があれば、その続きから再開する
+    let mut messages = match saved {
+        Some(saved) => {
+            info!(
+                "[{}] ⏩ 途中から再開します: {}/{} ({}) - ターン {} まで保存済み",
+                endpoint.name, repo_info.owner, repo_info.repo, debate_type, saved.turn
             );
-            return Err(e);
+            let (category, next_question) =
+                get_next_question(&repo_info, &deep_questions, saved.turn);
+            let next_question = rag
+                .augment_question(&openai_client, &category, next_question)
+                .await;
+
+            turn = saved.turn + 1;
+            current_category = category;
+            compactor = Compactor::new(&config.compaction, &debate_type, saved.context_summary);
+            let mut messages = saved.messages;
+            messages.push(ChatMessage {
+                role: "user".to_string(),
+                content: next_question,
+                ..Default::default()
+            });
+            messages
         }
+        // 初期の会話履歴を作成
+        None => match prepare_debate_messages(
+            &openai_client,
+            &github_client,
+            &repo_info,
+            &debate_type,
+            &config,
+        )
+        .await
+        {
+            Ok(messages) => messages,
+            Err(e) => {
+                error!(
+                    "[{}] リポジトリファイル取得エラー: {}/{} - {}",
+                    endpoint.name, repo_info.owner, repo_info.repo, e
+                );
+                return Err(e);
+            }
+        },
     };
 
-    // 初期プロンプト生成
-    let (system_prompt, initial_message) =
-        generate_repo_debate_prompt(&repo_info, &repo_files, &debate_type);
-
-    // 会話履歴を保持
-    let mut messages = vec![
-        ChatMessage {
-            role: "system".to_string(),
-            content: system_prompt,
-        },
-        ChatMessage {
-            role: "user".to_string(),
-            content: initial_message,
-        },
-    ];
+    // モデルがリポジトリのファイルを取得できるようにする（クローンしたディレクトリの中だけ）
+    let sandbox = match config.tools.enabled {
+        true => {
+            let repo_dir = github_client.clone_repository(&repo_info).await?;
+            Some(RepoSandbox::new(
+                &repo_dir,
+                config.file_limits().for_repo(&repo_info).max_file_bytes,
+                &config.tools,
+            )?)
+        }
+        false => None,
+    };
 
-    // 質問生成用
-    let deep_questions = DeepQuestions::new();
+    // 品質チェックで不採用にした応答と、聞き直す前の質問
+    let mut rejected: Vec<RejectedResponse> = Vec::new();
+    let mut original_question: Option<String> = None;
+    // 最後の聞き直しに使う別のエンドポイント
+    let mut alternate: Option<(usize, LlmClient)> = None;
 
-    // 会話ループ
-    let mut turn = 1;
-    let mut consecutive_errors = 0; // 連続エラーカウンター
-    
     while turn <= 20 {
         // 最大20ターンまでに制限
+        // 実行期限が近ければ新しいターンを開始しない（保存済みのターンから次回再開する）
+        if deadline::reached() {
+            info!(
+                "[{}] ⏳ 実行期限が近いため中断します: {}/{} ({}) - ターン {} まで保存済み",
+                endpoint.name,
+                repo_info.owner,
+                repo_info.repo,
+                debate_type,
+                turn - 1
+            );
+            return Ok(DebateOutcome::Interrupted);
+        }
+        // 使用額の上限に達していれば新しいターンを開始しない（保存済みのターンから次回再開する）
+        if ledger::ceiling_reached() {
+            return Ok(DebateOutcome::Interrupted);
+        }
+
+        // トークン予算に達したリポジトリは新しいターンを開始しない
+        if budget::exhausted(&repo_info) {
+            info!(
+                "[{}] 💰 トークン予算に達したため中断します: {}/{} ({}) - ターン {} まで保存済み",
+                endpoint.name,
+                repo_info.owner,
+                repo_info.repo,
+                debate_type,
+                turn - 1
+            );
+            return Ok(DebateOutcome::OverBudget);
+        }
+
         info!(
             "[{}] 分析実行中: {}/{} ({}) - ターン {}",
             endpoint.name, repo_info.owner, repo_info.repo, debate_type, turn
         );
 
-        // OpenAI APIを呼び出し
-        match openai_client
-            .chat_completion(
-                &messages,
-                "gpt-4.5-preview", // 最大モデルを使用
-                4000,              // 長い出力
-                0.8,               // 適度な創造性
-            )
-            .await
-        {
-            Ok((response, tokens_used)) => {
+        // 長くなった会話履歴は古いターンを要約して送信する
+        let context = compactor
+            .prepare(&openai_client, &repo_info, &messages)
+            .await;
+
+        // OpenAI APIを呼び出し（聞き直しの場合は不採用にした応答の記録を残す）
+        if rejected.is_empty() {
+            openai_client.reset_metrics();
+        }
+        let (client_index, client) = match &alternate {
+            Some((index, client)) => (*index, client),
+            None => (endpoint_index, &openai_client),
+        };
+        let answered_by = client.endpoint.name.clone();
+        // 回答するエンドポイントと議論タイプのモデル・最大トークン数・温度
+        let generation = generation::resolve(&client.endpoint, &debate_type);
+        let started = std::time::Instant::now();
+        // 応答が返ってこない接続で同時実行枠を占有し続けないよう、ターンに時間制限を設ける
+        let completion = async {
+            match &sandbox {
+                // ツールで必要なファイルを取得しながら回答させる
+                Some(sandbox) => tools::complete(client, &context, &generation, sandbox).await,
+                None => client
+                    .chat_completion_with_fallback(&context, &generation, &[])
+                    .await
+                    .map(|(message, tokens_used, answered)| {
+                        (message.content, tokens_used, answered.model)
+                    }),
+            }
+        };
+        let limit = config
+            .timeouts
+            .turn_for(generation::may_reason(&client.endpoint, &generation));
+        match deadline::with_timeout(limit, "ターン", completion).await {
+            Ok((response, tokens_used, model)) => {
                 // 成功したら連続エラーカウンターをリセット
                 consecutive_errors = 0;
-                
+
+                // スループットとトークン使用量を記録
+                scheduler.record(client_index, tokens_used, started.elapsed());
+                budget::record(&repo_info, tokens_used);
+                // 別のエンドポイントで聞き直した応答は、見直しも同じエンドポイントで行う
+                let answered_client = alternate.take();
+                if let Some((_, alternate_client)) = &answered_client {
+                    openai_client.absorb_metrics(alternate_client);
+                }
+
+                // 品質チェックに通らない応答は、言い回しを変えて（最後は別のエンドポイントで）聞き直す
+                if config.quality.enabled {
+                    let asked = messages
+                        .last()
+                        .map(|message| message.content.clone())
+                        .unwrap_or_default();
+                    let question = original_question.clone().unwrap_or_else(|| asked.clone());
+                    match quality::check(&config.quality, &question, &response) {
+                        Some(rejection) if rejected.len() < config.quality.max_reasks => {
+                            warn!(
+                                "[{}] 🙅 応答を採用しません: {}/{} - ターン {} - {}",
+                                answered_by, repo_info.owner, repo_info.repo, turn, rejection
+                            );
+                            rejected.push(RejectedResponse {
+                                endpoint: answered_by.clone(),
+                                question: asked,
+                                content: response.clone(),
+                                reason: rejection.to_string(),
+                                tokens_used,
+                            });
+                            if let Some(last) = messages.last_mut() {
+                                last.content =
+                                    quality::rephrase(&config.quality, &question, &rejection);
+                            }
+                            original_question = Some(question);
+                            if config.quality.switch_endpoint
+                                && rejected.len() == config.quality.max_reasks
+                                && endpoints.len() > 1
+                            {
+                                let index = (endpoint_index + 1) % endpoints.len();
+                                info!(
+                                    "[{}] 🔀 別のエンドポイントで聞き直します: {}",
+                                    endpoint.name, endpoints[index].name
+                                );
+                                alternate = Some((index, LlmClient::new(endpoints[index].clone())));
+                            }
+                            continue;
+                        }
+                        Some(rejection) => warn!(
+                            "[{}] ⚠️ 聞き直しの上限に達したため最後の応答を採用します: {}/{} - ターン {} - {}",
+                            answered_by, repo_info.owner, repo_info.repo, turn, rejection
+                        ),
+                        None => {}
+                    }
+                }
+                original_question = None;
+
+                // 応答をモデル自身に見直させ、改善版を会話履歴に残す（元の応答は下書きとして記録する）
+                let mut response = response;
+                let mut tokens_used = tokens_used;
+                let mut draft = None;
+                if config.refine.enabled {
+                    let (client_index, client) = match &answered_client {
+                        Some((index, client)) => (*index, client),
+                        None => (endpoint_index, &openai_client),
+                    };
+                    let started = std::time::Instant::now();
+                    let revision =
+                        refine::revise(client, &config.refine, &context, &generation, &response);
+                    match deadline::with_timeout(limit, "ターン", revision).await {
+                        Ok((revised, refine_tokens)) if !revised.trim().is_empty() => {
+                            scheduler.record(client_index, refine_tokens, started.elapsed());
+                            budget::record(&repo_info, refine_tokens);
+                            tokens_used += refine_tokens;
+                            draft = Some(std::mem::replace(&mut response, revised));
+                        }
+                        Ok(_) => warn!(
+                            "[{}] ⚠️ 改善版が空のため元の応答を採用します: {}/{} - ターン {}",
+                            answered_by, repo_info.owner, repo_info.repo, turn
+                        ),
+                        Err(e) => warn!(
+                            "[{}] ⚠️ 応答の見直しに失敗したため元の応答を採用します: {}/{} - ターン {} - {}",
+                            answered_by, repo_info.owner, repo_info.repo, turn, e
+                        ),
+                    }
+                    if let Some((_, alternate_client)) = &answered_client {
+                        openai_client.absorb_metrics(alternate_client);
+                    }
+                }
+
                 // レスポンスを会話履歴に追加
                 messages.push(ChatMessage {
                     role: "assistant".to_string(),
                     content: response,
+                    ..Default::default()
                 });
 
-                // 結果を保存
-                match save_response(
-                    &base_dir,
-                    &repo_info,
-                    &debate_type,
-                    &endpoint.name,
+                // 保存データを作成
+                let mut response_data = ResponseData {
+                    schema_version: RESPONSE_SCHEMA_VERSION,
+                    repo: format!("{}/{}", repo_info.owner, repo_info.repo),
+                    debate_type: debate_type.clone(),
                     turn,
-                    &messages,
+                    timestamp: Utc::now().to_rfc3339(),
+                    endpoint: answered_by,
+                    category: Some(current_category.clone()),
+                    messages: messages.clone(),
                     tokens_used,
-                )
-                .await
-                {
-                    Ok(filename) => {
+                    context_summary: compactor.summary(),
+                    run_id: runs::current_id(),
+                    file: None,
+                    metrics: Some(openai_client.take_metrics(started.elapsed())),
+                    task_id: Some(job.task_id.clone()),
+                    rejected: std::mem::take(&mut rejected),
+                    content_hash: None,
+                    duplicate_of: None,
+                    model: Some(model),
+                    draft,
+                };
+
+                // 結果を保存
+                match save_response(&config.output_dir, &repo_info, &mut response_data).await {
+                    Ok(Some(filename)) => {
                         info!(
-                            "[{}] 保存完了: {} (トークン数: {})",
-                            endpoint.name, filename, tokens_used
+                            "[{}] 保存完了: {} (トークン数: {}{})",
+                            endpoint.name,
+                            filename,
+                            tokens_used,
+                            ledger::cost_note(&response_data)
                         );
+                        journal.record_turn(&job.task_id, &response_data, &filename);
                     }
+                    Ok(None) => {}
                     Err(e) => {
                         error!(
                             "[{}] 保存エラー: {}/{} - ターン {} - {}",
@@ -740,17 +2699,27 @@ async fn debate_runner(
                 }
 
                 // 次の質問を生成
-                let next_question = get_next_question(&repo_info, &deep_questions, turn);
+                let (category, next_question) =
+                    get_next_question(&repo_info, &deep_questions, turn);
+                current_category = category.clone();
+
+                // 埋め込みインデックスから関連コードを付加
+                let next_question = rag
+                    .augment_question(&openai_client, &category, next_question)
+                    .await;
 
                 messages.push(ChatMessage {
                     role: "user".to_string(),
                     content: next_question,
+                    ..Default::default()
                 });
 
                 turn += 1;
 
-                // クレジット消費のためあまり待機しない
-                time::sleep(Duration::from_millis(1000)).await;
+                // クレジット消費のためあまり待機しない（PTU は容量を使い切るため待機しない）
+                if !ptu::is_provisioned(&openai_client.endpoint) {
+                    time::sleep(Duration::from_millis(1000)).await;
+                }
             }
             Err(e) => {
                 error!(
@@ -758,10 +2727,15 @@ async fn debate_runner(
                     endpoint.name, repo_info.owner, repo_info.repo, turn, e
                 );
 
+                // 認証エラーやコンテンツフィルターなど、再試行しても成功しない種類のエラーはすぐに終了する
+                if BurnerError::of(&e).is_some_and(|error| !error.is_retryable()) {
+                    return Err(e);
+                }
+
                 // OpenAI API側でのリトライを実装したので、
                 // ここでは短い待機を入れるだけでOK
                 time::sleep(Duration::from_secs(1)).await;
-                
+
                 // リトライカウントをトラッキングして一定回数以上失敗したら終了
                 consecutive_errors += 1;
                 if consecutive_errors >= 3 {
@@ -775,19 +2749,169 @@ async fn debate_runner(
         }
     }
 
-    Ok(())
+    // 会話から指摘事項を抽出して結果と並べて保存
+    if config.findings.enabled {
+        findings::extract_and_save(
+            &openai_client,
+            &config.output_dir,
+            &config.findings,
+            &repo_info,
+            &debate_type,
+            None,
+            &messages,
+        )
+        .await;
+    }
+
+    Ok(DebateOutcome::Completed)
 }
 
 // 設定ファイル用構造体
 #[derive(Serialize, Deserialize, Debug, Clone)]
 struct Config {
+    // 省略時は公開リポジトリだけを認証なしで取得する
+    #[serde(default)]
     github_token: String,
+    #[serde(default)]
+    github_app: GitHubAppConfig,
+    #[serde(default)]
+    ssh: SshConfig,
+    #[serde(default)]
+    clone: CloneConfig,
     output_dir: String,
+    #[serde(default)]
+    output_layout: Option<String>,
     endpoints: Vec<Endpoint>,
     repos: Vec<RepoInfo>,
     concurrency: usize,
     max_files: usize,
-    max_file_size: usize,
+    /// 読み込むファイルの最大サイズ（バイト。これを超えるファイルは読み飛ばす）
+    #[serde(alias = "max_file_size")]
+    max_file_bytes: usize,
+    /// ファイルの内容を切り詰める文字数（省略時は切り詰めない）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    truncate_chars: Option<usize>,
+    /// ファイルの内容を切り詰めるおおよそのトークン数（省略時は切り詰めない）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    truncate_tokens: Option<usize>,
+    /// リポジトリごとのトークン予算（達したリポジトリの議論は新しいターンを開始しない）
+    #[serde(default)]
+    max_tokens_per_repo: Option<usize>,
+    /// 全体のトークン予算
+    #[serde(default)]
+    max_total_tokens: Option<usize>,
+    /// 同じコミット・議論タイプ・モデルで完了済みの議論があっても再実行する
+    #[serde(default)]
+    force: bool,
+    #[serde(default)]
+    embeddings: EmbeddingsConfig,
+    #[serde(default)]
+    ai_search: AiSearchConfig,
+    #[serde(default)]
+    diagrams: DiagramConfig,
+    #[serde(default)]
+    vision: VisionConfig,
+    #[serde(default)]
+    summary: SummaryConfig,
+    #[serde(default)]
+    tts: TtsConfig,
+    #[serde(default)]
+    transcription: TranscriptionConfig,
+    #[serde(default)]
+    translator: TranslatorConfig,
+    #[serde(default)]
+    batch: BatchConfig,
+    #[serde(default)]
+    api: ApiConfig,
+    #[serde(default)]
+    discovery: DiscoveryConfig,
+    #[serde(default)]
+    provision: ProvisionConfig,
+    #[serde(default)]
+    quota: QuotaConfig,
+    #[serde(default)]
+    scheduler: SchedulerConfig,
+    #[serde(default)]
+    serve: ServeConfig,
+    #[serde(default)]
+    compaction: CompactionConfig,
+    #[serde(default)]
+    timeouts: TimeoutConfig,
+    #[serde(default)]
+    reload: ReloadConfig,
+    #[serde(default)]
+    file_review: FileReviewConfig,
+    #[serde(default)]
+    findings: FindingsConfig,
+    #[serde(default)]
+    tools: ToolsConfig,
+    #[serde(default)]
+    roleplay: RolePlayConfig,
+    #[serde(default)]
+    pricing: PricingConfig,
+    #[serde(default)]
+    retry: RetryConfig,
+    #[serde(default)]
+    ptu: PtuConfig,
+    #[serde(default)]
+    status: StatusConfig,
+    #[serde(default)]
+    questions: QuestionsConfig,
+    #[serde(default)]
+    quality: QualityConfig,
+    #[serde(default)]
+    system_prompts: SystemPromptsConfig,
+    #[serde(default)]
+    dedupe: DedupeConfig,
+    #[serde(default)]
+    retention: RetentionConfig,
+    #[serde(default)]
+    encryption: EncryptionConfig,
+    #[serde(default)]
+    redaction: RedactionConfig,
+    #[serde(default)]
+    scorecard: ScorecardConfig,
+    #[serde(default)]
+    org_report: OrgReportConfig,
+    #[serde(default)]
+    endpoint_groups: GroupsConfig,
+    #[serde(default)]
+    routing: RoutingConfig,
+    #[serde(default)]
+    generation: GenerationConfig,
+    #[serde(default)]
+    eval: EvalConfig,
+    #[serde(default)]
+    compare: CompareConfig,
+    #[serde(default)]
+    refine: RefineConfig,
+    #[serde(default)]
+    chunking: ChunkingConfig,
+    #[serde(default)]
+    languages: LanguagesConfig,
+    #[serde(default)]
+    stats: StatsConfig,
+    #[serde(default)]
+    ci: CiConfig,
+    #[serde(default)]
+    readme: ReadmeConfig,
+    #[serde(default)]
+    file_cache: FileCacheConfig,
+    #[serde(default)]
+    file_guard: FileGuardConfig,
+    #[serde(default)]
+    control: ControlConfig,
+}
+
+impl Config {
+    // ファイルの読み込みの制限（設定全体の値）
+    fn file_limits(&self) -> FileLimits {
+        FileLimits {
+            max_file_bytes: self.max_file_bytes,
+            truncate_chars: self.truncate_chars,
+            truncate_tokens: self.truncate_tokens,
+        }
+    }
 }
 
 // 環境変数の参照を解決する関数
@@ -800,7 +2924,7 @@ fn resolve_env_vars(input: &str) -> String {
     let mut replacements = Vec::new();
 
     // まず置換対象をすべて収集
-    for captures in env_var_regex.captures_iter(&input) {
+    for captures in env_var_regex.captures_iter(input) {
         let full_match = captures.get(0).unwrap().as_str().to_string();
         let var_name = captures.get(1).unwrap().as_str().to_string();
 
@@ -844,20 +2968,119 @@ async fn load_config(config_path: &str) -> Result<Config> {
     Ok(config)
 }
 
+// 作成したリソースを探すサブスクリプション（エンドポイントの subscription と自動検出の対象）
+fn provisioned_subscriptions(config: &Config) -> Vec<String> {
+    let mut subscriptions: Vec<String> = Vec::new();
+    let candidates = config
+        .endpoints
+        .iter()
+        .filter_map(|endpoint| endpoint.subscription.as_ref())
+        .chain(&config.discovery.subscription_ids);
+    for subscription in candidates {
+        if !subscription.is_empty() && !subscriptions.contains(subscription) {
+            subscriptions.push(subscription.clone());
+        }
+    }
+    subscriptions
+}
+
+// ログに出さない設定ファイルと環境変数の秘密の値
+fn secret_values(config: &Config) -> Vec<String> {
+    let mut secrets = vec![
+        config.github_token.clone(),
+        config.discovery.client_secret.clone(),
+        config.ai_search.key.clone(),
+        config.translator.key.clone(),
+        config.tts.key.clone(),
+        config.serve.api_token.clone(),
+        config.serve.webhook_secret.clone(),
+    ];
+    secrets.extend(config.github_app.private_key.clone());
+    for endpoint in &config.endpoints {
+        secrets.extend(endpoint.all_keys());
+    }
+    secrets
+}
+
+// 分析完了後の後処理（サマリー生成・翻訳・読み上げ・検索インデックス登録）
+async fn run_post_processing(config: &Config, force_summary: bool) {
+    if config.summary.enabled || force_summary {
+        summary::summarize_repos(
+            &config.endpoints,
+            &config.repos,
+            &config.output_dir,
+            &config.summary,
+        )
+        .await;
+    }
+
+    if config.scorecard.enabled {
+        scorecard::generate_scorecards(
+            &config.endpoints,
+            &config.repos,
+            &config.output_dir,
+            &config.scorecard,
+        )
+        .await;
+    }
+
+    if config.org_report.enabled {
+        org_report::run(&config.endpoints, &config.output_dir, &config.org_report).await;
+    }
+
+    if config.translator.enabled {
+        translator::translate_summaries(&config.output_dir, &config.repos, &config.translator)
+            .await;
+    }
+
+    if config.tts.enabled {
+        tts::narrate_summaries(&config.output_dir, &config.repos, &config.tts).await;
+    }
+
+    if config.ai_search.enabled {
+        let result = ai_search::export_outputs(&config.output_dir, &config.ai_search).await;
+        if let Err(e) = result {
+            error!("❌ Azure AI Search エクスポートエラー: {}", e);
+        }
+    }
+}
+
 // メイン関数
+// （エラーでもランタイムを閉じて終わり、書きかけの台帳の追記を待つ）
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> std::process::ExitCode {
+    // 終了時のエラーにもトークンが含まれることがあるため、伏せ字にしてから出力する
+    if let Err(e) = run().await {
+        eprintln!("Error: {}", redact::scrub(&format!("{:?}", e)));
+        return std::process::ExitCode::FAILURE;
+    }
+    std::process::ExitCode::SUCCESS
+}
+
+async fn run() -> Result<()> {
     // .envファイルを読み込み
     dotenv().ok();
 
-    // ロガー初期化
-    SimpleLogger::new()
-        .with_level(log::LevelFilter::Info)
-        .init()
-        .unwrap();
-
     // コマンドライン引数を解析
-    let args = Args::parse();
+    let mut args = Args::parse();
+
+    // ロガー初期化（ログも標準出力に出るため、CSV を標準出力に書き出す場合は止める）
+    let level = match &args.command {
+        Some(Commands::Report {
+            format: ReportFormat::Csv,
+            output: None,
+        }) => log::LevelFilter::Off,
+        _ => log::LevelFilter::Info,
+    };
+    // APIのエラー本文やURLに含まれるトークンを伏せ字にしてから出力する
+    redact::init_logger(level);
+
+    // 実行期限は起動直後から数える（クローンや埋め込みの生成などの準備にかかった時間も含める。デーモンモードでは使用しない）
+    if let Some(max_duration) = args.max_duration
+        && !matches!(args.command, Some(Commands::Serve { .. }))
+    {
+        deadline::start(max_duration);
+    }
 
     // 設定ファイルを読み込み
     let mut config = match load_config(&args.config_file).await {
@@ -874,7 +3097,11 @@ async fn main() -> Result<()> {
             // デフォルト設定
             Config {
                 github_token: std::env::var("GITHUB_TOKEN").unwrap_or_else(|_| "".to_string()),
+                github_app: GitHubAppConfig::default(),
+                ssh: SshConfig::default(),
+                clone: CloneConfig::default(),
                 output_dir: "llm_debates".to_string(),
+                output_layout: None,
                 endpoints: vec![
                     Endpoint {
                         name: "east-us".to_string(),
@@ -883,6 +3110,7 @@ async fn main() -> Result<()> {
                         endpoint: std::env::var("AZURE_OPENAI_ENDPOINT_EAST_US").unwrap_or_else(
                             |_| "https://eastus.api.cognitive.microsoft.com".to_string(),
                         ),
+                        ..Default::default()
                     },
                     Endpoint {
                         name: "west-us".to_string(),
@@ -891,6 +3119,7 @@ async fn main() -> Result<()> {
                         endpoint: std::env::var("AZURE_OPENAI_ENDPOINT_WEST_US").unwrap_or_else(
                             |_| "https://westus.api.cognitive.microsoft.com".to_string(),
                         ),
+                        ..Default::default()
                     },
                     Endpoint {
                         name: "japan-east".to_string(),
@@ -899,6 +3128,7 @@ async fn main() -> Result<()> {
                         endpoint: std::env::var("AZURE_OPENAI_ENDPOINT_JAPAN_EAST").unwrap_or_else(
                             |_| "https://japaneast.api.cognitive.microsoft.com".to_string(),
                         ),
+                        ..Default::default()
                     },
                     Endpoint {
                         name: "europe-west".to_string(),
@@ -908,6 +3138,7 @@ async fn main() -> Result<()> {
                             .unwrap_or_else(|_| {
                                 "https://westeurope.api.cognitive.microsoft.com".to_string()
                             }),
+                        ..Default::default()
                     },
                 ],
                 repos: vec![
@@ -915,21 +3146,78 @@ async fn main() -> Result<()> {
                         owner: "your-org".to_string(),
                         repo: "your-private-repo1".to_string(),
                         max_files: 50,
+                        ..Default::default()
                     },
                     RepoInfo {
                         owner: "your-org".to_string(),
                         repo: "your-private-repo2".to_string(),
                         max_files: 50,
+                        ..Default::default()
                     },
                     RepoInfo {
                         owner: "your-org".to_string(),
                         repo: "your-private-repo3".to_string(),
                         max_files: 50,
+                        ..Default::default()
                     },
                 ],
                 concurrency: 8,
                 max_files: 50,
-                max_file_size: 100000,
+                max_file_bytes: 100000,
+                truncate_chars: None,
+                truncate_tokens: None,
+                max_tokens_per_repo: None,
+                max_total_tokens: None,
+                force: false,
+                embeddings: EmbeddingsConfig::default(),
+                ai_search: AiSearchConfig::default(),
+                diagrams: DiagramConfig::default(),
+                vision: VisionConfig::default(),
+                summary: SummaryConfig::default(),
+                tts: TtsConfig::default(),
+                transcription: TranscriptionConfig::default(),
+                translator: TranslatorConfig::default(),
+                batch: BatchConfig::default(),
+                api: ApiConfig::default(),
+                discovery: DiscoveryConfig::default(),
+                provision: ProvisionConfig::default(),
+                quota: QuotaConfig::default(),
+                scheduler: SchedulerConfig::default(),
+                serve: ServeConfig::default(),
+                compaction: CompactionConfig::default(),
+                timeouts: TimeoutConfig::default(),
+                reload: ReloadConfig::default(),
+                file_review: FileReviewConfig::default(),
+                findings: FindingsConfig::default(),
+                tools: ToolsConfig::default(),
+                roleplay: RolePlayConfig::default(),
+                pricing: PricingConfig::default(),
+                retry: RetryConfig::default(),
+                ptu: PtuConfig::default(),
+                status: StatusConfig::default(),
+                questions: QuestionsConfig::default(),
+                quality: QualityConfig::default(),
+                system_prompts: SystemPromptsConfig::default(),
+                dedupe: DedupeConfig::default(),
+                retention: RetentionConfig::default(),
+                encryption: EncryptionConfig::default(),
+                redaction: RedactionConfig::default(),
+                scorecard: ScorecardConfig::default(),
+                org_report: OrgReportConfig::default(),
+                endpoint_groups: GroupsConfig::default(),
+                routing: RoutingConfig::default(),
+                generation: GenerationConfig::default(),
+                eval: EvalConfig::default(),
+                compare: CompareConfig::default(),
+                refine: RefineConfig::default(),
+                chunking: ChunkingConfig::default(),
+                languages: LanguagesConfig::default(),
+                stats: StatsConfig::default(),
+                ci: CiConfig::default(),
+                readme: ReadmeConfig::default(),
+                file_cache: FileCacheConfig::default(),
+                file_guard: FileGuardConfig::default(),
+                control: ControlConfig::default(),
             }
         }
     };
@@ -939,6 +3227,10 @@ async fn main() -> Result<()> {
         config.github_token = token;
     }
 
+    // 設定ファイルと環境変数の秘密の値をログに出さない
+    redact::configure(&config.redaction)?;
+    redact::add_secrets(secret_values(&config));
+
     if let Some(output_dir) = args.output_dir {
         config.output_dir = output_dir;
     }
@@ -951,119 +3243,705 @@ async fn main() -> Result<()> {
         config.max_files = max_files;
     }
 
-    if let Some(max_file_size) = args.max_file_size {
-        config.max_file_size = max_file_size;
+    if let Some(max_file_bytes) = args.max_file_bytes {
+        config.max_file_bytes = max_file_bytes;
+    }
+    if let Some(truncate_chars) = args.truncate_chars {
+        config.truncate_chars = Some(truncate_chars);
+    }
+    if let Some(truncate_tokens) = args.truncate_tokens {
+        config.truncate_tokens = Some(truncate_tokens);
+    }
+
+    if args.embeddings {
+        config.embeddings.enabled = true;
+    }
+
+    if args.batch {
+        config.batch.enabled = true;
+    }
+
+    if args.force {
+        config.force = true;
+    }
+
+    if let Some(path) = &args.repos_file {
+        config.repos = repo_list::load(path, config.max_files).await?;
+        info!(
+            "📃 リポジトリ一覧を読み込みました: {} 件（{}）",
+            config.repos.len(),
+            path
+        );
+    }
+
+    // ドライランではすべてのエンドポイントをモックに差し替え、課金が発生する外部サービスは無効にする
+    // （エンドポイントの自動検出・クォータの取得・遅延の計測・為替レートの取得も、認証付きの通信になるため行わない）
+    if args.dry_run {
+        info!("🧪 ドライランモード: APIは呼び出さずダミーの応答を使用します");
+        for endpoint in &mut config.endpoints {
+            endpoint.provider = ProviderKind::Mock;
+        }
+        config.discovery.enabled = false;
+        config.quota.enabled = false;
+        if config.pricing.exchange_rate.is_none() {
+            config.pricing.report_currency = None;
+        }
+        config.ai_search.enabled = false;
+        config.tts.enabled = false;
+        config.translator.enabled = false;
+    }
+
+    // 管理APIからエンドポイントを自動検出
+    let discover_only = matches!(args.command, Some(Commands::Discover));
+    if config.discovery.enabled && !discover_only {
+        match arm::discover_endpoints(&config.discovery).await {
+            Ok(discovered) => {
+                info!("🔍 検出したエンドポイント数: {}", discovered.len());
+                arm::merge_endpoints(
+                    &mut config.endpoints,
+                    discovered,
+                    config.discovery.replace_endpoints,
+                );
+            }
+            Err(e) => error!("❌ エンドポイントの自動検出エラー: {}", e),
+        }
+    }
+
+    // デプロイメントの容量から重みとレート制限を設定
+    if config.quota.enabled && !discover_only {
+        let result =
+            quota::introspect(&mut config.endpoints, &config.discovery, &config.quota).await;
+        if let Err(e) = result {
+            error!("❌ クォータの取得エラー: {}", e);
+        }
+    }
+
+    // APIバージョンとパスの全体設定を各エンドポイントに適用
+    for endpoint in &mut config.endpoints {
+        config.api.apply(endpoint);
+    }
+
+    // エンドポイントの遅延を計測する（ドライランと再生では実際のエンドポイントに接続しないため計測しない）
+    if args.replay.is_none() && !args.dry_run {
+        routing::configure(&config.routing, &config.endpoints).await;
+    }
+
+    // HTTPレスポンスの記録・再生
+    if let Some(path) = &args.record {
+        cassette::start_recording(path)?;
+    }
+    if let Some(seed) = args.seed {
+        seed::init(seed);
+    }
+    if let Some(path) = &args.replay {
+        cassette::start_replay(path)?;
+    }
+
+    // 単一リポジトリの分析は、対象のリポジトリと議論タイプを置き換えて通常の分析として実行する
+    let mut debate_types = get_debate_types(&config);
+    let analyze = args
+        .command
+        .take_if(|command| matches!(command, Commands::Analyze { .. }));
+    if let Some(Commands::Analyze {
+        repo,
+        debate,
+        branch,
+        commit,
+    }) = &analyze
+    {
+        let (owner, repo) = repo_list::parse_name(repo)
+            .ok_or_else(|| anyhow!("リポジトリは owner/repo の形式で指定してください: {}", repo))?;
+        config.repos = vec![RepoInfo {
+            owner,
+            repo,
+            max_files: config.max_files,
+            branch: branch.clone(),
+            commit: commit.clone(),
+            ..Default::default()
+        }];
+        if !debate.is_empty() {
+            debate_types = debate
+                .iter()
+                .map(|name| resolve_debate_type(name))
+                .collect::<Result<_>>()?;
+        }
+        info!(
+            "🎯 単一リポジトリを分析します: {}/{}（{}）",
+            config.repos[0].owner,
+            config.repos[0].repo,
+            debate_types.join(", ")
+        );
+    }
+
+    // リポジトリごとの議論タイプの指定を確認
+    for repo_info in &config.repos {
+        for name in repo_info
+            .debate_types
+            .iter()
+            .chain(&repo_info.skip_debate_types)
+        {
+            resolve_debate_type(name)
+                .map_err(|e| anyhow!("{}/{}: {}", repo_info.owner, repo_info.repo, e))?;
+        }
+        if scheduler::debate_types_for(repo_info, &debate_types).is_empty() {
+            warn!(
+                "⚠️ {}/{}: 実行する議論タイプがないため分析しません",
+                repo_info.owner, repo_info.repo
+            );
+        }
+    }
+
+    pricing::configure(&config.pricing).await;
+    ptu::configure(&config.ptu, &config.endpoints);
+    categories::configure(&config.questions);
+    llm::prompts::configure(&config.system_prompts);
+    languages::configure(&config.languages);
+    stats::configure(&config.stats);
+    ci::configure(&config.ci);
+    readme::configure(&config.readme);
+    file_guard::configure(&config.file_guard);
+    generation::configure(
+        &config.generation,
+        &config.endpoints,
+        GenerationOverride {
+            model: args.model,
+            max_tokens: args.max_tokens,
+            temperature: args.temperature,
+            reasoning_effort: args.reasoning_effort,
+            fallback_models: (!args.fallback_models.is_empty()).then_some(args.fallback_models),
+        },
+    )?;
+    outputs::configure_layout(config.output_layout.as_deref())?;
+    dedupe::configure(&config.dedupe);
+    encryption::configure(&config.encryption, &config.discovery).await?;
+    // キャッシュは暗号化していることがあるため、鍵を読み込んでから開く
+    file_cache::configure(&config.file_cache, &config.output_dir);
+    // サブコマンドの要約やエクスポートでのAPI呼び出しも台帳に記録する
+    ledger::open(&config.output_dir, args.dry_run);
+    if let Some(max_usd) = args.max_usd {
+        ledger::set_ceiling(max_usd)?;
+    }
+    groups::configure(&config.endpoint_groups, &config.endpoints);
+
+    // サブコマンドの実行
+    if let Some(command) = args.command {
+        match command {
+            Commands::Discover => {
+                let mut discovered = arm::discover_endpoints(&config.discovery).await?;
+                arm::redact_keys(&mut discovered);
+                println!("{}", serde_json::to_string_pretty(&discovered)?);
+            }
+            Commands::Provision {
+                subscription,
+                regions,
+                resource_group,
+                prefix,
+                model,
+                model_version,
+                deployment,
+                sku,
+                capacity,
+            } => {
+                let subscription = subscription
+                    .or_else(|| config.discovery.subscription_ids.first().cloned())
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "リソースを作成するサブスクリプションを指定してください（--subscription）"
+                        )
+                    })?;
+                let options = ProvisionOptions {
+                    subscription,
+                    regions,
+                    resource_group: resource_group
+                        .unwrap_or_else(|| config.provision.resource_group.clone()),
+                    prefix: prefix.unwrap_or_else(|| config.provision.prefix.clone()),
+                    model,
+                    model_version,
+                    deployment,
+                    sku,
+                    capacity,
+                };
+                provision::run_provision(
+                    &args.config_file,
+                    &config.discovery,
+                    &options,
+                    args.dry_run,
+                )
+                .await?;
+            }
+            Commands::Deprovision {
+                subscriptions,
+                resource_group,
+                prefix,
+                purge,
+                keep_config,
+                yes,
+            } => {
+                let options = DeprovisionOptions {
+                    subscriptions: if subscriptions.is_empty() {
+                        provisioned_subscriptions(&config)
+                    } else {
+                        subscriptions
+                    },
+                    resource_group: resource_group
+                        .unwrap_or_else(|| config.provision.resource_group.clone()),
+                    prefix: prefix.unwrap_or_else(|| config.provision.prefix.clone()),
+                    purge,
+                    keep_config,
+                    yes,
+                };
+                provision::run_deprovision(
+                    &args.config_file,
+                    &config.discovery,
+                    &options,
+                    args.dry_run,
+                )
+                .await?;
+            }
+            Commands::SearchIndex => {
+                ai_search::export_outputs(&config.output_dir, &config.ai_search).await?;
+            }
+            Commands::Summarize => {
+                run_post_processing(&config, true).await;
+            }
+            Commands::Export {
+                format,
+                output,
+                min_score,
+                judge_model,
+                repo,
+                debate,
+            } => {
+                let options = ExportOptions {
+                    format,
+                    output,
+                    min_score,
+                    judge_model,
+                    repo,
+                    debate,
+                };
+                export::run_export(&config.output_dir, &config.endpoints, &options).await?;
+            }
+            Commands::Eval {
+                judge_model,
+                repo,
+                debate,
+                rescore,
+            } => {
+                let options = EvalOptions {
+                    judge_model,
+                    repo,
+                    debate,
+                    rescore,
+                };
+                eval::run_eval(
+                    &config.endpoints,
+                    &config.output_dir,
+                    &config.eval,
+                    &options,
+                    args.dry_run,
+                )
+                .await?;
+            }
+            Commands::Finetune {
+                training_file,
+                model,
+                endpoint,
+                suffix,
+                epochs,
+                no_wait,
+                job_id,
+            } => {
+                let target = match &endpoint {
+                    Some(name) => config.endpoints.iter().find(|e| &e.name == name),
+                    None => config.endpoints.first(),
+                }
+                .ok_or_else(|| {
+                    anyhow!("ファインチューニングに使用するエンドポイントが見つかりません")
+                })?;
+
+                let options = FinetuneOptions {
+                    training_file: training_file
+                        .unwrap_or_else(|| format!("{}/finetune.jsonl", config.output_dir)),
+                    model,
+                    suffix,
+                    epochs,
+                    wait: !no_wait,
+                    job_id,
+                };
+                let client = LlmClient::new(target.clone());
+                finetune::run_finetune(&client, &options).await?;
+            }
+            Commands::Serve { bind } => {
+                if let Some(bind) = bind {
+                    config.serve.bind = bind;
+                }
+                keys::watch_sighup(args.config_file.clone());
+                serve::run_server(config, args.dry_run, args.force_lock).await?;
+            }
+            Commands::Diff {
+                repo,
+                branch,
+                from,
+                to,
+                output,
+                no_summary,
+            } => {
+                let journal =
+                    Journal::open(&journal::journal_path(&config.output_dir, args.dry_run))?;
+                let options = DiffOptions {
+                    repo,
+                    branch,
+                    from,
+                    to,
+                    output,
+                    summarize: !no_summary,
+                };
+                diff::run_diff(&journal, &config.endpoints, &config.output_dir, &options).await?;
+            }
+            Commands::Report { format, output } => {
+                report::run_report(&config.output_dir, format, output.as_deref(), args.dry_run)?;
+            }
+            Commands::Questions { action } => {
+                questions::run(&config, action, args.dry_run).await?;
+            }
+            Commands::Search {
+                query,
+                repo,
+                debate,
+                regex,
+                case_sensitive,
+                include_system,
+                context,
+                limit,
+            } => {
+                let options = SearchOptions {
+                    query,
+                    repo,
+                    debate,
+                    regex,
+                    case_sensitive,
+                    include_system,
+                    context,
+                    limit,
+                };
+                search::run_search(&config.output_dir, &options)?;
+            }
+            Commands::Clean { outputs } => {
+                if !outputs {
+                    bail!("整理する対象を指定してください（--outputs）");
+                }
+                retention::clean_outputs(
+                    &config.output_dir,
+                    &config.retention,
+                    &config.file_cache,
+                    args.dry_run,
+                )?;
+            }
+            Commands::Control { command } => {
+                let response =
+                    control::send(&config.control, &config.output_dir, &command.join(" ")).await?;
+                println!("{}", response);
+            }
+            Commands::Analyze { .. } => unreachable!(),
+        }
+        return Ok(());
     }
 
     // ベースディレクトリ作成
     fs::create_dir_all(&config.output_dir).await?;
 
+    // 同じ出力ディレクトリに対する別のインスタンスとの同時実行を防ぐ
+    let _lock = lock::acquire(&config.output_dir, args.force_lock)?;
+
+    // SIGHUP で設定ファイルのAPIキーを読み込み直す
+    keys::watch_sighup(args.config_file.clone());
+
     // 設定情報をログに出力
     info!("🔧 設定情報:");
     info!("📂 出力ディレクトリ: {}", config.output_dir);
     info!("🔄 同時実行数: {}", config.concurrency);
     info!("📊 リポジトリ数: {}", config.repos.len());
     info!("📄 最大ファイル数: {}", config.max_files);
-    info!("📦 最大ファイルサイズ: {} バイト", config.max_file_size);
+    info!("📦 最大ファイルサイズ: {} バイト", config.max_file_bytes);
+    if let Some(truncate_at) = config.file_limits().truncate_at() {
+        info!("✂️ ファイルの内容を切り詰める文字数: {}", truncate_at);
+    }
+    if config.embeddings.enabled {
+        info!(
+            "🧮 埋め込みデプロイメント: {}",
+            config.embeddings.deployment
+        );
+    }
 
     // GitHubクライアント
-    let github_client = Arc::new(GitHubClient::new(
-        config.github_token,
-        config.output_dir.clone(),
-        config.max_file_size,
-    ));
+    let github_client = Arc::new(GitHubClient::new(&config)?);
 
     // Azureエンドポイント
-    let endpoints = Arc::new(config.endpoints);
-
-    // 議論タイプ
-    let debate_types = get_debate_types();
+    let endpoints = Arc::new(config.endpoints.clone());
 
     // 開始メッセージ
     info!("💰💻 Azure Credit Burner 起動中... 💰💻");
 
     // タスク作成
     let mut tasks = Vec::new();
-    let mut task_index = 0;
 
-    // 各リポジトリと議論タイプの組み合わせでタスクを作成
-    // Vec<(RepoInfo, String, usize)>のタプルにして後で処理
-    let mut task_configs = Vec::new();
+    // エンドポイントの重みに比例してタスクを割り当てるスケジューラー
+    let scheduler = Arc::new(Scheduler::new(&endpoints, &config.scheduler));
+
+    // リポジトリと議論タイプを交互に並べてタスクを作成
+    // エンドポイントは開始時に決める
+    let mut task_configs =
+        scheduler::plan_jobs(&config.repos, &debate_types, config.scheduler.perspectives);
 
-    for (i, repo_info) in config.repos.iter().enumerate() {
-        for (j, debate_type) in debate_types.iter().enumerate() {
-            // 同じリポジトリでも異なる視点で分析
-            let endpoint_index = task_index % endpoints.len();
+    // ジャーナルで完了済みの議論を除外
+    let journal_path = journal::journal_path(&config.output_dir, args.dry_run);
+    let journal = Arc::new(Journal::open(&journal_path)?);
+    let total_tasks = task_configs.len();
+    task_configs.retain(|job| !journal.is_done(&job.task_id));
+    if task_configs.len() < total_tasks {
+        info!(
+            "📒 完了済みの議論をスキップします: {} 件（ジャーナル: {}）",
+            total_tasks - task_configs.len(),
+            journal_path
+        );
+    }
+    // 再試行を待っていた議論は予定の日時と試行回数を引き継ぎ、失敗の記録を残したままにする
+    work_queue::restore_retries(&mut task_configs, &journal);
+    for job in task_configs.iter().filter(|job| job.retry_at.is_none()) {
+        journal.record(
+            &job.task_id,
+            &job.repo_info,
+            &job.debate_type,
+            TaskState::Queued,
+            None,
+            None,
+        );
+    }
 
-            // タスク設定を記録
-            task_configs.push((repo_info.clone(), debate_type.clone(), endpoint_index));
-            task_index += 1;
+    // 実行記録と manifest.json を残す（出力ファイルには実行IDを記録する）
+    let started_at = Utc::now();
+    let run_metadata = runs::RunMetadata {
+        run_id: runs::start(&started_at, args.dry_run),
+        started_at: started_at.to_rfc3339(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        seed: args.seed,
+        dry_run: args.dry_run,
+        batch: config.batch.enabled,
+        max_duration_secs: args.max_duration.map(|duration| duration.as_secs()),
+        max_usd: args.max_usd,
+        repos: config
+            .repos
+            .iter()
+            .map(|repo_info| format!("{}/{}", repo_info.owner, repo_info.repo))
+            .collect(),
+        tasks: task_configs.len(),
+    };
+    match runs::record(&config.output_dir, &run_metadata) {
+        Ok(path) => info!(
+            "🎲 シード: {}（実行記録: {}）",
+            args.seed
+                .map(|seed| seed.to_string())
+                .unwrap_or_else(|| "なし".to_string()),
+            path
+        ),
+        Err(e) => error!("❌ 実行記録の書き込みエラー: {}", e),
+    }
+    let mut config_snapshot = serde_json::to_value(&config)?;
+    runs::redact(&mut config_snapshot);
+    let mut manifest = runs::Manifest {
+        metadata: run_metadata,
+        finished_at: None,
+        analyzed_repos: Vec::new(),
+        models: Vec::new(),
+        config: config_snapshot,
+    };
+    if let Err(e) = runs::write_manifest(&config.output_dir, &manifest) {
+        error!("❌ manifest.json の書き込みエラー: {}", e);
+    }
 
-            // 追加でタスクを作成してクレジット消費を増やす
-            if i % 2 == 0 && j % 2 == 0 {
-                let extra_endpoint_index = (task_index + 2) % endpoints.len();
+    // 議論があるリポジトリを先にまとめてクローン
+    if config.clone.prefetch {
+        let mut prefetch_repos: Vec<RepoInfo> = Vec::new();
+        for job in &task_configs {
+            let clone_dir = github_client.clone_dir(&job.repo_info);
+            if !prefetch_repos
+                .iter()
+                .any(|repo_info| github_client.clone_dir(repo_info) == clone_dir)
+            {
+                prefetch_repos.push(job.repo_info.clone());
+            }
+        }
+        if !prefetch_repos.is_empty() {
+            github_client.prefetch(&prefetch_repos).await;
+        }
+    }
 
-                // 追加タスクも記録
-                task_configs.push((repo_info.clone(), debate_type.clone(), extra_endpoint_index));
-                task_index += 1;
+    // ファイル単位のレビューを追加（クローンしたリポジトリのファイル一覧から作成する）
+    if config.file_review.enabled {
+        if config.batch.enabled {
+            warn!("⚠️ Batch APIモードではファイル単位のレビューを実行しません");
+        } else {
+            let mut file_jobs =
+                file_review::plan_jobs(&github_client, &config.repos, &config.file_review).await;
+            file_jobs.retain(|job| !journal.is_done(&job.task_id));
+            work_queue::restore_retries(&mut file_jobs, &journal);
+            for job in file_jobs.iter().filter(|job| job.retry_at.is_none()) {
+                journal.record(
+                    &job.task_id,
+                    &job.repo_info,
+                    &job.debate_type,
+                    TaskState::Queued,
+                    None,
+                    None,
+                );
             }
+            info!(
+                "📄 ファイル単位のレビュー: {} 件（1ファイルあたり {} ターン）",
+                file_jobs.len(),
+                config.file_review.turns.max(1)
+            );
+            task_configs.extend(file_jobs);
+        }
+    }
+
+    // 埋め込み生成タスクを作成（チャットの分析と並行して実行）
+    if config.embeddings.enabled {
+        for (i, repo_info) in config.repos.iter().enumerate() {
+            let github_client_owned = github_client.clone();
+            let endpoints_owned = endpoints.clone();
+            let output_dir_owned = config.output_dir.clone();
+            let embeddings_config = config.embeddings.clone();
+            let repo_info = repo_info.clone();
+
+            tasks.push(tokio::spawn(async move {
+                embeddings::embedding_runner(
+                    github_client_owned,
+                    endpoints_owned,
+                    repo_info,
+                    i,
+                    output_dir_owned,
+                    embeddings_config,
+                )
+                .await
+            }));
+        }
+    }
+
+    // アーキテクチャ図の生成タスクを作成
+    if config.diagrams.enabled {
+        for (i, repo_info) in config.repos.iter().enumerate() {
+            let github_client_owned = github_client.clone();
+            let endpoints_owned = endpoints.clone();
+            let output_dir_owned = config.output_dir.clone();
+            let diagram_config = config.diagrams.clone();
+            let repo_info = repo_info.clone();
+
+            tasks.push(tokio::spawn(async move {
+                diagrams::diagram_runner(
+                    github_client_owned,
+                    endpoints_owned,
+                    repo_info,
+                    i,
+                    output_dir_owned,
+                    diagram_config,
+                )
+                .await
+            }));
         }
     }
 
+    // 中断した実行を再開しても予算を数え直さないよう使用量を保存する
+    budget::configure(
+        config.max_tokens_per_repo,
+        config.max_total_tokens,
+        Some(budget::state_path(&config.output_dir, args.dry_run)),
+    );
+    status::start(&config.status, &config.output_dir, args.dry_run);
+
     // 記録したタスク設定を元にタスクを作成
-    for (repo_info, debate_type, endpoint_index) in task_configs {
+    let shared_config = Arc::new(config.clone());
+    let mut watcher = None;
+    let mut control = None;
+    if config.batch.enabled {
+        // Batch APIモードではすべての議論を1つのタスクでまとめて進める
         let github_client_owned = github_client.clone();
         let endpoints_owned = endpoints.clone();
-        let output_dir_owned = config.output_dir.clone();
+        let config_owned = shared_config.clone();
+        let journal_owned = journal.clone();
+        let batch_configs = task_configs
+            .into_iter()
+            .map(|job| (job, scheduler.pick()))
+            .collect();
 
         tasks.push(tokio::spawn(async move {
-            debate_runner(
+            batch::run_batch_debates(
                 github_client_owned,
                 endpoints_owned,
-                repo_info,
-                debate_type,
-                endpoint_index,
-                output_dir_owned,
+                batch_configs,
+                config_owned,
+                journal_owned,
             )
             .await
         }));
+    } else {
+        // 共有キューに議論を積み、エンドポイントごとのワーカーが取り出して実行する
+        // ワーカー数は同時実行数を重みに比例して配分する
+        let queue = Arc::new(WorkQueue::new(task_configs));
+
+        // 同時実行数・エンドポイント・リポジトリを実行中に操作するコマンドを受け付ける
+        // （ワーカーが実行枠を取る前に同時実行数の上限を設定する）
+        control = control::start(
+            &config.control,
+            &config.output_dir,
+            config.concurrency,
+            &endpoints,
+        );
+        tasks.extend(work_queue::spawn_workers(
+            queue.clone(),
+            github_client.clone(),
+            endpoints.clone(),
+            shared_config.clone(),
+            scheduler.clone(),
+            journal.clone(),
+        ));
+
+        // 設定ファイルに追加されたリポジトリとエンドポイントを実行中に取り込む
+        if config.reload.enabled {
+            watcher = Some(
+                reload::watch(
+                    args.config_file.clone(),
+                    args.dry_run,
+                    args.repos_file.is_none() && analyze.is_none(),
+                    queue,
+                    github_client.clone(),
+                    endpoints.clone(),
+                    shared_config.clone(),
+                    scheduler.clone(),
+                    journal.clone(),
+                )
+                .await,
+            );
+        }
     }
 
-    // バッファリングして同時実行数を制限
-    let mut active_tasks = Vec::new();
-
-    for task in tasks {
-        active_tasks.push(task);
-
-        if active_tasks.len() >= config.concurrency {
-            let (completed, _index, remaining) = futures::future::select_all(active_tasks).await;
+    // すべてのタスクが完了するまで待機
+    let mut active_tasks = tasks;
 
-            // 結果を処理
-            match completed {
-                Ok(Ok(_)) => {
-                    info!("🎉 タスク完了");
-                }
-                Ok(Err(e)) => {
-                    error!("❌ タスクエラー: {}", e);
-                }
-                Err(e) => {
-                    error!("💥 タスク実行エラー: {}", e);
-                }
-            }
-
-            // 残りのタスクを更新
-            active_tasks = remaining;
+    loop {
+        // 途中から起動したワーカーも待つ
+        if let Some(watcher) = &watcher {
+            active_tasks.extend(watcher.take_workers(active_tasks.is_empty()));
+        }
+        if active_tasks.is_empty() {
+            break;
         }
-    }
 
-    // 残りのタスクを完了まで待機
-    while !active_tasks.is_empty() {
         let (completed, _index, remaining) = futures::future::select_all(active_tasks).await;
 
+        // 結果を処理
         match completed {
-            Ok(Ok(_)) => {
-                info!("🎉 タスク完了");
-            }
+            Ok(Ok(_)) => {}
             Ok(Err(e)) => {
                 error!("❌ タスクエラー: {}", e);
             }
@@ -1072,10 +3950,72 @@ async fn main() -> Result<()> {
             }
         }
 
+        // 残りのタスクを更新
         active_tasks = remaining;
     }
 
+    if let Some(control) = control {
+        control::stop(control, &config.control, &config.output_dir);
+    }
+
+    // 実行中に追加されたリポジトリも後処理と manifest.json の対象にする
+    if let Some(watcher) = watcher {
+        config.repos.extend(watcher.stop());
+    }
+
+    scheduler.log_summary();
+    routing::log_summary();
+    deadline::log_summary();
+    budget::flush();
+    budget::log_summary();
+    pricing::log_summary();
+    ledger::log_summary();
+    groups::log_summary();
+    ptu::log_summary();
+    dedupe::log_summary();
+    status::finish().await;
+    credits::log_summary(&endpoints);
     info!("✅ すべてのタスク完了！");
 
+    // 後処理
+    run_post_processing(&config, false).await;
+
+    // 分析したコミットと使用したモデルを manifest.json に記録
+    let mut analyzed_repos = Vec::new();
+    for repo_info in &config.repos {
+        analyzed_repos.push(runs::RepoManifest {
+            repo: format!("{}/{}", repo_info.owner, repo_info.repo),
+            branch: repo_info.branch.clone(),
+            requested_commit: repo_info.commit.clone(),
+            resolved_commit: github_client.head_commit(repo_info).await,
+        });
+    }
+    manifest.finished_at = Some(Utc::now().to_rfc3339());
+    manifest.analyzed_repos = analyzed_repos;
+    manifest.models = runs::models();
+    match runs::write_manifest(&config.output_dir, &manifest) {
+        Ok(path) => info!("🧾 manifest.json を保存しました: {}", path),
+        Err(e) => error!("❌ manifest.json の書き込みエラー: {}", e),
+    }
+
+    // 作成したリソースを削除し、クレジットがなくなってからの課金を防ぐ
+    if args.deprovision_after {
+        let options = DeprovisionOptions {
+            subscriptions: provisioned_subscriptions(&config),
+            resource_group: config.provision.resource_group.clone(),
+            prefix: config.provision.prefix.clone(),
+            purge: false,
+            keep_config: false,
+            // 起動時に --deprovision-after を指定したことを削除の確認とする
+            yes: true,
+        };
+        if let Err(e) =
+            provision::run_deprovision(&args.config_file, &config.discovery, &options, args.dry_run)
+                .await
+        {
+            error!("❌ 作成したリソースの削除エラー: {}", e);
+        }
+    }
+
     Ok(())
 }
@@ -0,0 +1,143 @@
+// メディアファイルの書き起こしモジュール
+// リポジトリ内の音声・動画ファイルをWhisperデプロイメントで書き起こし、議論のコンテキストに加える
+
+use anyhow::Result;
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use walkdir::WalkDir;
+
+use crate::llm::schemas::github_response::RepoInfo;
+use crate::{GitHubClient, LlmClient, budget, encryption, outputs};
+
+// 書き起こしの設定
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct TranscriptionConfig {
+    /// 書き起こしを有効にするか
+    pub enabled: bool,
+    /// Whisperモデルのデプロイメント名
+    pub deployment: String,
+    /// リポジトリあたりの最大ファイル数
+    pub max_files: usize,
+    /// 書き起こし対象とするファイルの最大サイズ（バイト、APIの上限は25MB）
+    pub max_file_bytes: u64,
+}
+
+impl Default for TranscriptionConfig {
+    fn default() -> Self {
+        TranscriptionConfig {
+            enabled: false,
+            deployment: "whisper".to_string(),
+            max_files: 5,
+            max_file_bytes: 25 * 1024 * 1024,
+        }
+    }
+}
+
+// Whisperが受け付ける音声・動画の拡張子
+const MEDIA_EXTENSIONS: [&str; 9] = [
+    "mp3", "mp4", "mpeg", "mpga", "m4a", "wav", "webm", "ogg", "flac",
+];
+
+// メディアファイルかどうかを判定
+fn is_media_file(path: &Path) -> bool {
+    path.extension()
+        .map(|ext| MEDIA_EXTENSIONS.contains(&ext.to_string_lossy().to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+// リポジトリ内のメディアファイルを列挙
+fn collect_media_files(repo_dir: &Path) -> Vec<PathBuf> {
+    WalkDir::new(repo_dir)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|entry| entry.file_name() != ".git")
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file() && is_media_file(entry.path()))
+        .map(|entry| entry.into_path())
+        .collect()
+}
+
+// リポジトリのメディアファイルを書き起こし、システムプロンプトに付加するブロックを返す
+// 書き起こし結果はリポジトリごとにキャッシュし、同じリポジトリの別の議論では再利用する
+pub async fn transcribe_repo_media(
+    client: &LlmClient,
+    github_client: &GitHubClient,
+    repo_info: &RepoInfo,
+    base_dir: &str,
+    config: &TranscriptionConfig,
+) -> Result<Option<String>> {
+    let repo_dir = github_client.clone_repository(repo_info).await?;
+    let cache_path = format!(
+        "{}/{}_{}/transcripts.json",
+        base_dir, repo_info.owner, repo_info.repo
+    );
+
+    // キャッシュ済みの書き起こしを読み込む
+    let mut transcripts: BTreeMap<String, String> = match encryption::read_to_string(&cache_path) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_default(),
+        Err(_) => BTreeMap::new(),
+    };
+
+    let media_files = collect_media_files(&repo_dir);
+    let mut updated = false;
+
+    for path in media_files.iter().take(config.max_files) {
+        let rel_path = outputs::slash_path(path.strip_prefix(&repo_dir).unwrap_or(path));
+
+        if transcripts.contains_key(&rel_path) {
+            continue;
+        }
+
+        let size = fs::metadata(path).await.map(|m| m.len()).unwrap_or(0);
+        if size > config.max_file_bytes {
+            info!(
+                "⏩ サイズが大きいためメディアファイルをスキップ: {} ({} bytes)",
+                rel_path, size
+            );
+            continue;
+        }
+
+        let audio = fs::read(path).await?;
+        let filename = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| rel_path.clone());
+
+        match client
+            .transcribe(&audio, &filename, &config.deployment)
+            .await
+        {
+            Ok((text, tokens_used)) => {
+                info!("🎙️ 書き起こし完了: {}", rel_path);
+                budget::record(repo_info, tokens_used);
+                transcripts.insert(rel_path, text);
+                updated = true;
+            }
+            Err(e) => {
+                error!("⚠️ 書き起こしエラー: {} - {}", rel_path, e);
+            }
+        }
+    }
+
+    if updated {
+        if let Some(parent) = Path::new(&cache_path).parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        encryption::write(&cache_path, serde_json::to_string_pretty(&transcripts)?).await?;
+    }
+
+    if transcripts.is_empty() {
+        return Ok(None);
+    }
+
+    let mut block = String::from("\n\n【メディアファイルの書き起こし】");
+    for (path, text) in &transcripts {
+        block.push_str(&format!("\n--- {} ---\n{}", path, text));
+    }
+
+    Ok(Some(block))
+}
@@ -0,0 +1,141 @@
+// 組織全体の分析レポートモジュール
+// すべてのリポジトリのスコアカードを最後の会話でモデルに渡し、リポジトリをまたいで共通する
+// セキュリティ上の課題や設計上の負債などを組織全体の所見としてまとめ、実行の締めくくりのレポートとして保存する
+
+use anyhow::{Result, bail};
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use crate::llm::schemas::openai_response::{ChatMessage, Endpoint};
+use crate::{LlmClient, encryption, generation, runs, scorecard};
+
+// 組織全体のレポートの設定
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct OrgReportConfig {
+    /// スコアカードの生成後に組織全体のレポートを作成するか
+    pub enabled: bool,
+    /// レポートの作成に使うデプロイメント名（省略時は議論と同じモデル）
+    pub deployment: Option<String>,
+    /// レポートの最大出力トークン数
+    pub max_tokens: usize,
+    /// レポートの作成に渡すスコアカードの最大文字数
+    pub max_input_chars: usize,
+}
+
+impl Default for OrgReportConfig {
+    fn default() -> Self {
+        OrgReportConfig {
+            enabled: false,
+            deployment: None,
+            max_tokens: 6000,
+            max_input_chars: 100000,
+        }
+    }
+}
+
+// 最新のレポートの保存先（出力ディレクトリ直下）
+const REPORT_FILE: &str = "org_report.md";
+
+// レポート作成の指示
+const ORG_REPORT_PROMPT: &str = "あなたは組織全体のソフトウェア資産を評価するプリンシパルエンジニアです。\
+以下は組織内の複数のリポジトリについて、AIによる分析結果から作成したスコアカードです。\
+個々のリポジトリの要約を繰り返すのではなく、リポジトリをまたいで共通するパターンに注目し、\
+組織全体の所見をMarkdownでまとめてください。\
+共通するセキュリティ上の課題、共有している設計上の負債、テストやドキュメントの傾向、\
+評価の高いリポジトリから他へ展開できる良い実践、組織として優先すべき改善の取り組み（影響するリポジトリを明記）を含めてください。\
+スコアカードに含まれない内容を作らないでください。";
+
+// スコアカードから組織全体のレポートを作成する
+async fn generate(
+    client: &LlmClient,
+    config: &OrgReportConfig,
+    scorecards: &[scorecard::Scorecard],
+) -> Result<(String, usize)> {
+    let mut input = scorecard::render_ranking(scorecards);
+    for scorecard in scorecards {
+        input.push_str("\n\n");
+        input.push_str(&scorecard::render(scorecard));
+    }
+    let total_chars = input.chars().count();
+    if total_chars > config.max_input_chars {
+        warn!(
+            "⚠️ スコアカードが長いため、先頭の {} 文字だけを渡します（{} 文字）",
+            config.max_input_chars, total_chars
+        );
+        input = input.chars().take(config.max_input_chars).collect();
+    }
+
+    let messages = vec![
+        ChatMessage {
+            role: "system".to_string(),
+            content: ORG_REPORT_PROMPT.to_string(),
+            ..Default::default()
+        },
+        ChatMessage {
+            role: "user".to_string(),
+            content: format!("リポジトリ数: {}\n\n{}", scorecards.len(), input),
+            ..Default::default()
+        },
+    ];
+    let model = config
+        .deployment
+        .as_deref()
+        .unwrap_or(generation::default_model());
+    client
+        .chat_completion(&messages, model, config.max_tokens, 0.3)
+        .await
+}
+
+// 組織全体のレポートを作成して保存する（実行中なら実行の記録にも残す）
+pub async fn generate_org_report(
+    endpoints: &[Endpoint],
+    base_dir: &str,
+    config: &OrgReportConfig,
+) -> Result<String> {
+    let Some(endpoint) = endpoints.first() else {
+        bail!("組織全体のレポートの作成に使用できるエンドポイントがありません");
+    };
+    let scorecards = scorecard::load_scorecards(base_dir);
+    if scorecards.is_empty() {
+        bail!(
+            "スコアカードが見つかりません（scorecard.enabled を有効にしてください）: {}",
+            base_dir
+        );
+    }
+    if scorecards.len() == 1 {
+        warn!("⚠️ スコアカードが1件だけのため、リポジトリをまたいだ比較はできません");
+    }
+
+    let client = LlmClient::new(endpoint.clone());
+    let (report, tokens_used) = generate(&client, config, &scorecards).await?;
+    let report = format!(
+        "# 組織全体の分析レポート\n\n対象: {} リポジトリ\n\n{}\n",
+        scorecards.len(),
+        report.trim()
+    );
+
+    let path = format!("{}/{}", base_dir, REPORT_FILE);
+    encryption::write(&path, report.as_str()).await?;
+    if let Some(run_id) = runs::current_id() {
+        let run_dir = format!("{}/runs/{}", base_dir, run_id);
+        fs::create_dir_all(&run_dir).await?;
+        encryption::write(format!("{}/{}", run_dir, REPORT_FILE), report.as_str()).await?;
+    }
+
+    info!(
+        "🏛️ 組織全体のレポートを保存しました: {} ({} リポジトリ, トークン数: {})",
+        path,
+        scorecards.len(),
+        tokens_used
+    );
+    Ok(path)
+}
+
+// 後処理から呼び出す（失敗しても他の後処理には影響させない）
+pub async fn run(endpoints: &[Endpoint], base_dir: &str, config: &OrgReportConfig) {
+    if let Err(e) = generate_org_report(endpoints, base_dir, config).await {
+        error!("❌ 組織全体のレポートの作成エラー: {}", e);
+    }
+}
@@ -0,0 +1,495 @@
+// 保存済みの分析結果を読み込むモジュール
+// 古い形式で保存された結果は読み込み時に現在の形式へ移行する
+// ターンのファイルの配置（output_layout）もここで決める
+
+use anyhow::{Result, anyhow, bail};
+use chrono::prelude::*;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{MAIN_SEPARATOR, Path, PathBuf};
+use std::sync::OnceLock;
+use walkdir::WalkDir;
+
+use crate::llm::schemas::github_response::RepoInfo;
+use crate::llm::schemas::openai_response::{RESPONSE_SCHEMA_VERSION, ResponseData};
+use crate::{QUESTION_CATEGORIES, encryption, runs};
+
+// ターンのファイルの既定の配置（出力ディレクトリからの相対パス）
+pub const DEFAULT_LAYOUT: &str =
+    "{owner}_{repo}/{debate_type}_{endpoint}_{turn}_turn{timestamp}.json";
+
+// 配置に使える変数
+const LAYOUT_VARIABLES: [&str; 9] = [
+    "owner",
+    "repo",
+    "debate_type",
+    "endpoint",
+    "turn",
+    "n",
+    "date",
+    "timestamp",
+    "run_id",
+];
+
+// ターンの索引のファイル名
+const TURN_INDEX_FILE: &str = "turns.jsonl";
+
+// Windows のファイル名に使えない文字
+const RESERVED_CHARS: [char; 9] = ['<', '>', ':', '"', '/', '\\', '|', '?', '*'];
+
+// 区切りと紛らわしい文字（全角のスラッシュなど）
+const SLASH_LIKE_CHARS: [char; 5] = ['／', '＼', '∕', '⁄', '⧸'];
+
+// Windows で予約されたデバイス名（拡張子を付けても使えない）
+const RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+static LAYOUT: OnceLock<String> = OnceLock::new();
+
+// ターンのファイルの配置を設定する（出力ディレクトリの外を指す配置や未知の変数はエラー）
+pub fn configure_layout(layout: Option<&str>) -> Result<()> {
+    let Some(layout) = layout else {
+        return Ok(());
+    };
+    if !layout.ends_with(".json") {
+        bail!(
+            "output_layout は .json で終わるようにしてください: {}",
+            layout
+        );
+    }
+    if layout.contains('\\') {
+        bail!("output_layout の区切りには / を使ってください: {}", layout);
+    }
+    if layout.starts_with('/')
+        || Path::new(layout).is_absolute()
+        || layout.split('/').any(|segment| segment == "..")
+    {
+        bail!(
+            "output_layout には出力ディレクトリからの相対パスを指定してください: {}",
+            layout
+        );
+    }
+    for variable in layout
+        .split('{')
+        .skip(1)
+        .filter_map(|rest| rest.split_once('}'))
+        .map(|(variable, _)| variable)
+    {
+        if !LAYOUT_VARIABLES.contains(&variable) {
+            bail!(
+                "output_layout の変数 {{{}}} は使えません（使える変数: {}）",
+                variable,
+                LAYOUT_VARIABLES.join(", ")
+            );
+        }
+    }
+    if !["{turn}", "{n}", "{timestamp}"]
+        .iter()
+        .any(|variable| layout.contains(variable))
+    {
+        warn!(
+            "⚠️ output_layout に {{turn}} も {{timestamp}} も含まれていないため、ターンのファイルが上書きされます: {}",
+            layout
+        );
+    }
+    info!("🗂️ ターンのファイルの配置: {}", layout);
+    let _ = LAYOUT.set(layout.to_string());
+    Ok(())
+}
+
+fn layout() -> &'static str {
+    LAYOUT.get().map(String::as_str).unwrap_or(DEFAULT_LAYOUT)
+}
+
+// パスの区切りや空白を含む値を1つのファイル名・ディレクトリ名にする
+// Linux 以外でも同じ名前で保存できるよう、Windows で使えない文字・末尾のピリオド・デバイス名も置き換える
+pub fn path_segment(value: &str) -> String {
+    let mut segment: String = value
+        .chars()
+        .map(|c| {
+            if RESERVED_CHARS.contains(&c)
+                || SLASH_LIKE_CHARS.contains(&c)
+                || c.is_whitespace()
+                || c.is_control()
+            {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect();
+
+    // 末尾のピリオドは Windows で取り除かれるため置き換える（"." と ".." もここで別の名前になる）
+    if segment.ends_with('.') {
+        segment.pop();
+        segment.push('_');
+    }
+    if segment.is_empty() {
+        return "_".to_string();
+    }
+    let stem = segment.split('.').next().unwrap_or_default();
+    if RESERVED_NAMES
+        .iter()
+        .any(|name| name.eq_ignore_ascii_case(stem))
+    {
+        segment.insert(0, '_');
+    }
+    segment
+}
+
+// パスを / 区切りの文字列にする（ログや保存するファイルのパスを OS によらず同じ形にする）
+pub fn slash_path(path: &Path) -> String {
+    let path = path.to_string_lossy();
+    match MAIN_SEPARATOR {
+        '/' => path.to_string(),
+        separator => path.replace(separator, "/"),
+    }
+}
+
+// ターンのファイルのパス
+pub fn turn_path(base_dir: &str, repo_info: &RepoInfo, response_data: &ResponseData) -> PathBuf {
+    let timestamp = DateTime::parse_from_rfc3339(&response_data.timestamp)
+        .map(|t| t.with_timezone(&Utc))
+        .unwrap_or_else(|_| Utc::now());
+    let turn = response_data.turn.to_string();
+    let values = [
+        ("owner", path_segment(&repo_info.owner)),
+        ("repo", path_segment(&repo_info.repo)),
+        ("debate_type", path_segment(&response_data.debate_type)),
+        ("endpoint", path_segment(&response_data.endpoint)),
+        ("turn", turn.clone()),
+        ("n", turn),
+        ("date", timestamp.format("%Y-%m-%d").to_string()),
+        ("timestamp", timestamp.format("%Y%m%d_%H%M%S").to_string()),
+        (
+            "run_id",
+            path_segment(response_data.run_id.as_deref().unwrap_or("no-run")),
+        ),
+    ];
+    let mut path = layout().to_string();
+    for (name, value) in values {
+        path = path.replace(&format!("{{{}}}", name), &value);
+    }
+    let mut file = PathBuf::from(base_dir);
+    file.extend(path.split('/').filter(|segment| !segment.is_empty()));
+    file
+}
+
+// リポジトリのターンのファイルを探すディレクトリ
+// （配置のうちリポジトリだけで決まる部分と、サマリーなどを置くリポジトリの出力ディレクトリ）
+pub fn repo_dirs(base_dir: &str, owner: &str, repo: &str) -> Vec<String> {
+    let mut turn_dir = base_dir.to_string();
+    let layout = layout();
+    let directories = layout.rsplit_once('/').map_or("", |(dir, _)| dir);
+    for segment in directories.split('/').filter(|segment| !segment.is_empty()) {
+        let rest = segment
+            .replace("{owner}", &path_segment(owner))
+            .replace("{repo}", &path_segment(repo));
+        if rest.contains('{') {
+            break;
+        }
+        turn_dir = format!("{}/{}", turn_dir, rest);
+    }
+
+    let repo_dir = format!(
+        "{}/{}_{}",
+        base_dir,
+        path_segment(owner),
+        path_segment(repo)
+    );
+    if turn_dir == repo_dir {
+        vec![repo_dir]
+    } else {
+        vec![repo_dir, turn_dir]
+    }
+}
+
+// ResponseData として保存されたJSONか（埋め込みインデックスなどと区別する）
+fn is_response_data(value: &Value) -> bool {
+    value.get("debate_type").is_some() && value.get("messages").is_some()
+}
+
+// バージョン1 → 2: category を既定の出題順から補う
+// 初期の形式は質問を固定の順番で出題しており、1・2ターン目は概要、3ターン目以降はカテゴリを順に回る
+fn migrate_v1(value: &mut Value) {
+    if value.get("category").is_none_or(Value::is_null) {
+        let category = match value["turn"].as_u64() {
+            Some(turn) if turn >= 3 => {
+                QUESTION_CATEGORIES[(turn as usize - 3) % QUESTION_CATEGORIES.len()]
+            }
+            _ => "概要",
+        };
+        value["category"] = json!(category);
+    }
+}
+
+// 保存された結果を読み込み、古い形式なら現在の形式へ移行する
+pub fn parse_response(content: &str) -> Result<ResponseData> {
+    let mut value: Value = serde_json::from_str(content)?;
+    let version = value
+        .get("schema_version")
+        .and_then(Value::as_u64)
+        .unwrap_or(1) as u32;
+    if version > RESPONSE_SCHEMA_VERSION {
+        return Err(anyhow!(
+            "新しい形式（バージョン {}）で保存された結果です。このバージョンはバージョン {} まで読み込めます",
+            version,
+            RESPONSE_SCHEMA_VERSION
+        ));
+    }
+
+    if version < 2 {
+        migrate_v1(&mut value);
+    }
+    value["schema_version"] = json!(RESPONSE_SCHEMA_VERSION);
+    Ok(serde_json::from_value(value)?)
+}
+
+// 出力ディレクトリ以下の分析結果（ResponseData）をすべて読み込む
+pub fn load_responses(base_dir: &str) -> Vec<(PathBuf, ResponseData)> {
+    let mut responses = Vec::new();
+
+    let walker = WalkDir::new(base_dir)
+        .sort_by_file_name()
+        .into_iter()
+        // クローンしたリポジトリは対象外
+        .filter_entry(|entry| !(entry.depth() == 1 && entry.file_name() == "repos"));
+
+    for entry in walker.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        if !path.is_file() || path.extension().is_none_or(|ext| ext != "json") {
+            continue;
+        }
+
+        let content = match encryption::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) => {
+                warn!("⚠️ ファイル読み込みエラー: {}", e);
+                continue;
+            }
+        };
+
+        // ResponseData以外のJSON（埋め込みインデックスなど）は読み飛ばす
+        let is_response =
+            serde_json::from_str::<Value>(&content).is_ok_and(|value| is_response_data(&value));
+        if !is_response {
+            continue;
+        }
+        match parse_response(&content) {
+            Ok(response) => responses.push((path.to_path_buf(), response)),
+            Err(e) => warn!("⚠️ 分析結果を読み込めません: {} - {}", path.display(), e),
+        }
+    }
+
+    responses
+}
+
+// 議論を区別するキー（実行ごと、タスクIDがなければリポジトリ・議論タイプ・エンドポイント・ファイルごと）
+pub fn debate_key(response: &ResponseData) -> String {
+    let debate = response.task_id.clone().unwrap_or_else(|| {
+        format!(
+            "{}|{}|{}|{}",
+            response.repo,
+            response.debate_type,
+            response.endpoint,
+            response.file.as_deref().unwrap_or_default()
+        )
+    });
+    format!(
+        "{}|{}",
+        response.run_id.as_deref().unwrap_or_default(),
+        debate
+    )
+}
+
+// (リポジトリ, 議論タイプ, エンドポイント, ファイル) ごとに最も進んだターンの結果だけを残す
+// 各ターンのファイルはそれまでの会話履歴をすべて含むため、最終ターンだけで議論全体を表せる
+pub fn latest_per_debate(responses: Vec<(PathBuf, ResponseData)>) -> Vec<(PathBuf, ResponseData)> {
+    let mut latest: BTreeMap<_, (PathBuf, ResponseData)> = BTreeMap::new();
+
+    for (path, response) in responses {
+        let key = (
+            response.repo.clone(),
+            response.debate_type.clone(),
+            response.endpoint.clone(),
+            response.file.clone(),
+        );
+        let is_newer = latest
+            .get(&key)
+            .is_none_or(|(_, current)| response.turn > current.turn);
+        if is_newer {
+            latest.insert(key, (path, response));
+        }
+    }
+
+    latest.into_values().collect()
+}
+
+// ターンの索引のファイル（リポジトリの出力ディレクトリの turns.jsonl）
+// 保存したターンごとにタスクID・ターン・ファイルを1行ずつ追記し、途中経過の復元で全ファイルを復号せずに済むようにする
+fn turn_index_path(base_dir: &str, owner: &str, repo: &str) -> PathBuf {
+    Path::new(&format!(
+        "{}/{}_{}",
+        base_dir,
+        path_segment(owner),
+        path_segment(repo)
+    ))
+    .join(TURN_INDEX_FILE)
+}
+
+// ターンの索引の1行
+#[derive(Serialize, Deserialize)]
+struct TurnIndexEntry {
+    task_id: String,
+    turn: usize,
+    file: String,
+}
+
+// 保存したターンを索引に追記する（repo は owner/repo）
+pub fn index_turn(base_dir: &str, repo: &str, task_id: &str, turn: usize, file: &str) {
+    let Some((owner, repo)) = repo.split_once('/') else {
+        return;
+    };
+    let path = turn_index_path(base_dir, owner, repo);
+    let entry = TurnIndexEntry {
+        task_id: task_id.to_string(),
+        turn,
+        file: file.to_string(),
+    };
+    let result = serde_json::to_string(&entry)
+        .map_err(anyhow::Error::from)
+        .and_then(|line| {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            // 1行を1回の書き込みで追記する（並行して保存するワーカーの行が混ざらないようにする）
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)?
+                .write_all(format!("{}\n", line).as_bytes())?;
+            Ok(())
+        });
+    if let Err(e) = result {
+        warn!(
+            "⚠️ ターンの索引に追記できません: {} - {}",
+            path.display(),
+            e
+        );
+    }
+}
+
+// タスクIDを記録した保存結果のうち、最も進んだターンを探す（ジャーナルに途中経過が残っていない場合の復元用）
+// 索引があれば索引の新しいターンから順に開き、なければ（索引を作る前の出力）ターンのファイルを探す
+// ドライランの結果と本番の結果は混ぜない
+pub fn find_latest_turn(
+    base_dir: &str,
+    owner: &str,
+    repo: &str,
+    task_id: &str,
+    dry_run: bool,
+) -> Option<(PathBuf, ResponseData)> {
+    let index = turn_index_path(base_dir, owner, repo);
+    let Ok(content) = fs::read_to_string(&index) else {
+        return repo_dirs(base_dir, owner, repo)
+            .iter()
+            .filter_map(|dir| scan_latest_turn(dir, task_id, dry_run))
+            .max_by_key(|(_, saved)| saved.turn);
+    };
+
+    let mut entries: Vec<TurnIndexEntry> = content
+        .lines()
+        .filter_map(|line| serde_json::from_str::<TurnIndexEntry>(line).ok())
+        .filter(|entry| entry.task_id == task_id)
+        .collect();
+    entries.sort_by_key(|entry| std::cmp::Reverse(entry.turn));
+    // 保持期間で削除されたファイルや別の実行（ドライラン）のファイルは飛ばす
+    entries.into_iter().find_map(|entry| {
+        let path = PathBuf::from(&entry.file);
+        let response = parse_response(&encryption::read_to_string(&path).ok()?).ok()?;
+        (response.task_id.as_deref() == Some(task_id)
+            && runs::is_dry_run_id(response.run_id.as_deref()) == dry_run)
+            .then_some((path, response))
+    })
+}
+
+// ディレクトリ以下のターンのファイルを復号して、タスクの最も進んだターンを探す
+fn scan_latest_turn(dir: &str, task_id: &str, dry_run: bool) -> Option<(PathBuf, ResponseData)> {
+    let mut latest: Option<(PathBuf, ResponseData)> = None;
+    for entry in WalkDir::new(dir).into_iter().filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        if !path.is_file() || path.extension().is_none_or(|ext| ext != "json") {
+            continue;
+        }
+        // タスクIDを含まないファイルは解析しない
+        let Ok(content) = encryption::read_to_string(path) else {
+            continue;
+        };
+        if !content.contains(task_id) {
+            continue;
+        }
+        let Ok(response) = parse_response(&content) else {
+            continue;
+        };
+        if response.task_id.as_deref() != Some(task_id)
+            || runs::is_dry_run_id(response.run_id.as_deref()) != dry_run
+        {
+            continue;
+        }
+        if latest
+            .as_ref()
+            .is_none_or(|(_, current)| response.turn > current.turn)
+        {
+            latest = Some((path.to_path_buf(), response));
+        }
+    }
+    latest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn path_segment_replaces_drive_letters_and_separators() {
+        assert_eq!(path_segment("C:"), "C_");
+        assert_eq!(path_segment(r"C:\Windows\System32"), "C__Windows_System32");
+        assert_eq!(path_segment(r"..\..\secret"), ".._.._secret");
+        assert_eq!(path_segment("src/main.rs"), "src_main.rs");
+        assert_eq!(path_segment("west us"), "west_us");
+    }
+
+    #[test]
+    fn path_segment_avoids_reserved_names() {
+        assert_eq!(path_segment("CON"), "_CON");
+        assert_eq!(path_segment("nul.txt"), "_nul.txt");
+        assert_eq!(path_segment("com1.tar.gz"), "_com1.tar.gz");
+        assert_eq!(path_segment("console"), "console");
+    }
+
+    #[test]
+    fn path_segment_replaces_trailing_dots() {
+        assert_eq!(path_segment("readme."), "readme_");
+        assert_eq!(path_segment("."), "_");
+        assert_eq!(path_segment(".."), "._");
+        assert_eq!(path_segment(""), "_");
+    }
+
+    #[test]
+    fn repo_dirs_stay_inside_the_output_directory() {
+        assert_eq!(
+            repo_dirs("out", "C:", "con"),
+            vec!["out/C___con".to_string()]
+        );
+        assert_eq!(
+            repo_dirs("out", "..", r"..\x"),
+            vec!["out/.__.._x".to_string()]
+        );
+    }
+}
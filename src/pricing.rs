@@ -0,0 +1,274 @@
+// 料金表モジュール
+// モデルごとの1000トークンあたりの単価から、API呼び出しごとの推定コストを求める
+// 保存する各ターンの結果とレポートにコストを記録し、実行全体の推定コストを集計する
+// クレジットの付与額や社内の報告が USD でない場合に備えて、レポートとログは為替レートで換算して表示できる
+
+use anyhow::{Result, anyhow};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::sync::{Mutex, OnceLock};
+
+// モデルの単価
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ModelPrice {
+    /// 入力1000トークンあたりの単価
+    pub prompt: f64,
+    /// 出力1000トークンあたりの単価
+    pub completion: f64,
+}
+
+// 料金表の設定
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct PricingConfig {
+    /// 単価の通貨
+    pub currency: String,
+    /// モデルごとの単価（デプロイメント名と一致しない場合は、デプロイメント名の先頭に一致する最も長いモデル名を使う）
+    pub models: BTreeMap<String, ModelPrice>,
+    /// レポートとログに表示する通貨（省略時は currency のまま表示する）
+    pub report_currency: Option<String>,
+    /// currency 1単位あたりの report_currency の額（省略時は exchange_rate_url から取得する）
+    pub exchange_rate: Option<f64>,
+    /// 為替レートを取得するURL（{from} を currency に置き換え、応答の rates.<report_currency> を使う）
+    pub exchange_rate_url: String,
+}
+
+impl Default for PricingConfig {
+    // 主なモデルの公開価格（USD）
+    fn default() -> Self {
+        let models = [
+            ("gpt-4.5-preview", 0.075, 0.15),
+            ("gpt-4.1", 0.002, 0.008),
+            ("gpt-4.1-mini", 0.0004, 0.0016),
+            ("gpt-4o", 0.0025, 0.01),
+            ("gpt-4o-mini", 0.00015, 0.0006),
+            ("o1", 0.015, 0.06),
+            ("o1-mini", 0.0011, 0.0044),
+            ("o3-mini", 0.0011, 0.0044),
+            ("text-embedding-3-large", 0.00013, 0.0),
+            ("text-embedding-3-small", 0.00002, 0.0),
+        ]
+        .into_iter()
+        .map(|(name, prompt, completion)| (name.to_string(), ModelPrice { prompt, completion }))
+        .collect();
+        PricingConfig {
+            currency: "USD".to_string(),
+            models,
+            report_currency: None,
+            exchange_rate: None,
+            exchange_rate_url: "https://open.er-api.com/v6/latest/{from}".to_string(),
+        }
+    }
+}
+
+impl PricingConfig {
+    // デプロイメント名に対応する単価
+    fn price(&self, model: &str) -> Option<&ModelPrice> {
+        self.models.get(model).or_else(|| {
+            self.models
+                .iter()
+                .filter(|(name, _)| model.starts_with(name.as_str()))
+                .max_by_key(|(name, _)| name.len())
+                .map(|(_, price)| price)
+        })
+    }
+
+    // トークン数から推定コストを求める（単価が分からないモデルは None）
+    fn cost(&self, model: &str, prompt_tokens: usize, completion_tokens: usize) -> Option<f64> {
+        self.price(model).map(|price| {
+            (prompt_tokens as f64 * price.prompt + completion_tokens as f64 * price.completion)
+                / 1000.0
+        })
+    }
+}
+
+static PRICING: OnceLock<PricingConfig> = OnceLock::new();
+
+// 表示用の通貨への換算
+struct Conversion {
+    currency: String,
+    rate: f64,
+}
+
+static CONVERSION: OnceLock<Conversion> = OnceLock::new();
+
+// 実行中に記録した推定コストの合計と、単価が分からなかったモデルの呼び出し回数
+struct Spend {
+    calls: usize,
+    total: f64,
+    unpriced: BTreeMap<String, usize>,
+}
+
+static SPEND: Mutex<Spend> = Mutex::new(Spend {
+    calls: 0,
+    total: 0.0,
+    unpriced: BTreeMap::new(),
+});
+
+// 料金表を設定し、表示用の通貨が指定されていれば為替レートを決める
+pub async fn configure(config: &PricingConfig) {
+    let _ = PRICING.set(config.clone());
+
+    let Some(report_currency) = &config.report_currency else {
+        return;
+    };
+    if report_currency.eq_ignore_ascii_case(&config.currency) {
+        return;
+    }
+    let rate = match config.exchange_rate {
+        Some(rate) => Ok(rate),
+        None => fetch_exchange_rate(config, report_currency).await,
+    };
+    match rate {
+        Ok(rate) => {
+            info!(
+                "💱 為替レート: 1 {} = {} {}",
+                config.currency, rate, report_currency
+            );
+            let _ = CONVERSION.set(Conversion {
+                currency: report_currency.clone(),
+                rate,
+            });
+        }
+        Err(e) => warn!(
+            "⚠️ 為替レートを取得できないため、コストを {} のまま表示します: {}",
+            config.currency, e
+        ),
+    }
+}
+
+// 為替レートを取得
+async fn fetch_exchange_rate(config: &PricingConfig, report_currency: &str) -> Result<f64> {
+    let url = config
+        .exchange_rate_url
+        .replace("{from}", &config.currency.to_uppercase());
+    let response = reqwest::Client::new()
+        .get(&url)
+        .timeout(std::time::Duration::from_secs(30))
+        .send()
+        .await
+        .map_err(|e| anyhow!("為替レートの取得に失敗しました: {} - {}", url, e))?;
+    if !response.status().is_success() {
+        return Err(anyhow!(
+            "為替レートの取得に失敗しました: {} - ステータス {}",
+            url,
+            response.status()
+        ));
+    }
+    let body: Value = response.json().await?;
+    body["rates"][report_currency.to_uppercase()]
+        .as_f64()
+        .ok_or_else(|| {
+            anyhow!(
+                "為替レートの応答に {} のレートがありません",
+                report_currency
+            )
+        })
+}
+
+fn config() -> &'static PricingConfig {
+    PRICING.get_or_init(PricingConfig::default)
+}
+
+// 料金表の通貨
+pub fn currency() -> &'static str {
+    &config().currency
+}
+
+// API呼び出しの推定コストを求めて実行全体の合計に加える
+pub fn record(model: &str, prompt_tokens: usize, completion_tokens: usize) -> Option<f64> {
+    let cost = config().cost(model, prompt_tokens, completion_tokens);
+    let mut spend = SPEND.lock().unwrap();
+    spend.calls += 1;
+    match cost {
+        Some(cost) => spend.total += cost,
+        None => *spend.unpriced.entry(model.to_string()).or_default() += 1,
+    }
+    cost
+}
+
+// 表示用の通貨
+pub fn display_currency() -> &'static str {
+    match CONVERSION.get() {
+        Some(conversion) => &conversion.currency,
+        None => currency(),
+    }
+}
+
+// 記録したコストを表示用の通貨に換算する（料金表と異なる通貨で記録したコストはそのまま）
+pub fn convert(amount: f64, currency: &str) -> (f64, String) {
+    match CONVERSION.get() {
+        Some(conversion) if currency == config().currency => {
+            (amount * conversion.rate, conversion.currency.clone())
+        }
+        _ => (amount, currency.to_string()),
+    }
+}
+
+// 金額を通貨記号と桁区切りを付けて整形する（例: $1,204.55, ¥12,345, 1,204.55 CHF）
+pub fn format_amount(amount: f64, currency: &str) -> String {
+    let code = currency.to_uppercase();
+    let (symbol, decimals) = match code.as_str() {
+        "USD" => (Some("$"), 2),
+        "EUR" => (Some("€"), 2),
+        "GBP" => (Some("£"), 2),
+        "JPY" => (Some("¥"), 0),
+        _ => (None, 2),
+    };
+    let fixed = format!("{:.*}", decimals, amount.abs());
+    let (integer, fraction) = match fixed.split_once('.') {
+        Some((integer, fraction)) => (integer, Some(fraction)),
+        None => (fixed.as_str(), None),
+    };
+    let mut grouped = String::new();
+    for (i, digit) in integer.chars().enumerate() {
+        if i > 0 && (integer.len() - i) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    if let Some(fraction) = fraction {
+        grouped = format!("{}.{}", grouped, fraction);
+    }
+    let sign = if amount < 0.0 { "-" } else { "" };
+    match symbol {
+        Some(symbol) => format!("{}{}{}", sign, symbol, grouped),
+        None => format!("{}{} {}", sign, grouped, code),
+    }
+}
+
+// 料金表に単価があるモデルか
+pub fn has_price(model: &str) -> bool {
+    config().price(model).is_some()
+}
+
+// 料金表からトークン数の推定コストを求める（実行全体の合計には加えない）
+pub fn estimate(model: &str, prompt_tokens: usize, completion_tokens: usize) -> Option<f64> {
+    config().cost(model, prompt_tokens, completion_tokens)
+}
+
+// 実行全体の推定コストをログに出力
+pub fn log_summary() {
+    let spend = SPEND.lock().unwrap();
+    if spend.calls == 0 {
+        return;
+    }
+    match CONVERSION.get() {
+        Some(conversion) => info!(
+            "💴 推定コスト合計: {:.2} {}（{:.2} {}）",
+            spend.total * conversion.rate,
+            conversion.currency,
+            spend.total,
+            currency()
+        ),
+        None => info!("💴 推定コスト合計: {:.2} {}", spend.total, currency()),
+    }
+    for (model, calls) in &spend.unpriced {
+        info!(
+            "   {} - 料金表に単価がないため {} 回の呼び出しを集計していません",
+            model, calls
+        );
+    }
+}
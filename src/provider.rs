@@ -0,0 +1,471 @@
+// LLMプロバイダーモジュール
+// Azure OpenAI と OpenAI互換API（api.openai.com, vLLM, LM Studio など）のURLと認証の違いを吸収する
+
+use anyhow::Result;
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use futures::future::BoxFuture;
+use reqwest::RequestBuilder;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::sync::Arc;
+
+use crate::cassette;
+use crate::keys::{self, KeyRing};
+use crate::llm::schemas::openai_response::{Endpoint, ProviderKind};
+
+// Azure OpenAI の既定のAPIバージョン
+const AZURE_API_VERSION: &str = "2024-12-01-preview";
+
+// APIバージョンとURLのパスの全体設定（エンドポイントごとの設定が優先される）
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ApiConfig {
+    /// APIバージョン（例: "2024-10-21"。空文字の場合はクエリに付けない）
+    pub api_version: Option<String>,
+    /// モデル単位のAPIのパステンプレート（例: "/openai/deployments/{deployment}/{operation}"）
+    pub deployment_path: Option<String>,
+    /// リソース単位のAPIのパステンプレート（例: "/openai/{path}"）
+    pub resource_path: Option<String>,
+}
+
+impl ApiConfig {
+    // エンドポイントで未指定の項目に全体設定を適用
+    pub fn apply(&self, endpoint: &mut Endpoint) {
+        if endpoint.api_version.is_none() {
+            endpoint.api_version = self.api_version.clone();
+        }
+        if endpoint.deployment_path.is_none() {
+            endpoint.deployment_path = self.deployment_path.clone();
+        }
+        if endpoint.resource_path.is_none() {
+            endpoint.resource_path = self.resource_path.clone();
+        }
+    }
+}
+
+// URLの組み立て方
+struct UrlTemplate {
+    base_url: String,
+    deployment_path: String,
+    resource_path: String,
+    api_version: String,
+}
+
+impl UrlTemplate {
+    // エンドポイント設定と、プロバイダーごとの既定値から作成
+    fn new(
+        endpoint: &Endpoint,
+        deployment_path: &str,
+        resource_path: &str,
+        api_version: &str,
+    ) -> Self {
+        UrlTemplate {
+            base_url: endpoint.endpoint.trim_end_matches('/').to_string(),
+            deployment_path: endpoint
+                .deployment_path
+                .clone()
+                .unwrap_or_else(|| deployment_path.to_string()),
+            resource_path: endpoint
+                .resource_path
+                .clone()
+                .unwrap_or_else(|| resource_path.to_string()),
+            api_version: endpoint
+                .api_version
+                .clone()
+                .unwrap_or_else(|| api_version.to_string()),
+        }
+    }
+
+    // パスにベースURLとAPIバージョンを付ける
+    fn url(&self, path: &str) -> String {
+        if self.api_version.is_empty() {
+            format!("{}{}", self.base_url, path)
+        } else {
+            format!("{}{}?api-version={}", self.base_url, path, self.api_version)
+        }
+    }
+
+    fn deployment_url(&self, deployment: &str, operation: &str) -> String {
+        self.url(
+            &self
+                .deployment_path
+                .replace("{deployment}", deployment)
+                .replace("{operation}", operation),
+        )
+    }
+
+    fn resource_url(&self, path: &str) -> String {
+        self.url(&self.resource_path.replace("{path}", path))
+    }
+}
+
+// LLMプロバイダーの共通インターフェース
+pub trait LlmProvider: Send + Sync {
+    // モデル（デプロイメント）単位のAPIのURL（operation: chat/completions, embeddings など）
+    fn deployment_url(&self, deployment: &str, operation: &str) -> String;
+
+    // リソース単位のAPIのURL（path: files, batches, fine_tuning/jobs など）
+    fn resource_url(&self, path: &str) -> String;
+
+    // 認証情報をリクエストに付与
+    fn authorize(&self, request: RequestBuilder) -> RequestBuilder;
+
+    // 認証に使うAPIキー（キーを使わないプロバイダーはNone）
+    fn key_ring(&self) -> Option<&KeyRing> {
+        None
+    }
+
+    // リクエストボディでモデル名を指定する必要があるか
+    fn model_in_body(&self) -> bool;
+
+    // Batch APIの入力ファイルで指定するエンドポイントのパス
+    fn batch_endpoint(&self) -> &str;
+
+    // リクエストを送信
+    fn send(&self, request: RequestBuilder) -> BoxFuture<'_, Result<reqwest::Response>> {
+        Box::pin(async move { Ok(request.send().await?) })
+    }
+}
+
+// Azure OpenAI
+pub struct AzureProvider {
+    template: UrlTemplate,
+    keys: Arc<KeyRing>,
+}
+
+impl LlmProvider for AzureProvider {
+    fn deployment_url(&self, deployment: &str, operation: &str) -> String {
+        self.template.deployment_url(deployment, operation)
+    }
+
+    fn resource_url(&self, path: &str) -> String {
+        self.template.resource_url(path)
+    }
+
+    fn authorize(&self, request: RequestBuilder) -> RequestBuilder {
+        request.header("api-key", self.keys.current())
+    }
+
+    fn key_ring(&self) -> Option<&KeyRing> {
+        Some(&self.keys)
+    }
+
+    fn model_in_body(&self) -> bool {
+        false
+    }
+
+    fn batch_endpoint(&self) -> &str {
+        "/chat/completions"
+    }
+}
+
+// OpenAI互換API（endpoint には https://api.openai.com/v1 のようなベースURLを指定）
+pub struct OpenAICompatibleProvider {
+    template: UrlTemplate,
+    keys: Arc<KeyRing>,
+}
+
+impl LlmProvider for OpenAICompatibleProvider {
+    fn deployment_url(&self, deployment: &str, operation: &str) -> String {
+        self.template.deployment_url(deployment, operation)
+    }
+
+    fn resource_url(&self, path: &str) -> String {
+        self.template.resource_url(path)
+    }
+
+    fn authorize(&self, request: RequestBuilder) -> RequestBuilder {
+        // ローカルのサーバーなどキーが不要な場合はヘッダーを付けない
+        let key = self.keys.current();
+        if key.is_empty() {
+            request
+        } else {
+            request.bearer_auth(key)
+        }
+    }
+
+    fn key_ring(&self) -> Option<&KeyRing> {
+        Some(&self.keys)
+    }
+
+    fn model_in_body(&self) -> bool {
+        true
+    }
+
+    fn batch_endpoint(&self) -> &str {
+        "/v1/chat/completions"
+    }
+}
+
+// ドライラン用のモック（通信せずにダミーの応答と推定トークン数を返す）
+pub struct MockProvider;
+
+// モックが返すURLのベース（実際には接続しない）
+const MOCK_BASE_URL: &str = "https://dry-run.invalid";
+
+// 1x1ピクセルの透明PNG
+const MOCK_PNG: &[u8] = &[
+    0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A, 0x00, 0x00, 0x00, 0x0D, 0x49, 0x48, 0x44, 0x52,
+    0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x08, 0x06, 0x00, 0x00, 0x00, 0x1F, 0x15, 0xC4,
+    0x89, 0x00, 0x00, 0x00, 0x0D, 0x49, 0x44, 0x41, 0x54, 0x78, 0x9C, 0x63, 0x00, 0x01, 0x00, 0x00,
+    0x05, 0x00, 0x01, 0x0D, 0x0A, 0x2D, 0xB4, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4E, 0x44, 0xAE,
+    0x42, 0x60, 0x82,
+];
+
+// 文字数からトークン数を大まかに見積もる
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+impl MockProvider {
+    // チャット補完のダミー応答
+    fn chat_response(model: &str, body: &Value) -> Value {
+        let messages = body["messages"].as_array().cloned().unwrap_or_default();
+        let prompt_chars: usize = messages
+            .iter()
+            .map(|message| message["content"].to_string().chars().count())
+            .sum();
+        let dummy = format!(
+            "【ドライラン】{} への {} 件のメッセージ（約 {} 文字）に対するダミーの回答です。",
+            model,
+            messages.len(),
+            prompt_chars
+        );
+        // JSON スキーマを指定した構造化出力では、スキーマに合うダミーのJSONを返す
+        let content = match &body["response_format"]["json_schema"]["schema"] {
+            Value::Null => dummy,
+            schema => Self::sample_json(schema, &dummy).to_string(),
+        };
+        let prompt_tokens = prompt_chars.div_ceil(4);
+        // 推論の労力を指定された場合は、労力に応じた推論トークンを出力トークンに含める
+        let reasoning_tokens = match body["reasoning_effort"].as_str() {
+            Some("low") => estimate_tokens(&content),
+            Some("medium") => estimate_tokens(&content) * 4,
+            Some("high") => estimate_tokens(&content) * 16,
+            _ => 0,
+        };
+        let completion_tokens = estimate_tokens(&content) + reasoning_tokens;
+
+        // ツールを渡された場合は、ツールの結果を受け取るまで最初のツールを呼び出す
+        let answered = messages
+            .last()
+            .is_some_and(|message| message["role"] == "tool");
+        match body["tools"].as_array().and_then(|tools| tools.first()) {
+            Some(tool) if !answered => {
+                let arguments = Self::sample_json(&tool["function"]["parameters"], ".");
+                return json!({
+                    "id": "dry-run",
+                    "object": "chat.completion",
+                    "created": 0,
+                    "model": model,
+                    "choices": [{
+                        "index": 0,
+                        "message": {
+                            "role": "assistant",
+                            "content": null,
+                            "tool_calls": [{
+                                "id": format!("dry-run-call-{}", messages.len()),
+                                "type": "function",
+                                "function": {
+                                    "name": tool["function"]["name"],
+                                    "arguments": arguments.to_string(),
+                                },
+                            }],
+                        },
+                        "finish_reason": "tool_calls",
+                    }],
+                    "usage": {
+                        "prompt_tokens": prompt_tokens,
+                        "completion_tokens": 0,
+                        "total_tokens": prompt_tokens,
+                    },
+                });
+            }
+            _ => {}
+        }
+
+        json!({
+            "id": "dry-run",
+            "object": "chat.completion",
+            "created": 0,
+            "model": model,
+            "choices": [{
+                "index": 0,
+                "message": { "role": "assistant", "content": content },
+                "finish_reason": "stop",
+            }],
+            "usage": {
+                "prompt_tokens": prompt_tokens,
+                "completion_tokens": completion_tokens,
+                "total_tokens": prompt_tokens + completion_tokens,
+                "completion_tokens_details": { "reasoning_tokens": reasoning_tokens },
+            },
+        })
+    }
+
+    // JSON スキーマに合う最小のダミー値（配列は要素を1つだけ含める）
+    fn sample_json(schema: &Value, text: &str) -> Value {
+        if let Some(first) = schema["enum"].as_array().and_then(|values| values.first()) {
+            return first.clone();
+        }
+        // ["string", "null"] のような型は null 以外の最初の型を使う
+        let kind = match &schema["type"] {
+            Value::Array(kinds) => kinds
+                .iter()
+                .filter_map(Value::as_str)
+                .find(|kind| *kind != "null")
+                .unwrap_or("null"),
+            kind => kind.as_str().unwrap_or("null"),
+        };
+        match kind {
+            "object" => Value::Object(
+                schema["properties"]
+                    .as_object()
+                    .map(|properties| {
+                        properties
+                            .iter()
+                            .map(|(name, property)| {
+                                (name.clone(), Self::sample_json(property, text))
+                            })
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+            ),
+            "array" => json!([Self::sample_json(&schema["items"], text)]),
+            "string" => json!(text),
+            "integer" | "number" => json!(0),
+            "boolean" => json!(false),
+            _ => Value::Null,
+        }
+    }
+
+    // 埋め込みのダミー応答（入力ごとに文字数から決まる小さなベクトル）
+    fn embeddings_response(model: &str, body: &Value) -> Value {
+        let inputs = body["input"].as_array().cloned().unwrap_or_default();
+        let mut total_tokens = 0;
+        let data: Vec<Value> = inputs
+            .iter()
+            .enumerate()
+            .map(|(index, input)| {
+                let text = input.as_str().unwrap_or_default();
+                total_tokens += estimate_tokens(text);
+                let seed = text.len() as f32;
+                let embedding: Vec<f32> = (0..8).map(|i| ((seed + i as f32) * 0.1).sin()).collect();
+                json!({ "index": index, "embedding": embedding })
+            })
+            .collect();
+
+        json!({
+            "data": data,
+            "model": model,
+            "usage": { "prompt_tokens": total_tokens, "total_tokens": total_tokens },
+        })
+    }
+
+    // リクエストのパスに応じたダミー応答を作成
+    fn respond(path: &str, body: &Value) -> String {
+        let segments: Vec<&str> = path.trim_start_matches('/').split('/').collect();
+
+        match segments.as_slice() {
+            ["deployments", model, "chat", "completions"] => {
+                Self::chat_response(model, body).to_string()
+            }
+            ["deployments", model, "embeddings"] => {
+                Self::embeddings_response(model, body).to_string()
+            }
+            ["deployments", _, "images", "generations"] => json!({
+                "created": 0,
+                "data": [{ "b64_json": BASE64.encode(MOCK_PNG), "revised_prompt": null }],
+            })
+            .to_string(),
+            ["deployments", _, "audio", "transcriptions"] => {
+                json!({ "text": "【ドライラン】ダミーの書き起こしです。" }).to_string()
+            }
+            // 結果ファイルは空として扱う
+            ["files", _, "content"] => String::new(),
+            ["files", ..] => json!({
+                "id": "dry-run-file",
+                "status": "processed",
+                "filename": null,
+                "purpose": null,
+            })
+            .to_string(),
+            ["batches", ..] => json!({
+                "id": "dry-run-batch",
+                "status": "completed",
+                "output_file_id": "dry-run-output",
+                "error_file_id": null,
+                "request_counts": { "total": 0, "completed": 0, "failed": 0 },
+            })
+            .to_string(),
+            ["fine_tuning", "jobs", ..] => json!({
+                "id": "dry-run-job",
+                "status": "succeeded",
+                "fine_tuned_model": "dry-run-model",
+                "error": null,
+            })
+            .to_string(),
+            _ => json!({}).to_string(),
+        }
+    }
+}
+
+impl LlmProvider for MockProvider {
+    fn deployment_url(&self, deployment: &str, operation: &str) -> String {
+        format!("{}/deployments/{}/{}", MOCK_BASE_URL, deployment, operation)
+    }
+
+    fn resource_url(&self, path: &str) -> String {
+        format!("{}/{}", MOCK_BASE_URL, path)
+    }
+
+    fn authorize(&self, request: RequestBuilder) -> RequestBuilder {
+        request
+    }
+
+    fn model_in_body(&self) -> bool {
+        false
+    }
+
+    fn batch_endpoint(&self) -> &str {
+        "/chat/completions"
+    }
+
+    fn send(&self, request: RequestBuilder) -> BoxFuture<'_, Result<reqwest::Response>> {
+        Box::pin(async move {
+            let request = request.build()?;
+            let body: Value = request
+                .body()
+                .and_then(|body| body.as_bytes())
+                .and_then(|bytes| serde_json::from_slice(bytes).ok())
+                .unwrap_or(Value::Null);
+
+            let response = http::Response::builder()
+                .status(200)
+                .body(Self::respond(request.url().path(), &body))?;
+            Ok(reqwest::Response::from(response))
+        })
+    }
+}
+
+// エンドポイント設定からプロバイダーを作成（カセットが設定されていれば記録・再生を挟む）
+pub fn from_endpoint(endpoint: &Endpoint) -> Box<dyn LlmProvider> {
+    let provider: Box<dyn LlmProvider> = match endpoint.provider {
+        ProviderKind::Azure => Box::new(AzureProvider {
+            template: UrlTemplate::new(
+                endpoint,
+                "/openai/deployments/{deployment}/{operation}",
+                "/openai/{path}",
+                AZURE_API_VERSION,
+            ),
+            keys: keys::key_ring(endpoint),
+        }),
+        ProviderKind::OpenAI => Box::new(OpenAICompatibleProvider {
+            template: UrlTemplate::new(endpoint, "/{operation}", "/{path}", ""),
+            keys: keys::key_ring(endpoint),
+        }),
+        ProviderKind::Mock => Box::new(MockProvider),
+    };
+
+    cassette::wrap(provider)
+}
@@ -0,0 +1,598 @@
+// Azure OpenAI リソースの作成・削除モジュール
+// サブスクリプションとリージョンの一覧から、管理APIで Azure OpenAI アカウントとモデルのデプロイメントを作成し、
+// 設定ファイルのエンドポイントに追加する（エンドポイントを4つから20に増やすのもコマンド1つで済む）
+// 作成したリソースにはタグを付け、実行後にタグの付いたリソースだけを削除してクレジットがなくなってからの課金を防ぐ
+// （削除は設定のリソースグループと接頭辞に一致するものに限り、一覧を表示して確認してから行う）
+
+use anyhow::{Result, anyhow, bail};
+use chrono::prelude::*;
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::io::{IsTerminal, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+use tokio::{fs, time};
+
+use crate::arm::{self, ArmClient, DiscoveryConfig};
+use crate::llm::schemas::openai_response::Endpoint;
+
+// 作成したリソースに付けるタグ（値は作成日時）
+pub const PROVISION_TAG: &str = "azure-credit-burner";
+
+// リソースグループとアカウントのAPIバージョン
+const RESOURCE_GROUP_API_VERSION: &str = "2021-04-01";
+const COGNITIVE_API_VERSION: &str = "2023-05-01";
+
+// 作成の完了を待つ間隔と上限
+const POLL_INTERVAL_SECS: u64 = 5;
+const POLL_TIMEOUT_SECS: u64 = 900;
+
+// 作成・削除するリソースの設定
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ProvisionConfig {
+    /// アカウントを作成・削除するリソースグループ
+    pub resource_group: String,
+    /// アカウント名の接頭辞（削除はこの接頭辞のアカウントに限る）
+    pub prefix: String,
+}
+
+impl Default for ProvisionConfig {
+    fn default() -> Self {
+        ProvisionConfig {
+            resource_group: "azure-credit-burner".to_string(),
+            prefix: "burner".to_string(),
+        }
+    }
+}
+
+// リソース作成のオプション
+pub struct ProvisionOptions {
+    pub subscription: String,
+    pub regions: Vec<String>,
+    pub resource_group: String,
+    pub prefix: String,
+    pub model: String,
+    pub model_version: Option<String>,
+    pub deployment: Option<String>,
+    pub sku: String,
+    pub capacity: u64,
+}
+
+impl ProvisionOptions {
+    // リージョンのアカウント名（カスタムサブドメインを兼ねるため、サブスクリプションIDの先頭を付けて一意にする）
+    fn account_name(&self, region: &str) -> String {
+        let suffix: String = self
+            .subscription
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .take(8)
+            .collect();
+        format!("{}-{}-{}", self.prefix, region, suffix)
+            .to_lowercase()
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric() || *c == '-')
+            .take(64)
+            .collect()
+    }
+
+    fn deployment_name(&self) -> &str {
+        self.deployment.as_deref().unwrap_or(&self.model)
+    }
+
+    fn resource_group_path(&self) -> String {
+        format!(
+            "/subscriptions/{}/resourceGroups/{}",
+            self.subscription, self.resource_group
+        )
+    }
+}
+
+fn tags(created_at: &str) -> Value {
+    json!({ PROVISION_TAG: created_at })
+}
+
+// 作成が終わるまでリソースの provisioningState を確認する
+async fn wait_until_succeeded(client: &ArmClient, path: &str) -> Result<Value> {
+    let started = Instant::now();
+    loop {
+        let resource = client
+            .request(reqwest::Method::GET, path, COGNITIVE_API_VERSION, None)
+            .await?;
+        match resource["properties"]["provisioningState"].as_str() {
+            Some("Succeeded") => return Ok(resource),
+            Some(state @ ("Failed" | "Canceled")) => {
+                bail!("リソースの作成に失敗しました: {} ({})", path, state)
+            }
+            _ => {}
+        }
+        if started.elapsed() >= Duration::from_secs(POLL_TIMEOUT_SECS) {
+            bail!(
+                "リソースの作成が {} 秒以内に終わりませんでした: {}",
+                POLL_TIMEOUT_SECS,
+                path
+            );
+        }
+        time::sleep(Duration::from_secs(POLL_INTERVAL_SECS)).await;
+    }
+}
+
+// 1リージョンのアカウントとデプロイメントを作成し、エンドポイント設定を返す
+async fn provision_region(
+    client: &ArmClient,
+    options: &ProvisionOptions,
+    region: &str,
+    tenant: Option<&str>,
+    created_at: &str,
+) -> Result<Endpoint> {
+    let name = options.account_name(region);
+    let account_path = format!(
+        "{}/providers/Microsoft.CognitiveServices/accounts/{}",
+        options.resource_group_path(),
+        name
+    );
+    info!("🏗️ アカウントを作成しています: {} ({})", name, region);
+    let account = json!({
+        "location": region,
+        "kind": "OpenAI",
+        "sku": { "name": "S0" },
+        "properties": {
+            "customSubDomainName": name,
+            "publicNetworkAccess": "Enabled"
+        },
+        "tags": tags(created_at)
+    });
+    client
+        .request(
+            reqwest::Method::PUT,
+            &account_path,
+            COGNITIVE_API_VERSION,
+            Some(&account),
+        )
+        .await?;
+    let account = wait_until_succeeded(client, &account_path).await?;
+
+    let deployment_path = format!("{}/deployments/{}", account_path, options.deployment_name());
+    info!(
+        "🏗️ デプロイメントを作成しています: {} - {} ({} {})",
+        name,
+        options.deployment_name(),
+        options.sku,
+        options.capacity
+    );
+    let mut model = json!({ "format": "OpenAI", "name": options.model });
+    if let Some(version) = &options.model_version {
+        model["version"] = json!(version);
+    }
+    let deployment = json!({
+        "sku": { "name": options.sku, "capacity": options.capacity },
+        "properties": { "model": model }
+    });
+    client
+        .request(
+            reqwest::Method::PUT,
+            &deployment_path,
+            COGNITIVE_API_VERSION,
+            Some(&deployment),
+        )
+        .await?;
+    wait_until_succeeded(client, &deployment_path).await?;
+
+    arm::account_to_endpoint(client, &account, tenant).await
+}
+
+// 設定ファイルを JSON として読み込む（環境変数の参照（${VAR}）をそのまま残すため、解決する前の内容を書き換える）
+async fn read_raw_config(config_path: &str) -> Result<Value> {
+    let text = fs::read_to_string(config_path)
+        .await
+        .map_err(|e| anyhow!("設定ファイルを読み込めません: {} - {}", config_path, e))?;
+    serde_json::from_str(&text).map_err(|e| {
+        anyhow!(
+            "設定ファイルを JSON として読み込めません: {} - {}",
+            config_path,
+            e
+        )
+    })
+}
+
+// 一時ファイルに書いてから設定ファイルと置き換える
+async fn write_raw_config(config_path: &str, config: &Value) -> Result<()> {
+    let temp = format!("{}.tmp", config_path);
+    fs::write(&temp, serde_json::to_string_pretty(config)? + "\n").await?;
+    fs::rename(&temp, config_path).await?;
+    Ok(())
+}
+
+// 作成したエンドポイントを設定ファイルに追加する（同じURLのエンドポイントは追加しない）
+async fn append_to_config(config_path: &str, endpoints: &[Endpoint]) -> Result<usize> {
+    let mut config = read_raw_config(config_path).await?;
+    if config["endpoints"].is_null() {
+        config["endpoints"] = json!([]);
+    }
+    let existing = config["endpoints"]
+        .as_array_mut()
+        .ok_or_else(|| anyhow!("設定ファイルの endpoints が配列ではありません"))?;
+
+    let mut added = 0;
+    for endpoint in endpoints {
+        let url = endpoint.endpoint.trim_end_matches('/');
+        let exists = existing.iter().any(|existing| {
+            existing["endpoint"]
+                .as_str()
+                .is_some_and(|existing| existing.trim_end_matches('/') == url)
+        });
+        if exists {
+            continue;
+        }
+        existing.push(serde_json::to_value(endpoint)?);
+        added += 1;
+    }
+
+    write_raw_config(config_path, &config).await?;
+    Ok(added)
+}
+
+// リソースを作成して設定ファイルに追加する（ドライランでは作成するリソースを表示するだけ）
+pub async fn run_provision(
+    config_path: &str,
+    discovery: &DiscoveryConfig,
+    options: &ProvisionOptions,
+    dry_run: bool,
+) -> Result<()> {
+    if options.regions.is_empty() {
+        bail!("リソースを作成するリージョンを指定してください（--region）");
+    }
+    if !Path::new(config_path).exists() {
+        bail!(
+            "作成したエンドポイントを追加する設定ファイルが見つかりません: {}",
+            config_path
+        );
+    }
+
+    if dry_run {
+        println!(
+            "リソースグループ: {} ({})",
+            options.resource_group_path(),
+            options.regions[0]
+        );
+        for region in &options.regions {
+            println!(
+                "アカウント: {} ({}) - デプロイメント {}: {} {} × {}",
+                options.account_name(region),
+                region,
+                options.deployment_name(),
+                options.model,
+                options.sku,
+                options.capacity
+            );
+        }
+        info!("🧪 ドライランのため、リソースは作成しません");
+        return Ok(());
+    }
+
+    let client = ArmClient::new(discovery).await?;
+    let created_at = Utc::now().to_rfc3339();
+
+    // リソースグループは最初のリージョンに作成する（既にあればそのまま使う）
+    info!(
+        "🏗️ リソースグループを作成しています: {}",
+        options.resource_group
+    );
+    let resource_group = json!({ "location": options.regions[0], "tags": tags(&created_at) });
+    let existing = client
+        .request(
+            reqwest::Method::GET,
+            &options.resource_group_path(),
+            RESOURCE_GROUP_API_VERSION,
+            None,
+        )
+        .await;
+    if existing.is_err() {
+        client
+            .request(
+                reqwest::Method::PUT,
+                &options.resource_group_path(),
+                RESOURCE_GROUP_API_VERSION,
+                Some(&resource_group),
+            )
+            .await?;
+    } else {
+        warn!(
+            "⚠️ リソースグループは既にあるため、作成したアカウントだけにタグを付けます: {}",
+            options.resource_group
+        );
+    }
+
+    let tenant = arm::tenant_id(discovery);
+    let mut endpoints = Vec::new();
+    for region in &options.regions {
+        match provision_region(&client, options, region, tenant.as_deref(), &created_at).await {
+            Ok(endpoint) => {
+                info!(
+                    "✅ エンドポイントを作成しました: {} ({})",
+                    endpoint.name, endpoint.endpoint
+                );
+                endpoints.push(endpoint);
+            }
+            Err(e) => error!("❌ リソースの作成エラー: {} - {}", region, e),
+        }
+    }
+    if endpoints.is_empty() {
+        bail!("エンドポイントを1つも作成できませんでした");
+    }
+
+    let added = append_to_config(config_path, &endpoints).await?;
+    info!(
+        "📝 設定ファイルにエンドポイントを {} 件追加しました: {}（作成 {} / {} リージョン）",
+        added,
+        config_path,
+        endpoints.len(),
+        options.regions.len()
+    );
+    Ok(())
+}
+
+// リソース削除のオプション
+pub struct DeprovisionOptions {
+    pub subscriptions: Vec<String>,
+    pub resource_group: String,
+    pub prefix: String,
+    pub purge: bool,
+    pub keep_config: bool,
+    // 確認せずに削除する
+    pub yes: bool,
+}
+
+// 1つのサブスクリプションで削除するリソース
+struct DeprovisionTarget<'a> {
+    subscription: &'a str,
+    accounts: Vec<Value>,
+    // タグの付いたリソースグループ（空になった場合だけ削除する）
+    resource_group: Option<String>,
+}
+
+// タグを付けて作成したリソースの一覧
+async fn list_tagged(client: &ArmClient, subscription: &str, path: &str) -> Result<Vec<Value>> {
+    client
+        .list(
+            &format!(
+                "/subscriptions/{}{}?$filter=tagName eq '{}'",
+                subscription, path, PROVISION_TAG
+            ),
+            RESOURCE_GROUP_API_VERSION,
+        )
+        .await
+}
+
+// リソースIDのリソースグループ名
+fn resource_group_of(id: &str) -> Option<&str> {
+    id.split('/')
+        .skip_while(|segment| !segment.eq_ignore_ascii_case("resourceGroups"))
+        .nth(1)
+}
+
+// 削除したアカウントのエンドポイントを設定ファイルから取り除く
+async fn remove_from_config(config_path: &str, urls: &[String]) -> Result<usize> {
+    let mut config = read_raw_config(config_path).await?;
+    let Some(endpoints) = config["endpoints"].as_array_mut() else {
+        return Ok(0);
+    };
+    let before = endpoints.len();
+    endpoints.retain(|endpoint| {
+        !endpoint["endpoint"].as_str().is_some_and(|url| {
+            urls.iter()
+                .any(|removed| removed.trim_end_matches('/') == url.trim_end_matches('/'))
+        })
+    });
+    let removed = before - endpoints.len();
+    if removed > 0 {
+        write_raw_config(config_path, &config).await?;
+    }
+    Ok(removed)
+}
+
+// 設定のリソースグループにある、タグの付いた接頭辞の一致するアカウントとリソースグループを探す
+async fn find_targets<'a>(
+    client: &ArmClient,
+    subscription: &'a str,
+    options: &DeprovisionOptions,
+) -> Result<DeprovisionTarget<'a>> {
+    let scope = format!("/resourceGroups/{}", options.resource_group);
+    let prefix = format!("{}-", options.prefix.to_lowercase());
+    let accounts = list_tagged(client, subscription, &format!("{}/resources", scope))
+        .await?
+        .into_iter()
+        .filter(|resource| {
+            resource["type"].as_str().is_some_and(|kind| {
+                kind.eq_ignore_ascii_case("Microsoft.CognitiveServices/accounts")
+            }) && resource["name"]
+                .as_str()
+                .is_some_and(|name| name.to_lowercase().starts_with(&prefix))
+        })
+        .collect();
+    // リソースグループは、作成したもの（タグ付き）だけを対象にする
+    let resource_group = list_tagged(client, subscription, "/resourcegroups")
+        .await?
+        .into_iter()
+        .filter_map(|resource_group| resource_group["id"].as_str().map(str::to_string))
+        .find(|id| {
+            resource_group_of(id)
+                .is_some_and(|name| name.eq_ignore_ascii_case(&options.resource_group))
+        });
+    Ok(DeprovisionTarget {
+        subscription,
+        accounts,
+        resource_group,
+    })
+}
+
+// 削除してよいか端末で確認する（端末でなければ --yes を求める）
+fn confirm() -> Result<bool> {
+    if !std::io::stdin().is_terminal() {
+        bail!("対話的に確認できないため、一覧を確認してから --yes を指定して実行してください");
+    }
+    print!("これらのリソースを削除しますか？ [y/N]: ");
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+// タグを付けて作成したアカウントとリソースグループを削除する（ドライランでは削除するリソースを表示するだけ）
+pub async fn run_deprovision(
+    config_path: &str,
+    discovery: &DiscoveryConfig,
+    options: &DeprovisionOptions,
+    dry_run: bool,
+) -> Result<()> {
+    if options.subscriptions.is_empty() {
+        bail!("リソースを削除するサブスクリプションを指定してください（--subscription）");
+    }
+    if options.prefix.is_empty() {
+        bail!("削除するアカウント名の接頭辞を指定してください（--prefix）");
+    }
+    let client = ArmClient::new(discovery).await?;
+
+    // 削除する前に、対象のリソースをすべて表示する
+    let mut targets = Vec::new();
+    for subscription in &options.subscriptions {
+        match find_targets(&client, subscription, options).await {
+            Ok(target) => targets.push(target),
+            Err(e) => warn!(
+                "⚠️ 削除するリソースを探せません: {} ({}) - {}",
+                subscription, options.resource_group, e
+            ),
+        }
+    }
+    for target in &targets {
+        for account in &target.accounts {
+            println!(
+                "アカウント: {} ({})",
+                account["id"].as_str().unwrap_or_default(),
+                account["location"].as_str().unwrap_or_default()
+            );
+        }
+        if let Some(id) = &target.resource_group {
+            println!("リソースグループ（空の場合）: {}", id);
+        }
+    }
+    if targets
+        .iter()
+        .all(|target| target.accounts.is_empty() && target.resource_group.is_none())
+    {
+        info!(
+            "🧹 削除するリソースはありません（リソースグループ {}、接頭辞 {}）",
+            options.resource_group, options.prefix
+        );
+        return Ok(());
+    }
+    if dry_run {
+        info!("🧪 ドライランのため、リソースは削除しません");
+        return Ok(());
+    }
+    if !options.yes && !confirm()? {
+        info!("🧹 削除を取り消しました");
+        return Ok(());
+    }
+
+    let mut removed_urls = Vec::new();
+    let mut failures = 0;
+    for target in targets {
+        let subscription = target.subscription;
+        for account in &target.accounts {
+            let Some(id) = account["id"].as_str() else {
+                continue;
+            };
+            let name = account["name"].as_str().unwrap_or_default();
+            let location = account["location"].as_str().unwrap_or_default();
+            // 削除すると情報を取得できなくなるため、先にエンドポイントのURLを取得しておく
+            let url = client
+                .request(reqwest::Method::GET, id, COGNITIVE_API_VERSION, None)
+                .await
+                .ok()
+                .and_then(|detail| {
+                    detail["properties"]["endpoint"]
+                        .as_str()
+                        .map(str::to_string)
+                });
+
+            info!("🧹 アカウントを削除しています: {} ({})", name, location);
+            if let Err(e) = client
+                .request(reqwest::Method::DELETE, id, COGNITIVE_API_VERSION, None)
+                .await
+            {
+                error!("❌ アカウントの削除エラー: {} - {}", name, e);
+                failures += 1;
+                continue;
+            }
+            removed_urls.extend(url);
+
+            // 削除したアカウントは一定期間名前を保持するため、同じ名前で作り直せるよう完全に削除する
+            if options.purge {
+                let Some(resource_group) = resource_group_of(id) else {
+                    continue;
+                };
+                let path = format!(
+                    "/subscriptions/{}/providers/Microsoft.CognitiveServices/locations/{}/resourceGroups/{}/deletedAccounts/{}",
+                    subscription, location, resource_group, name
+                );
+                if let Err(e) = client
+                    .request(reqwest::Method::DELETE, &path, COGNITIVE_API_VERSION, None)
+                    .await
+                {
+                    warn!(
+                        "⚠️ 削除したアカウントを完全に削除できません: {} - {}",
+                        name, e
+                    );
+                }
+            }
+        }
+
+        if let Some(id) = target.resource_group.as_deref() {
+            // 作成後に追加された他のリソースを巻き込まないよう、空のリソースグループだけを削除する
+            let remaining = client
+                .list(&format!("{}/resources", id), RESOURCE_GROUP_API_VERSION)
+                .await?;
+            if !remaining.is_empty() {
+                warn!(
+                    "⚠️ リソースが {} 件残っているため、リソースグループは削除しません: {}",
+                    remaining.len(),
+                    id
+                );
+                continue;
+            }
+            info!("🧹 リソースグループを削除しています: {}", id);
+            if let Err(e) = client
+                .request(
+                    reqwest::Method::DELETE,
+                    id,
+                    RESOURCE_GROUP_API_VERSION,
+                    None,
+                )
+                .await
+            {
+                error!("❌ リソースグループの削除エラー: {} - {}", id, e);
+                failures += 1;
+            }
+        }
+    }
+
+    info!(
+        "🧹 作成したアカウントを {} 件削除しました",
+        removed_urls.len()
+    );
+    if !options.keep_config && Path::new(config_path).exists() {
+        let removed = remove_from_config(config_path, &removed_urls).await?;
+        if removed > 0 {
+            info!(
+                "📝 設定ファイルから削除したエンドポイントを {} 件取り除きました: {}",
+                removed, config_path
+            );
+        }
+    }
+    if failures > 0 {
+        bail!("{} 件のリソースを削除できませんでした", failures);
+    }
+    Ok(())
+}
@@ -0,0 +1,193 @@
+// プロビジョンドスループット（PTU）モジュール
+// PTU デプロイメントは使っても使わなくても時間単位で課金されるため、リクエストごとのペース調整をせず、
+// 割り当て容量を使い切るようにワーカーを割り当てて、容量に対する利用率を記録する
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use tokio::time::{self, Instant};
+
+use crate::llm::schemas::openai_response::Endpoint;
+
+// PTU モードの設定
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct PtuConfig {
+    /// provisioned_units が設定されたエンドポイントを PTU として扱うか
+    pub enabled: bool,
+    /// 1 PTU あたりの処理できるトークン数（1分あたり、モデルによって異なる）
+    pub tokens_per_minute_per_unit: u64,
+    /// 1 PTU あたりのワーカー数（同時実行数の配分とは別に割り当てる）
+    pub workers_per_unit: f64,
+    /// 利用率をログに出力する間隔（秒、0 で出力しない）
+    pub report_interval_secs: u64,
+}
+
+impl Default for PtuConfig {
+    fn default() -> Self {
+        PtuConfig {
+            enabled: false,
+            tokens_per_minute_per_unit: 2500,
+            workers_per_unit: 0.5,
+            report_interval_secs: 300,
+        }
+    }
+}
+
+// 利用率の計算に使う直近の期間
+const WINDOW: Duration = Duration::from_secs(60);
+
+// PTU エンドポイントごとの使用量
+struct Usage {
+    units: u64,
+    started: Instant,
+    total_tokens: usize,
+    // 直近の期間のAPI呼び出しごとのトークン数
+    recent: VecDeque<(Instant, usize)>,
+    // 1分あたりのトークン数の最大値
+    peak_per_minute: usize,
+}
+
+impl Usage {
+    // 直近1分間のトークン数
+    fn recent_tokens(&mut self, now: Instant) -> usize {
+        while self
+            .recent
+            .front()
+            .is_some_and(|(at, _)| now.duration_since(*at) > WINDOW)
+        {
+            self.recent.pop_front();
+        }
+        self.recent.iter().map(|(_, tokens)| tokens).sum()
+    }
+}
+
+static PTU: OnceLock<PtuConfig> = OnceLock::new();
+static USAGE: Mutex<BTreeMap<String, Usage>> = Mutex::new(BTreeMap::new());
+
+// PTU モードを設定し、利用率を定期的にログに出力する
+pub fn configure(config: &PtuConfig, endpoints: &[Endpoint]) {
+    let _ = PTU.set(config.clone());
+    if !config.enabled {
+        return;
+    }
+
+    let provisioned: Vec<&Endpoint> = endpoints
+        .iter()
+        .filter(|endpoint| endpoint.provisioned_units.is_some())
+        .collect();
+    if provisioned.is_empty() {
+        warn!(
+            "⚠️ PTU モードが有効ですが、provisioned_units が設定されたエンドポイントがありません"
+        );
+        return;
+    }
+    for endpoint in &provisioned {
+        let units = endpoint.provisioned_units.unwrap_or_default();
+        info!(
+            "🏭 [{}] PTU モード: {} PTU（容量 {} トークン/分, ワーカー数 {}）",
+            endpoint.name,
+            units,
+            capacity_per_minute(units),
+            saturation_workers(endpoint).unwrap_or_default()
+        );
+    }
+
+    if config.report_interval_secs > 0 {
+        let interval = Duration::from_secs(config.report_interval_secs);
+        tokio::spawn(async move {
+            loop {
+                time::sleep(interval).await;
+                log_utilization();
+            }
+        });
+    }
+}
+
+fn config() -> &'static PtuConfig {
+    PTU.get_or_init(PtuConfig::default)
+}
+
+// PTU の容量（1分あたりのトークン数）
+fn capacity_per_minute(units: u64) -> u64 {
+    units * config().tokens_per_minute_per_unit
+}
+
+// PTU として扱うエンドポイントか
+pub fn is_provisioned(endpoint: &Endpoint) -> bool {
+    config().enabled && endpoint.provisioned_units.is_some()
+}
+
+// 容量を使い切るためのワーカー数（PTU として扱わないエンドポイントは None）
+pub fn saturation_workers(endpoint: &Endpoint) -> Option<usize> {
+    if !is_provisioned(endpoint) {
+        return None;
+    }
+    let units = endpoint.provisioned_units.unwrap_or_default();
+    Some(((units as f64 * config().workers_per_unit).ceil() as usize).max(1))
+}
+
+// API呼び出しのトークン数を記録
+pub fn record(endpoint: &Endpoint, tokens: usize) {
+    if !is_provisioned(endpoint) {
+        return;
+    }
+    let now = Instant::now();
+    let mut usage = USAGE.lock().unwrap();
+    let entry = usage.entry(endpoint.name.clone()).or_insert_with(|| Usage {
+        units: endpoint.provisioned_units.unwrap_or_default(),
+        started: now,
+        total_tokens: 0,
+        recent: VecDeque::new(),
+        peak_per_minute: 0,
+    });
+    entry.total_tokens += tokens;
+    entry.recent.push_back((now, tokens));
+    let recent = entry.recent_tokens(now);
+    entry.peak_per_minute = entry.peak_per_minute.max(recent);
+}
+
+// 利用率（%）
+fn utilization(tokens_per_minute: f64, units: u64) -> f64 {
+    let capacity = capacity_per_minute(units);
+    if capacity == 0 {
+        return 0.0;
+    }
+    tokens_per_minute / capacity as f64 * 100.0
+}
+
+// 直近1分間の利用率をログに出力
+fn log_utilization() {
+    let now = Instant::now();
+    let mut usage = USAGE.lock().unwrap();
+    for (name, entry) in usage.iter_mut() {
+        let recent = entry.recent_tokens(now);
+        info!(
+            "🏭 [{}] PTU 利用率: {:.1}%（直近1分 {} トークン / 容量 {} トークン）",
+            name,
+            utilization(recent as f64, entry.units),
+            recent,
+            capacity_per_minute(entry.units)
+        );
+    }
+}
+
+// 実行全体の PTU の利用率をログに出力
+pub fn log_summary() {
+    let usage = USAGE.lock().unwrap();
+    for (name, entry) in usage.iter() {
+        let minutes = (entry.started.elapsed().as_secs_f64() / 60.0).max(1.0);
+        let average = entry.total_tokens as f64 / minutes;
+        info!(
+            "🏭 [{}] PTU 利用率: 平均 {:.1}%, 最大 {:.1}%（{} トークン / {:.1} 分, {} PTU）",
+            name,
+            utilization(average, entry.units),
+            utilization(entry.peak_per_minute as f64, entry.units),
+            entry.total_tokens,
+            minutes,
+            entry.units
+        );
+    }
+}
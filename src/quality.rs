@@ -0,0 +1,132 @@
+// 応答の品質チェックモジュール
+// 短すぎる応答、謝罪して回答を断る応答、質問を繰り返しただけの応答を検出し、
+// 言い回しを変えて（最後は別のエンドポイントで）聞き直す（不採用にした応答はターンの結果に記録する）
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt;
+
+// 品質チェックの設定
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct QualityConfig {
+    /// 議論の応答の品質をチェックして聞き直すか
+    pub enabled: bool,
+    /// 応答の最小文字数
+    pub min_chars: usize,
+    /// 回答の拒否とみなす表現（応答の先頭200文字に含まれる場合）
+    pub refusal_patterns: Vec<String>,
+    /// 質問の繰り返しとみなす、応答の文字の並び（2文字）が質問にも含まれる割合（0〜1）
+    pub max_question_overlap: f64,
+    /// 1ターンあたりの聞き直しの上限
+    pub max_reasks: usize,
+    /// 最後の聞き直しを別のエンドポイントで行うか
+    pub switch_endpoint: bool,
+}
+
+impl Default for QualityConfig {
+    fn default() -> Self {
+        QualityConfig {
+            enabled: false,
+            min_chars: 200,
+            refusal_patterns: [
+                "申し訳ありませんが",
+                "申し訳ございませんが",
+                "お答えできません",
+                "お手伝いできません",
+                "I'm sorry",
+                "I am sorry",
+                "I can't help",
+                "I cannot help",
+                "I can't assist",
+                "I cannot assist",
+            ]
+            .into_iter()
+            .map(str::to_string)
+            .collect(),
+            max_question_overlap: 0.8,
+            max_reasks: 2,
+            switch_endpoint: true,
+        }
+    }
+}
+
+// 不採用にした理由
+#[derive(Debug, Clone)]
+pub enum Rejection {
+    TooShort { chars: usize, min_chars: usize },
+    Refusal { pattern: String },
+    Repetition { overlap: f64 },
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rejection::TooShort { chars, min_chars } => {
+                write!(f, "応答が短すぎます（{} 文字 < {} 文字）", chars, min_chars)
+            }
+            Rejection::Refusal { pattern } => {
+                write!(f, "回答を断る応答です（「{}」）", pattern)
+            }
+            Rejection::Repetition { overlap } => {
+                write!(
+                    f,
+                    "質問を繰り返しただけの応答です（重複 {:.0}%）",
+                    overlap * 100.0
+                )
+            }
+        }
+    }
+}
+
+// 空白を除いた文字の並び（2文字）
+fn bigrams(text: &str) -> HashSet<(char, char)> {
+    let chars: Vec<char> = text.chars().filter(|c| !c.is_whitespace()).collect();
+    chars.windows(2).map(|pair| (pair[0], pair[1])).collect()
+}
+
+// 応答をチェックする（問題がなければ None）
+pub fn check(config: &QualityConfig, question: &str, response: &str) -> Option<Rejection> {
+    let chars = response.trim().chars().count();
+    if chars < config.min_chars {
+        return Some(Rejection::TooShort {
+            chars,
+            min_chars: config.min_chars,
+        });
+    }
+
+    let head: String = response.trim().chars().take(200).collect();
+    if let Some(pattern) = config
+        .refusal_patterns
+        .iter()
+        .find(|pattern| head.contains(pattern.as_str()))
+    {
+        return Some(Rejection::Refusal {
+            pattern: pattern.clone(),
+        });
+    }
+
+    let response_bigrams = bigrams(response);
+    if !response_bigrams.is_empty() {
+        let question_bigrams = bigrams(question);
+        let overlap = response_bigrams.intersection(&question_bigrams).count() as f64
+            / response_bigrams.len() as f64;
+        if overlap >= config.max_question_overlap {
+            return Some(Rejection::Repetition { overlap });
+        }
+    }
+    None
+}
+
+// 聞き直す質問（元の質問に、不採用にした理由と回答の仕方の指示を加える）
+pub fn rephrase(config: &QualityConfig, question: &str, rejection: &Rejection) -> String {
+    let instruction = match rejection {
+        Rejection::TooShort { .. } => format!(
+            "前回の回答は短すぎたため採用しませんでした。リポジトリのコードを具体的に引用しながら、{} 文字以上で詳しく回答してください。",
+            config.min_chars
+        ),
+        Rejection::Refusal { .. } => "前回は回答が得られませんでした。これは公開されているソースコードの技術的なレビューです。分かる範囲で構いませんので、コードに基づいて具体的に回答してください。".to_string(),
+        Rejection::Repetition { .. } => "前回の回答は質問の繰り返しになっていたため採用しませんでした。質問を繰り返さず、分析の内容だけを具体的に回答してください。".to_string(),
+    };
+    format!("{}\n\n（{}）", question, instruction)
+}
@@ -0,0 +1,402 @@
+// 質問バンクの管理モジュール
+// カテゴリごとの質問バンクのファイルを一覧・追加・CSV から取り込みできるようにする
+// （手で JSON を編集すると形式の誤りや重複に気づきにくく、読み込めないファイルは組み込みの質問に戻ってしまう）
+// 設定で追加したカテゴリの質問バンクがなければ、モデル自身に質問を生成させて保存する
+
+use anyhow::{Result, anyhow, bail};
+use clap::Subcommand;
+use log::{error, info};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::llm::categories::{self, CategoryDefinition, QuestionCategory, QuestionsConfig};
+use crate::llm::schemas::openai_response::ChatMessage;
+use crate::{Config, LlmClient, generation, routing};
+
+// 質問バンクの操作
+#[derive(Subcommand, Debug)]
+pub enum QuestionsCommand {
+    /// カテゴリと質問数の一覧を表示する（カテゴリを指定するとその質問を表示する）
+    List {
+        /// 質問を表示するカテゴリ
+        category: Option<String>,
+    },
+    /// カテゴリに質問を追加する（ファイルがなければ組み込みの質問を元に作成する）
+    Add {
+        /// カテゴリのキー（例: testing）
+        category: String,
+        /// 追加する質問
+        question: String,
+        /// 新しいカテゴリの日本語名
+        #[clap(long)]
+        name: Option<String>,
+    },
+    /// CSV から質問を取り込む（ヘッダー行に question 列、category を指定しない場合は category 列も必要。name 列は任意）
+    Import {
+        /// CSV ファイル
+        file: String,
+        /// すべての行を取り込むカテゴリ
+        #[clap(long)]
+        category: Option<String>,
+        /// 新しいカテゴリの日本語名
+        #[clap(long)]
+        name: Option<String>,
+    },
+    /// 設定の questions.categories のうち質問バンクがないカテゴリの質問をモデルで生成して保存する
+    Bootstrap {
+        /// 質問の生成に使うエンドポイント名（省略時は最初のエンドポイント）
+        #[clap(long)]
+        endpoint: Option<String>,
+        /// 1カテゴリあたりに生成する質問数（省略時は questions.generate_count）
+        #[clap(long)]
+        count: Option<usize>,
+    },
+}
+
+// 質問バンクの操作を実行
+pub async fn run(config: &Config, command: QuestionsCommand, dry_run: bool) -> Result<()> {
+    let questions = &config.questions;
+    match command {
+        QuestionsCommand::List { category } => list(questions, category.as_deref()),
+        QuestionsCommand::Add {
+            category,
+            question,
+            name,
+        } => add(questions, &category, question, name.as_deref()),
+        QuestionsCommand::Import {
+            file,
+            category,
+            name,
+        } => import(questions, &file, category.as_deref(), name.as_deref()),
+        QuestionsCommand::Bootstrap { endpoint, count } => {
+            let target = match &endpoint {
+                Some(name) => config.endpoints.iter().find(|e| &e.name == name),
+                None => routing::interactive_endpoint(&config.endpoints),
+            }
+            .ok_or_else(|| anyhow!("質問の生成に使用するエンドポイントが見つかりません"))?;
+            let client = LlmClient::new(target.clone());
+            let count = count.unwrap_or(questions.generate_count);
+            bootstrap(&client, questions, count, dry_run).await
+        }
+    }
+}
+
+// カテゴリに質問を追加
+fn add(
+    config: &QuestionsConfig,
+    category: &str,
+    question: String,
+    name: Option<&str>,
+) -> Result<()> {
+    let mut bank = open(config, category, name)?;
+    let before = bank.questions.len();
+    bank.questions.push(question);
+    bank.dedupe();
+    if bank.questions.len() == before {
+        bail!("同じ質問が既に登録されています: {}", category);
+    }
+    let path = save(config, &bank)?;
+    info!(
+        "📚 質問を追加しました: {} ({} 問) - {}",
+        category,
+        bank.questions.len(),
+        path
+    );
+    Ok(())
+}
+
+// 編集するカテゴリを開く（ファイル、組み込みの質問、新しいカテゴリの順に探す）
+fn open(config: &QuestionsConfig, category: &str, name: Option<&str>) -> Result<QuestionCategory> {
+    let path = categories::file_path(&config.dir, category);
+    if Path::new(&path).exists() {
+        let mut bank = categories::load_file(Path::new(&path))?;
+        bank.dedupe();
+        if let Some(name) = name {
+            bank.name = name.to_string();
+        }
+        return Ok(bank);
+    }
+    if let Some(builtin) = categories::reload(config)
+        .into_iter()
+        .find(|existing| existing.category == category)
+    {
+        return Ok(QuestionCategory {
+            name: name.map(str::to_string).unwrap_or(builtin.name),
+            ..builtin
+        });
+    }
+    let Some(name) = name else {
+        bail!(
+            "新しいカテゴリ {} には --name で日本語のカテゴリ名を指定してください",
+            category
+        );
+    };
+    Ok(QuestionCategory {
+        category: category.to_string(),
+        name: name.to_string(),
+        questions: Vec::new(),
+    })
+}
+
+// 形式を確認して保存する
+fn save(config: &QuestionsConfig, bank: &QuestionCategory) -> Result<String> {
+    let problems = bank.validate(None);
+    if !problems.is_empty() {
+        bail!(
+            "質問バンクの形式が正しくありません: {} - {}",
+            bank.category,
+            problems.join(", ")
+        );
+    }
+    categories::save_file(&config.dir, bank)
+}
+
+// カテゴリの一覧または質問を表示
+fn list(config: &QuestionsConfig, category: Option<&str>) -> Result<()> {
+    let merged = categories::reload(config);
+
+    if let Some(category) = category {
+        let bank = merged
+            .iter()
+            .find(|existing| existing.category == category)
+            .ok_or_else(|| anyhow!("カテゴリ {} は定義されていません", category))?;
+        println!("# {} ({})\n", bank.name, bank.category);
+        for (i, question) in bank.questions.iter().enumerate() {
+            println!("{}. {}", i + 1, question);
+        }
+        return Ok(());
+    }
+
+    let files: BTreeMap<String, String> = categories::load_dir(&config.dir)
+        .into_iter()
+        .filter_map(|(path, loaded)| loaded.ok().map(|bank| (bank.category, path)))
+        .collect();
+    println!("| カテゴリ | 名前 | 質問数 | 読み込み元 |");
+    println!("|---|---|---:|---|");
+    for bank in &merged {
+        let source = match (
+            files.get(&bank.category),
+            categories::is_builtin(&bank.category),
+        ) {
+            (Some(path), true) => format!("{}（組み込みを上書き）", path),
+            (Some(path), false) => path.clone(),
+            (None, _) => "組み込み".to_string(),
+        };
+        println!(
+            "| {} | {} | {} | {} |",
+            bank.category,
+            bank.name,
+            bank.questions.len(),
+            source
+        );
+    }
+
+    // 読み込めないファイルは出題に使われないため、理由を表示する
+    for (path, loaded) in categories::load_dir(&config.dir) {
+        if let Err(e) = loaded {
+            println!("\n⚠️ {} は使用されません: {}", path, e);
+        }
+    }
+    Ok(())
+}
+
+// CSV から質問を取り込む
+fn import(
+    config: &QuestionsConfig,
+    file: &str,
+    category: Option<&str>,
+    name: Option<&str>,
+) -> Result<()> {
+    let mut reader = csv::Reader::from_path(file)
+        .map_err(|e| anyhow!("CSV を読み込めません: {} - {}", file, e))?;
+    let headers = reader.headers()?.clone();
+    let column = |name: &str| headers.iter().position(|header| header.trim() == name);
+    let Some(question_column) = column("question") else {
+        bail!("CSV のヘッダー行に question 列がありません: {}", file);
+    };
+    let category_column = column("category");
+    let name_column = column("name");
+    if category.is_none() && category_column.is_none() {
+        bail!(
+            "CSV のヘッダー行に category 列がありません。--category で取り込むカテゴリを指定してください: {}",
+            file
+        );
+    }
+
+    // カテゴリごとに質問と日本語名をまとめる
+    let mut rows: BTreeMap<String, (Option<String>, Vec<String>)> = BTreeMap::new();
+    for record in reader.records() {
+        let record = record?;
+        let field = |index: Option<usize>| {
+            index
+                .and_then(|index| record.get(index))
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+                .map(str::to_string)
+        };
+        let Some(row_category) = category.map(str::to_string).or(field(category_column)) else {
+            continue;
+        };
+        let entry = rows.entry(row_category).or_default();
+        if entry.0.is_none() {
+            entry.0 = name.map(str::to_string).or(field(name_column));
+        }
+        if let Some(question) = field(Some(question_column)) {
+            entry.1.push(question);
+        }
+    }
+    if rows.is_empty() {
+        bail!("取り込む質問がありません: {}", file);
+    }
+
+    for (row_category, (row_name, questions)) in rows {
+        let mut bank = open(config, &row_category, row_name.as_deref())?;
+        let before = bank.questions.len();
+        let imported = questions.len();
+        bank.questions.extend(questions);
+        bank.dedupe();
+        let added = bank.questions.len() - before;
+        let path = save(config, &bank)?;
+        info!(
+            "📥 {}: {} 問を取り込みました（重複 {} 問を除外, 合計 {} 問） - {}",
+            row_category,
+            added,
+            imported - added,
+            bank.questions.len(),
+            path
+        );
+    }
+    Ok(())
+}
+
+// 質問の生成の指示
+const GENERATE_PROMPT: &str = "あなたは経験豊富なソフトウェアアーキテクトです。\
+GitHubリポジトリのコードを複数ターンで深く分析する議論で使う、深掘り質問を作成してください。\
+質問は日本語で、リポジトリを特定せずどのリポジトリにも使える内容にし、\
+具体的なコードを引用した分析や改善案を引き出すように、1問ごとに観点を変えてください。";
+
+// 構造化出力の JSON スキーマ
+fn schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "questions": {
+                "type": "array",
+                "items": { "type": "string" },
+            },
+        },
+        "required": ["questions"],
+        "additionalProperties": false,
+    })
+}
+
+// 構造化出力の応答
+#[derive(Deserialize)]
+struct GenerateResponse {
+    questions: Vec<String>,
+}
+
+// カテゴリの質問をモデルで生成する
+async fn generate(
+    client: &LlmClient,
+    config: &QuestionsConfig,
+    definition: &CategoryDefinition,
+    count: usize,
+) -> Result<QuestionCategory> {
+    // 組み込みの質問を文体の例として渡す
+    let examples = categories::reload(config)
+        .into_iter()
+        .next()
+        .map(|category| category.questions.join("\n- "))
+        .unwrap_or_default();
+    let request = vec![
+        ChatMessage {
+            role: "system".to_string(),
+            content: GENERATE_PROMPT.to_string(),
+            ..Default::default()
+        },
+        ChatMessage {
+            role: "user".to_string(),
+            content: format!(
+                "カテゴリ「{}」（{}）の深掘り質問を {} 問作成してください。{}\n\n質問の例（別のカテゴリ）:\n- {}",
+                definition.name,
+                definition.category,
+                count,
+                definition
+                    .description
+                    .as_deref()
+                    .map(|description| format!("\nカテゴリの説明: {}", description))
+                    .unwrap_or_default(),
+                examples
+            ),
+            ..Default::default()
+        },
+    ];
+    let model = config
+        .deployment
+        .as_deref()
+        .unwrap_or(generation::default_model());
+    let (response, _) = client
+        .structured_completion(&request, model, config.max_tokens, "questions", schema())
+        .await?;
+    let parsed: GenerateResponse = serde_json::from_str(&response)
+        .map_err(|e| anyhow!("生成した質問のJSONを解析できません: {} - {}", e, response))?;
+
+    let mut bank = QuestionCategory {
+        category: definition.category.clone(),
+        name: definition.name.clone(),
+        questions: parsed.questions,
+    };
+    bank.dedupe();
+    Ok(bank)
+}
+
+// 質問バンクがないカテゴリの質問を生成して保存する（失敗したカテゴリは飛ばして次へ進む）
+// ドライランではダミーの質問を保存しない
+async fn bootstrap(
+    client: &LlmClient,
+    config: &QuestionsConfig,
+    count: usize,
+    dry_run: bool,
+) -> Result<()> {
+    let merged = categories::reload(config);
+    let missing = categories::missing(config, &merged);
+    if missing.is_empty() {
+        info!("📚 質問バンクを生成する必要のあるカテゴリはありません");
+        return Ok(());
+    }
+
+    let mut failed = 0;
+    for definition in missing {
+        info!(
+            "[{}] 🪄 質問を生成しています: {} ({}) - {} 問",
+            client.endpoint.name, definition.category, definition.name, count
+        );
+        let saved = match generate(client, config, definition, count).await {
+            Ok(bank) if dry_run => {
+                info!(
+                    "🧪 ドライランのため保存しません: {}",
+                    categories::file_path(&config.dir, &bank.category)
+                );
+                continue;
+            }
+            Ok(bank) => save(config, &bank).map(|path| (path, bank.questions.len())),
+            Err(e) => Err(e),
+        };
+        match saved {
+            Ok((path, generated)) => {
+                info!("📚 質問バンクを保存しました: {} ({} 問)", path, generated)
+            }
+            Err(e) => {
+                error!("❌ 質問を生成できません: {} - {}", definition.category, e);
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        bail!("{} カテゴリの質問を生成できませんでした", failed);
+    }
+    Ok(())
+}
@@ -0,0 +1,164 @@
+// クォータ取得とエンドポイントの容量計画モジュール
+// 管理APIでデプロイメントの割り当て容量（TPM）を取得し、レート制限とタスク割り当ての重みに反映する
+
+use anyhow::Result;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use tokio::time::{self, Instant};
+
+use crate::CHAT_MODEL;
+use crate::arm::{self, ArmClient, DiscoveryConfig};
+use crate::llm::schemas::openai_response::Endpoint;
+
+// クォータ取得の設定
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct QuotaConfig {
+    /// 起動時にクォータを取得するか（認証情報は discovery の設定を使用）
+    pub enabled: bool,
+    /// 容量を参照するデプロイメント名
+    pub deployment: String,
+    /// 容量からレート制限（RPM）を設定するか
+    pub apply_rate_limits: bool,
+}
+
+impl Default for QuotaConfig {
+    fn default() -> Self {
+        QuotaConfig {
+            enabled: false,
+            deployment: CHAT_MODEL.to_string(),
+            apply_rate_limits: true,
+        }
+    }
+}
+
+// Standard系デプロイメントの1容量単位あたりのRPM（1,000 TPM = 6 RPM）
+const RPM_PER_CAPACITY_UNIT: u64 = 6;
+
+// 各エンドポイントのデプロイメント容量を取得し、重みとレート制限を設定
+pub async fn introspect(
+    endpoints: &mut [Endpoint],
+    discovery: &DiscoveryConfig,
+    config: &QuotaConfig,
+) -> Result<()> {
+    // デプロイメント一覧が未取得のエンドポイントは、URLが一致するアカウントから取得する
+    if endpoints
+        .iter()
+        .any(|endpoint| endpoint.deployments.is_empty())
+    {
+        let client = ArmClient::new(discovery).await?;
+        let accounts = arm::list_configured_accounts(&client, discovery).await?;
+
+        for endpoint in endpoints
+            .iter_mut()
+            .filter(|endpoint| endpoint.deployments.is_empty())
+        {
+            let account = accounts.iter().find(|account| {
+                account["properties"]["endpoint"]
+                    .as_str()
+                    .is_some_and(|url| {
+                        url.trim_end_matches('/') == endpoint.endpoint.trim_end_matches('/')
+                    })
+            });
+            let Some(account_id) = account.and_then(|account| account["id"].as_str()) else {
+                warn!(
+                    "⚠️ エンドポイントに対応するアカウントが見つかりません: {}",
+                    endpoint.name
+                );
+                continue;
+            };
+            endpoint.deployments = client.list_deployments(account_id).await?;
+        }
+    }
+
+    for endpoint in endpoints.iter_mut() {
+        let Some(deployment) = endpoint
+            .deployments
+            .iter()
+            .find(|deployment| deployment.name == config.deployment)
+        else {
+            warn!(
+                "⚠️ デプロイメント {} が見つかりません: {}",
+                config.deployment, endpoint.name
+            );
+            continue;
+        };
+
+        let capacity = deployment.capacity;
+        let is_provisioned = deployment.sku.contains("Provisioned");
+
+        // 明示的に設定された値を優先する
+        if endpoint.weight.is_none() {
+            endpoint.weight = Some(capacity as f64);
+        }
+        if is_provisioned && endpoint.provisioned_units.is_none() {
+            endpoint.provisioned_units = Some(capacity);
+        }
+        if config.apply_rate_limits && endpoint.rpm_limit.is_none() && !is_provisioned {
+            endpoint.rpm_limit = Some((capacity * RPM_PER_CAPACITY_UNIT) as u32);
+        }
+
+        info!(
+            "📏 [{}] {} ({}): 容量 {}{}, 重み {}, RPM上限 {}",
+            endpoint.name,
+            deployment.name,
+            deployment.sku,
+            capacity,
+            if is_provisioned { " PTU" } else { "K TPM" },
+            endpoint.weight.unwrap_or(1.0),
+            endpoint
+                .rpm_limit
+                .map(|rpm| rpm.to_string())
+                .unwrap_or_else(|| "なし".to_string())
+        );
+    }
+
+    Ok(())
+}
+
+// リクエスト間隔を一定以上に保つレート制限
+pub struct RateLimiter {
+    interval: Duration,
+    next_slot: tokio::sync::Mutex<Instant>,
+}
+
+impl RateLimiter {
+    fn new(rpm: u32) -> Self {
+        RateLimiter {
+            interval: Duration::from_secs_f64(60.0 / rpm.max(1) as f64),
+            next_slot: tokio::sync::Mutex::new(Instant::now()),
+        }
+    }
+
+    // 次のリクエストを送信できるまで待機
+    pub async fn acquire(&self) {
+        let wait_until = {
+            let mut next_slot = self.next_slot.lock().await;
+            let slot = (*next_slot).max(Instant::now());
+            *next_slot = slot + self.interval;
+            slot
+        };
+        time::sleep_until(wait_until).await;
+    }
+}
+
+// エンドポイント名ごとのレート制限（同じエンドポイントのクライアント間で共有する）
+static RATE_LIMITERS: OnceLock<Mutex<HashMap<String, Arc<RateLimiter>>>> = OnceLock::new();
+
+// エンドポイントのレート制限を取得（制限がなければNone）
+pub fn rate_limiter(endpoint: &Endpoint) -> Option<Arc<RateLimiter>> {
+    let rpm = endpoint.rpm_limit?;
+    let mut limiters = RATE_LIMITERS
+        .get_or_init(|| Mutex::new(HashMap::new()))
+        .lock()
+        .unwrap();
+    Some(
+        limiters
+            .entry(endpoint.name.clone())
+            .or_insert_with(|| Arc::new(RateLimiter::new(rpm)))
+            .clone(),
+    )
+}
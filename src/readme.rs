@@ -0,0 +1,216 @@
+// README の検出モジュール
+// リポジトリのルートの README を形式や大文字小文字によらず探し（README.md・README.rst・readme.txt・docs/index など）、
+// 見つからなければファイルのサンプルからモデルに短い概要を書かせてプロンプトの README の代わりに使う
+
+use anyhow::{Result, anyhow};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+use tokio::fs;
+
+use crate::generation;
+use crate::llm::schemas::{
+    github_response::{FileInfo, RepoInfo},
+    openai_response::ChatMessage,
+};
+use crate::{GitHubClient, LlmClient, budget};
+
+// README の検出の設定
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ReadmeConfig {
+    /// README がないリポジトリで、モデルに概要を書かせるか
+    pub generate_overview: bool,
+    /// 概要の生成に渡すファイル内容の最大文字数
+    pub max_input_chars: usize,
+    /// 概要の最大出力トークン数
+    pub max_tokens: usize,
+    /// 概要の生成に使うデプロイメント名（省略時は議論と同じモデル）
+    pub deployment: Option<String>,
+}
+
+impl Default for ReadmeConfig {
+    fn default() -> Self {
+        ReadmeConfig {
+            generate_overview: true,
+            max_input_chars: 20000,
+            max_tokens: 800,
+            deployment: None,
+        }
+    }
+}
+
+// README がなく、概要も生成できない場合のプロンプトの README の内容
+pub const NO_README: &str = "READMEが見つかりませんでした。";
+
+// README のファイル名（拡張子を除き、大文字小文字を区別しない）と、優先する拡張子の順
+const README_STEMS: [&str; 2] = ["readme", "read_me"];
+const README_EXTENSIONS: [&str; 7] = ["md", "markdown", "rst", "adoc", "org", "txt", ""];
+
+// README がないときに探すドキュメントの入口（リポジトリのルートからの相対パス）
+const DOC_INDEXES: [&str; 6] = [
+    "docs/index.md",
+    "docs/index.rst",
+    "docs/README.md",
+    "doc/index.md",
+    "doc/index.rst",
+    "doc/README.md",
+];
+
+// 概要の生成の指示
+const OVERVIEW_PROMPT: &str = "あなたはリポジトリに README を書くシニアエンジニアです。\
+以下のファイル一覧とファイルの内容だけをもとに、このプロジェクトの目的、主な機能、主要なコンポーネント、使われている技術を\
+日本語で短くまとめてください。推測した内容は推測であることがわかるように書き、ファイルに含まれない内容を作らないでください。";
+
+static CONFIG: OnceLock<ReadmeConfig> = OnceLock::new();
+
+// リポジトリ（owner/repo）ごとの README の内容（議論タイプごとに探し直したり、概要を生成し直したりしない）
+static READMES: OnceLock<Mutex<HashMap<String, String>>> = OnceLock::new();
+
+// README の検出の設定を登録する
+pub fn configure(config: &ReadmeConfig) {
+    let _ = CONFIG.set(config.clone());
+}
+
+// ルートの README のパス（拡張子の優先順で最初のもの）
+async fn find_readme(repo_dir: &Path) -> Option<String> {
+    let mut candidates = Vec::new();
+    let mut entries = fs::read_dir(repo_dir).await.ok()?;
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        if !entry
+            .file_type()
+            .await
+            .is_ok_and(|file_type| file_type.is_file())
+        {
+            continue;
+        }
+        let name = entry.file_name().to_string_lossy().to_string();
+        let lower = name.to_lowercase();
+        let (stem, extension) = lower.split_once('.').unwrap_or((&lower, ""));
+        if !README_STEMS.contains(&stem) {
+            continue;
+        }
+        if let Some(rank) = README_EXTENSIONS.iter().position(|ext| *ext == extension) {
+            candidates.push((rank, name));
+        }
+    }
+    candidates.sort();
+    if let Some((_, name)) = candidates.into_iter().next() {
+        return Some(name);
+    }
+    for index in DOC_INDEXES {
+        if fs::metadata(repo_dir.join(index)).await.is_ok() {
+            return Some(index.to_string());
+        }
+    }
+    None
+}
+
+// ファイルのサンプルからモデルに概要を書かせる
+async fn generate_overview(
+    client: &LlmClient,
+    config: &ReadmeConfig,
+    repo_info: &RepoInfo,
+    repo_files: &[FileInfo],
+) -> Result<String> {
+    let file_list = repo_files
+        .iter()
+        .map(|file| format!("- {}", file.path))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let mut contents = String::new();
+    for file in repo_files {
+        let section = format!("\n--- {} ---\n{}\n", file.path, file.content);
+        if contents.chars().count() + section.chars().count() > config.max_input_chars {
+            break;
+        }
+        contents.push_str(&section);
+    }
+    let messages = vec![
+        ChatMessage {
+            role: "system".to_string(),
+            content: OVERVIEW_PROMPT.to_string(),
+            ..Default::default()
+        },
+        ChatMessage {
+            role: "user".to_string(),
+            content: format!(
+                "リポジトリ: {}/{}\n\n【ファイル一覧】\n{}\n\n【ファイルの内容】{}",
+                repo_info.owner, repo_info.repo, file_list, contents
+            ),
+            ..Default::default()
+        },
+    ];
+
+    let mut generation = generation::resolve(&client.endpoint, "概要");
+    generation.max_tokens = config.max_tokens;
+    if let Some(deployment) = &config.deployment {
+        generation.model = deployment.clone();
+        generation.reasoning = generation::is_reasoning(&client.endpoint, deployment);
+    }
+    let (message, tokens_used, _) = client
+        .chat_completion_with_fallback(&messages, &generation, &[])
+        .await?;
+    budget::record(repo_info, tokens_used);
+    if message.content.trim().is_empty() {
+        return Err(anyhow!("概要が空でした"));
+    }
+    Ok(message.content)
+}
+
+// プロンプトに使う README の内容（README がなければ生成した概要、どちらもなければ NO_README）
+pub async fn resolve(
+    client: &LlmClient,
+    github_client: &GitHubClient,
+    repo_info: &RepoInfo,
+    repo_files: &[FileInfo],
+) -> String {
+    let default_config = ReadmeConfig::default();
+    let config = CONFIG.get().unwrap_or(&default_config);
+    let repo = format!("{}/{}", repo_info.owner, repo_info.repo);
+    let cache = READMES.get_or_init(|| Mutex::new(HashMap::new()));
+    if let Some(cached) = cache.lock().unwrap().get(&repo) {
+        return cached.clone();
+    }
+
+    let readme = match github_client.list_code_files(repo_info).await {
+        Ok((repo_dir, _)) => match find_readme(&repo_dir).await {
+            Some(name) => fs::read_to_string(repo_dir.join(&name)).await.ok(),
+            None => None,
+        },
+        Err(e) => {
+            warn!("⚠️ README を探せません: {} - {}", repo, e);
+            None
+        }
+    };
+    let content = match readme {
+        Some(content) => content,
+        None if config.generate_overview && !repo_files.is_empty() => {
+            match generate_overview(client, config, repo_info, repo_files).await {
+                Ok(overview) => {
+                    info!(
+                        "[{}] 📄 README がないため概要を生成しました: {}",
+                        client.endpoint.name, repo
+                    );
+                    format!(
+                        "（README がないため、ファイルの内容からモデルが生成した概要です）\n{}",
+                        overview.trim()
+                    )
+                }
+                Err(e) => {
+                    warn!(
+                        "[{}] ⚠️ 概要の生成エラー: {} - {}",
+                        client.endpoint.name, repo, e
+                    );
+                    // 生成に失敗した場合は次の議論で再び試す
+                    return NO_README.to_string();
+                }
+            }
+        }
+        None => NO_README.to_string(),
+    };
+    cache.lock().unwrap().insert(repo, content.clone());
+    content
+}
@@ -0,0 +1,225 @@
+// 定期実行モジュール
+// デーモンモードでcron式に従ってリポジトリの議論を定期的に登録し、完了後に前回の結果との差分レポートを保存する
+
+use chrono::{DateTime, Utc};
+use cron::Schedule;
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time;
+
+use crate::journal::{Journal, TaskState, TaskStatus};
+use crate::llm::schemas::github_response::RepoInfo;
+use crate::work_queue::{DebateJob, WorkQueue};
+use crate::{Config, diff, get_debate_types, scheduler};
+
+// スケジュールと完了状況を確認する間隔（秒）
+const CHECK_INTERVAL_SECS: u64 = 30;
+
+// 定期実行の設定
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RecurringSchedule {
+    /// cron式（秒 分 時 日 月 曜日、UTC）
+    pub cron: String,
+    /// 対象リポジトリ（"owner/repo"、空の場合は設定ファイルの repos すべて）
+    #[serde(default)]
+    pub repos: Vec<String>,
+    /// 議論タイプ（空の場合はすべて）
+    #[serde(default)]
+    pub debate_types: Vec<String>,
+}
+
+// 完了を待って差分を取る議論
+struct PendingDiff {
+    task_id: String,
+    previous_task_id: String,
+    repo_info: RepoInfo,
+    debate_type: String,
+}
+
+// 対象リポジトリの一覧を作成（設定ファイルにあるリポジトリはその設定を使う）
+fn target_repos(schedule: &RecurringSchedule, config: &Config) -> Vec<RepoInfo> {
+    if schedule.repos.is_empty() {
+        return config.repos.clone();
+    }
+
+    schedule
+        .repos
+        .iter()
+        .filter_map(|name| {
+            let Some((owner, repo)) = name.split_once('/') else {
+                warn!("⚠️ リポジトリ名の形式が正しくありません: {}", name);
+                return None;
+            };
+            Some(
+                config
+                    .repos
+                    .iter()
+                    .find(|repo_info| repo_info.owner == owner && repo_info.repo == repo)
+                    .cloned()
+                    .unwrap_or_else(|| RepoInfo {
+                        owner: owner.to_string(),
+                        repo: repo.to_string(),
+                        max_files: config.max_files,
+                        ..Default::default()
+                    }),
+            )
+        })
+        .collect()
+}
+
+// 同じリポジトリ・ブランチ・議論タイプで最後に完了した議論
+fn latest_done(journal: &Journal, repo_info: &RepoInfo, debate_type: &str) -> Option<TaskStatus> {
+    let repo = format!("{}/{}", repo_info.owner, repo_info.repo);
+    journal.list().into_iter().find(|task| {
+        task.state == TaskState::Done
+            && task.repo == repo
+            && task.branch == repo_info.branch
+            && task.debate_type == debate_type
+    })
+}
+
+// スケジュールの議論を登録
+fn fire(
+    schedule: &RecurringSchedule,
+    run_at: DateTime<Utc>,
+    queue: &WorkQueue,
+    journal: &Journal,
+    config: &Config,
+    pending: &mut Vec<PendingDiff>,
+) {
+    let debate_types = if schedule.debate_types.is_empty() {
+        get_debate_types(config)
+    } else {
+        schedule.debate_types.clone()
+    };
+
+    let mut count = 0;
+    for repo_info in target_repos(schedule, config) {
+        for debate_type in &scheduler::debate_types_for(&repo_info, &debate_types) {
+            let previous = latest_done(journal, &repo_info, debate_type);
+            let job = DebateJob::scheduled(repo_info.clone(), debate_type.clone(), run_at);
+            let task_id = job.task_id.clone();
+            queue.submit(journal, job);
+            count += 1;
+
+            // 前回の定期実行が終わっていなければ、その差分は取らずに今回のものに置き換える
+            pending.retain(|item| {
+                !(item.repo_info.owner == repo_info.owner
+                    && item.repo_info.repo == repo_info.repo
+                    && item.repo_info.branch == repo_info.branch
+                    && &item.debate_type == debate_type)
+            });
+            if let Some(previous) = previous {
+                pending.push(PendingDiff {
+                    task_id,
+                    previous_task_id: previous.task_id,
+                    repo_info: repo_info.clone(),
+                    debate_type: debate_type.clone(),
+                });
+            }
+        }
+    }
+
+    info!(
+        "⏰ 定期実行: {} - {} 件の議論を登録しました",
+        schedule.cron, count
+    );
+}
+
+// 完了した議論について前回の結果との差分レポートを保存
+async fn save_diff(journal: &Journal, output_dir: &str, pending: &PendingDiff) {
+    let (previous, current) = match (
+        journal.load_latest(&pending.previous_task_id),
+        journal.load_latest(&pending.task_id),
+    ) {
+        (Ok(Some(previous)), Ok(Some(current))) => (previous, current),
+        (Err(e), _) | (_, Err(e)) => {
+            error!("❌ 差分の作成に必要な結果を読み込めません: {}", e);
+            return;
+        }
+        _ => return,
+    };
+
+    let report = diff::render_report(&previous, &current, 1);
+    match diff::save_report(
+        output_dir,
+        &pending.repo_info,
+        &pending.debate_type,
+        &report,
+    )
+    .await
+    {
+        Ok(filename) => info!("🔀 前回との差分を保存しました: {}", filename),
+        Err(e) => error!("❌ 差分の保存エラー: {}", e),
+    }
+}
+
+// スケジュールに従って議論を登録し続ける
+pub async fn run_schedules(queue: Arc<WorkQueue>, journal: Arc<Journal>, config: Arc<Config>) {
+    let schedules: Vec<(&RecurringSchedule, Schedule)> = config
+        .serve
+        .schedules
+        .iter()
+        .filter_map(|recurring| match Schedule::from_str(&recurring.cron) {
+            Ok(schedule) => Some((recurring, schedule)),
+            Err(e) => {
+                error!("❌ cron式の解析エラー: {} - {}", recurring.cron, e);
+                None
+            }
+        })
+        .collect();
+    if schedules.is_empty() {
+        return;
+    }
+
+    let mut next_runs: Vec<Option<DateTime<Utc>>> = schedules
+        .iter()
+        .map(|(recurring, schedule)| {
+            let next = schedule.upcoming(Utc).next();
+            if let Some(next) = next {
+                info!("⏰ 次回の定期実行: {} ({})", next, recurring.cron);
+            }
+            next
+        })
+        .collect();
+    let mut pending: Vec<PendingDiff> = Vec::new();
+
+    loop {
+        // 次の実行予定か確認間隔の早い方まで待機
+        let wait = next_runs
+            .iter()
+            .flatten()
+            .min()
+            .and_then(|next| (*next - Utc::now()).to_std().ok())
+            .unwrap_or_default()
+            .min(Duration::from_secs(CHECK_INTERVAL_SECS));
+        time::sleep(wait).await;
+        let now = Utc::now();
+
+        // 完了した議論は差分を保存する（失敗した議論は再実行される可能性があるため待ち続ける）
+        let mut remaining = Vec::new();
+        for item in pending {
+            if journal.status(&item.task_id).map(|status| status.state) == Some(TaskState::Done) {
+                save_diff(&journal, &config.output_dir, &item).await;
+            } else {
+                remaining.push(item);
+            }
+        }
+        pending = remaining;
+
+        for ((recurring, schedule), next_run) in schedules.iter().zip(next_runs.iter_mut()) {
+            let Some(run_at) = *next_run else {
+                continue;
+            };
+            if run_at > now {
+                continue;
+            }
+
+            fire(recurring, run_at, &queue, &journal, &config, &mut pending);
+            *next_run = schedule.after(&now).next();
+        }
+    }
+}
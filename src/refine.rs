@@ -0,0 +1,64 @@
+// 応答の自己改善モジュール
+// 議論の応答をモデル自身に見直させ、誤りや不足を直した改善版を作らせてから保存する
+// （改善版を会話履歴に残し、元の応答は下書きとしてターンの結果に記録する。ターンあたりのトークン数はおよそ2倍になる）
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::LlmClient;
+use crate::generation::Generation;
+use crate::llm::schemas::openai_response::ChatMessage;
+
+// 自己改善の設定
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct RefineConfig {
+    /// 議論の応答を保存する前にモデル自身に見直させるか
+    pub enabled: bool,
+    /// 見直しの指示
+    pub prompt: String,
+    /// 改善版の最大出力トークン数（省略時は議論の応答と同じ）
+    pub max_tokens: Option<usize>,
+}
+
+impl Default for RefineConfig {
+    fn default() -> Self {
+        RefineConfig {
+            enabled: false,
+            prompt: "あなたの直前の回答を批判的に見直してください。\
+技術的な誤り、根拠のない断定、コードへの具体的な言及の不足、見落としている観点がないかを確認し、\
+それらを直した改善版の回答を作成してください。見直しの過程や元の回答との違いの説明は含めず、改善版の回答だけを出力してください。"
+                .to_string(),
+            max_tokens: None,
+        }
+    }
+}
+
+// 応答をモデル自身に見直させ、改善版とそのトークン数を返す
+pub async fn revise(
+    client: &LlmClient,
+    config: &RefineConfig,
+    context: &[ChatMessage],
+    generation: &Generation,
+    draft: &str,
+) -> Result<(String, usize)> {
+    let mut messages = context.to_vec();
+    messages.push(ChatMessage {
+        role: "assistant".to_string(),
+        content: draft.to_string(),
+        ..Default::default()
+    });
+    messages.push(ChatMessage {
+        role: "user".to_string(),
+        content: config.prompt.clone(),
+        ..Default::default()
+    });
+    let generation = Generation {
+        max_tokens: config.max_tokens.unwrap_or(generation.max_tokens),
+        ..generation.clone()
+    };
+    let (message, tokens_used, _) = client
+        .chat_completion_with_fallback(&messages, &generation, &[])
+        .await?;
+    Ok((message.content, tokens_used))
+}
@@ -0,0 +1,364 @@
+// 設定ファイルの再読み込みモジュール
+// 実行中に設定ファイルを監視し、追加されたリポジトリとエンドポイントを再起動せずに使い始める
+// 削除されたものはまだ始まっていない議論にだけ反映する（実行中の議論はそのまま最後まで進める）
+
+use anyhow::Result;
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tokio::task::JoinHandle;
+use tokio::time;
+
+use crate::journal::{Journal, TaskState};
+use crate::llm::schemas::github_response::RepoInfo;
+use crate::llm::schemas::openai_response::{Endpoint, ProviderKind};
+use crate::scheduler::{self, Scheduler};
+use crate::work_queue::{self, WorkQueue};
+use crate::{Config, GitHubClient, control, keys};
+
+// 設定ファイルの再読み込みの設定
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ReloadConfig {
+    /// 設定ファイルの変更を監視するか
+    pub enabled: bool,
+    /// 変更を確認する間隔（秒）
+    pub interval_secs: u64,
+}
+
+impl Default for ReloadConfig {
+    fn default() -> Self {
+        ReloadConfig {
+            enabled: true,
+            interval_secs: 10,
+        }
+    }
+}
+
+// 設定ファイルから削除されたエンドポイント
+static REMOVED: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+// 設定ファイルから削除されたエンドポイントか（ワーカーは新しい議論を取らずに終了する）
+pub fn is_removed(endpoint: &str) -> bool {
+    REMOVED.lock().unwrap().contains(endpoint)
+}
+
+// リポジトリを同じものとみなすキー
+fn repo_key(repo_info: &RepoInfo) -> (String, String, Option<String>, Option<String>) {
+    (
+        repo_info.owner.clone(),
+        repo_info.repo.clone(),
+        repo_info.branch.clone(),
+        repo_info.commit.clone(),
+    )
+}
+
+fn repo_label(repo_info: &RepoInfo) -> String {
+    match &repo_info.branch {
+        Some(branch) => format!("{}/{} ({})", repo_info.owner, repo_info.repo, branch),
+        None => format!("{}/{}", repo_info.owner, repo_info.repo),
+    }
+}
+
+// 設定ファイルの更新日時
+async fn modified(path: &str) -> Option<SystemTime> {
+    tokio::fs::metadata(path).await.ok()?.modified().ok()
+}
+
+// 監視中の状態（追加したリポジトリと途中から起動したワーカー）
+#[derive(Default)]
+struct Added {
+    repos: Vec<RepoInfo>,
+    workers: Vec<JoinHandle<Result<()>>>,
+    // メインの待機ループが終了した（以降はワーカーを起動しない）
+    closed: bool,
+}
+
+// 実行中の設定の監視
+pub struct Watcher {
+    task: JoinHandle<()>,
+    added: Arc<Mutex<Added>>,
+}
+
+impl Watcher {
+    // 途中から起動したワーカーを取り出す（メインの待機ループに加える）
+    // idle なら待機中のワーカーが残っていないので、取り出すワーカーもなければ以降はワーカーを起動しない
+    pub fn take_workers(&self, idle: bool) -> Vec<JoinHandle<Result<()>>> {
+        let mut added = self.added.lock().unwrap();
+        if idle && added.workers.is_empty() {
+            added.closed = true;
+        }
+        std::mem::take(&mut added.workers)
+    }
+
+    // 監視を止め、実行中に追加されたリポジトリを返す
+    pub fn stop(self) -> Vec<RepoInfo> {
+        self.task.abort();
+        std::mem::take(&mut self.added.lock().unwrap().repos)
+    }
+}
+
+// 設定ファイルの変更を反映する
+struct Reloader {
+    config_path: String,
+    dry_run: bool,
+    // リポジトリも設定ファイルから読み込み直すか（--repos-file で指定した場合は読み込み直さない）
+    reload_repos: bool,
+    queue: Arc<WorkQueue>,
+    github_client: Arc<GitHubClient>,
+    endpoints: Arc<Vec<Endpoint>>,
+    config: Arc<Config>,
+    scheduler: Arc<Scheduler>,
+    journal: Arc<Journal>,
+    repos: Vec<RepoInfo>,
+    added: Arc<Mutex<Added>>,
+}
+
+impl Reloader {
+    async fn reload(&mut self) {
+        let mut config = match crate::load_config(&self.config_path).await {
+            Ok(config) => config,
+            Err(e) => {
+                error!(
+                    "❌ 設定ファイルの読み込みエラー（変更は反映しません）: {}",
+                    e
+                );
+                return;
+            }
+        };
+        for endpoint in &mut config.endpoints {
+            config.api.apply(endpoint);
+            if self.dry_run {
+                endpoint.provider = ProviderKind::Mock;
+            }
+        }
+
+        keys::reload(&config.endpoints);
+        self.reload_endpoints(&config.endpoints);
+        if self.reload_repos {
+            self.reload_repos(&config.repos);
+        }
+    }
+
+    fn reload_endpoints(&mut self, endpoints: &[Endpoint]) {
+        let names: BTreeSet<&str> = endpoints
+            .iter()
+            .map(|endpoint| endpoint.name.as_str())
+            .collect();
+
+        // 削除されたエンドポイントのワーカーは、実行中の議論を終えたら終了する
+        for endpoint in self.endpoints.iter() {
+            if !names.contains(endpoint.name.as_str())
+                && REMOVED.lock().unwrap().insert(endpoint.name.clone())
+            {
+                warn!(
+                    "[{}] 🔌 設定ファイルから削除されたため、実行中の議論を終えたら停止します",
+                    endpoint.name
+                );
+            }
+        }
+
+        for endpoint in endpoints {
+            let existing = self
+                .endpoints
+                .iter()
+                .position(|known| known.name == endpoint.name);
+            let endpoint_index = match existing {
+                // 削除後に戻されたエンドポイントはワーカーを起動し直す
+                Some(index) if REMOVED.lock().unwrap().remove(&endpoint.name) => index,
+                Some(_) => continue,
+                None => {
+                    // 既存のワーカーが持つインデックスを変えないよう末尾に加える
+                    let mut all = (*self.endpoints).clone();
+                    all.push(endpoint.clone());
+                    self.endpoints = Arc::new(all);
+                    control::add_endpoint(&endpoint.name);
+                    self.scheduler.add_endpoint(endpoint)
+                }
+            };
+
+            info!("[{}] ➕ エンドポイントを追加しました", endpoint.name);
+            // 同時実行数を配分し直した分だけ起動する（既存のエンドポイントの配分より多いワーカーは次の議論を取る前に終了する）
+            let worker_count = self.scheduler.allocate_workers(control::worker_count(
+                &self.config.control,
+                self.config.concurrency,
+            ))[endpoint_index];
+            if worker_count == 0 {
+                warn!(
+                    "[{}] 👷 同時実行数の配分でワーカーが割り当てられませんでした",
+                    endpoint.name
+                );
+                continue;
+            }
+            let mut added = self.added.lock().unwrap();
+            if added.closed {
+                continue;
+            }
+            let workers = work_queue::spawn_endpoint_workers(
+                &self.queue,
+                &self.github_client,
+                &self.endpoints,
+                endpoint_index,
+                worker_count,
+                &self.config,
+                &self.scheduler,
+                &self.journal,
+            );
+            added.workers.extend(workers);
+        }
+    }
+
+    fn reload_repos(&mut self, repos: &[RepoInfo]) {
+        let keys: BTreeSet<_> = repos.iter().map(repo_key).collect();
+        let removed: Vec<RepoInfo> = self
+            .repos
+            .iter()
+            .filter(|repo_info| !keys.contains(&repo_key(repo_info)))
+            .cloned()
+            .collect();
+        if !removed.is_empty() {
+            let removed_keys: BTreeSet<_> = removed.iter().map(repo_key).collect();
+            let withdrawn = self
+                .queue
+                .withdraw(|job| removed_keys.contains(&repo_key(&job.repo_info)));
+            for repo_info in &removed {
+                info!(
+                    "➖ リポジトリが設定ファイルから削除されました: {}",
+                    repo_label(repo_info)
+                );
+            }
+            info!(
+                "➖ まだ始まっていない議論を取り下げました: {} 件",
+                withdrawn.len()
+            );
+            self.repos
+                .retain(|repo_info| !removed_keys.contains(&repo_key(repo_info)));
+        }
+
+        let known: BTreeSet<_> = self.repos.iter().map(repo_key).collect();
+        let new_repos: Vec<RepoInfo> = repos
+            .iter()
+            .filter(|repo_info| !known.contains(&repo_key(repo_info)))
+            .cloned()
+            .collect();
+        if new_repos.is_empty() {
+            return;
+        }
+
+        let jobs = scheduler::plan_jobs(
+            &new_repos,
+            &crate::get_debate_types(&self.config),
+            self.config.scheduler.perspectives,
+        );
+        let mut queued = 0;
+        for job in jobs {
+            // 完了済み・実行中の議論は積まない（取り下げた議論は積み直す）
+            let state = self.journal.status(&job.task_id).map(|status| status.state);
+            if matches!(state, Some(TaskState::Done | TaskState::Running)) {
+                continue;
+            }
+            self.journal.record(
+                &job.task_id,
+                &job.repo_info,
+                &job.debate_type,
+                TaskState::Queued,
+                None,
+                None,
+            );
+            self.queue.push(job);
+            queued += 1;
+        }
+        for repo_info in &new_repos {
+            info!("➕ リポジトリを追加しました: {}", repo_label(repo_info));
+        }
+        info!(
+            "➕ 追加したリポジトリの議論をキューに積みました: {} 件",
+            queued
+        );
+        // 他の議論がすべて終わってワーカーが終了していた場合は、ワーカーを起動し直す
+        // （メインの待機ループが終了した後に積んだ議論はジャーナルに登録済みのまま次回に回す）
+        let mut added = self.added.lock().unwrap();
+        if !added.closed && self.queue.reopen() {
+            info!("👷 追加した議論を実行するワーカーを起動し直します");
+            self.scheduler.reset_workers();
+            let workers = work_queue::spawn_workers(
+                self.queue.clone(),
+                self.github_client.clone(),
+                self.endpoints.clone(),
+                self.config.clone(),
+                self.scheduler.clone(),
+                self.journal.clone(),
+            );
+            added.workers.extend(workers);
+        }
+
+        for repo_info in &new_repos {
+            if !added
+                .repos
+                .iter()
+                .any(|known| repo_key(known) == repo_key(repo_info))
+            {
+                added.repos.push(repo_info.clone());
+            }
+        }
+        drop(added);
+        self.repos.extend(new_repos);
+    }
+}
+
+// 設定ファイルの監視を始める
+#[allow(clippy::too_many_arguments)]
+pub async fn watch(
+    config_path: String,
+    dry_run: bool,
+    // リポジトリも設定ファイルから読み込み直すか（--repos-file で指定した場合は読み込み直さない）
+    reload_repos: bool,
+    queue: Arc<WorkQueue>,
+    github_client: Arc<GitHubClient>,
+    endpoints: Arc<Vec<Endpoint>>,
+    config: Arc<Config>,
+    scheduler: Arc<Scheduler>,
+    journal: Arc<Journal>,
+) -> Watcher {
+    let added = Arc::new(Mutex::new(Added::default()));
+    let interval = Duration::from_secs(config.reload.interval_secs.max(1));
+    let mut last_modified = modified(&config_path).await;
+    let mut reloader = Reloader {
+        config_path,
+        dry_run,
+        reload_repos,
+        queue,
+        github_client,
+        endpoints,
+        repos: config.repos.clone(),
+        config,
+        scheduler,
+        journal,
+        added: added.clone(),
+    };
+
+    info!(
+        "👀 設定ファイルの変更を {} 秒ごとに確認します: {}",
+        interval.as_secs(),
+        reloader.config_path
+    );
+    let task = tokio::spawn(async move {
+        loop {
+            time::sleep(interval).await;
+            let current = modified(&reloader.config_path).await;
+            if current.is_none() || current == last_modified {
+                continue;
+            }
+            last_modified = current;
+            info!(
+                "🔄 設定ファイルが更新されました。リポジトリとエンドポイントを読み込み直します: {}",
+                reloader.config_path
+            );
+            reloader.reload().await;
+        }
+    });
+
+    Watcher { task, added }
+}
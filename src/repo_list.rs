@@ -0,0 +1,140 @@
+// リポジトリ一覧の読み込みモジュール
+// --repos-file で指定したファイル（- なら標準入力）から分析対象のリポジトリを読み込み、
+// `gh repo list` などのシェルのパイプラインで一覧を作れるようにする
+
+use anyhow::{Result, anyhow};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::io::Read;
+use tokio::fs;
+
+use crate::llm::schemas::github_response::RepoInfo;
+
+// owner/repo 形式のリポジトリ名を分解（GitHubのURLも受け付ける）
+pub fn parse_name(name: &str) -> Option<(String, String)> {
+    let name = name.trim();
+    let name = name
+        .strip_prefix("https://github.com/")
+        .or_else(|| name.strip_prefix("git@github.com:"))
+        .unwrap_or(name);
+    let name = name.trim_end_matches('/');
+    let name = name.strip_suffix(".git").unwrap_or(name);
+
+    let (owner, repo) = name.split_once('/')?;
+    if owner.is_empty()
+        || repo.is_empty()
+        || repo.contains('/')
+        || name.contains(char::is_whitespace)
+    {
+        return None;
+    }
+    Some((owner.to_string(), repo.to_string()))
+}
+
+// JSONの配列から読み込む（`gh repo list --json nameWithOwner` の出力をそのまま渡せる）
+fn parse_json(value: &Value) -> Result<Vec<(String, String)>> {
+    let items = value
+        .as_array()
+        .ok_or_else(|| anyhow!("リポジトリ一覧のJSONは配列である必要があります"))?;
+
+    let mut names = Vec::new();
+    for (i, item) in items.iter().enumerate() {
+        let name = match item {
+            Value::String(name) => Some(name.clone()),
+            Value::Object(object) => ["nameWithOwner", "full_name", "fullName", "url"]
+                .iter()
+                .find_map(|key| object.get(*key).and_then(Value::as_str))
+                .map(str::to_string)
+                .or_else(|| {
+                    match (
+                        object.get("owner"),
+                        object.get("repo").or(object.get("name")),
+                    ) {
+                        (Some(owner), Some(Value::String(repo))) => {
+                            // gh の owner は {"login": ...} の形で返る
+                            let owner = owner.as_str().or_else(|| owner["login"].as_str())?;
+                            Some(format!("{}/{}", owner, repo))
+                        }
+                        _ => None,
+                    }
+                }),
+            _ => None,
+        };
+        let parsed = name.as_deref().and_then(parse_name).ok_or_else(|| {
+            anyhow!(
+                "リポジトリ一覧の {} 番目の要素を owner/repo として読めません: {}",
+                i + 1,
+                item
+            )
+        })?;
+        names.push(parsed);
+    }
+    Ok(names)
+}
+
+// 1行に1つの owner/repo から読み込む（空行と # 以降は無視）
+fn parse_lines(text: &str) -> Result<Vec<(String, String)>> {
+    let mut names = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+        let parsed = parse_name(line).ok_or_else(|| {
+            anyhow!(
+                "リポジトリ一覧の {} 行目を owner/repo として読めません: {}",
+                i + 1,
+                line
+            )
+        })?;
+        names.push(parsed);
+    }
+    Ok(names)
+}
+
+// リポジトリ一覧を解析（重複は最初のものだけ残す）
+pub fn parse(text: &str, max_files: usize) -> Result<Vec<RepoInfo>> {
+    let names = match text.trim_start().starts_with('[') {
+        true => parse_json(
+            &serde_json::from_str(text)
+                .map_err(|e| anyhow!("リポジトリ一覧のJSONを解析できません: {}", e))?,
+        )?,
+        false => parse_lines(text)?,
+    };
+
+    let mut seen = HashSet::new();
+    Ok(names
+        .into_iter()
+        .filter(|name| seen.insert(name.clone()))
+        .map(|(owner, repo)| RepoInfo {
+            owner,
+            repo,
+            max_files,
+            ..Default::default()
+        })
+        .collect())
+}
+
+// ファイル（- なら標準入力）からリポジトリ一覧を読み込む
+pub async fn load(path: &str, max_files: usize) -> Result<Vec<RepoInfo>> {
+    let text = if path == "-" {
+        let mut text = String::new();
+        std::io::stdin()
+            .read_to_string(&mut text)
+            .map_err(|e| anyhow!("標準入力からリポジトリ一覧を読み込めません: {}", e))?;
+        text
+    } else {
+        fs::read_to_string(path)
+            .await
+            .map_err(|e| anyhow!("リポジトリ一覧を読み込めません: {} - {}", path, e))?
+    };
+
+    let repos = parse(&text, max_files)?;
+    if repos.is_empty() {
+        return Err(anyhow!(
+            "リポジトリ一覧に分析対象のリポジトリがありません: {}",
+            path
+        ));
+    }
+    Ok(repos)
+}
@@ -0,0 +1,424 @@
+// 実行状況のレポートモジュール
+// 保存済みの分析結果に記録したターンごとの計測値から、エンドポイントごとの応答時間（p50 / p99）と
+// リトライ・エラー応答の回数を集計し、遅いリージョンを見つけられるようにする
+// API呼び出しごとの使用量を CSV / Parquet に書き出し、Excel やデータウェアハウスで分析できるようにする
+// リポジトリごとに、以前の応答と重複したターンの数も集計する
+
+use anyhow::{Result, anyhow, bail};
+use arrow::array::{ArrayRef, Float64Array, RecordBatch, StringArray, UInt64Array};
+use arrow::datatypes::{DataType, Field, Schema};
+use clap::ValueEnum;
+use log::{info, warn};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Arc;
+
+use crate::llm::schemas::openai_response::{ApiCall, TurnMetrics};
+use crate::{dedupe, ledger, outputs, pricing};
+
+// レポートの出力形式
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum ReportFormat {
+    /// エンドポイントごとの応答時間の Markdown の表
+    Table,
+    /// API呼び出しごとの使用量の CSV
+    Csv,
+    /// API呼び出しごとの使用量の Parquet
+    Parquet,
+}
+
+// エクスポートする1回のAPI呼び出し
+#[derive(Serialize)]
+struct UsageRow {
+    timestamp: String,
+    endpoint: String,
+    repo: String,
+    debate_type: String,
+    turn: usize,
+    model: String,
+    prompt_tokens: usize,
+    completion_tokens: usize,
+    total_tokens: usize,
+    latency_ms: u64,
+    estimated_cost: Option<f64>,
+    currency: String,
+}
+
+// エンドポイントごとの集計
+#[derive(Default)]
+struct EndpointStats {
+    latencies: Vec<u64>,
+    requests: usize,
+    retries: usize,
+    // 表示用の通貨での推定コストの合計
+    cost: f64,
+    // 2xx 以外のHTTPステータスごとの回数
+    errors: BTreeMap<u16, usize>,
+}
+
+// 小さい順に並べた値のパーセンタイル（最近接順位法）
+fn percentile(sorted: &[u64], p: f64) -> u64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let rank = ((p / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+// API呼び出しの推定コストを表示用の通貨で求める（保存時に求めたコストがなければ現在の料金表で求める）
+fn call_cost(call: &ApiCall, metrics: &TurnMetrics) -> Option<(f64, String)> {
+    let (cost, currency) = match (call.estimated_cost, &metrics.currency) {
+        (Some(cost), Some(currency)) => (cost, currency.as_str()),
+        _ => (
+            pricing::estimate(&call.model, call.prompt_tokens, call.completion_tokens)?,
+            pricing::currency(),
+        ),
+    };
+    Some(pricing::convert(cost, currency))
+}
+
+// 指定された形式でレポートを出力
+pub fn run_report(
+    base_dir: &str,
+    format: ReportFormat,
+    output: Option<&str>,
+    dry_run: bool,
+) -> Result<()> {
+    match format {
+        ReportFormat::Table => {
+            print_latency_table(base_dir)?;
+            print_ledger_table(base_dir, dry_run);
+            print_duplicate_table(base_dir);
+            Ok(())
+        }
+        ReportFormat::Csv => write_csv(&usage_rows(base_dir), output),
+        ReportFormat::Parquet => {
+            let default_path = format!("{}/usage.parquet", base_dir);
+            write_parquet(&usage_rows(base_dir), output.unwrap_or(&default_path))
+        }
+    }
+}
+
+// 保存済みのターンからAPI呼び出しごとの行を集める（呼び出しの記録がないターンは含めない）
+fn usage_rows(base_dir: &str) -> Vec<UsageRow> {
+    let mut rows = Vec::new();
+    let mut unrecorded = 0;
+    for (_, response) in outputs::load_responses(base_dir) {
+        let metrics = response.metrics.unwrap_or_default();
+        if metrics.calls.is_empty() {
+            unrecorded += 1;
+            continue;
+        }
+        for call in &metrics.calls {
+            let (estimated_cost, currency) = match call_cost(call, &metrics) {
+                Some((cost, currency)) => (Some(cost), currency),
+                None => (None, pricing::display_currency().to_string()),
+            };
+            rows.push(UsageRow {
+                estimated_cost,
+                currency,
+                timestamp: call.timestamp.clone(),
+                endpoint: response.endpoint.clone(),
+                repo: response.repo.clone(),
+                debate_type: response.debate_type.clone(),
+                turn: response.turn,
+                model: call.model.clone(),
+                prompt_tokens: call.prompt_tokens,
+                completion_tokens: call.completion_tokens,
+                total_tokens: call.prompt_tokens + call.completion_tokens,
+                latency_ms: call.latency_ms,
+            });
+        }
+    }
+    rows.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
+    if unrecorded > 0 {
+        warn!(
+            "⚠️ API呼び出しの記録がない {} ターン（以前のバージョンや Batch API の結果）は含めていません",
+            unrecorded
+        );
+    }
+    rows
+}
+
+// CSV で書き出す（出力先を省略した場合は標準出力）
+fn write_csv(rows: &[UsageRow], output: Option<&str>) -> Result<()> {
+    let writer: Box<dyn std::io::Write> = match output {
+        Some(path) => Box::new(
+            std::fs::File::create(path)
+                .map_err(|e| anyhow!("出力ファイルを作成できません: {} - {}", path, e))?,
+        ),
+        None => Box::new(std::io::stdout()),
+    };
+    let mut writer = csv::Writer::from_writer(writer);
+    for row in rows {
+        writer.serialize(row)?;
+    }
+    writer.flush()?;
+    if let Some(path) = output {
+        info!("📈 API呼び出し {} 件を書き出しました: {}", rows.len(), path);
+    }
+    Ok(())
+}
+
+// Parquet で書き出す
+fn write_parquet(rows: &[UsageRow], path: &str) -> Result<()> {
+    if rows.is_empty() {
+        bail!("書き出すAPI呼び出しの記録がありません");
+    }
+    let strings = |value: fn(&UsageRow) -> &str| -> ArrayRef {
+        Arc::new(StringArray::from_iter_values(rows.iter().map(value)))
+    };
+    let numbers = |value: fn(&UsageRow) -> u64| -> ArrayRef {
+        Arc::new(UInt64Array::from_iter_values(rows.iter().map(value)))
+    };
+    let schema = Schema::new(vec![
+        Field::new("timestamp", DataType::Utf8, false),
+        Field::new("endpoint", DataType::Utf8, false),
+        Field::new("repo", DataType::Utf8, false),
+        Field::new("debate_type", DataType::Utf8, false),
+        Field::new("turn", DataType::UInt64, false),
+        Field::new("model", DataType::Utf8, false),
+        Field::new("prompt_tokens", DataType::UInt64, false),
+        Field::new("completion_tokens", DataType::UInt64, false),
+        Field::new("total_tokens", DataType::UInt64, false),
+        Field::new("latency_ms", DataType::UInt64, false),
+        Field::new("estimated_cost", DataType::Float64, true),
+        Field::new("currency", DataType::Utf8, false),
+    ]);
+    let columns: Vec<ArrayRef> = vec![
+        strings(|row| &row.timestamp),
+        strings(|row| &row.endpoint),
+        strings(|row| &row.repo),
+        strings(|row| &row.debate_type),
+        numbers(|row| row.turn as u64),
+        strings(|row| &row.model),
+        numbers(|row| row.prompt_tokens as u64),
+        numbers(|row| row.completion_tokens as u64),
+        numbers(|row| row.total_tokens as u64),
+        numbers(|row| row.latency_ms),
+        Arc::new(Float64Array::from_iter(
+            rows.iter().map(|row| row.estimated_cost),
+        )),
+        strings(|row| &row.currency),
+    ];
+    let batch = RecordBatch::try_new(Arc::new(schema), columns)?;
+
+    let file = std::fs::File::create(path)
+        .map_err(|e| anyhow!("出力ファイルを作成できません: {} - {}", path, e))?;
+    let properties = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .build();
+    let mut writer = ArrowWriter::try_new(file, batch.schema(), Some(properties))?;
+    writer.write(&batch)?;
+    writer.close()?;
+    info!("📈 API呼び出し {} 件を書き出しました: {}", rows.len(), path);
+    Ok(())
+}
+
+// エンドポイントごとの応答時間を集計して表示
+fn print_latency_table(base_dir: &str) -> Result<()> {
+    let mut stats: BTreeMap<String, EndpointStats> = BTreeMap::new();
+    let mut unmeasured = 0;
+    for (_, response) in outputs::load_responses(base_dir) {
+        let Some(metrics) = response.metrics else {
+            unmeasured += 1;
+            continue;
+        };
+        let entry = stats.entry(response.endpoint).or_default();
+        entry.latencies.push(metrics.latency_ms);
+        entry.requests += metrics.requests;
+        entry.retries += metrics.retries;
+        entry.cost += metrics
+            .calls
+            .iter()
+            .filter_map(|call| call_cost(call, &metrics))
+            .map(|(cost, _)| cost)
+            .sum::<f64>();
+        for status in metrics.http_statuses {
+            if !(200..300).contains(&status) {
+                *entry.errors.entry(status).or_default() += 1;
+            }
+        }
+    }
+
+    if stats.is_empty() {
+        println!("計測値が記録されたターンがありません: {}", base_dir);
+        return Ok(());
+    }
+
+    // p99 の遅い順に並べる
+    let mut rows: Vec<(String, EndpointStats)> = stats.into_iter().collect();
+    for (_, entry) in rows.iter_mut() {
+        entry.latencies.sort_unstable();
+    }
+    rows.sort_by_key(|(_, entry)| std::cmp::Reverse(percentile(&entry.latencies, 99.0)));
+
+    println!("# エンドポイントごとの応答時間\n");
+    println!(
+        "| エンドポイント | ターン数 | リクエスト数 | p50 (秒) | p99 (秒) | 平均 (秒) | リトライ | エラー応答 | 推定コスト ({}) |",
+        pricing::display_currency()
+    );
+    println!("|---|---:|---:|---:|---:|---:|---:|---|---:|");
+    for (endpoint, entry) in &rows {
+        let mean = entry.latencies.iter().sum::<u64>() as f64 / entry.latencies.len() as f64;
+        let errors = entry
+            .errors
+            .iter()
+            .map(|(status, count)| format!("{}×{}", status, count))
+            .collect::<Vec<_>>()
+            .join(", ");
+        println!(
+            "| {} | {} | {} | {:.1} | {:.1} | {:.1} | {} | {} | {:.2} |",
+            endpoint,
+            entry.latencies.len(),
+            entry.requests,
+            percentile(&entry.latencies, 50.0) as f64 / 1000.0,
+            percentile(&entry.latencies, 99.0) as f64 / 1000.0,
+            mean / 1000.0,
+            entry.retries,
+            if errors.is_empty() { "-" } else { &errors },
+            entry.cost
+        );
+    }
+    if unmeasured > 0 {
+        println!(
+            "\n※ 計測値のない {} ターン（以前のバージョンや Batch API の結果）は集計していません",
+            unmeasured
+        );
+    }
+    Ok(())
+}
+
+// リポジトリごとの重複した応答の集計
+#[derive(Default)]
+struct DuplicateStats {
+    turns: usize,
+    duplicates: usize,
+    duplicate_tokens: usize,
+    seen: HashSet<String>,
+}
+
+// 台帳のエンドポイント・モデルごと（またはグループごと）の集計
+#[derive(Default)]
+struct LedgerStats {
+    calls: usize,
+    tokens: usize,
+    cost: f64,
+    unpriced: usize,
+}
+
+impl LedgerStats {
+    // 台帳の1行を加え、表示用の通貨を返す（単価がない呼び出しは None）
+    fn add(&mut self, entry: &ledger::LedgerEntry) -> Option<String> {
+        self.calls += 1;
+        self.tokens += entry.total_tokens;
+        match entry.estimated_cost {
+            Some(cost) => {
+                let (cost, display) = pricing::convert(cost, &entry.currency);
+                self.cost += cost;
+                Some(display)
+            }
+            None => {
+                self.unpriced += 1;
+                None
+            }
+        }
+    }
+}
+
+// 台帳（ledger.jsonl）から、エンドポイントとモデルごとの推定使用額を集計して表示
+// 保存したターンに含まれない呼び出し（サマリーや指摘事項の抽出など）も含む
+fn print_ledger_table(base_dir: &str, dry_run: bool) {
+    let entries = ledger::load(base_dir, dry_run);
+    if entries.is_empty() {
+        return;
+    }
+
+    let mut stats: BTreeMap<(String, String), LedgerStats> = BTreeMap::new();
+    let mut groups: BTreeMap<String, LedgerStats> = BTreeMap::new();
+    let mut total = LedgerStats::default();
+    let mut currency = pricing::display_currency().to_string();
+    for entry in &entries {
+        stats
+            .entry((entry.endpoint.clone(), entry.model.clone()))
+            .or_default()
+            .add(entry);
+        for group in entry.groups() {
+            groups.entry(group.clone()).or_default().add(entry);
+        }
+        if let Some(display) = total.add(entry) {
+            currency = display;
+        }
+    }
+
+    println!("\n# 推定使用額（台帳）\n");
+    println!("| エンドポイント | モデル | 呼び出し | トークン数 | 推定コスト | 単価なし |");
+    println!("|---|---|---:|---:|---:|---:|");
+    for ((endpoint, model), row) in &stats {
+        println!(
+            "| {} | {} | {} | {} | {:.2} {} | {} |",
+            endpoint, model, row.calls, row.tokens, row.cost, currency, row.unpriced
+        );
+    }
+    println!(
+        "| **合計** | | {} | {} | {:.2} {} | {} |",
+        total.calls, total.tokens, total.cost, currency, total.unpriced
+    );
+
+    // エンドポイントにサブスクリプションやテナントを設定した場合は、グループごとにも集計する
+    if groups.is_empty() {
+        return;
+    }
+    println!("\n# グループごとの推定使用額（台帳）\n");
+    println!("| グループ | 呼び出し | トークン数 | 推定コスト | 単価なし |");
+    println!("|---|---:|---:|---:|---:|");
+    for (group, row) in &groups {
+        println!(
+            "| {} | {} | {} | {:.2} {} | {} |",
+            group, row.calls, row.tokens, row.cost, currency, row.unpriced
+        );
+    }
+}
+
+// リポジトリごとに、以前の応答と同じ内容のターンを集計して表示
+fn print_duplicate_table(base_dir: &str) {
+    let mut responses = outputs::load_responses(base_dir);
+    responses.sort_by(|(_, a), (_, b)| a.timestamp.cmp(&b.timestamp));
+
+    let mut stats: BTreeMap<String, DuplicateStats> = BTreeMap::new();
+    for (_, response) in &responses {
+        let entry = stats.entry(response.repo.clone()).or_default();
+        entry.turns += 1;
+        let Some(hash) = response
+            .content_hash
+            .clone()
+            .or_else(|| dedupe::assistant_content(response).and_then(dedupe::duplicate_key))
+        else {
+            continue;
+        };
+        if !entry.seen.insert(hash) {
+            entry.duplicates += 1;
+            entry.duplicate_tokens += response.tokens_used;
+        }
+    }
+    if stats.values().all(|entry| entry.duplicates == 0) {
+        return;
+    }
+
+    println!("\n# 重複した応答\n");
+    println!("| リポジトリ | ターン数 | 重複 | 重複率 | 重複のトークン数 |");
+    println!("|---|---:|---:|---:|---:|");
+    for (repo, entry) in stats.iter().filter(|(_, entry)| entry.duplicates > 0) {
+        println!(
+            "| {} | {} | {} | {:.1}% | {} |",
+            repo,
+            entry.turns,
+            entry.duplicates,
+            entry.duplicates as f64 / entry.turns as f64 * 100.0,
+            entry.duplicate_tokens
+        );
+    }
+}
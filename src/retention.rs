@@ -0,0 +1,154 @@
+// 出力の保持期間モジュール
+// 古いターンのファイルを削除して出力ディレクトリの肥大化を防ぐ
+// 各ターンのファイルはそれまでの会話履歴をすべて含むため、議論ごとの最終ターンは残し、
+// サマリー・指摘事項・実行記録などターン以外のファイルは削除しない
+// ファイル内容のキャッシュは、保持期間を過ぎた内容を取り除く
+
+use anyhow::{Result, bail};
+use chrono::prelude::*;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::path::PathBuf;
+
+use crate::file_cache::{self, FileCacheConfig};
+use crate::outputs;
+
+// 保持期間の設定
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct RetentionConfig {
+    /// ターンのファイルを残す日数（省略時は日数では削除しない）
+    pub max_age_days: Option<u64>,
+    /// リポジトリごとのターンのファイルの合計サイズの上限（MB、超えた分は古いものから削除する）
+    pub max_repo_size_mb: Option<u64>,
+}
+
+// 削除を検討するターンのファイル
+struct TurnFile {
+    path: PathBuf,
+    size: u64,
+    timestamp: DateTime<Utc>,
+    // 議論の最終ターン（削除しない）
+    is_final: bool,
+}
+
+// リポジトリごとのターンのファイルを集める
+fn collect_turn_files(base_dir: &str) -> BTreeMap<String, Vec<TurnFile>> {
+    let responses = outputs::load_responses(base_dir);
+
+    // 議論ごとの最終ターン
+    let mut finals: BTreeMap<String, (usize, PathBuf)> = BTreeMap::new();
+    for (path, response) in &responses {
+        let key = outputs::debate_key(response);
+        if finals
+            .get(&key)
+            .is_none_or(|(turn, _)| response.turn > *turn)
+        {
+            finals.insert(key, (response.turn, path.clone()));
+        }
+    }
+    let finals: HashSet<PathBuf> = finals.into_values().map(|(_, path)| path).collect();
+
+    let mut repos: BTreeMap<String, Vec<TurnFile>> = BTreeMap::new();
+    for (path, response) in responses {
+        let Ok(metadata) = std::fs::metadata(&path) else {
+            warn!("⚠️ ファイルの情報を取得できません: {}", path.display());
+            continue;
+        };
+        let timestamp = DateTime::parse_from_rfc3339(&response.timestamp)
+            .map(|t| t.with_timezone(&Utc))
+            .unwrap_or_else(|_| Utc::now());
+        repos.entry(response.repo).or_default().push(TurnFile {
+            is_final: finals.contains(&path),
+            path,
+            size: metadata.len(),
+            timestamp,
+        });
+    }
+    repos
+}
+
+// 保持期間を過ぎたターンと、サイズの上限を超えた分の古いターンのファイルを削除する
+// （保持期間を設定した場合は、ファイル内容のキャッシュからも古い内容を取り除く）
+pub fn clean_outputs(
+    base_dir: &str,
+    config: &RetentionConfig,
+    file_cache: &FileCacheConfig,
+    dry_run: bool,
+) -> Result<()> {
+    if config.max_age_days.is_none() && config.max_repo_size_mb.is_none() {
+        bail!("retention に max_age_days か max_repo_size_mb を設定してください");
+    }
+    let cutoff = config
+        .max_age_days
+        .map(|days| Utc::now() - chrono::Duration::days(days as i64));
+    let max_size = config.max_repo_size_mb.map(|mb| mb * 1024 * 1024);
+
+    let mut total_files = 0;
+    let mut total_bytes = 0;
+    for (repo, mut files) in collect_turn_files(base_dir) {
+        files.sort_by_key(|file| file.timestamp);
+        let mut size: u64 = files.iter().map(|file| file.size).sum();
+
+        let mut deleted = Vec::new();
+        for file in files.iter().filter(|file| !file.is_final) {
+            let expired = cutoff.is_some_and(|cutoff| file.timestamp < cutoff);
+            let oversized = max_size.is_some_and(|max_size| size > max_size);
+            if expired || oversized {
+                size -= file.size;
+                deleted.push(file);
+            }
+        }
+        if deleted.is_empty() {
+            continue;
+        }
+
+        let bytes: u64 = deleted.iter().map(|file| file.size).sum();
+        if !dry_run {
+            for file in &deleted {
+                if let Err(e) = std::fs::remove_file(&file.path) {
+                    warn!(
+                        "⚠️ ファイルを削除できません: {} - {}",
+                        file.path.display(),
+                        e
+                    );
+                }
+            }
+        }
+        info!(
+            "🧹 {}: 古いターンのファイル {} 件（{:.1} MB）を削除{}",
+            repo,
+            deleted.len(),
+            bytes as f64 / 1024.0 / 1024.0,
+            if dry_run { "します" } else { "しました" }
+        );
+        if max_size.is_some_and(|max_size| size > max_size) {
+            warn!(
+                "⚠️ {}: 最終ターンだけでサイズの上限を超えています（{:.1} MB）",
+                repo,
+                size as f64 / 1024.0 / 1024.0
+            );
+        }
+        total_files += deleted.len();
+        total_bytes += bytes;
+    }
+
+    let megabytes = total_bytes as f64 / 1024.0 / 1024.0;
+    if dry_run {
+        info!(
+            "🧪 ドライランのため削除しません（対象 {} 件, {:.1} MB）",
+            total_files, megabytes
+        );
+    } else {
+        info!(
+            "🧹 合計 {} 件（{:.1} MB）のターンのファイルを削除しました",
+            total_files, megabytes
+        );
+    }
+
+    if let Some(cutoff) = cutoff {
+        file_cache::prune(file_cache, base_dir, cutoff, dry_run)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,326 @@
+// ロールプレイ討論モジュール
+// アーキテクト・セキュリティエンジニア・SRE・プロダクトマネージャーなどの役割ごとに別の会話を持ち、
+// 順番に他の役割の発言へ応答させる（1つの会話より多面的な分析になり、リポジトリあたりのリクエスト数も増える）
+
+use anyhow::{Result, bail};
+use chrono::prelude::*;
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time;
+
+use crate::error::BurnerError;
+use crate::generation::Generation;
+use crate::journal::Journal;
+use crate::llm::schemas::openai_response::{ChatMessage, RESPONSE_SCHEMA_VERSION, ResponseData};
+use crate::scheduler::Scheduler;
+use crate::work_queue::DebateJob;
+use crate::{
+    Config, DebateOutcome, GitHubClient, LlmClient, budget, deadline, findings, generation, ledger,
+    prepare_debate_messages, runs, save_response,
+};
+
+// ロールプレイ討論の議論タイプ
+pub const ROLEPLAY_TYPE: &str = "ロールプレイ討論";
+
+// 討論に参加する役割
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Persona {
+    /// 役割の名前（発言者として他の役割に伝える）
+    pub name: String,
+    /// 役割の立場と関心事（システムプロンプトの先頭に加える）
+    pub instruction: String,
+}
+
+// ロールプレイ討論の設定
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct RolePlayConfig {
+    /// すべてのリポジトリの議論タイプにロールプレイ討論を加えるか（無効でも --debate roleplay で指定できる）
+    pub enabled: bool,
+    /// 全員が1回ずつ発言するのを1ラウンドとしたラウンド数
+    pub rounds: usize,
+    /// 1回の発言の最大出力トークン数
+    pub max_tokens: usize,
+    /// 参加する役割（発言する順）
+    pub personas: Vec<Persona>,
+}
+
+impl Default for RolePlayConfig {
+    fn default() -> Self {
+        let persona = |name: &str, instruction: &str| Persona {
+            name: name.to_string(),
+            instruction: instruction.to_string(),
+        };
+        RolePlayConfig {
+            enabled: false,
+            rounds: 3,
+            max_tokens: 2000,
+            personas: vec![
+                persona(
+                    "アーキテクト",
+                    "あなたはソフトウェアアーキテクトです。モジュールの分割、依存関係、拡張性、設計上のトレードオフに注目してください。",
+                ),
+                persona(
+                    "セキュリティエンジニア",
+                    "あなたはセキュリティエンジニアです。入力の検証、認証・認可、秘密情報の扱い、依存パッケージの脆弱性に注目してください。",
+                ),
+                persona(
+                    "SRE",
+                    "あなたはSRE（サイト信頼性エンジニア）です。運用のしやすさ、監視、障害時の挙動、性能とスケーラビリティに注目してください。",
+                ),
+                persona(
+                    "プロダクトマネージャー",
+                    "あなたはプロダクトマネージャーです。利用者にとっての価値、機能の優先順位、ドキュメント、ロードマップに注目してください。",
+                ),
+            ],
+        }
+    }
+}
+
+// 討論の進め方の指示（役割のシステムプロンプトの末尾に加える）
+const DISCUSSION_RULES: &str = "\n\nあなたは複数の専門家による討論に参加しています。\
+他の参加者の発言を踏まえ、賛成・反論・補足を明確にしながら、自分の役割の観点でコードを具体的に引用して発言してください。\
+発言は簡潔にまとめ、他の参加者の役割を演じないでください。";
+
+// 1人の役割の会話
+struct Thread {
+    persona: Persona,
+    messages: Vec<ChatMessage>,
+    // まだこの役割に伝えていない他の役割の発言
+    unheard: Vec<(String, String)>,
+}
+
+impl Thread {
+    // 前回の発言以降の他の役割の発言をまとめて、次の発言を促す
+    // 初めて発言する役割には、討論の題材として最初の質問も伝える（以降は会話履歴に残る）
+    fn next_prompt(&mut self, opening: &str) -> String {
+        let first = self.messages.iter().all(|message| message.role == "system");
+        if self.unheard.is_empty() {
+            return format!(
+                "{}\nあなたから討論を始めてください。あなたの役割の観点で最も重要だと考える点を挙げてください。",
+                opening
+            );
+        }
+        let statements = self
+            .unheard
+            .drain(..)
+            .map(|(name, content)| format!("【{}】\n{}", name, content))
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        let prompt = format!(
+            "これまでの他の参加者の発言です。\n\n{}\n\n{}として、これらの発言に応答してください。",
+            statements, self.persona.name
+        );
+        if first {
+            format!("{}\n\n{}", opening, prompt)
+        } else {
+            prompt
+        }
+    }
+}
+
+// ロールプレイ討論を実行
+#[allow(clippy::too_many_arguments)]
+pub async fn roleplay_runner(
+    github_client: &GitHubClient,
+    openai_client: &LlmClient,
+    job: &DebateJob,
+    endpoint_index: usize,
+    config: &Arc<Config>,
+    scheduler: &Scheduler,
+    journal: &Journal,
+) -> Result<DebateOutcome> {
+    let endpoint_name = &openai_client.endpoint.name;
+    let repo_info = &job.repo_info;
+    let roleplay = &config.roleplay;
+    if roleplay.personas.is_empty() {
+        bail!("ロールプレイ討論の役割（roleplay.personas）がありません");
+    }
+
+    info!(
+        "[{}] 🎭 ロールプレイ討論開始: {}/{} - {}",
+        endpoint_name,
+        repo_info.owner,
+        repo_info.repo,
+        roleplay
+            .personas
+            .iter()
+            .map(|persona| persona.name.as_str())
+            .collect::<Vec<_>>()
+            .join(" / ")
+    );
+
+    // 途中から再開せず、リポジトリの情報から役割ごとの会話を作り直す
+    let base = prepare_debate_messages(
+        openai_client,
+        github_client,
+        repo_info,
+        &job.debate_type,
+        config,
+    )
+    .await?;
+    let opening = base[1].content.clone();
+    let mut threads: Vec<Thread> = roleplay
+        .personas
+        .iter()
+        .map(|persona| Thread {
+            persona: persona.clone(),
+            messages: vec![ChatMessage {
+                role: "system".to_string(),
+                content: format!(
+                    "{}\n\n{}{}",
+                    persona.instruction, base[0].content, DISCUSSION_RULES
+                ),
+                ..Default::default()
+            }],
+            unheard: Vec::new(),
+        })
+        .collect();
+
+    // 指摘事項の抽出に渡す、発言者付きの全員の発言
+    let mut statements: Vec<ChatMessage> = Vec::new();
+
+    let total_turns = roleplay.rounds.max(1) * threads.len();
+    let mut consecutive_errors = 0;
+    let mut turn = 1;
+    while turn <= total_turns {
+        if deadline::reached() || ledger::ceiling_reached() {
+            return Ok(DebateOutcome::Interrupted);
+        }
+        if budget::exhausted(repo_info) {
+            return Ok(DebateOutcome::OverBudget);
+        }
+
+        let speaker = (turn - 1) % threads.len();
+        let thread = &mut threads[speaker];
+        if thread
+            .messages
+            .last()
+            .is_none_or(|message| message.role != "user")
+        {
+            let prompt = thread.next_prompt(&opening);
+            thread.messages.push(ChatMessage {
+                role: "user".to_string(),
+                content: prompt,
+                ..Default::default()
+            });
+        }
+
+        info!(
+            "[{}] 🎭 {} の発言: {}/{} - ターン {}/{}",
+            endpoint_name, thread.persona.name, repo_info.owner, repo_info.repo, turn, total_turns
+        );
+        openai_client.reset_metrics();
+        let started = std::time::Instant::now();
+        let generation = Generation {
+            max_tokens: roleplay.max_tokens,
+            ..generation::resolve(&openai_client.endpoint, &job.debate_type)
+        };
+        let completion =
+            openai_client.chat_completion_with_fallback(&thread.messages, &generation, &[]);
+        let limit = config
+            .timeouts
+            .turn_for(generation::may_reason(&openai_client.endpoint, &generation));
+        match deadline::with_timeout(limit, "ターン", completion).await {
+            Ok((message, tokens_used, answered)) => {
+                let response = message.content;
+                consecutive_errors = 0;
+                scheduler.record(endpoint_index, tokens_used, started.elapsed());
+                budget::record(repo_info, tokens_used);
+
+                thread.messages.push(ChatMessage {
+                    role: "assistant".to_string(),
+                    content: response.clone(),
+                    ..Default::default()
+                });
+                let name = thread.persona.name.clone();
+                let mut response_data = ResponseData {
+                    schema_version: RESPONSE_SCHEMA_VERSION,
+                    repo: format!("{}/{}", repo_info.owner, repo_info.repo),
+                    debate_type: job.debate_type.clone(),
+                    turn,
+                    timestamp: Utc::now().to_rfc3339(),
+                    endpoint: endpoint_name.clone(),
+                    category: Some(name.clone()),
+                    messages: thread.messages.clone(),
+                    tokens_used,
+                    context_summary: None,
+                    run_id: runs::current_id(),
+                    file: None,
+                    metrics: Some(openai_client.take_metrics(started.elapsed())),
+                    task_id: Some(job.task_id.clone()),
+                    rejected: Vec::new(),
+                    content_hash: None,
+                    duplicate_of: None,
+                    model: Some(answered.model),
+                    draft: None,
+                };
+
+                statements.push(ChatMessage {
+                    role: "assistant".to_string(),
+                    content: format!("【{}】\n{}", name, response),
+                    ..Default::default()
+                });
+
+                // 他の役割に発言を伝える
+                for (i, other) in threads.iter_mut().enumerate() {
+                    if i != speaker {
+                        other.unheard.push((name.clone(), response.clone()));
+                    }
+                }
+
+                match save_response(&config.output_dir, repo_info, &mut response_data).await {
+                    Ok(Some(filename)) => {
+                        info!(
+                            "[{}] 保存完了: {} (トークン数: {}{})",
+                            endpoint_name,
+                            filename,
+                            tokens_used,
+                            ledger::cost_note(&response_data)
+                        );
+                        journal.record_turn(&job.task_id, &response_data, &filename);
+                    }
+                    Ok(None) => {}
+                    Err(e) => {
+                        error!(
+                            "[{}] 保存エラー: {}/{} - ターン {} - {}",
+                            endpoint_name, repo_info.owner, repo_info.repo, turn, e
+                        );
+                    }
+                }
+                turn += 1;
+            }
+            Err(e) => {
+                error!(
+                    "[{}] OpenAI API エラー: {}/{} - ターン {} - {}",
+                    endpoint_name, repo_info.owner, repo_info.repo, turn, e
+                );
+                if BurnerError::of(&e).is_some_and(|error| !error.is_retryable()) {
+                    return Err(e);
+                }
+                consecutive_errors += 1;
+                if consecutive_errors >= 3 {
+                    bail!("OpenAI API 呼び出しに複数回失敗しました。終了します。");
+                }
+                time::sleep(Duration::from_secs(1)).await;
+            }
+        }
+    }
+
+    if config.findings.enabled {
+        findings::extract_and_save(
+            openai_client,
+            &config.output_dir,
+            &config.findings,
+            repo_info,
+            &job.debate_type,
+            None,
+            &statements,
+        )
+        .await;
+    }
+
+    Ok(DebateOutcome::Completed)
+}
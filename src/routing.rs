@@ -0,0 +1,198 @@
+// リージョンを考慮したルーティングモジュール
+// エンドポイントごとの応答時間（往復の遅延）を定期的に計測し、対話的なコマンドは近い（速い）エンドポイントを使い、
+// 大量の議論は近いエンドポイントの重みを下げて遠いリージョンに振り分ける（対話的なコマンドの待ち時間を短く保つ）
+
+use futures::future::join_all;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::time;
+
+use crate::llm::schemas::openai_response::Endpoint;
+use crate::provider::{self, LlmProvider};
+
+// 1回の計測の待ち時間の上限
+const PROBE_TIMEOUT_SECS: u64 = 10;
+
+// ルーティングの方針
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RoutingPolicy {
+    /// 遅延を計測せず、エンドポイントの重みだけで振り分ける（対話的なコマンドは最初のエンドポイントを使う）
+    #[default]
+    Weighted,
+    /// 遅延を計測し、対話的なコマンドは近いエンドポイント、大量の議論は遠いエンドポイントを優先する
+    Latency,
+}
+
+// ルーティングの設定
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct RoutingConfig {
+    /// ルーティングの方針
+    pub policy: RoutingPolicy,
+    /// 遅延を計測する間隔（秒）
+    pub probe_interval_secs: u64,
+    /// 遅延の移動平均の係数（0〜1、大きいほど直近の計測を重視）
+    pub smoothing: f64,
+    /// 近いエンドポイントとして対話的なコマンドに残す数（遅延の小さい順）
+    pub near_endpoints: usize,
+    /// 大量の議論を振り分けるときの、近いエンドポイントの重みの倍率（0 にすると議論には使わない）
+    pub bulk_near_weight: f64,
+}
+
+impl Default for RoutingConfig {
+    fn default() -> Self {
+        RoutingConfig {
+            policy: RoutingPolicy::Weighted,
+            probe_interval_secs: 60,
+            smoothing: 0.3,
+            near_endpoints: 1,
+            bulk_near_weight: 0.25,
+        }
+    }
+}
+
+static ROUTING: OnceLock<RoutingConfig> = OnceLock::new();
+// エンドポイント名ごとの遅延（ミリ秒）の移動平均
+static LATENCY: Mutex<BTreeMap<String, f64>> = Mutex::new(BTreeMap::new());
+
+fn enabled() -> bool {
+    ROUTING
+        .get()
+        .is_some_and(|config| config.policy == RoutingPolicy::Latency)
+}
+
+// 1回のリクエストの往復時間を計測する（エラーのステータスでも応答が返れば計測できたものとする）
+async fn probe(client: &reqwest::Client, provider: &dyn LlmProvider) -> Option<Duration> {
+    let started = Instant::now();
+    let request = provider.authorize(client.get(provider.resource_url("models")));
+    match time::timeout(
+        Duration::from_secs(PROBE_TIMEOUT_SECS),
+        provider.send(request),
+    )
+    .await
+    {
+        Ok(Ok(_)) => Some(started.elapsed()),
+        _ => None,
+    }
+}
+
+// すべてのエンドポイントの遅延を計測して移動平均に加える
+async fn probe_all(
+    client: &reqwest::Client,
+    providers: &[(String, Box<dyn LlmProvider>)],
+    smoothing: f64,
+) {
+    let results = join_all(
+        providers
+            .iter()
+            .map(|(_, provider)| probe(client, provider.as_ref())),
+    )
+    .await;
+    let mut latency = LATENCY.lock().unwrap();
+    for ((name, _), result) in providers.iter().zip(results) {
+        let Some(elapsed) = result else {
+            continue;
+        };
+        let sample = elapsed.as_secs_f64() * 1000.0;
+        latency
+            .entry(name.clone())
+            .and_modify(|average| *average += smoothing * (sample - *average))
+            .or_insert(sample);
+    }
+}
+
+// ルーティングを設定し、遅延を計測する（latency では最初の計測を待ってから、以降は一定間隔で計測し続ける）
+pub async fn configure(config: &RoutingConfig, endpoints: &[Endpoint]) {
+    let _ = ROUTING.set(config.clone());
+    if config.policy != RoutingPolicy::Latency || endpoints.is_empty() {
+        return;
+    }
+
+    let client = reqwest::Client::new();
+    let providers: Vec<(String, Box<dyn LlmProvider>)> = endpoints
+        .iter()
+        .map(|endpoint| (endpoint.name.clone(), provider::from_endpoint(endpoint)))
+        .collect();
+    probe_all(&client, &providers, config.smoothing).await;
+    log_summary();
+
+    let interval = Duration::from_secs(config.probe_interval_secs.max(1));
+    let smoothing = config.smoothing;
+    tokio::spawn(async move {
+        loop {
+            time::sleep(interval).await;
+            probe_all(&client, &providers, smoothing).await;
+        }
+    });
+}
+
+// 遅延の小さい順に並べたエンドポイント名（計測できなかったエンドポイントは含めない）
+fn ranked() -> Vec<String> {
+    let latency = LATENCY.lock().unwrap();
+    let mut ranked: Vec<(&String, &f64)> = latency.iter().collect();
+    ranked.sort_by(|a, b| a.1.total_cmp(b.1));
+    ranked.into_iter().map(|(name, _)| name.clone()).collect()
+}
+
+// 近いエンドポイントか（遅延の小さい順に near_endpoints 件）
+fn is_near(ranked: &[String], name: &str) -> bool {
+    let near = ROUTING
+        .get()
+        .map(|config| config.near_endpoints)
+        .unwrap_or(0);
+    ranked.iter().take(near).any(|near| near == name)
+}
+
+// 対話的なコマンドで使うエンドポイント（latency では最も遅延の小さいエンドポイント）
+pub fn interactive_endpoint(endpoints: &[Endpoint]) -> Option<&Endpoint> {
+    if enabled() {
+        for name in ranked() {
+            if let Some(endpoint) = endpoints.iter().find(|endpoint| endpoint.name == name) {
+                return Some(endpoint);
+            }
+        }
+    }
+    endpoints.first()
+}
+
+// 大量の議論を振り分けるときの重みの倍率（近いエンドポイントの重みを下げ、遠いリージョンに振り分ける）
+pub fn bulk_factor(name: &str) -> f64 {
+    let Some(config) = ROUTING.get().filter(|_| enabled()) else {
+        return 1.0;
+    };
+    // すべてのエンドポイントが近いエンドポイントなら、重みを下げても振り分け先がない
+    let ranked = ranked();
+    if ranked.len() <= config.near_endpoints || !is_near(&ranked, name) {
+        return 1.0;
+    }
+    config.bulk_near_weight.max(0.0)
+}
+
+// 計測した遅延をログに出力
+pub fn log_summary() {
+    if !enabled() {
+        return;
+    }
+    let ranked = ranked();
+    if ranked.is_empty() {
+        warn!("⚠️ エンドポイントの遅延を計測できませんでした（重みだけで振り分けます）");
+        return;
+    }
+    let latency = LATENCY.lock().unwrap();
+    for name in &ranked {
+        info!(
+            "📡 [{}] 遅延: {:.0} ms{}",
+            name,
+            latency[name],
+            if is_near(&ranked, name) {
+                "（近いエンドポイント: 対話的なコマンドに優先して使用）"
+            } else {
+                ""
+            }
+        );
+    }
+}
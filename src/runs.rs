@@ -0,0 +1,165 @@
+// 実行記録モジュール
+// 分析の実行ごとに開始日時・シード・主な設定を runs.jsonl に追記し、後から同じ条件で再実行できるようにする
+// 実行ごとの manifest.json には設定（秘密情報は伏せる）・分析したコミット・使用したモデル・開始/終了日時を残し、
+// 出力ファイルの run_id から何がその結果を作ったのかをたどれるようにする
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::io::Write;
+use std::sync::{Mutex, OnceLock};
+
+// 設定のスナップショットで伏せる値の表示
+const REDACTED: &str = "***";
+
+// 1回の実行の記録
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RunMetadata {
+    pub run_id: String,
+    pub started_at: String,
+    pub version: String,
+    // 未指定ならNone（質問は既定の順番で出題する）
+    pub seed: Option<u64>,
+    pub dry_run: bool,
+    pub batch: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_duration_secs: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_usd: Option<f64>,
+    pub repos: Vec<String>,
+    pub tasks: usize,
+}
+
+// 分析したリポジトリ
+#[derive(Serialize, Debug, Clone)]
+pub struct RepoManifest {
+    pub repo: String,
+    pub branch: Option<String>,
+    // 設定で指定したコミット
+    pub requested_commit: Option<String>,
+    // クローンしたディレクトリの HEAD（クローンできなかった場合はNone）
+    pub resolved_commit: Option<String>,
+}
+
+// 実行の manifest.json
+#[derive(Serialize, Debug, Clone)]
+pub struct Manifest {
+    #[serde(flatten)]
+    pub metadata: RunMetadata,
+    // 実行中はNone
+    pub finished_at: Option<String>,
+    pub analyzed_repos: Vec<RepoManifest>,
+    pub models: Vec<String>,
+    pub config: Value,
+}
+
+// 実行中の情報
+struct CurrentRun {
+    run_id: String,
+    models: Mutex<BTreeSet<String>>,
+}
+
+static CURRENT_RUN: OnceLock<CurrentRun> = OnceLock::new();
+
+// 実行IDを決めて実行を開始（ドライランは本番の実行と区別できるよう接頭辞を付ける）
+pub fn start(started_at: &chrono::DateTime<chrono::Utc>, dry_run: bool) -> String {
+    let mut run_id = started_at.format("%Y%m%d_%H%M%S").to_string();
+    if dry_run {
+        run_id = format!("dry-run_{}", run_id);
+    }
+    let _ = CURRENT_RUN.set(CurrentRun {
+        run_id: run_id.clone(),
+        models: Mutex::new(BTreeSet::new()),
+    });
+    run_id
+}
+
+// ドライランの実行IDか
+pub fn is_dry_run_id(run_id: Option<&str>) -> bool {
+    run_id.is_some_and(|run_id| run_id.starts_with("dry-run_"))
+}
+
+// 実行中の実行ID（分析の実行以外ではNone）
+pub fn current_id() -> Option<String> {
+    CURRENT_RUN.get().map(|run| run.run_id.clone())
+}
+
+// 呼び出したモデル（デプロイメント）を記録
+pub fn note_model(model: &str) {
+    if let Some(run) = CURRENT_RUN.get() {
+        run.models.lock().unwrap().insert(model.to_string());
+    }
+}
+
+// これまでに呼び出したモデル
+pub fn models() -> Vec<String> {
+    CURRENT_RUN
+        .get()
+        .map(|run| run.models.lock().unwrap().iter().cloned().collect())
+        .unwrap_or_default()
+}
+
+// 実行記録のパス（ドライランは本番の記録と混ざらないよう別ファイル）
+pub fn runs_path(output_dir: &str, dry_run: bool) -> String {
+    if dry_run {
+        format!("{}/runs.dry-run.jsonl", output_dir)
+    } else {
+        format!("{}/runs.jsonl", output_dir)
+    }
+}
+
+// 実行記録を追記
+pub fn record(output_dir: &str, metadata: &RunMetadata) -> Result<String> {
+    let path = runs_path(output_dir, metadata.dry_run);
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)?;
+    writeln!(file, "{}", serde_json::to_string(metadata)?)?;
+    Ok(path)
+}
+
+// 秘密情報らしいキーか（max_tokens などのトークン数の設定は対象外）
+fn is_secret_key(key: &str) -> bool {
+    let key = key.to_lowercase();
+    ["key", "keys", "token", "secret", "password"].contains(&key.as_str())
+        || ["_key", "_token", "_secret", "_password"]
+            .iter()
+            .any(|suffix| key.ends_with(suffix))
+}
+
+// 秘密情報の値を伏せる（予備のキーのような文字列の配列も伏せる）
+fn redact_secret(value: &mut Value) {
+    match value {
+        Value::String(secret) if !secret.is_empty() => *value = Value::String(REDACTED.to_string()),
+        Value::Array(items) => items.iter_mut().for_each(redact_secret),
+        _ => {}
+    }
+}
+
+// 設定のスナップショットから秘密情報を伏せる
+pub fn redact(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                if is_secret_key(key) && (value.is_string() || value.is_array()) {
+                    redact_secret(value);
+                } else {
+                    redact(value);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(redact),
+        _ => {}
+    }
+}
+
+// manifest.json を書き出す（実行開始時と終了時に上書きする）
+pub fn write_manifest(output_dir: &str, manifest: &Manifest) -> Result<String> {
+    let dir = format!("{}/runs/{}", output_dir, manifest.metadata.run_id);
+    std::fs::create_dir_all(&dir)?;
+    let path = format!("{}/manifest.json", dir);
+    std::fs::write(&path, serde_json::to_string_pretty(manifest)?)?;
+    Ok(path)
+}
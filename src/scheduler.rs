@@ -0,0 +1,486 @@
+// エンドポイントのスケジューラーモジュール
+// 設定された重み（または実測のスループット）に比例して、タスクをエンドポイントへ割り当てる
+// 議論はリポジトリと議論タイプを交互に並べ、途中で止まってもすべてのリポジトリが早い段階で分析されるようにする
+
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use tokio::sync::Semaphore;
+
+use crate::llm::schemas::github_response::{Priority, RepoInfo};
+use crate::llm::schemas::openai_response::Endpoint;
+use crate::work_queue::DebateJob;
+use crate::{ptu, routing};
+
+// 重みの決め方
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Weighting {
+    /// エンドポイントの weight（未設定なら1）をそのまま使う
+    #[default]
+    Configured,
+    /// 実行中に計測したスループット（トークン/秒）で weight を補正する
+    Measured,
+}
+
+// スケジューラーの設定
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct SchedulerConfig {
+    /// 重みの決め方
+    pub weighting: Weighting,
+    /// スループットの移動平均の係数（0〜1、大きいほど直近の計測を重視）
+    pub smoothing: f64,
+    /// 同じリポジトリ・議論タイプを何回（別の視点として）分析するか
+    pub perspectives: usize,
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        SchedulerConfig {
+            weighting: Weighting::Configured,
+            smoothing: 0.3,
+            perspectives: 1,
+        }
+    }
+}
+
+// 議論タイプの指定（短い名前または議論タイプ名）が議論タイプに当たるか
+fn names_debate_type(name: &str, debate_type: &str) -> bool {
+    name == debate_type
+        || crate::resolve_debate_type(name).is_ok_and(|resolved| resolved == debate_type)
+}
+
+// リポジトリで実行する議論タイプ（debate_types と skip_debate_types で絞り込む）
+pub fn debate_types_for(repo_info: &RepoInfo, debate_types: &[String]) -> Vec<String> {
+    debate_types
+        .iter()
+        .filter(|debate_type| {
+            repo_info.debate_types.is_empty()
+                || repo_info
+                    .debate_types
+                    .iter()
+                    .any(|name| names_debate_type(name, debate_type))
+        })
+        .filter(|debate_type| {
+            !repo_info
+                .skip_debate_types
+                .iter()
+                .any(|name| names_debate_type(name, debate_type))
+        })
+        .cloned()
+        .collect()
+}
+
+// リポジトリと議論タイプを交互に並べた議論の一覧を作成
+// 1周目は各リポジトリに1つずつ議論を割り当て、リポジトリごとに議論タイプの開始位置をずらす
+// 追加の視点の議論は、すべてのリポジトリ・議論タイプを1回ずつ並べた後に続ける
+// 優先度の高いリポジトリから順に並べ、high のリポジトリは視点を1つ増やす
+pub fn plan_jobs(
+    repos: &[RepoInfo],
+    debate_types: &[String],
+    perspectives: usize,
+) -> Vec<DebateJob> {
+    let mut jobs = Vec::new();
+    for priority in [Priority::High, Priority::Normal, Priority::Low] {
+        let group: Vec<&RepoInfo> = repos
+            .iter()
+            .filter(|repo_info| repo_info.priority == priority)
+            .collect();
+        let perspectives = match priority {
+            Priority::High => perspectives.max(1) + 1,
+            _ => perspectives.max(1),
+        };
+
+        // リポジトリごとに実行する議論タイプ
+        let repo_types: Vec<Vec<String>> = group
+            .iter()
+            .map(|repo_info| debate_types_for(repo_info, debate_types))
+            .collect();
+        let rounds = repo_types.iter().map(Vec::len).max().unwrap_or(0);
+
+        for perspective in 0..perspectives {
+            for round in 0..rounds {
+                for (i, repo_info) in group.iter().enumerate() {
+                    let types = &repo_types[i];
+                    if round >= types.len() {
+                        continue;
+                    }
+                    let debate_type = &types[(i + round) % types.len()];
+                    jobs.push(DebateJob::new(
+                        (*repo_info).clone(),
+                        debate_type.clone(),
+                        perspective,
+                    ));
+                }
+            }
+        }
+    }
+    jobs
+}
+
+// 同時実行数を重みに比例して配分（重みが正のエンドポイントには最低1つ、端数は小数部の大きい順に配る）
+// ワーカー数の合計を total 以下にする（最も多いエンドポイントから、同じ数なら重みの小さい方から1つずつ減らす）
+fn clamp_total(workers: &mut [usize], weights: &[f64], total: usize) {
+    while workers.iter().sum::<usize>() > total {
+        let Some(largest) = (0..workers.len()).max_by(|a, b| {
+            workers[*a]
+                .cmp(&workers[*b])
+                .then(weights[*b].total_cmp(&weights[*a]))
+        }) else {
+            return;
+        };
+        workers[largest] -= 1;
+    }
+}
+
+fn distribute(total: usize, weights: &[f64]) -> Vec<usize> {
+    let weight_sum: f64 = weights.iter().sum();
+    if weight_sum <= 0.0 {
+        return vec![0; weights.len()];
+    }
+
+    let shares: Vec<f64> = weights
+        .iter()
+        .map(|weight| total as f64 * weight / weight_sum)
+        .collect();
+    let mut workers: Vec<usize> = shares
+        .iter()
+        .zip(weights)
+        .map(|(share, weight)| {
+            if *weight > 0.0 {
+                (share.floor() as usize).max(1)
+            } else {
+                0
+            }
+        })
+        .collect();
+
+    let mut remainders: Vec<(usize, f64)> = shares
+        .iter()
+        .enumerate()
+        .map(|(index, share)| (index, share - share.floor()))
+        .collect();
+    remainders.sort_by(|a, b| b.1.total_cmp(&a.1));
+    for (index, _) in remainders {
+        if workers.iter().sum::<usize>() >= total {
+            break;
+        }
+        if weights[index] > 0.0 {
+            workers[index] += 1;
+        }
+    }
+
+    workers
+}
+
+// エンドポイント名ごとの同時実行数の上限（同じエンドポイントのクライアント間で共有する）
+static CONCURRENCY_LIMITS: OnceLock<Mutex<HashMap<String, Arc<Semaphore>>>> = OnceLock::new();
+
+// エンドポイントの同時実行数の上限を取得（上限がなければNone）
+pub fn concurrency_limit(endpoint: &Endpoint) -> Option<Arc<Semaphore>> {
+    let limit = endpoint.max_concurrent?.max(1);
+    let mut limits = CONCURRENCY_LIMITS
+        .get_or_init(|| Mutex::new(HashMap::new()))
+        .lock()
+        .unwrap();
+    Some(
+        limits
+            .entry(endpoint.name.clone())
+            .or_insert_with(|| Arc::new(Semaphore::new(limit)))
+            .clone(),
+    )
+}
+
+// スケジューラーの内部状態
+struct SchedulerState {
+    // エンドポイント名
+    names: Vec<String>,
+    // 設定された重み
+    weights: Vec<f64>,
+    // スムーズ重み付きラウンドロビンの現在値
+    current: Vec<f64>,
+    // 実測スループット（トークン/秒）の移動平均
+    throughput: Vec<Option<f64>>,
+    // 割り当てたタスク数
+    assigned: Vec<usize>,
+    // 同時実行数の上限
+    limits: Vec<Option<usize>>,
+    // 容量を使い切るためのワーカー数（PTU モードの PTU エンドポイント）
+    saturation: Vec<Option<usize>>,
+    // 起動したワーカー数
+    workers: Vec<usize>,
+}
+
+// 重みに比例してエンドポイントを選ぶスケジューラー（スムーズ重み付きラウンドロビン）
+pub struct Scheduler {
+    config: SchedulerConfig,
+    state: Mutex<SchedulerState>,
+}
+
+impl Scheduler {
+    pub fn new(endpoints: &[Endpoint], config: &SchedulerConfig) -> Self {
+        let weights: Vec<f64> = endpoints
+            .iter()
+            .map(|endpoint| endpoint.weight.unwrap_or(1.0).max(0.0))
+            .collect();
+        let count = weights.len();
+
+        Scheduler {
+            config: config.clone(),
+            state: Mutex::new(SchedulerState {
+                names: endpoints
+                    .iter()
+                    .map(|endpoint| endpoint.name.clone())
+                    .collect(),
+                weights,
+                current: vec![0.0; count],
+                throughput: vec![None; count],
+                assigned: vec![0; count],
+                limits: endpoints
+                    .iter()
+                    .map(|endpoint| endpoint.max_concurrent)
+                    .collect(),
+                saturation: endpoints.iter().map(ptu::saturation_workers).collect(),
+                workers: vec![0; count],
+            }),
+        }
+    }
+
+    // 実行中に追加されたエンドポイントを末尾に加え、そのインデックスを返す
+    pub fn add_endpoint(&self, endpoint: &Endpoint) -> usize {
+        let mut state = self.state.lock().unwrap();
+        state.names.push(endpoint.name.clone());
+        state.weights.push(endpoint.weight.unwrap_or(1.0).max(0.0));
+        state.current.push(0.0);
+        state.throughput.push(None);
+        state.assigned.push(0);
+        state.limits.push(endpoint.max_concurrent);
+        state.saturation.push(ptu::saturation_workers(endpoint));
+        state.workers.push(0);
+        state.names.len() - 1
+    }
+
+    // エンドポイントのワーカーを起動したことを記録
+    pub fn note_workers(&self, endpoint_index: usize, count: usize) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(workers) = state.workers.get_mut(endpoint_index) {
+            *workers += count;
+        }
+    }
+
+    // エンドポイントのワーカーが終了したことを記録
+    pub fn note_worker_exited(&self, endpoint_index: usize) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(workers) = state.workers.get_mut(endpoint_index) {
+            *workers = workers.saturating_sub(1);
+        }
+    }
+
+    // 新しい世代のワーカーを起動する前に、起動したワーカー数を数え直す（古い世代のワーカーは終了を記録しない）
+    pub fn reset_workers(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.workers.iter_mut().for_each(|workers| *workers = 0);
+    }
+
+    // ワーカーの合計が total を超えていて、このエンドポイントのワーカーが配分より多ければ1つ減らす
+    // （true なら呼び出したワーカーが終了する。エンドポイントの追加で増えたワーカーを同時実行数に戻す）
+    pub fn retire_worker(&self, endpoint_index: usize, total: usize) -> bool {
+        let allocated = self.allocate_workers(total);
+        let mut state = self.state.lock().unwrap();
+        if state.workers.iter().sum::<usize>() <= total {
+            return false;
+        }
+        match (
+            state.workers.get_mut(endpoint_index),
+            allocated.get(endpoint_index),
+        ) {
+            (Some(workers), Some(allocated)) if *workers > *allocated => {
+                *workers -= 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // ワーカーがいるエンドポイントか（同時実行数の配分でワーカーが割り当てられなかったエンドポイントは議論を引き受けない）
+    pub fn has_workers(&self, endpoint: &str) -> bool {
+        let state = self.state.lock().unwrap();
+        state
+            .names
+            .iter()
+            .zip(&state.workers)
+            .any(|(name, workers)| name == endpoint && *workers > 0)
+    }
+
+    // 設定された重みに、ルーティングの方針による倍率（近いエンドポイントの重みを下げる）を掛ける
+    fn routed_weights(state: &SchedulerState) -> Vec<f64> {
+        state
+            .weights
+            .iter()
+            .zip(&state.names)
+            .map(|(weight, name)| weight * routing::bulk_factor(name))
+            .collect()
+    }
+
+    // 実効的な重みを計算
+    fn effective_weights(&self, state: &SchedulerState) -> Vec<f64> {
+        let weights = Self::routed_weights(state);
+        if self.config.weighting == Weighting::Configured {
+            return weights;
+        }
+
+        // 計測済みのエンドポイントは、計測済み全体の平均に対する比率で重みを補正する
+        let measured: Vec<f64> = state.throughput.iter().flatten().copied().collect();
+        if measured.is_empty() {
+            return weights;
+        }
+        let mean = measured.iter().sum::<f64>() / measured.len() as f64;
+        if mean <= 0.0 {
+            return weights;
+        }
+
+        weights
+            .iter()
+            .zip(&state.throughput)
+            .map(|(weight, throughput)| match throughput {
+                Some(throughput) => weight * throughput / mean,
+                None => *weight,
+            })
+            .collect()
+    }
+
+    // 次のタスクを割り当てるエンドポイントを選ぶ（Batch APIモードの事前割り当てに使用）
+    pub fn pick(&self) -> usize {
+        let mut state = self.state.lock().unwrap();
+        let weights = self.effective_weights(&state);
+        let total: f64 = weights.iter().sum();
+        if total <= 0.0 {
+            return 0;
+        }
+
+        for (current, weight) in state.current.iter_mut().zip(&weights) {
+            *current += weight;
+        }
+        // 同じ値の場合は先頭を優先する（重みが均等なら従来のラウンドロビンと同じ順序）
+        let (index, _) = state
+            .current
+            .iter()
+            .enumerate()
+            .rev()
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .unwrap();
+        state.current[index] -= total;
+        state.assigned[index] += 1;
+        index
+    }
+
+    // ワーカーが議論を取り出したことを記録
+    pub fn note_assigned(&self, endpoint_index: usize) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(assigned) = state.assigned.get_mut(endpoint_index) {
+            *assigned += 1;
+        }
+    }
+
+    // 同時実行数を重みに比例してエンドポイントごとのワーカー数に配分
+    // （重みが正のエンドポイントには最低1つ割り当てる）
+    // 同時実行数の上限を超えるエンドポイントは上限で止め、残りを他のエンドポイントに配り直す
+    // PTU モードの PTU エンドポイントには、容量を使い切るワーカー数を割り当てる
+    // 合計は同時実行数を超えない（最低1つの割り当てや PTU のワーカーで超える分は、多いエンドポイントから減らす）
+    pub fn allocate_workers(&self, total: usize) -> Vec<usize> {
+        let mut workers = self.distribute_workers(total);
+        let routed = Self::routed_weights(&self.state.lock().unwrap());
+        clamp_total(&mut workers, &routed, total);
+        workers
+    }
+
+    fn distribute_workers(&self, total: usize) -> Vec<usize> {
+        let state = self.state.lock().unwrap();
+        let routed = Self::routed_weights(&state);
+        let cap = |index: usize, workers: usize| match state.limits[index] {
+            Some(limit) => workers.min(limit.max(1)),
+            None => workers,
+        };
+
+        let mut workers = vec![0; routed.len()];
+        let mut capped = vec![false; routed.len()];
+        for (index, saturation) in state.saturation.iter().enumerate() {
+            if let Some(saturation) = saturation {
+                workers[index] = cap(index, *saturation);
+                capped[index] = true;
+            }
+        }
+        if routed.iter().sum::<f64>() <= 0.0 {
+            for index in 0..workers.len() {
+                if !capped[index] {
+                    workers[index] = cap(index, 1);
+                }
+            }
+            return workers;
+        }
+
+        let mut remaining = total;
+        loop {
+            let weights: Vec<f64> = routed
+                .iter()
+                .zip(&capped)
+                .map(|(weight, capped)| if *capped { 0.0 } else { *weight })
+                .collect();
+            let shares = distribute(remaining, &weights);
+
+            let over: Vec<usize> = (0..shares.len())
+                .filter(|index| !capped[*index] && cap(*index, shares[*index]) < shares[*index])
+                .collect();
+            if over.is_empty() || weights.iter().sum::<f64>() <= 0.0 {
+                for (index, share) in shares.into_iter().enumerate() {
+                    if !capped[index] {
+                        workers[index] = share;
+                    }
+                }
+                return workers;
+            }
+            for index in over {
+                workers[index] = cap(index, shares[index]);
+                capped[index] = true;
+                remaining = remaining.saturating_sub(workers[index]);
+            }
+        }
+    }
+
+    // リクエストの結果からスループットを記録
+    pub fn record(&self, endpoint_index: usize, tokens: usize, elapsed: Duration) {
+        let seconds = elapsed.as_secs_f64();
+        if seconds <= 0.0 {
+            return;
+        }
+
+        let mut state = self.state.lock().unwrap();
+        let Some(throughput) = state.throughput.get_mut(endpoint_index) else {
+            return;
+        };
+        let sample = tokens as f64 / seconds;
+        *throughput = Some(match *throughput {
+            Some(average) => average + self.config.smoothing * (sample - average),
+            None => sample,
+        });
+    }
+
+    // 割り当て結果をログに出力
+    pub fn log_summary(&self) {
+        let state = self.state.lock().unwrap();
+        for (index, name) in state.names.iter().enumerate() {
+            info!(
+                "📊 [{}] 割り当てタスク数: {}, 重み: {}, スループット: {}",
+                name,
+                state.assigned[index],
+                state.weights[index],
+                state.throughput[index]
+                    .map(|throughput| format!("{:.1} トークン/秒", throughput))
+                    .unwrap_or_else(|| "未計測".to_string())
+            );
+        }
+    }
+}
@@ -0,0 +1,402 @@
+// リポジトリのスコアカード生成モジュール
+// 議論の結果を最後にもう一度モデルに渡し、観点ごとの評価・主なリスク・改善提案を構造化出力で受け取って
+// リポジトリごとに1ページの scorecard.md にまとめる。すべてのリポジトリの評価は組織全体のランキングの表にする
+
+use anyhow::{Result, anyhow, bail};
+use chrono::prelude::*;
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::path::Path;
+use tokio::fs;
+
+use crate::llm::schemas::{
+    github_response::RepoInfo,
+    openai_response::{ChatMessage, Endpoint},
+};
+use crate::{LlmClient, budget, encryption, generation, runs, summary};
+
+// スコアカード生成の設定
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ScorecardConfig {
+    /// 分析完了後にスコアカードを生成するか
+    pub enabled: bool,
+    /// 評価に使うデプロイメント名（省略時は議論と同じモデル）
+    pub deployment: Option<String>,
+    /// 評価の最大出力トークン数
+    pub max_tokens: usize,
+    /// 評価に渡す議論結果の最大文字数
+    pub max_input_chars: usize,
+}
+
+impl Default for ScorecardConfig {
+    fn default() -> Self {
+        ScorecardConfig {
+            enabled: false,
+            deployment: None,
+            max_tokens: 3000,
+            max_input_chars: 60000,
+        }
+    }
+}
+
+// 評価する観点（リポジトリどうしを比べられるよう固定する）
+pub const SCORE_CATEGORIES: [&str; 7] = [
+    "コード品質",
+    "アーキテクチャ",
+    "セキュリティ",
+    "パフォーマンス",
+    "テスト",
+    "ドキュメント",
+    "保守性",
+];
+
+// 組織全体のランキングの保存先（出力ディレクトリ直下）
+const RANKING_FILE: &str = "scorecards.md";
+
+// 観点ごとの評価
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CategoryScore {
+    pub category: String,
+    // 1〜10
+    pub score: u8,
+    pub comment: String,
+}
+
+// 保存するスコアカード
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Scorecard {
+    pub repo: String,
+    pub timestamp: String,
+    pub endpoint: String,
+    // 総合評価（1〜10）
+    pub overall: u8,
+    pub scores: Vec<CategoryScore>,
+    pub top_risks: Vec<String>,
+    pub top_recommendations: Vec<String>,
+    pub tokens_used: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run_id: Option<String>,
+}
+
+impl Scorecard {
+    // 観点の評価（評価されていなければ None）
+    pub fn score(&self, category: &str) -> Option<u8> {
+        self.scores
+            .iter()
+            .find(|score| score.category == category)
+            .map(|score| score.score)
+    }
+}
+
+// 評価の指示
+const SCORECARD_PROMPT: &str = "あなたは複数のリポジトリを横断して技術的な健全性を評価するシニアエンジニアです。\
+以下はあるリポジトリについて複数の観点から行われたAIによる分析結果です。\
+これをもとに、指定された観点ごとに1〜10点（10が最良）で評価し、根拠を1文で添えてください。\
+分析結果から判断できない観点も、わかる範囲で控えめに評価してください。\
+あわせて総合評価（1〜10点）、影響の大きい順に主なリスクを3〜5件、優先度の高い順に改善提案を3〜5件挙げてください。\
+分析結果に含まれない内容を作らないでください。";
+
+// 構造化出力の JSON スキーマ（strict モードではすべてのプロパティを必須にする）
+fn schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "overall": { "type": "integer" },
+            "scores": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "category": { "type": "string", "enum": SCORE_CATEGORIES },
+                        "score": { "type": "integer" },
+                        "comment": { "type": "string" },
+                    },
+                    "required": ["category", "score", "comment"],
+                    "additionalProperties": false,
+                },
+            },
+            "top_risks": { "type": "array", "items": { "type": "string" } },
+            "top_recommendations": { "type": "array", "items": { "type": "string" } },
+        },
+        "required": ["overall", "scores", "top_risks", "top_recommendations"],
+        "additionalProperties": false,
+    })
+}
+
+// 構造化出力の応答
+#[derive(Deserialize)]
+struct ScorecardResponse {
+    overall: i64,
+    scores: Vec<ScoreResponse>,
+    top_risks: Vec<String>,
+    top_recommendations: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct ScoreResponse {
+    category: String,
+    score: i64,
+    comment: String,
+}
+
+// 評価を 1〜10 に収める
+fn clamp_score(score: i64) -> u8 {
+    score.clamp(1, 10) as u8
+}
+
+// リポジトリの出力ディレクトリ
+fn repo_dir(base_dir: &str, repo: &str) -> String {
+    format!("{}/{}", base_dir, repo.replace('/', "_"))
+}
+
+// 評価を棒で表す（10点満点を10マス）
+fn bar(score: u8) -> String {
+    format!(
+        "{}{}",
+        "■".repeat(score as usize),
+        "□".repeat(10 - score as usize)
+    )
+}
+
+// スコアカードを1ページの Markdown にする
+pub fn render(scorecard: &Scorecard) -> String {
+    let mut markdown = format!(
+        "# {} スコアカード\n\n総合評価: **{}/10**（{}{}）\n\n## 観点ごとの評価\n\n| 観点 | 評価 | | 根拠 |\n|---|---:|---|---|\n",
+        scorecard.repo,
+        scorecard.overall,
+        scorecard.timestamp,
+        scorecard
+            .run_id
+            .as_ref()
+            .map(|run_id| format!("・実行 `{}`", run_id))
+            .unwrap_or_default()
+    );
+    for score in &scorecard.scores {
+        markdown.push_str(&format!(
+            "| {} | {}/10 | {} | {} |\n",
+            score.category,
+            score.score,
+            bar(score.score),
+            score.comment.replace('|', "\\|").replace('\n', " ")
+        ));
+    }
+    markdown.push_str("\n## 主なリスク\n\n");
+    for (i, risk) in scorecard.top_risks.iter().enumerate() {
+        markdown.push_str(&format!("{}. {}\n", i + 1, risk));
+    }
+    markdown.push_str("\n## 優先度の高い改善提案\n\n");
+    for (i, recommendation) in scorecard.top_recommendations.iter().enumerate() {
+        markdown.push_str(&format!("{}. {}\n", i + 1, recommendation));
+    }
+    markdown
+}
+
+// 1リポジトリのスコアカードを生成して保存
+pub async fn generate_scorecard(
+    client: &LlmClient,
+    base_dir: &str,
+    repo_info: &RepoInfo,
+    config: &ScorecardConfig,
+) -> Result<String> {
+    let results = summary::collect_debate_results(base_dir, repo_info, config.max_input_chars);
+    if results.trim().is_empty() {
+        bail!(
+            "分析結果が見つかりません: {}/{}",
+            repo_info.owner,
+            repo_info.repo
+        );
+    }
+    let repo = format!("{}/{}", repo_info.owner, repo_info.repo);
+
+    let messages = vec![
+        ChatMessage {
+            role: "system".to_string(),
+            content: SCORECARD_PROMPT.to_string(),
+            ..Default::default()
+        },
+        ChatMessage {
+            role: "user".to_string(),
+            content: format!(
+                "リポジトリ: {}\n評価する観点: {}\n{}",
+                repo,
+                SCORE_CATEGORIES.join("、"),
+                results
+            ),
+            ..Default::default()
+        },
+    ];
+    let model = config
+        .deployment
+        .as_deref()
+        .unwrap_or(generation::default_model());
+    let (response, tokens_used) = client
+        .structured_completion(&messages, model, config.max_tokens, "scorecard", schema())
+        .await?;
+    budget::record(repo_info, tokens_used);
+    let parsed: ScorecardResponse = serde_json::from_str(&response)
+        .map_err(|e| anyhow!("スコアカードのJSONを解析できません: {} - {}", e, response))?;
+
+    // 観点は決めた順に並べ、同じ観点が重複した場合は最初の評価を使う
+    let scores = SCORE_CATEGORIES
+        .iter()
+        .filter_map(|category| {
+            parsed
+                .scores
+                .iter()
+                .find(|score| score.category == *category)
+                .map(|score| CategoryScore {
+                    category: score.category.clone(),
+                    score: clamp_score(score.score),
+                    comment: score.comment.clone(),
+                })
+        })
+        .collect();
+    let scorecard = Scorecard {
+        repo,
+        timestamp: Utc::now().to_rfc3339(),
+        endpoint: client.endpoint.name.clone(),
+        overall: clamp_score(parsed.overall),
+        scores,
+        top_risks: parsed.top_risks,
+        top_recommendations: parsed.top_recommendations,
+        tokens_used,
+        run_id: runs::current_id(),
+    };
+
+    let dir = repo_dir(base_dir, &scorecard.repo);
+    fs::create_dir_all(&dir).await?;
+    encryption::write(
+        format!("{}/scorecard.json", dir),
+        serde_json::to_string_pretty(&scorecard)?,
+    )
+    .await?;
+    let path = format!("{}/scorecard.md", dir);
+    encryption::write(&path, render(&scorecard)).await?;
+
+    info!(
+        "📋 スコアカードを保存しました: {} (総合 {}/10, トークン数: {})",
+        path, scorecard.overall, tokens_used
+    );
+    Ok(path)
+}
+
+// 出力ディレクトリのすべてのスコアカードを読み込む（今回分析しなかったリポジトリも含む）
+pub fn load_scorecards(base_dir: &str) -> Vec<Scorecard> {
+    let Ok(entries) = std::fs::read_dir(base_dir) else {
+        return Vec::new();
+    };
+    let mut scorecards = Vec::new();
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let path = entry.path().join("scorecard.json");
+        if !path.is_file() {
+            continue;
+        }
+        match encryption::read_to_string(&path).and_then(|content| {
+            serde_json::from_str::<Scorecard>(&content).map_err(|e| anyhow!("{}", e))
+        }) {
+            Ok(scorecard) => scorecards.push(scorecard),
+            Err(e) => warn!(
+                "⚠️ スコアカードを読み込めません: {} - {}",
+                path.display(),
+                e
+            ),
+        }
+    }
+    scorecards.sort_by(|a, b| a.repo.cmp(&b.repo));
+    scorecards
+}
+
+// 組織全体のランキングの表を作成する（総合評価、観点の平均の順）
+pub fn render_ranking(scorecards: &[Scorecard]) -> String {
+    let average = |scorecard: &Scorecard| {
+        let scores: Vec<f64> = scorecard
+            .scores
+            .iter()
+            .map(|score| score.score as f64)
+            .collect();
+        if scores.is_empty() {
+            0.0
+        } else {
+            scores.iter().sum::<f64>() / scores.len() as f64
+        }
+    };
+    let mut ranked: Vec<&Scorecard> = scorecards.iter().collect();
+    ranked.sort_by(|a, b| {
+        b.overall
+            .cmp(&a.overall)
+            .then(average(b).total_cmp(&average(a)))
+            .then(a.repo.cmp(&b.repo))
+    });
+
+    let mut markdown = format!(
+        "# スコアカードのランキング\n\n| 順位 | リポジトリ | 総合 | 平均 | {} |\n|---:|---|---:|---:|{}\n",
+        SCORE_CATEGORIES.join(" | "),
+        "---:|".repeat(SCORE_CATEGORIES.len())
+    );
+    for (i, scorecard) in ranked.iter().enumerate() {
+        let scores: Vec<String> = SCORE_CATEGORIES
+            .iter()
+            .map(|category| {
+                scorecard
+                    .score(category)
+                    .map(|score| score.to_string())
+                    .unwrap_or_else(|| "-".to_string())
+            })
+            .collect();
+        markdown.push_str(&format!(
+            "| {} | [{}]({}/scorecard.md) | {} | {:.1} | {} |\n",
+            i + 1,
+            scorecard.repo,
+            scorecard.repo.replace('/', "_"),
+            scorecard.overall,
+            average(scorecard),
+            scores.join(" | ")
+        ));
+    }
+    markdown
+}
+
+// 組織全体のランキングを保存する
+async fn save_ranking(base_dir: &str) -> Result<Option<String>> {
+    let scorecards = load_scorecards(base_dir);
+    if scorecards.is_empty() {
+        return Ok(None);
+    }
+    let path = Path::new(base_dir).join(RANKING_FILE);
+    encryption::write(&path, render_ranking(&scorecards)).await?;
+    info!(
+        "🏆 スコアカードのランキングを保存しました: {} ({} リポジトリ)",
+        path.display(),
+        scorecards.len()
+    );
+    Ok(Some(path.to_string_lossy().to_string()))
+}
+
+// すべてのリポジトリのスコアカードを生成し、ランキングを更新する
+pub async fn generate_scorecards(
+    endpoints: &[Endpoint],
+    repos: &[RepoInfo],
+    base_dir: &str,
+    config: &ScorecardConfig,
+) {
+    if endpoints.is_empty() {
+        error!("❌ スコアカードの生成に使用できるエンドポイントがありません");
+        return;
+    }
+
+    for (i, repo_info) in repos.iter().enumerate() {
+        let client = LlmClient::new(endpoints[i % endpoints.len()].clone());
+        if let Err(e) = generate_scorecard(&client, base_dir, repo_info, config).await {
+            error!(
+                "❌ スコアカードの生成エラー: {}/{} - {}",
+                repo_info.owner, repo_info.repo, e
+            );
+        }
+    }
+
+    if let Err(e) = save_ranking(base_dir).await {
+        error!("❌ スコアカードのランキングの保存エラー: {}", e);
+    }
+}
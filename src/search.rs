@@ -0,0 +1,183 @@
+// 保存済みの分析結果の検索モジュール
+// 出力ディレクトリの会話履歴からキーワード（または正規表現）に一致する発言を探し、
+// リポジトリ・議論タイプ・ターンと前後の抜粋をターミナルに表示する
+// 各ターンのファイルはそれまでの会話履歴をすべて含むため、議論ごとの最終ターンだけを検索する
+
+use anyhow::{Result, anyhow};
+use log::info;
+use regex::{Regex, RegexBuilder};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::llm::schemas::openai_response::ResponseData;
+use crate::outputs;
+
+// search コマンドのオプション
+pub struct SearchOptions {
+    pub query: String,
+    pub repo: Option<String>,
+    pub debate: Option<String>,
+    pub regex: bool,
+    pub case_sensitive: bool,
+    pub include_system: bool,
+    pub context: usize,
+    pub limit: usize,
+}
+
+// 一致した発言
+struct SearchHit {
+    path: PathBuf,
+    repo: String,
+    debate_type: String,
+    endpoint: String,
+    file: Option<String>,
+    turn: usize,
+    role: String,
+    matches: usize,
+    excerpt: String,
+}
+
+fn build_pattern(options: &SearchOptions) -> Result<Regex> {
+    let pattern = if options.regex {
+        options.query.clone()
+    } else {
+        regex::escape(&options.query)
+    };
+    RegexBuilder::new(&pattern)
+        .case_insensitive(!options.case_sensitive)
+        .build()
+        .map_err(|e| anyhow!("検索する正規表現が不正です: {} - {}", options.query, e))
+}
+
+// 一致した位置の前後の文字を1行の抜粋にする（一致した部分は【】で囲む）
+fn excerpt(content: &str, start: usize, end: usize, context: usize) -> String {
+    let before: String = {
+        let mut chars: Vec<char> = content[..start].chars().rev().take(context).collect();
+        chars.reverse();
+        chars.into_iter().collect()
+    };
+    let after: String = content[end..].chars().take(context).collect();
+    let text = format!(
+        "{}{}【{}】{}{}",
+        if before.len() < start { "…" } else { "" },
+        before,
+        &content[start..end],
+        after,
+        if end + after.len() < content.len() {
+            "…"
+        } else {
+            ""
+        }
+    );
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+// 議論の会話履歴から一致する発言を探す（ユーザーの発言はそれに答えたターンとして数える）
+fn search_debate(
+    path: &Path,
+    response: &ResponseData,
+    pattern: &Regex,
+    options: &SearchOptions,
+) -> Vec<SearchHit> {
+    let mut hits = Vec::new();
+    let mut turn = 1;
+    for message in &response.messages {
+        let searchable = message.role != "system" || options.include_system;
+        if searchable {
+            let mut found = pattern.find_iter(&message.content);
+            if let Some(first) = found.next() {
+                hits.push(SearchHit {
+                    path: path.to_path_buf(),
+                    repo: response.repo.clone(),
+                    debate_type: response.debate_type.clone(),
+                    endpoint: response.endpoint.clone(),
+                    file: response.file.clone(),
+                    turn,
+                    role: message.role.clone(),
+                    matches: 1 + found.count(),
+                    excerpt: excerpt(
+                        &message.content,
+                        first.start(),
+                        first.end(),
+                        options.context,
+                    ),
+                });
+            }
+        }
+        if message.role == "assistant" {
+            turn += 1;
+        }
+    }
+    hits
+}
+
+// 保存済みの分析結果を検索して表示する
+pub fn run_search(base_dir: &str, options: &SearchOptions) -> Result<()> {
+    let pattern = build_pattern(options)?;
+
+    // 議論ごとの最終ターン
+    let mut latest: BTreeMap<String, (PathBuf, ResponseData)> = BTreeMap::new();
+    for (path, response) in outputs::load_responses(base_dir) {
+        if options
+            .repo
+            .as_ref()
+            .is_some_and(|repo| !response.repo.eq_ignore_ascii_case(repo))
+        {
+            continue;
+        }
+        if options
+            .debate
+            .as_ref()
+            .is_some_and(|debate| !response.debate_type.contains(debate.as_str()))
+        {
+            continue;
+        }
+        let key = outputs::debate_key(&response);
+        if latest
+            .get(&key)
+            .is_none_or(|(_, current)| response.turn > current.turn)
+        {
+            latest.insert(key, (path, response));
+        }
+    }
+
+    let hits: Vec<SearchHit> = latest
+        .values()
+        .flat_map(|(path, response)| search_debate(path, response, &pattern, options))
+        .collect();
+    if hits.is_empty() {
+        info!(
+            "🔍 「{}」に一致する発言は見つかりませんでした（{} 件の議論を検索）",
+            options.query,
+            latest.len()
+        );
+        return Ok(());
+    }
+
+    for hit in hits.iter().take(options.limit) {
+        let file = hit
+            .file
+            .as_ref()
+            .map(|file| format!(" - {}", file))
+            .unwrap_or_default();
+        println!(
+            "{} ({}{}) [{}] ターン {} {} - {} 件",
+            hit.repo, hit.debate_type, file, hit.endpoint, hit.turn, hit.role, hit.matches
+        );
+        println!("  {}", hit.excerpt);
+        println!("  {}", hit.path.display());
+        println!();
+    }
+    info!(
+        "🔍 「{}」に一致する発言: {} 件（{} 件の議論を検索{}）",
+        options.query,
+        hits.len(),
+        latest.len(),
+        if hits.len() > options.limit {
+            format!("、先頭の {} 件を表示", options.limit)
+        } else {
+            String::new()
+        }
+    );
+    Ok(())
+}
@@ -0,0 +1,59 @@
+// シードモジュール
+// --seed で質問の出題順とAPIの seed パラメーターを固定し、興味深い実行をおおよそ再現できるようにする
+
+use sha2::{Digest, Sha256};
+use std::sync::OnceLock;
+
+static SEED: OnceLock<u64> = OnceLock::new();
+
+// 実行全体のシードを設定
+pub fn init(seed: u64) {
+    let _ = SEED.set(seed);
+}
+
+// 実行全体のシード（未指定ならNone）
+pub fn get() -> Option<u64> {
+    SEED.get().copied()
+}
+
+// 議論ごとのシード（実行全体のシードと議論タスクのIDから決まる）
+pub fn for_task(task_id: &str) -> Option<u64> {
+    let seed = get()?;
+    let mut hasher = Sha256::new();
+    hasher.update(format!("{}\n{}", seed, task_id));
+    let digest = hasher.finalize();
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&digest[..8]);
+    Some(u64::from_le_bytes(bytes))
+}
+
+// シードから決まる疑似乱数列（SplitMix64）
+pub struct SeededRng {
+    state: u64,
+}
+
+impl SeededRng {
+    pub fn new(seed: u64) -> Self {
+        SeededRng { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    // 0..n の乱数
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n.max(1) as u64) as usize
+    }
+
+    // 並び順をシャッフル（Fisher-Yates）
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            items.swap(i, self.below(i + 1));
+        }
+    }
+}
@@ -0,0 +1,554 @@
+// デーモンモードのHTTP APIモジュール
+// リポジトリと議論の登録、タスクの状態確認、結果の取得を行うREST APIを提供し、登録された議論を常駐ワーカーで実行する
+
+use anyhow::Result;
+use axum::body::Bytes;
+use axum::extract::{Path, Query, Request, State};
+use axum::http::{HeaderMap, StatusCode, header};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use hmac::{Hmac, Mac};
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::Sha256;
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+use crate::budget;
+use crate::control;
+use crate::file_review::{self, FILE_REVIEW_TYPE};
+use crate::journal::{self, Journal, TaskState, TaskStatus};
+use crate::llm::schemas::github_response::{Priority, RepoInfo};
+use crate::lock;
+use crate::recurring::{self, RecurringSchedule};
+use crate::scheduler::{self, Scheduler};
+use crate::status;
+use crate::webhook;
+use crate::work_queue::{self, DebateJob, WorkQueue};
+use crate::{Config, GitHubClient, get_debate_types};
+
+// デーモンモードの設定
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ServeConfig {
+    /// 待ち受けるアドレス
+    pub bind: String,
+    /// APIの認証トークン（空の場合は認証なし）
+    pub api_token: String,
+    /// GitHub Webhook のシークレット（空の場合は Webhook を受け付けない）
+    pub webhook_secret: String,
+    /// Webhook で登録する議論タイプ（空の場合はすべて）
+    pub webhook_debate_types: Vec<String>,
+    /// 定期実行のスケジュール
+    pub schedules: Vec<RecurringSchedule>,
+}
+
+impl Default for ServeConfig {
+    fn default() -> Self {
+        ServeConfig {
+            bind: "127.0.0.1:8080".to_string(),
+            api_token: String::new(),
+            webhook_secret: String::new(),
+            webhook_debate_types: Vec::new(),
+            schedules: Vec::new(),
+        }
+    }
+}
+
+// 認証トークンの比較で HMAC を求めるメッセージ
+const TOKEN_CHECK_MESSAGE: &[u8] = b"azure-credit-burner api token";
+
+// ハンドラー間で共有する状態
+struct AppState {
+    queue: Arc<WorkQueue>,
+    journal: Arc<Journal>,
+    config: Arc<Config>,
+    // ファイル単位のレビューのファイル一覧を作るためにリポジトリをクローンする
+    github_client: Arc<GitHubClient>,
+}
+
+// 議論の登録リクエスト
+#[derive(Deserialize, Debug)]
+struct EnqueueRequest {
+    owner: String,
+    repo: String,
+    // 省略時は設定の max_files
+    max_files: Option<usize>,
+    // 省略時はデフォルトブランチ
+    branch: Option<String>,
+    // 省略時はブランチの最新
+    commit: Option<String>,
+    // 省略時は normal
+    #[serde(default)]
+    priority: Priority,
+    // 省略時はすべての議論タイプ
+    #[serde(default)]
+    debate_types: Vec<String>,
+}
+
+// タスク一覧の絞り込み条件
+#[derive(Deserialize, Debug)]
+struct ListQuery {
+    state: Option<TaskState>,
+    repo: Option<String>,
+}
+
+// エラーレスポンス
+fn api_error(status: StatusCode, message: impl Into<String>) -> Response {
+    (status, Json(json!({ "error": message.into() }))).into_response()
+}
+
+// GitHubのオーナー名・リポジトリ名として使える文字だけか
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+// ブランチ名として使える文字だけか（gitのオプションと解釈されないものに限る）
+fn is_valid_branch(branch: &str) -> bool {
+    !branch.is_empty()
+        && !branch.starts_with(['-', '.', '/'])
+        && !branch.contains("..")
+        && branch
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '/'))
+}
+
+// コミットのハッシュとして正しい形式か
+fn is_valid_commit(commit: &str) -> bool {
+    (7..=40).contains(&commit.len()) && commit.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+// 分析対象のリポジトリ情報を検証
+fn validate_repo(repo_info: &RepoInfo) -> Result<(), &'static str> {
+    if !is_valid_name(&repo_info.owner) || !is_valid_name(&repo_info.repo) {
+        return Err("owner または repo の形式が正しくありません");
+    }
+    if repo_info
+        .branch
+        .as_deref()
+        .is_some_and(|branch| !is_valid_branch(branch))
+    {
+        return Err("branch の形式が正しくありません");
+    }
+    if repo_info
+        .commit
+        .as_deref()
+        .is_some_and(|commit| !is_valid_commit(commit))
+    {
+        return Err("commit の形式が正しくありません");
+    }
+    Ok(())
+}
+
+// POST /jobs
+async fn create_jobs(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<EnqueueRequest>,
+) -> Response {
+    let repo_info = RepoInfo {
+        owner: request.owner,
+        repo: request.repo,
+        max_files: request.max_files.unwrap_or(state.config.max_files),
+        branch: request.branch,
+        commit: request.commit,
+        priority: request.priority,
+        ..Default::default()
+    };
+    enqueue_repo(&state, repo_info, request.debate_types)
+}
+
+// リポジトリの議論をまとめて登録（議論タイプが空ならすべて）
+fn enqueue_repo(state: &AppState, repo_info: RepoInfo, debate_types: Vec<String>) -> Response {
+    if let Err(message) = validate_repo(&repo_info) {
+        return api_error(StatusCode::BAD_REQUEST, message);
+    }
+
+    let debate_types = if debate_types.is_empty() {
+        scheduler::debate_types_for(&repo_info, &get_debate_types(&state.config))
+    } else {
+        debate_types
+    };
+
+    // 新しく登録した議論は、前回の登録以降のリモートの変更を取り込んで実行する
+    state.github_client.refresh_clone(&repo_info);
+    let tasks: Vec<TaskStatus> = debate_types
+        .into_iter()
+        .map(|debate_type| {
+            state.queue.submit(
+                &state.journal,
+                DebateJob::new(repo_info.clone(), debate_type, 0),
+            )
+        })
+        .collect();
+    info!(
+        "📥 議論を登録しました: {}/{} ({}件)",
+        repo_info.owner,
+        repo_info.repo,
+        tasks.len()
+    );
+    // ファイル単位のレビューはクローンしてファイル一覧を作ってから登録する
+    if state.config.file_review.enabled {
+        tokio::spawn(queue_file_reviews(
+            state.queue.clone(),
+            state.journal.clone(),
+            state.config.clone(),
+            state.github_client.clone(),
+            vec![repo_info],
+            None,
+        ));
+    }
+
+    (StatusCode::ACCEPTED, Json(json!({ "tasks": tasks }))).into_response()
+}
+
+// POST /webhooks/github
+async fn github_webhook(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let secret = &state.config.serve.webhook_secret;
+    if secret.is_empty() {
+        return api_error(StatusCode::NOT_FOUND, "Webhook は無効です");
+    }
+
+    let signature = headers
+        .get("X-Hub-Signature-256")
+        .and_then(|value| value.to_str().ok());
+    if !webhook::verify_signature(secret, &body, signature) {
+        return api_error(StatusCode::UNAUTHORIZED, "Webhook の署名が正しくありません");
+    }
+
+    let event = headers
+        .get("X-GitHub-Event")
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    if event == "ping" {
+        return Json(json!({ "message": "pong" })).into_response();
+    }
+
+    let payload: serde_json::Value = match serde_json::from_slice(&body) {
+        Ok(payload) => payload,
+        Err(e) => return api_error(StatusCode::BAD_REQUEST, e.to_string()),
+    };
+    match webhook::parse_event(event, &payload, state.config.max_files) {
+        Ok(Some(mut repo_info)) => {
+            // 設定ファイルにあるリポジトリは議論タイプの指定を引き継ぐ
+            if let Some(configured) = state.config.repos.iter().find(|configured| {
+                configured.owner == repo_info.owner && configured.repo == repo_info.repo
+            }) {
+                repo_info.debate_types = configured.debate_types.clone();
+                repo_info.skip_debate_types = configured.skip_debate_types.clone();
+            }
+            info!(
+                "🪝 Webhook を受信しました: {} {}/{} ({})",
+                event,
+                repo_info.owner,
+                repo_info.repo,
+                repo_info.commit.as_deref().unwrap_or_default()
+            );
+            enqueue_repo(
+                &state,
+                repo_info,
+                state.config.serve.webhook_debate_types.clone(),
+            )
+        }
+        Ok(None) => (
+            StatusCode::ACCEPTED,
+            Json(json!({ "tasks": [], "message": "分析対象外のイベントです" })),
+        )
+            .into_response(),
+        Err(e) => api_error(StatusCode::BAD_REQUEST, e.to_string()),
+    }
+}
+
+// GET /tasks
+async fn list_tasks(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ListQuery>,
+) -> Json<Vec<TaskStatus>> {
+    let tasks = state
+        .journal
+        .list()
+        .into_iter()
+        .filter(|task| query.state.is_none_or(|state| task.state == state))
+        .filter(|task| query.repo.as_ref().is_none_or(|repo| &task.repo == repo))
+        .collect();
+    Json(tasks)
+}
+
+// GET /tasks/{id}
+async fn get_task(State(state): State<Arc<AppState>>, Path(task_id): Path<String>) -> Response {
+    match state.journal.status(&task_id) {
+        Some(status) => Json(status).into_response(),
+        None => api_error(StatusCode::NOT_FOUND, "タスクが見つかりません"),
+    }
+}
+
+// GET /tasks/{id}/result
+async fn get_result(State(state): State<Arc<AppState>>, Path(task_id): Path<String>) -> Response {
+    if state.journal.status(&task_id).is_none() {
+        return api_error(StatusCode::NOT_FOUND, "タスクが見つかりません");
+    }
+    match state.journal.load_latest(&task_id) {
+        Ok(Some(response_data)) => Json(response_data).into_response(),
+        Ok(None) => api_error(StatusCode::NOT_FOUND, "まだ結果が保存されていません"),
+        Err(e) => api_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+// 送られたトークンが設定したトークンと一致するか
+// 比較にかかる時間からトークンを推測されないよう、両方の HMAC を定数時間で比較する（長さの違いも漏らさない）
+fn token_matches(token: &str, value: &str) -> bool {
+    let (Ok(mut expected), Ok(mut candidate)) = (
+        Hmac::<Sha256>::new_from_slice(token.as_bytes()),
+        Hmac::<Sha256>::new_from_slice(value.as_bytes()),
+    ) else {
+        return false;
+    };
+    candidate.update(TOKEN_CHECK_MESSAGE);
+    expected.update(TOKEN_CHECK_MESSAGE);
+    expected
+        .verify_slice(&candidate.finalize().into_bytes())
+        .is_ok()
+}
+
+// 認証トークンの確認
+async fn authorize(State(state): State<Arc<AppState>>, request: Request, next: Next) -> Response {
+    let token = &state.config.serve.api_token;
+    if token.is_empty() {
+        return next.run(request).await;
+    }
+
+    let authorized = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|value| token_matches(token, value));
+    if !authorized {
+        return api_error(StatusCode::UNAUTHORIZED, "認証トークンが正しくありません");
+    }
+    next.run(request).await
+}
+
+// リポジトリのファイル単位のレビューをキューに積む（CLI と同じく完了済みのファイルは積まず、再試行の予定を引き継ぐ）
+// pending を指定した場合は、前回停止時に未完了だったタスクだけを積み直す
+async fn queue_file_reviews(
+    queue: Arc<WorkQueue>,
+    journal: Arc<Journal>,
+    config: Arc<Config>,
+    github_client: Arc<GitHubClient>,
+    repos: Vec<RepoInfo>,
+    pending: Option<BTreeSet<String>>,
+) {
+    let mut jobs = file_review::plan_jobs(&github_client, &repos, &config.file_review).await;
+    jobs.retain(|job| match &pending {
+        Some(pending) => pending.contains(&job.task_id),
+        // 登録済み・実行中のファイルは重ねて積まない
+        None => !matches!(
+            journal.status(&job.task_id).map(|status| status.state),
+            Some(TaskState::Done | TaskState::Queued | TaskState::Running)
+        ),
+    });
+    work_queue::restore_retries(&mut jobs, &journal);
+    for job in jobs.iter().filter(|job| job.retry_at.is_none()) {
+        journal.record(
+            &job.task_id,
+            &job.repo_info,
+            &job.debate_type,
+            TaskState::Queued,
+            None,
+            None,
+        );
+    }
+    info!("📄 ファイル単位のレビューを登録しました: {} 件", jobs.len());
+    for job in jobs {
+        queue.push(job);
+    }
+}
+
+// 前回停止時に未完了だった議論をキューに戻す
+fn restore_pending(state: &AppState) {
+    let pending: Vec<TaskStatus> = state
+        .journal
+        .list()
+        .into_iter()
+        .filter(|task| {
+            matches!(task.state, TaskState::Queued | TaskState::Running)
+                || (task.state == TaskState::Failed && task.retry_at.is_some())
+        })
+        .collect();
+
+    // ファイル単位のレビューは対象のファイルがジャーナルに残らないため、ファイル一覧を作り直して積み直す
+    let mut file_review_repos: Vec<RepoInfo> = Vec::new();
+    let mut file_review_tasks = BTreeSet::new();
+    for task in &pending {
+        let Some((owner, repo)) = task.repo.split_once('/') else {
+            continue;
+        };
+        let configured = state
+            .config
+            .repos
+            .iter()
+            .find(|repo_info| repo_info.owner == owner && repo_info.repo == repo);
+
+        let mut job = DebateJob {
+            task_id: task.task_id.clone(),
+            repo_info: RepoInfo {
+                owner: owner.to_string(),
+                repo: repo.to_string(),
+                max_files: configured
+                    .map(|repo_info| repo_info.max_files)
+                    .unwrap_or(state.config.max_files),
+                branch: task.branch.clone(),
+                commit: task.commit.clone(),
+                priority: configured
+                    .map(|repo_info| repo_info.priority)
+                    .unwrap_or_default(),
+                ..Default::default()
+            },
+            debate_type: task.debate_type.clone(),
+            perspective: task.perspective.unwrap_or_default(),
+            file: None,
+            attempts: 0,
+            retry_at: None,
+            failed_endpoints: Vec::new(),
+        };
+        if task.debate_type == FILE_REVIEW_TYPE {
+            file_review_tasks.insert(task.task_id.clone());
+            if !file_review_repos.iter().any(|repo_info| {
+                (
+                    &repo_info.owner,
+                    &repo_info.repo,
+                    &repo_info.branch,
+                    &repo_info.commit,
+                ) == (
+                    &job.repo_info.owner,
+                    &job.repo_info.repo,
+                    &job.repo_info.branch,
+                    &job.repo_info.commit,
+                )
+            }) {
+                file_review_repos.push(job.repo_info);
+            }
+            continue;
+        }
+        job.restore_retry(task);
+        state.queue.push(job);
+    }
+
+    if !pending.is_empty() {
+        info!("📒 未完了の議論をキューに戻しました: {} 件", pending.len());
+    }
+    if !file_review_repos.is_empty() {
+        tokio::spawn(queue_file_reviews(
+            state.queue.clone(),
+            state.journal.clone(),
+            state.config.clone(),
+            state.github_client.clone(),
+            file_review_repos,
+            Some(file_review_tasks),
+        ));
+    }
+}
+
+// デーモンモードで起動
+pub async fn run_server(config: Config, dry_run: bool, force_lock: bool) -> Result<()> {
+    tokio::fs::create_dir_all(&config.output_dir).await?;
+    let _lock = lock::acquire(&config.output_dir, force_lock)?;
+
+    let github_client = Arc::new(GitHubClient::new(&config)?);
+    let endpoints = Arc::new(config.endpoints.clone());
+    let scheduler = Arc::new(Scheduler::new(&endpoints, &config.scheduler));
+    let journal = Arc::new(Journal::open(&journal::journal_path(
+        &config.output_dir,
+        dry_run,
+    ))?);
+    let config = Arc::new(config);
+
+    let state = Arc::new(AppState {
+        queue: Arc::new(WorkQueue::open()),
+        journal: journal.clone(),
+        config: config.clone(),
+        github_client: github_client.clone(),
+    });
+    restore_pending(&state);
+    // 常駐している間の使用量を予算と比べ、再起動しても数え直さないよう保存する
+    budget::configure(
+        config.max_tokens_per_repo,
+        config.max_total_tokens,
+        Some(budget::state_path(&config.output_dir, dry_run)),
+    );
+    status::start(&config.status, &config.output_dir, dry_run);
+
+    // 実行中の操作コマンドを受け付ける
+    let control = control::start(
+        &config.control,
+        &config.output_dir,
+        config.concurrency,
+        &endpoints,
+    );
+
+    // 常駐ワーカーを起動（キューが空でも終了しない）
+    let workers = work_queue::spawn_workers(
+        state.queue.clone(),
+        github_client,
+        endpoints,
+        config.clone(),
+        scheduler,
+        journal.clone(),
+    );
+
+    // cron式に従った定期実行
+    let schedules = tokio::spawn(recurring::run_schedules(
+        state.queue.clone(),
+        journal,
+        config.clone(),
+    ));
+
+    let app = Router::new()
+        .route("/jobs", post(create_jobs))
+        .route("/tasks", get(list_tasks))
+        .route("/tasks/:task_id", get(get_task))
+        .route("/tasks/:task_id/result", get(get_result))
+        .layer(middleware::from_fn_with_state(state.clone(), authorize))
+        .route("/health", get(|| async { "ok" }))
+        // Webhook は署名で認証する
+        .route("/webhooks/github", post(github_webhook))
+        .with_state(state);
+
+    let listener = tokio::net::TcpListener::bind(&config.serve.bind).await?;
+    info!(
+        "🛰️ デーモンモードで起動しました: http://{}",
+        config.serve.bind
+    );
+
+    let result = axum::serve(listener, app)
+        .with_graceful_shutdown(async {
+            tokio::signal::ctrl_c().await.ok();
+            info!("🛑 停止します（実行中の議論は次回起動時に再開されます）");
+        })
+        .await;
+    if let Err(e) = &result {
+        error!("❌ HTTPサーバーエラー: {}", e);
+    }
+
+    schedules.abort();
+    if let Some(control) = control {
+        control::stop(control, &config.control, &config.output_dir);
+    }
+    for worker in workers {
+        worker.abort();
+    }
+    budget::flush();
+    budget::log_summary();
+    Ok(result?)
+}
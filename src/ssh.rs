@@ -0,0 +1,72 @@
+// SSH でのクローンモジュール
+// HTTPS のアクセストークンが使えない組織向けに、git@github.com: 形式の URL と指定した鍵でリポジトリを取得する
+
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+// SSH でのクローンの設定
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct SshConfig {
+    /// SSH でクローンするか（有効にすると github_token と github_app より優先する）
+    pub enabled: bool,
+    /// 秘密鍵のパス（省略時は ssh の既定の鍵と ssh-agent を使う）
+    pub key_path: Option<String>,
+    /// 接続先のホスト（GitHub Enterprise Server では変更する）
+    pub host: String,
+    /// 初めて接続するホストの鍵を known_hosts に追加するか（無効なら登録済みのホストにだけ接続する）
+    pub accept_new_host_keys: bool,
+}
+
+impl Default for SshConfig {
+    fn default() -> Self {
+        SshConfig {
+            enabled: false,
+            key_path: None,
+            host: "github.com".to_string(),
+            accept_new_host_keys: true,
+        }
+    }
+}
+
+impl SshConfig {
+    // 鍵のパス（先頭の ~/ はホームディレクトリに展開する）
+    fn key_path(&self) -> Option<String> {
+        let key_path = self.key_path.as_ref()?;
+        Some(match (key_path.strip_prefix("~/"), std::env::var("HOME")) {
+            (Some(rest), Ok(home)) => format!("{}/{}", home, rest),
+            _ => key_path.clone(),
+        })
+    }
+
+    // 鍵のファイルがあるか確認
+    pub fn validate(&self) -> Result<()> {
+        match self.key_path() {
+            Some(key_path) if self.enabled && !Path::new(&key_path).exists() => {
+                Err(anyhow!("SSH の秘密鍵が見つかりません: {}", key_path))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // クローンに使う URL
+    pub fn clone_url(&self, owner: &str, repo: &str) -> String {
+        format!("git@{}:{}/{}.git", self.host, owner, repo)
+    }
+
+    // git に渡す GIT_SSH_COMMAND（パスフレーズやホスト鍵の確認で止まらないようにする）
+    pub fn command(&self) -> String {
+        let mut command = vec!["ssh".to_string(), "-o BatchMode=yes".to_string()];
+        if let Some(key_path) = self.key_path() {
+            command.push(format!("-i '{}'", key_path.replace('\'', r"'\''")));
+            command.push("-o IdentitiesOnly=yes".to_string());
+        }
+        let host_key_checking = match self.accept_new_host_keys {
+            true => "accept-new",
+            false => "yes",
+        };
+        command.push(format!("-o StrictHostKeyChecking={}", host_key_checking));
+        command.join(" ")
+    }
+}
@@ -0,0 +1,228 @@
+// コード統計モジュール
+// cloc のようにリポジトリのコードファイルの行数（コード・コメント・空行）とファイル数を言語ごとに数え、
+// プロンプトのテンプレート変数 {code_stats} として議論に渡す（コードベースの規模を数字で踏まえて議論させる）
+
+use anyhow::{Result, anyhow};
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+use tokio::fs;
+
+use crate::llm::schemas::github_response::RepoInfo;
+use crate::{GitHubClient, file_guard};
+
+// コード統計の設定
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct StatsConfig {
+    /// コード統計をプロンプトに加えるか
+    pub enabled: bool,
+    /// 表示する言語の最大数（コード行数の多い順、残りは「その他」にまとめる）
+    pub max_languages: usize,
+}
+
+impl Default for StatsConfig {
+    fn default() -> Self {
+        StatsConfig {
+            enabled: true,
+            max_languages: 8,
+        }
+    }
+}
+
+// コード統計がない場合のテンプレート変数の値
+pub const NO_STATS: &str = "コード統計はありません。";
+
+// 言語名・拡張子・行コメントの記号・ブロックコメントの開始と終了
+type CommentSyntax = (
+    &'static str,
+    &'static [&'static str],
+    &'static [&'static str],
+    Option<(&'static str, &'static str)>,
+);
+
+const C_BLOCK: Option<(&str, &str)> = Some(("/*", "*/"));
+
+const SYNTAXES: [CommentSyntax; 25] = [
+    ("Rust", &["rs"], &["//"], C_BLOCK),
+    ("Java", &["java"], &["//"], C_BLOCK),
+    ("Kotlin", &["kt"], &["//"], C_BLOCK),
+    ("Go", &["go"], &["//"], C_BLOCK),
+    ("TypeScript", &["ts", "tsx"], &["//"], C_BLOCK),
+    ("JavaScript", &["js", "jsx"], &["//"], C_BLOCK),
+    ("C/C++", &["c", "h", "cpp", "hpp"], &["//"], C_BLOCK),
+    ("C#", &["cs"], &["//"], C_BLOCK),
+    ("Swift", &["swift"], &["//"], C_BLOCK),
+    ("PHP", &["php"], &["//", "#"], C_BLOCK),
+    ("Protocol Buffers", &["proto"], &["//"], C_BLOCK),
+    ("Python", &["py"], &["#"], Some(("\"\"\"", "\"\"\""))),
+    ("Ruby", &["rb"], &["#"], Some(("=begin", "=end"))),
+    ("Shell", &["sh", "bash"], &["#"], None),
+    ("PowerShell", &["ps1"], &["#"], Some(("<#", "#>"))),
+    ("SQL", &["sql"], &["--"], C_BLOCK),
+    ("GraphQL", &["graphql"], &["#"], None),
+    ("CSS", &["css"], &[], C_BLOCK),
+    ("SCSS/Less", &["scss", "less"], &["//"], C_BLOCK),
+    ("HTML", &["html"], &[], Some(("<!--", "-->"))),
+    ("XML", &["xml"], &[], Some(("<!--", "-->"))),
+    ("YAML", &["yaml", "yml"], &["#"], None),
+    ("TOML", &["toml"], &["#"], None),
+    ("JSON", &["json"], &[], None),
+    ("Markdown", &["md"], &[], None),
+];
+
+// 言語ごとの行数
+#[derive(Debug, Clone, Default)]
+struct LanguageStats {
+    files: usize,
+    code: usize,
+    comment: usize,
+    blank: usize,
+}
+
+impl LanguageStats {
+    fn add(&mut self, other: &LanguageStats) {
+        self.files += other.files;
+        self.code += other.code;
+        self.comment += other.comment;
+        self.blank += other.blank;
+    }
+}
+
+static CONFIG: OnceLock<StatsConfig> = OnceLock::new();
+
+// リポジトリ（owner/repo）ごとのコード統計（議論タイプごとに数え直さない）
+static STATS: OnceLock<Mutex<HashMap<String, String>>> = OnceLock::new();
+
+// コード統計の設定を登録する
+pub fn configure(config: &StatsConfig) {
+    let _ = CONFIG.set(config.clone());
+}
+
+// ファイルの言語の構文（拡張子が表にないファイルは None）
+fn syntax_for(path: &Path) -> Option<&'static CommentSyntax> {
+    let extension = path.extension()?.to_string_lossy().to_lowercase();
+    SYNTAXES
+        .iter()
+        .find(|(_, extensions, _, _)| extensions.contains(&extension.as_str()))
+}
+
+// 1つのファイルの行をコード・コメント・空行に分ける
+fn count_lines(content: &str, syntax: Option<&CommentSyntax>) -> LanguageStats {
+    let (line_comments, block): (&[&str], _) = match syntax {
+        Some((_, _, line_comments, block)) => (line_comments, *block),
+        None => (&[], None),
+    };
+    let mut stats = LanguageStats {
+        files: 1,
+        ..Default::default()
+    };
+    let mut in_block = false;
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            stats.blank += 1;
+        } else if in_block {
+            stats.comment += 1;
+            if let Some((_, end)) = block {
+                in_block = !line.contains(end);
+            }
+        } else if line_comments.iter().any(|prefix| line.starts_with(prefix)) {
+            stats.comment += 1;
+        } else if let Some((start, end)) = block.filter(|(start, _)| line.starts_with(start)) {
+            stats.comment += 1;
+            in_block = !line[start.len()..].contains(end);
+        } else {
+            stats.code += 1;
+        }
+    }
+    stats
+}
+
+// 言語ごとの行数をプロンプト用の文章にする
+fn render(languages: BTreeMap<&'static str, LanguageStats>, max_languages: usize) -> String {
+    let mut total = LanguageStats::default();
+    for stats in languages.values() {
+        total.add(stats);
+    }
+    let mut languages: Vec<_> = languages.into_iter().collect();
+    languages.sort_by_key(|(_, stats)| std::cmp::Reverse(stats.code));
+    if languages.len() > max_languages {
+        let mut others = LanguageStats::default();
+        for (_, stats) in languages.drain(max_languages..) {
+            others.add(&stats);
+        }
+        languages.push(("その他", others));
+    }
+
+    let mut text = format!(
+        "総ファイル数: {} / 総行数: {}（コード: {}, コメント: {}, 空行: {}）\n",
+        total.files,
+        total.code + total.comment + total.blank,
+        total.code,
+        total.comment,
+        total.blank
+    );
+    for (name, stats) in languages {
+        text.push_str(&format!(
+            "- {}: {} ファイル, コード {} 行（{:.0}%）, コメント {} 行, 空行 {} 行\n",
+            name,
+            stats.files,
+            stats.code,
+            stats.code as f64 * 100.0 / total.code.max(1) as f64,
+            stats.comment,
+            stats.blank
+        ));
+    }
+    text
+}
+
+// リポジトリのコード統計（無効なら None）
+pub async fn collect(github_client: &GitHubClient, repo_info: &RepoInfo) -> Result<Option<String>> {
+    let Some(config) = CONFIG.get().filter(|config| config.enabled) else {
+        return Ok(None);
+    };
+    let repo = format!("{}/{}", repo_info.owner, repo_info.repo);
+    let cache = STATS.get_or_init(|| Mutex::new(HashMap::new()));
+    if let Some(cached) = cache.lock().unwrap().get(&repo) {
+        return Ok(Some(cached.clone()));
+    }
+
+    let (_, paths) = github_client
+        .list_code_files(repo_info)
+        .await
+        .map_err(|e| anyhow!("コード統計を取得できません: {} - {}", repo, e))?;
+    let mut languages: BTreeMap<&'static str, LanguageStats> = BTreeMap::new();
+    for path in &paths {
+        // 読めないファイルやバイナリは数えない
+        let Ok(bytes) = fs::read(path).await else {
+            continue;
+        };
+        if file_guard::is_binary(&bytes) {
+            continue;
+        }
+        let Ok(content) = String::from_utf8(bytes) else {
+            continue;
+        };
+        let syntax = syntax_for(path);
+        let name = syntax.map(|(name, _, _, _)| *name).unwrap_or("その他");
+        languages
+            .entry(name)
+            .or_default()
+            .add(&count_lines(&content, syntax));
+    }
+    if languages.is_empty() {
+        return Ok(None);
+    }
+
+    let text = render(languages, config.max_languages.max(1));
+    info!(
+        "📏 コード統計: {} - {}",
+        repo,
+        text.lines().next().unwrap_or_default()
+    );
+    cache.lock().unwrap().insert(repo, text.clone());
+    Ok(Some(text))
+}
@@ -0,0 +1,195 @@
+// 実行状況ファイルモジュール
+// 実行中の議論、エンドポイントごとの最後に成功した呼び出し、累計トークン数、エラーを
+// 定期的に status.json に書き出し、外部の監視（や watch cat）から止まっていないことを確認できるようにする
+
+use chrono::prelude::*;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time;
+
+use crate::llm::schemas::github_response::RepoInfo;
+use crate::{ledger, redact, runs};
+
+// 実行状況ファイルの設定
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct StatusConfig {
+    /// 実行状況ファイルを書き出すか
+    pub enabled: bool,
+    /// 書き出す間隔（秒）
+    pub interval_secs: u64,
+    /// 書き出すファイル（省略時は出力ディレクトリの status.json、ドライランでは status.dry-run.json）
+    pub path: Option<String>,
+}
+
+impl Default for StatusConfig {
+    fn default() -> Self {
+        StatusConfig {
+            enabled: false,
+            interval_secs: 30,
+            path: None,
+        }
+    }
+}
+
+// 実行中の議論
+#[derive(Serialize, Clone)]
+struct ActiveTask {
+    repo: String,
+    debate_type: String,
+    endpoint: String,
+    started_at: String,
+}
+
+// エンドポイントごとの状況
+#[derive(Serialize, Default, Clone)]
+struct EndpointStatus {
+    calls: usize,
+    tokens: usize,
+    last_success_at: Option<String>,
+    errors: usize,
+    last_error: Option<String>,
+    last_error_at: Option<String>,
+}
+
+// status.json の内容
+#[derive(Serialize, Default, Clone)]
+struct Status {
+    pid: u32,
+    run_id: Option<String>,
+    started_at: String,
+    updated_at: String,
+    // running / finished
+    state: String,
+    total_tokens: usize,
+    total_errors: usize,
+    completed_tasks: usize,
+    // 台帳の推定使用額（今回の実行と累計）
+    spend: Option<ledger::Spend>,
+    active_tasks: BTreeMap<String, ActiveTask>,
+    endpoints: BTreeMap<String, EndpointStatus>,
+}
+
+static STATUS: Mutex<Option<Status>> = Mutex::new(None);
+static PATH: Mutex<Option<String>> = Mutex::new(None);
+
+// 実行状況ファイルの書き出しを開始（無効なら何もしない）
+pub fn start(config: &StatusConfig, output_dir: &str, dry_run: bool) {
+    if !config.enabled {
+        return;
+    }
+    let path = config.path.clone().unwrap_or_else(|| {
+        if dry_run {
+            format!("{}/status.dry-run.json", output_dir)
+        } else {
+            format!("{}/status.json", output_dir)
+        }
+    });
+    info!(
+        "💓 実行状況を {} 秒ごとに書き出します: {}",
+        config.interval_secs, path
+    );
+    *PATH.lock().unwrap() = Some(path);
+    *STATUS.lock().unwrap() = Some(Status {
+        pid: std::process::id(),
+        run_id: runs::current_id(),
+        started_at: Utc::now().to_rfc3339(),
+        state: "running".to_string(),
+        ..Default::default()
+    });
+
+    let interval = Duration::from_secs(config.interval_secs.max(1));
+    tokio::spawn(async move {
+        loop {
+            write().await;
+            time::sleep(interval).await;
+        }
+    });
+}
+
+// 状況を更新する（書き出しが無効なら何もしない）
+fn update(apply: impl FnOnce(&mut Status)) {
+    if let Some(status) = STATUS.lock().unwrap().as_mut() {
+        apply(status);
+    }
+}
+
+// 議論の開始を記録
+pub fn task_started(task_id: &str, repo_info: &RepoInfo, debate_type: &str, endpoint: &str) {
+    update(|status| {
+        status.active_tasks.insert(
+            task_id.to_string(),
+            ActiveTask {
+                repo: format!("{}/{}", repo_info.owner, repo_info.repo),
+                debate_type: debate_type.to_string(),
+                endpoint: endpoint.to_string(),
+                started_at: Utc::now().to_rfc3339(),
+            },
+        );
+    });
+}
+
+// 議論の終了を記録
+pub fn task_finished(task_id: &str, completed: bool) {
+    update(|status| {
+        status.active_tasks.remove(task_id);
+        if completed {
+            status.completed_tasks += 1;
+        }
+    });
+}
+
+// 成功したAPI呼び出しを記録
+pub fn record_call(endpoint: &str, tokens: usize) {
+    update(|status| {
+        status.total_tokens += tokens;
+        let entry = status.endpoints.entry(endpoint.to_string()).or_default();
+        entry.calls += 1;
+        entry.tokens += tokens;
+        entry.last_success_at = Some(Utc::now().to_rfc3339());
+    });
+}
+
+// エラーを記録
+pub fn record_error(endpoint: &str, message: &str) {
+    update(|status| {
+        status.total_errors += 1;
+        let entry = status.endpoints.entry(endpoint.to_string()).or_default();
+        entry.errors += 1;
+        entry.last_error = Some(redact::scrub(message));
+        entry.last_error_at = Some(Utc::now().to_rfc3339());
+    });
+}
+
+// 実行状況ファイルを書き出す（途中の状態を読まれないように一時ファイルから置き換える）
+async fn write() {
+    let Some(path) = PATH.lock().unwrap().clone() else {
+        return;
+    };
+    let Some(mut status) = STATUS.lock().unwrap().clone() else {
+        return;
+    };
+    status.updated_at = Utc::now().to_rfc3339();
+    status.spend = ledger::spend();
+
+    let result = async {
+        let json = serde_json::to_string_pretty(&status)?;
+        let temp = format!("{}.tmp", path);
+        tokio::fs::write(&temp, json).await?;
+        tokio::fs::rename(&temp, &path).await?;
+        anyhow::Ok(())
+    }
+    .await;
+    if let Err(e) = result {
+        warn!("⚠️ 実行状況ファイルを書き出せません: {} - {}", path, e);
+    }
+}
+
+// 実行の終了を書き出す
+pub async fn finish() {
+    update(|status| status.state = "finished".to_string());
+    write().await;
+}
@@ -0,0 +1,143 @@
+// リポジトリ単位のエグゼクティブサマリー生成モジュール
+// 保存済みの議論結果をまとめてモデルに渡し、リポジトリごとの要約を作成する
+
+use anyhow::{Result, bail};
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use crate::llm::schemas::{
+    github_response::RepoInfo,
+    openai_response::{ChatMessage, Endpoint, ResponseData},
+};
+use crate::{LlmClient, encryption, generation, outputs};
+
+// サマリー生成の設定
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct SummaryConfig {
+    /// 分析完了後にサマリーを生成するか
+    pub enabled: bool,
+    /// サマリー生成に渡す議論結果の最大文字数
+    pub max_input_chars: usize,
+}
+
+impl Default for SummaryConfig {
+    fn default() -> Self {
+        SummaryConfig {
+            enabled: false,
+            max_input_chars: 60000,
+        }
+    }
+}
+
+// サマリー作成の指示
+const SUMMARY_PROMPT: &str = "あなたはCTO向けに技術レポートをまとめるシニアエンジニアです。\
+以下はあるリポジトリについて複数の観点から行われたAIによる分析結果です。\
+これらをもとに、1ページ程度のエグゼクティブサマリーをMarkdownで作成してください。\
+全体評価、主要なリスク、優先度の高い改善提案を簡潔にまとめてください。";
+
+// サマリーの保存先パス
+pub fn summary_path(base_dir: &str, repo_info: &RepoInfo) -> String {
+    format!(
+        "{}/{}_{}/summary.md",
+        base_dir, repo_info.owner, repo_info.repo
+    )
+}
+
+// 議論タイプごとに最終ターンの回答を集めてサマリー入力を作る
+pub fn collect_debate_results(base_dir: &str, repo_info: &RepoInfo, max_chars: usize) -> String {
+    let repo_name = format!("{}/{}", repo_info.owner, repo_info.repo);
+
+    // 議論タイプ（＋エンドポイント）ごとに最も進んだターンを選ぶ
+    let latest: Vec<ResponseData> = outputs::latest_per_debate(outputs::load_responses(base_dir))
+        .into_iter()
+        .map(|(_, response)| response)
+        .filter(|response| response.repo == repo_name)
+        .collect();
+
+    let mut input = String::new();
+    for response in latest {
+        input.push_str(&format!("\n\n## {}\n", response.debate_type));
+        for message in response.messages.iter().filter(|m| m.role == "assistant") {
+            input.push_str(&message.content);
+            input.push('\n');
+        }
+    }
+
+    input.chars().take(max_chars).collect()
+}
+
+// 1リポジトリのサマリーを生成して保存
+pub async fn generate_repo_summary(
+    client: &LlmClient,
+    base_dir: &str,
+    repo_info: &RepoInfo,
+    config: &SummaryConfig,
+) -> Result<String> {
+    let results = collect_debate_results(base_dir, repo_info, config.max_input_chars);
+    if results.trim().is_empty() {
+        bail!(
+            "分析結果が見つかりません: {}/{}",
+            repo_info.owner,
+            repo_info.repo
+        );
+    }
+
+    let messages = vec![
+        ChatMessage {
+            role: "system".to_string(),
+            content: SUMMARY_PROMPT.to_string(),
+            ..Default::default()
+        },
+        ChatMessage {
+            role: "user".to_string(),
+            content: format!(
+                "リポジトリ: {}/{}\n{}",
+                repo_info.owner, repo_info.repo, results
+            ),
+            ..Default::default()
+        },
+    ];
+
+    let (summary, tokens_used) = client
+        .chat_completion(&messages, generation::default_model(), 4000, 0.3)
+        .await?;
+
+    // ターンのファイルを別の配置で保存した場合は、リポジトリの出力ディレクトリがまだないことがある
+    let path = summary_path(base_dir, repo_info);
+    if let Some(dir) = std::path::Path::new(&path).parent() {
+        fs::create_dir_all(dir).await?;
+    }
+    encryption::write(&path, summary).await?;
+
+    info!(
+        "📝 サマリーを保存しました: {} (トークン数: {})",
+        path, tokens_used
+    );
+
+    Ok(path)
+}
+
+// すべてのリポジトリのサマリーを生成
+pub async fn summarize_repos(
+    endpoints: &[Endpoint],
+    repos: &[RepoInfo],
+    base_dir: &str,
+    config: &SummaryConfig,
+) {
+    if endpoints.is_empty() {
+        error!("❌ サマリー生成に使用できるエンドポイントがありません");
+        return;
+    }
+
+    for (i, repo_info) in repos.iter().enumerate() {
+        let client = LlmClient::new(endpoints[i % endpoints.len()].clone());
+        if let Err(e) = generate_repo_summary(&client, base_dir, repo_info, config).await {
+            error!(
+                "❌ サマリー生成エラー: {}/{} - {}",
+                repo_info.owner, repo_info.repo, e
+            );
+        }
+    }
+}
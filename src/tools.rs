@@ -0,0 +1,332 @@
+// ファイル取得ツールのモジュール
+// 議論中のモデルにクローンしたリポジトリのファイル一覧と内容を取得するツールを渡し、
+// 次に読みたいコードをモデル自身が選べるようにする（クローンしたディレクトリの外と .git は読ませない）
+
+use anyhow::{Result, anyhow, bail};
+use log::info;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Component, Path, PathBuf};
+
+use crate::generation::Generation;
+use crate::llm::schemas::openai_response::{
+    ChatMessage, FunctionDefinition, ToolCall, ToolDefinition,
+};
+use crate::{LlmClient, file_guard};
+
+// ファイル取得ツールの設定
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ToolsConfig {
+    /// 議論中にモデルがリポジトリのファイルを取得できるようにするか
+    pub enabled: bool,
+    /// 1ターンあたりのツール呼び出しの上限
+    pub max_calls_per_turn: usize,
+    /// ツールの結果としてモデルに返す最大文字数
+    pub max_result_chars: usize,
+    /// ディレクトリ一覧で返す最大件数
+    pub max_entries: usize,
+}
+
+impl Default for ToolsConfig {
+    fn default() -> Self {
+        ToolsConfig {
+            enabled: false,
+            max_calls_per_turn: 8,
+            max_result_chars: 20000,
+            max_entries: 200,
+        }
+    }
+}
+
+// ツールの定義（ドライランのモックは先頭のツールを呼び出す）
+fn definitions() -> Vec<ToolDefinition> {
+    let function = |name: &str, description: &str, parameters: Value| ToolDefinition {
+        kind: "function".to_string(),
+        function: FunctionDefinition {
+            name: name.to_string(),
+            description: description.to_string(),
+            parameters,
+        },
+    };
+    vec![
+        function(
+            "list_directory",
+            "リポジトリ内のディレクトリにあるファイルとサブディレクトリの一覧を取得する",
+            json!({
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "リポジトリのルートからの相対パス（ルートは \".\"）",
+                    },
+                },
+                "required": ["path"],
+            }),
+        ),
+        function(
+            "read_file",
+            "リポジトリ内のファイルの内容を取得する（行番号を指定すると一部だけ取得する）",
+            json!({
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "リポジトリのルートからの相対パス",
+                    },
+                    "start_line": {
+                        "type": "integer",
+                        "description": "取得を始める行（1から数える、省略時は先頭）",
+                    },
+                    "end_line": {
+                        "type": "integer",
+                        "description": "取得を終える行（この行を含む、省略時は末尾）",
+                    },
+                },
+                "required": ["path"],
+            }),
+        ),
+    ]
+}
+
+// ツールから読めるリポジトリの範囲
+pub struct RepoSandbox {
+    root: PathBuf,
+    max_file_size: usize,
+    config: ToolsConfig,
+}
+
+impl RepoSandbox {
+    pub fn new(repo_dir: &Path, max_file_size: usize, config: &ToolsConfig) -> Result<Self> {
+        let root = repo_dir.canonicalize().map_err(|e| {
+            anyhow!(
+                "リポジトリのディレクトリを開けません: {} - {}",
+                repo_dir.display(),
+                e
+            )
+        })?;
+        Ok(RepoSandbox {
+            root,
+            max_file_size,
+            config: config.clone(),
+        })
+    }
+
+    // モデルが指定したパスをリポジトリ内の実際のパスに変換する（外側や .git を指すパスは拒否する）
+    fn resolve(&self, path: &str) -> Result<PathBuf> {
+        let relative = Path::new(path.trim_start_matches("./"));
+        if relative.is_absolute() {
+            bail!("絶対パスは指定できません: {}", path);
+        }
+        let resolved = self
+            .root
+            .join(relative)
+            .canonicalize()
+            .map_err(|_| anyhow!("見つかりません: {}", path))?;
+        let inside = resolved
+            .strip_prefix(&self.root)
+            .map_err(|_| anyhow!("リポジトリの外側は参照できません: {}", path))?;
+        if inside
+            .components()
+            .any(|component| component == Component::Normal(".git".as_ref()))
+        {
+            bail!(".git は参照できません: {}", path);
+        }
+        Ok(resolved)
+    }
+
+    fn list_directory(&self, path: &str) -> Result<String> {
+        let dir = self.resolve(path)?;
+        if !dir.is_dir() {
+            bail!("ディレクトリではありません: {}", path);
+        }
+
+        let mut entries: Vec<String> = std::fs::read_dir(&dir)?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_name() != ".git")
+            .map(|entry| {
+                let name = entry.file_name().to_string_lossy().to_string();
+                match entry.path().is_dir() {
+                    true => format!("{}/", name),
+                    false => name,
+                }
+            })
+            .collect();
+        entries.sort();
+
+        let total = entries.len();
+        entries.truncate(self.config.max_entries);
+        let mut listing = entries.join("\n");
+        if total > entries.len() {
+            listing.push_str(&format!("\n...（ほか {} 件）", total - entries.len()));
+        }
+        Ok(listing)
+    }
+
+    fn read_file(
+        &self,
+        path: &str,
+        start_line: Option<usize>,
+        end_line: Option<usize>,
+    ) -> Result<String> {
+        let file = self.resolve(path)?;
+        if !file.is_file() {
+            bail!("ファイルではありません: {}", path);
+        }
+        if start_line.is_some() || end_line.is_some() {
+            return self.read_lines(&file, path, start_line, end_line);
+        }
+
+        // 範囲の指定がなければファイル全体を読むため、サイズの上限を確かめる
+        let size = std::fs::metadata(&file)?.len();
+        if size > self.max_file_size as u64 {
+            bail!(
+                "ファイルが大きすぎます: {} ({} bytes)。start_line と end_line で範囲を指定してください",
+                path,
+                size
+            );
+        }
+        let bytes = std::fs::read(&file)?;
+        if file_guard::is_binary(&bytes) {
+            bail!("バイナリファイルは読めません: {}", path);
+        }
+        let content = String::from_utf8(bytes)
+            .map_err(|_| anyhow!("テキストファイルとして読めません: {}", path))?;
+
+        // 行番号を付けて返す
+        Ok(content
+            .lines()
+            .enumerate()
+            .map(|(i, line)| format!("{:>5}: {}", i + 1, line))
+            .collect::<Vec<_>>()
+            .join("\n"))
+    }
+
+    // 指定範囲の行だけを先頭から順に読む（サイズの上限を超えるファイルでも範囲を指定すれば読める）
+    // 返す文字数の上限に達したら、それ以降の行は読まない
+    fn read_lines(
+        &self,
+        file: &Path,
+        path: &str,
+        start_line: Option<usize>,
+        end_line: Option<usize>,
+    ) -> Result<String> {
+        let mut reader = BufReader::new(File::open(file)?);
+        if file_guard::is_binary(reader.fill_buf()?) {
+            bail!("バイナリファイルは読めません: {}", path);
+        }
+
+        let start = start_line.unwrap_or(1).max(1);
+        let count = end_line
+            .map(|end| (end + 1).saturating_sub(start))
+            .unwrap_or(usize::MAX);
+        let mut lines = Vec::new();
+        let mut chars = 0;
+        for (i, line) in reader.lines().enumerate().skip(start - 1).take(count) {
+            let line = line.map_err(|_| anyhow!("テキストファイルとして読めません: {}", path))?;
+            chars += line.len();
+            lines.push(format!("{:>5}: {}", i + 1, line));
+            if chars > self.config.max_result_chars {
+                break;
+            }
+        }
+        Ok(lines.join("\n"))
+    }
+
+    // ツールの呼び出しを実行（失敗した場合もその理由をモデルに返す）
+    fn execute(&self, call: &ToolCall) -> String {
+        let arguments: Value = serde_json::from_str(&call.function.arguments).unwrap_or_default();
+        let path = arguments["path"].as_str().unwrap_or(".");
+        // 0 や負の値は未指定として扱う
+        let line = |key: &str| {
+            arguments[key]
+                .as_u64()
+                .filter(|line| *line > 0)
+                .map(|line| line as usize)
+        };
+
+        let result = match call.function.name.as_str() {
+            "list_directory" => self.list_directory(path),
+            "read_file" => self.read_file(path, line("start_line"), line("end_line")),
+            name => Err(anyhow!("不明なツールです: {}", name)),
+        };
+        let output = result.unwrap_or_else(|e| format!("エラー: {}", e));
+
+        if output.chars().count() > self.config.max_result_chars {
+            let truncated: String = output.chars().take(self.config.max_result_chars).collect();
+            format!(
+                "{}\n...(省略: 続きは start_line を指定して取得してください)...",
+                truncated
+            )
+        } else {
+            output
+        }
+    }
+}
+
+// ツールを使いながら1ターン分の回答を得る
+// ツールのやり取りはこのターンの中だけで使い、会話履歴には最終的な回答だけを残す
+pub async fn complete(
+    client: &LlmClient,
+    messages: &[ChatMessage],
+    generation: &Generation,
+    sandbox: &RepoSandbox,
+) -> Result<(String, usize, String)> {
+    let tools = definitions();
+    let mut conversation = messages.to_vec();
+    let mut tokens_used = 0;
+    let mut calls = 0;
+    let mut limit_reached = false;
+
+    // フォールバックしたら、このターンの残りのツールのやり取りも回答したモデルで続ける
+    let mut generation = generation.clone();
+    loop {
+        let (message, tokens, answered) = client
+            .chat_completion_with_fallback(&conversation, &generation, &tools)
+            .await?;
+        generation = answered;
+        tokens_used += tokens;
+        if message.tool_calls.is_empty() {
+            if calls > 0 {
+                info!(
+                    "[{}] 🔧 ツールを {} 回呼び出して回答しました",
+                    client.endpoint.name, calls
+                );
+            }
+            return Ok((message.content, tokens_used, generation.model));
+        }
+
+        // 上限に達したことを伝えてもツールを呼び続ける場合は打ち切る
+        if limit_reached {
+            bail!(
+                "1ターンのツール呼び出しの上限（{} 回）を超えました",
+                sandbox.config.max_calls_per_turn
+            );
+        }
+
+        let tool_calls = message.tool_calls.clone();
+        conversation.push(message);
+        for call in &tool_calls {
+            calls += 1;
+            let output = if calls > sandbox.config.max_calls_per_turn {
+                limit_reached = true;
+                "ツール呼び出しの上限に達しました。これまでに取得した情報で回答してください。"
+                    .to_string()
+            } else {
+                info!(
+                    "[{}] 🔧 {}({})",
+                    client.endpoint.name, call.function.name, call.function.arguments
+                );
+                sandbox.execute(call)
+            };
+            conversation.push(ChatMessage {
+                role: "tool".to_string(),
+                content: output,
+                tool_call_id: Some(call.id.clone()),
+                ..Default::default()
+            });
+        }
+    }
+}
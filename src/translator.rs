@@ -0,0 +1,180 @@
+// Azure Translator による翻訳モジュール
+// リポジトリごとのサマリーを指定した言語に翻訳し、言語別のファイルとして保存する
+
+use anyhow::{Result, anyhow, bail};
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::llm::schemas::github_response::RepoInfo;
+use crate::{encryption, summary};
+
+// 翻訳の設定
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct TranslatorConfig {
+    /// サマリーの翻訳を有効にするか
+    pub enabled: bool,
+    /// Translatorのエンドポイント
+    pub endpoint: String,
+    /// Translatorリソースのキー
+    pub key: String,
+    /// Translatorリソースのリージョン（グローバルリソースの場合は空）
+    pub region: String,
+    /// 翻訳元の言語（空の場合は自動判定）
+    pub source_language: String,
+    /// 翻訳先の言語一覧
+    pub target_languages: Vec<String>,
+}
+
+impl Default for TranslatorConfig {
+    fn default() -> Self {
+        TranslatorConfig {
+            enabled: false,
+            endpoint: "https://api.cognitive.microsofttranslator.com".to_string(),
+            key: String::new(),
+            region: String::new(),
+            source_language: "ja".to_string(),
+            target_languages: vec!["en".to_string()],
+        }
+    }
+}
+
+// Translator APIのレスポンス
+#[derive(Deserialize, Debug)]
+struct TranslateResult {
+    translations: Vec<Translation>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Translation {
+    text: String,
+    to: String,
+}
+
+// 1リクエストあたりの最大文字数（APIの上限は50,000文字）
+const MAX_CHARS_PER_REQUEST: usize = 10000;
+
+// 行単位で最大文字数ごとに分割
+fn split_text(text: &str, max_chars: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+
+    for line in text.lines() {
+        if !current.is_empty() && current.chars().count() + line.chars().count() > max_chars {
+            chunks.push(std::mem::take(&mut current));
+        }
+        current.push_str(line);
+        current.push('\n');
+    }
+
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+
+    chunks
+}
+
+// テキストを翻訳し、言語ごとの翻訳結果を返す
+async fn translate(
+    client: &reqwest::Client,
+    config: &TranslatorConfig,
+    text: &str,
+) -> Result<BTreeMap<String, String>> {
+    let mut query: Vec<(&str, &str)> = vec![("api-version", "3.0"), ("textType", "plain")];
+    if !config.source_language.is_empty() {
+        query.push(("from", &config.source_language));
+    }
+    for language in &config.target_languages {
+        query.push(("to", language));
+    }
+
+    let mut translated: BTreeMap<String, String> = BTreeMap::new();
+
+    for chunk in split_text(text, MAX_CHARS_PER_REQUEST) {
+        let mut request = client
+            .post(format!(
+                "{}/translate",
+                config.endpoint.trim_end_matches('/')
+            ))
+            .query(&query)
+            .header("Ocp-Apim-Subscription-Key", &config.key)
+            .json(&json!([{ "Text": chunk }]));
+        if !config.region.is_empty() {
+            request = request.header("Ocp-Apim-Subscription-Region", &config.region);
+        }
+
+        let response = request.send().await?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await?;
+            return Err(anyhow!(
+                "Translator API エラー: ステータス {}, レスポンス: {}",
+                status,
+                error_text
+            ));
+        }
+
+        let results: Vec<TranslateResult> = response.json().await?;
+        for translation in results.into_iter().flat_map(|r| r.translations) {
+            translated
+                .entry(translation.to)
+                .or_default()
+                .push_str(&translation.text);
+        }
+    }
+
+    Ok(translated)
+}
+
+// 1リポジトリのサマリーを翻訳して保存
+async fn translate_repo_summary(
+    client: &reqwest::Client,
+    base_dir: &str,
+    repo_info: &RepoInfo,
+    config: &TranslatorConfig,
+) -> Result<Vec<String>> {
+    let summary_path = summary::summary_path(base_dir, repo_info);
+    if !Path::new(&summary_path).exists() {
+        bail!("サマリーが見つかりません: {}", summary_path);
+    }
+
+    let text = encryption::read_to_string(&summary_path)?;
+    let mut saved = Vec::new();
+
+    for (language, translated) in translate(client, config, &text).await? {
+        let path = format!(
+            "{}/{}_{}/summary.{}.md",
+            base_dir, repo_info.owner, repo_info.repo, language
+        );
+        encryption::write(&path, translated).await?;
+        saved.push(path);
+    }
+
+    Ok(saved)
+}
+
+// すべてのリポジトリのサマリーを翻訳する
+pub async fn translate_summaries(base_dir: &str, repos: &[RepoInfo], config: &TranslatorConfig) {
+    if config.key.is_empty() {
+        error!("❌ Translator のキーが設定されていないため翻訳をスキップします");
+        return;
+    }
+
+    let client = reqwest::Client::new();
+    for repo_info in repos {
+        match translate_repo_summary(&client, base_dir, repo_info, config).await {
+            Ok(paths) => {
+                for path in paths {
+                    info!("🌐 翻訳したサマリーを保存しました: {}", path);
+                }
+            }
+            Err(e) => error!(
+                "❌ 翻訳エラー: {}/{} - {}",
+                repo_info.owner, repo_info.repo, e
+            ),
+        }
+    }
+}
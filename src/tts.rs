@@ -0,0 +1,174 @@
+// Azure Speech による読み上げモジュール
+// リポジトリごとのサマリーを音声合成し、MP3ファイルとして保存する
+
+use anyhow::{Result, anyhow, bail};
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tokio::fs;
+
+use crate::llm::schemas::github_response::RepoInfo;
+use crate::{encryption, summary};
+
+// 音声合成の設定
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct TtsConfig {
+    /// サマリーの読み上げを有効にするか
+    pub enabled: bool,
+    /// Speechリソースのリージョン（例: japaneast）
+    pub region: String,
+    /// Speechリソースのキー
+    pub key: String,
+    /// 音声名
+    pub voice: String,
+    /// 出力フォーマット
+    pub output_format: String,
+    /// 1リクエストあたりの最大文字数
+    pub max_chars_per_request: usize,
+}
+
+impl Default for TtsConfig {
+    fn default() -> Self {
+        TtsConfig {
+            enabled: false,
+            region: "japaneast".to_string(),
+            key: String::new(),
+            voice: "ja-JP-NanamiNeural".to_string(),
+            output_format: "audio-24khz-96kbitrate-mono-mp3".to_string(),
+            max_chars_per_request: 3000,
+        }
+    }
+}
+
+// SSML用にXMLの特殊文字をエスケープ
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+// 読み上げに不要なMarkdown記法を取り除く
+fn strip_markdown(text: &str) -> String {
+    text.lines()
+        .map(|line| {
+            line.trim_start_matches(['#', '>', ' '])
+                .trim_start_matches("- ")
+                .replace(['*', '`', '|'], "")
+        })
+        .filter(|line| !line.trim().is_empty() && !line.trim().starts_with("---"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// 行単位で最大文字数ごとに分割
+fn split_text(text: &str, max_chars: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+
+    for line in text.lines() {
+        if !current.is_empty() && current.chars().count() + line.chars().count() > max_chars {
+            chunks.push(std::mem::take(&mut current));
+        }
+        // 1行が長すぎる場合は文字単位で切る
+        let mut rest: Vec<char> = line.chars().collect();
+        while rest.len() > max_chars {
+            chunks.push(rest.drain(..max_chars).collect());
+        }
+        current.push_str(&rest.into_iter().collect::<String>());
+        current.push('\n');
+    }
+
+    if !current.trim().is_empty() {
+        chunks.push(current);
+    }
+
+    chunks
+}
+
+// テキストを音声合成してMP3のバイト列を返す
+async fn synthesize(client: &reqwest::Client, config: &TtsConfig, text: &str) -> Result<Vec<u8>> {
+    let url = format!(
+        "https://{}.tts.speech.microsoft.com/cognitiveservices/v1",
+        config.region
+    );
+
+    let ssml = format!(
+        "<speak version='1.0' xml:lang='ja-JP'><voice name='{}'>{}</voice></speak>",
+        config.voice,
+        escape_xml(text)
+    );
+
+    let response = client
+        .post(&url)
+        .header("Ocp-Apim-Subscription-Key", &config.key)
+        .header("Content-Type", "application/ssml+xml")
+        .header("X-Microsoft-OutputFormat", &config.output_format)
+        .header("User-Agent", "azure-credit-burner")
+        .body(ssml)
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_text = response.text().await?;
+        return Err(anyhow!(
+            "Speech API エラー: ステータス {}, レスポンス: {}",
+            status,
+            error_text
+        ));
+    }
+
+    Ok(response.bytes().await?.to_vec())
+}
+
+// 1リポジトリのサマリーを読み上げて保存
+async fn narrate_repo_summary(
+    client: &reqwest::Client,
+    base_dir: &str,
+    repo_info: &RepoInfo,
+    config: &TtsConfig,
+) -> Result<String> {
+    let summary_path = summary::summary_path(base_dir, repo_info);
+    if !Path::new(&summary_path).exists() {
+        bail!("サマリーが見つかりません: {}", summary_path);
+    }
+
+    let text = strip_markdown(&encryption::read_to_string(&summary_path)?);
+
+    // MP3はフレーム単位なので、分割して合成した音声はそのまま連結できる
+    let mut audio = Vec::new();
+    for chunk in split_text(&text, config.max_chars_per_request) {
+        audio.extend(synthesize(client, config, &chunk).await?);
+    }
+
+    let audio_path = format!(
+        "{}/{}_{}/summary.mp3",
+        base_dir, repo_info.owner, repo_info.repo
+    );
+    fs::write(&audio_path, audio).await?;
+    encryption::warn_plaintext("サマリーの音声", &audio_path);
+
+    Ok(audio_path)
+}
+
+// すべてのリポジトリのサマリーを読み上げる
+pub async fn narrate_summaries(base_dir: &str, repos: &[RepoInfo], config: &TtsConfig) {
+    if config.key.is_empty() {
+        error!("❌ Speech のキーが設定されていないため読み上げをスキップします");
+        return;
+    }
+
+    let client = reqwest::Client::new();
+    for repo_info in repos {
+        match narrate_repo_summary(&client, base_dir, repo_info, config).await {
+            Ok(path) => info!("🔊 読み上げ音声を保存しました: {}", path),
+            Err(e) => error!(
+                "❌ 読み上げエラー: {}/{} - {}",
+                repo_info.owner, repo_info.repo, e
+            ),
+        }
+    }
+}
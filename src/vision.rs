@@ -0,0 +1,186 @@
+// ドキュメント画像の解析モジュール
+// リポジトリのdocs配下にある図やスクリーンショットをビジョンモデルで説明させ、議論のコンテキストに加える
+
+use anyhow::Result;
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use walkdir::WalkDir;
+
+use crate::llm::schemas::{github_response::RepoInfo, openai_response::ChatMessage};
+use crate::{GitHubClient, LlmClient, budget, encryption, outputs};
+
+// 画像解析の設定
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct VisionConfig {
+    /// 画像解析を有効にするか
+    pub enabled: bool,
+    /// ビジョン対応モデルのデプロイメント名
+    pub deployment: String,
+    /// リポジトリあたりの最大画像数
+    pub max_images: usize,
+    /// 解析対象とする画像の最大サイズ（バイト）
+    pub max_image_bytes: u64,
+}
+
+impl Default for VisionConfig {
+    fn default() -> Self {
+        VisionConfig {
+            enabled: false,
+            deployment: "gpt-4o".to_string(),
+            max_images: 10,
+            max_image_bytes: 4 * 1024 * 1024,
+        }
+    }
+}
+
+// 画像の説明を依頼するプロンプト
+const DESCRIBE_PROMPT: &str = "これはソフトウェアリポジトリのドキュメントに含まれる画像です。\
+図であれば構成要素とその関係、データの流れを、スクリーンショットであれば画面の内容と機能を詳しく説明してください。";
+
+// ドキュメントディレクトリとみなすディレクトリ名
+const DOC_DIRS: [&str; 3] = ["docs", "doc", "documentation"];
+
+// 拡張子から画像のMIMEタイプを判定（SVGはテキストとして扱う）
+fn image_mime_type(path: &Path) -> Option<&'static str> {
+    let ext = path.extension()?.to_string_lossy().to_lowercase();
+    match ext.as_str() {
+        "png" => Some("image/png"),
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "gif" => Some("image/gif"),
+        "webp" => Some("image/webp"),
+        "svg" => Some("image/svg+xml"),
+        _ => None,
+    }
+}
+
+// docs配下の画像ファイルを列挙
+fn collect_doc_images(repo_dir: &Path) -> Vec<PathBuf> {
+    WalkDir::new(repo_dir)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|entry| entry.file_name() != ".git")
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .map(|entry| entry.into_path())
+        .filter(|path| {
+            let in_docs = path
+                .strip_prefix(repo_dir)
+                .map(|rel| {
+                    rel.components().any(|c| {
+                        DOC_DIRS.contains(&c.as_os_str().to_string_lossy().to_lowercase().as_str())
+                    })
+                })
+                .unwrap_or(false);
+            in_docs && image_mime_type(path).is_some()
+        })
+        .collect()
+}
+
+// 1枚の画像を説明させる
+async fn describe_image(
+    client: &LlmClient,
+    path: &Path,
+    config: &VisionConfig,
+) -> Result<(String, usize)> {
+    let mime_type = image_mime_type(path).unwrap_or("image/png");
+    let bytes = fs::read(path).await?;
+
+    // SVGはビジョンAPIが受け付けないため、ソースをテキストとして渡す
+    let (description, tokens_used) = if mime_type == "image/svg+xml" {
+        let prompt = format!(
+            "{}\n\n以下はSVG画像のソースです。\n{}",
+            DESCRIBE_PROMPT,
+            String::from_utf8_lossy(&bytes)
+        );
+        let messages = [ChatMessage {
+            role: "user".to_string(),
+            content: prompt,
+            ..Default::default()
+        }];
+        client
+            .chat_completion(&messages, &config.deployment, 1500, 0.0)
+            .await?
+    } else {
+        client
+            .describe_image(&bytes, mime_type, DESCRIBE_PROMPT, &config.deployment)
+            .await?
+    };
+
+    Ok((description, tokens_used))
+}
+
+// リポジトリのドキュメント画像を説明させ、システムプロンプトに付加するブロックを返す
+// 説明文はリポジトリごとにキャッシュし、同じリポジトリの別の議論では再利用する
+pub async fn describe_repo_images(
+    client: &LlmClient,
+    github_client: &GitHubClient,
+    repo_info: &RepoInfo,
+    base_dir: &str,
+    config: &VisionConfig,
+) -> Result<Option<String>> {
+    let repo_dir = github_client.clone_repository(repo_info).await?;
+    let cache_path = format!(
+        "{}/{}_{}/image_descriptions.json",
+        base_dir, repo_info.owner, repo_info.repo
+    );
+
+    // キャッシュ済みの説明を読み込む
+    let mut descriptions: BTreeMap<String, String> = match encryption::read_to_string(&cache_path) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_default(),
+        Err(_) => BTreeMap::new(),
+    };
+
+    let images = collect_doc_images(&repo_dir);
+    let mut updated = false;
+
+    for path in images.iter().take(config.max_images) {
+        let rel_path = outputs::slash_path(path.strip_prefix(&repo_dir).unwrap_or(path));
+
+        if descriptions.contains_key(&rel_path) {
+            continue;
+        }
+
+        let size = fs::metadata(path).await.map(|m| m.len()).unwrap_or(0);
+        if size > config.max_image_bytes {
+            info!(
+                "⏩ サイズが大きいため画像をスキップ: {} ({} bytes)",
+                rel_path, size
+            );
+            continue;
+        }
+
+        match describe_image(client, path, config).await {
+            Ok((description, tokens_used)) => {
+                info!("👁️ 画像の説明を取得: {}", rel_path);
+                budget::record(repo_info, tokens_used);
+                descriptions.insert(rel_path, description);
+                updated = true;
+            }
+            Err(e) => {
+                error!("⚠️ 画像の説明取得エラー: {} - {}", rel_path, e);
+            }
+        }
+    }
+
+    if updated {
+        if let Some(parent) = Path::new(&cache_path).parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        encryption::write(&cache_path, serde_json::to_string_pretty(&descriptions)?).await?;
+    }
+
+    if descriptions.is_empty() {
+        return Ok(None);
+    }
+
+    let mut block = String::from("\n\n【ドキュメント画像の説明】");
+    for (path, description) in &descriptions {
+        block.push_str(&format!("\n--- {} ---\n{}", path, description));
+    }
+
+    Ok(Some(block))
+}
@@ -0,0 +1,89 @@
+// GitHub Webhook モジュール
+// push / pull_request イベントの署名を検証し、分析対象のリポジトリ・ブランチ・コミットを取り出す
+
+use anyhow::{Result, anyhow};
+use hmac::{Hmac, Mac};
+use serde_json::Value;
+use sha2::Sha256;
+
+use crate::llm::schemas::github_response::RepoInfo;
+
+// 分析対象にする pull_request イベントのアクション
+const PULL_REQUEST_ACTIONS: [&str; 4] = ["opened", "synchronize", "reopened", "ready_for_review"];
+
+// X-Hub-Signature-256 ヘッダーの署名を検証
+pub fn verify_signature(secret: &str, body: &[u8], signature: Option<&str>) -> bool {
+    let Some(signature) = signature.and_then(|value| value.strip_prefix("sha256=")) else {
+        return false;
+    };
+    let Ok(signature) = hex::decode(signature) else {
+        return false;
+    };
+    let Ok(mut mac) = Hmac::<Sha256>::new_from_slice(secret.as_bytes()) else {
+        return false;
+    };
+    mac.update(body);
+    mac.verify_slice(&signature).is_ok()
+}
+
+// イベントの対象リポジトリ（owner/repo）を取得
+fn repository(payload: &Value) -> Result<(String, String)> {
+    let full_name = payload["repository"]["full_name"]
+        .as_str()
+        .ok_or_else(|| anyhow!("repository.full_name がありません"))?;
+    let (owner, repo) = full_name
+        .split_once('/')
+        .ok_or_else(|| anyhow!("リポジトリ名の形式が正しくありません: {}", full_name))?;
+    Ok((owner.to_string(), repo.to_string()))
+}
+
+// イベントから分析対象を取り出す（分析しないイベントはNone）
+pub fn parse_event(event: &str, payload: &Value, max_files: usize) -> Result<Option<RepoInfo>> {
+    match event {
+        "push" => {
+            // ブランチの削除やタグのpushは対象外
+            if payload["deleted"].as_bool().unwrap_or(false) {
+                return Ok(None);
+            }
+            let Some(branch) = payload["ref"]
+                .as_str()
+                .and_then(|git_ref| git_ref.strip_prefix("refs/heads/"))
+            else {
+                return Ok(None);
+            };
+            let commit = payload["after"]
+                .as_str()
+                .ok_or_else(|| anyhow!("after がありません"))?;
+
+            let (owner, repo) = repository(payload)?;
+            Ok(Some(RepoInfo {
+                owner,
+                repo,
+                max_files,
+                branch: Some(branch.to_string()),
+                commit: Some(commit.to_string()),
+                ..Default::default()
+            }))
+        }
+        "pull_request" => {
+            let action = payload["action"].as_str().unwrap_or_default();
+            if !PULL_REQUEST_ACTIONS.contains(&action) {
+                return Ok(None);
+            }
+            // フォークからのPRでもベースリポジトリからheadのコミットを取得できる
+            let commit = payload["pull_request"]["head"]["sha"]
+                .as_str()
+                .ok_or_else(|| anyhow!("pull_request.head.sha がありません"))?;
+
+            let (owner, repo) = repository(payload)?;
+            Ok(Some(RepoInfo {
+                owner,
+                repo,
+                max_files,
+                commit: Some(commit.to_string()),
+                ..Default::default()
+            }))
+        }
+        _ => Ok(None),
+    }
+}
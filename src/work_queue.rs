@@ -0,0 +1,658 @@
+// 議論タスクの共有キューとエンドポイントごとのワーカー
+// 各エンドポイントのワーカーが共有キューから議論を取り出すため、止まったリージョンの残りの議論は他のリージョンが引き受ける
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time;
+
+use crate::error::BurnerError;
+use crate::file_review::FILE_REVIEW_TYPE;
+use crate::journal::{self, Journal, TaskState, TaskStatus};
+use crate::llm::schemas::github_response::RepoInfo;
+use crate::llm::schemas::openai_response::Endpoint;
+use crate::scheduler::Scheduler;
+use crate::{
+    Config, DebateOutcome, GitHubClient, budget, control, credits, deadline, debate_runner, groups,
+    ledger, reload, status,
+};
+
+// 失敗した議論の再試行の設定
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct RetryConfig {
+    /// 1つの議論を最大何回まで試すか
+    pub max_attempts: usize,
+    /// 最初の再試行までの待機時間（秒、失敗するたびに倍増）
+    pub initial_delay_secs: u64,
+    /// 再試行までの待機時間の上限（秒）
+    pub max_delay_secs: u64,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        RetryConfig {
+            max_attempts: 5,
+            initial_delay_secs: 60,
+            max_delay_secs: 1800,
+        }
+    }
+}
+
+impl RetryConfig {
+    // attempts 回目の失敗から再試行までの待機時間
+    fn delay(&self, attempts: usize) -> chrono::Duration {
+        let secs = self
+            .initial_delay_secs
+            .saturating_mul(1 << attempts.saturating_sub(1).min(16))
+            .min(self.max_delay_secs);
+        chrono::Duration::seconds(secs as i64)
+    }
+}
+
+// 失敗したワーカーが次の議論を取りに行くまでの待機時間（秒、連続失敗ごとに倍増）
+const FAILURE_COOLDOWN_SECS: u64 = 30;
+const MAX_FAILURE_COOLDOWN_SECS: u64 = 300;
+
+// キューに積む議論
+pub struct DebateJob {
+    pub task_id: String,
+    pub repo_info: RepoInfo,
+    pub debate_type: String,
+    // 同じリポジトリ・議論タイプを別の視点として分析する場合の番号
+    pub perspective: usize,
+    // ファイル単位のレビューで対象にするファイルのパス
+    pub file: Option<String>,
+    pub attempts: usize,
+    // 再試行を待っている場合、再試行できるようになる日時
+    pub retry_at: Option<DateTime<Utc>>,
+    // 失敗したエンドポイント（他のエンドポイントがあればそちらで再試行する）
+    pub failed_endpoints: Vec<String>,
+}
+
+impl DebateJob {
+    pub fn new(repo_info: RepoInfo, debate_type: String, perspective: usize) -> Self {
+        DebateJob {
+            task_id: journal::task_id(&repo_info, &debate_type, &perspective.to_string()),
+            repo_info,
+            debate_type,
+            perspective,
+            file: None,
+            attempts: 0,
+            retry_at: None,
+            failed_endpoints: Vec::new(),
+        }
+    }
+
+    // 定期実行の議論（実行予定時刻ごとに別のタスクになる）
+    pub fn scheduled(repo_info: RepoInfo, debate_type: String, run_at: DateTime<Utc>) -> Self {
+        let variant = format!("scheduled {}", run_at.to_rfc3339());
+        DebateJob {
+            task_id: journal::task_id(&repo_info, &debate_type, &variant),
+            repo_info,
+            debate_type,
+            perspective: 0,
+            file: None,
+            attempts: 0,
+            retry_at: None,
+            failed_endpoints: Vec::new(),
+        }
+    }
+
+    // ファイル単位のレビュー（ファイルごとに別のタスクになる）
+    pub fn file_review(repo_info: RepoInfo, path: String) -> Self {
+        let debate_type = FILE_REVIEW_TYPE.to_string();
+        DebateJob {
+            task_id: journal::task_id(&repo_info, &debate_type, &format!("file {}", path)),
+            repo_info,
+            debate_type,
+            perspective: 0,
+            file: Some(path),
+            attempts: 0,
+            retry_at: None,
+            failed_endpoints: Vec::new(),
+        }
+    }
+
+    // ジャーナルに記録された再試行の予定を引き継ぐ（予定がなければ何もしない）
+    pub fn restore_retry(&mut self, status: &TaskStatus) -> bool {
+        let Some(retry_at) = status
+            .retry_at
+            .as_deref()
+            .and_then(|retry_at| DateTime::parse_from_rfc3339(retry_at).ok())
+        else {
+            return false;
+        };
+        self.attempts = status.attempts.unwrap_or_default();
+        self.retry_at = Some(retry_at.with_timezone(&Utc));
+        self.failed_endpoints = status.endpoint.iter().cloned().collect();
+        true
+    }
+
+    // このエンドポイントで実行できるか（再試行の日時が来ていて、失敗していない他のエンドポイントがあればそちらに任せる）
+    fn runnable_on(&self, endpoint: &str, active_endpoints: &[String], now: DateTime<Utc>) -> bool {
+        if self.retry_at.is_some_and(|retry_at| retry_at > now) {
+            return false;
+        }
+        !self
+            .failed_endpoints
+            .iter()
+            .any(|failed| failed == endpoint)
+            || active_endpoints
+                .iter()
+                .all(|active| self.failed_endpoints.contains(active))
+    }
+}
+
+// ジャーナルに再試行の予定が残っている議論に予定を引き継ぐ
+pub fn restore_retries(jobs: &mut [DebateJob], journal: &Journal) {
+    let mut restored = 0;
+    for job in jobs.iter_mut() {
+        let status = journal
+            .status(&job.task_id)
+            .filter(|status| status.state == TaskState::Failed);
+        if status.is_some_and(|status| job.restore_retry(&status)) {
+            restored += 1;
+        }
+    }
+    if restored > 0 {
+        info!("🔁 再試行を待っている議論を引き継ぎます: {} 件", restored);
+    }
+}
+
+struct QueueState {
+    jobs: VecDeque<DebateJob>,
+    // 取り出されて実行中の議論の数（失敗して戻される可能性がある）
+    in_flight: usize,
+    // 新しい議論を受け付け続けるか（デーモンモード）
+    open: bool,
+    // 空になってワーカーに終了を伝えたか
+    drained: bool,
+    // ワーカーの世代（空になった後に議論を積み直したら、新しい世代のワーカーを起動する）
+    generation: usize,
+}
+
+impl QueueState {
+    fn enqueue(&mut self, job: DebateJob) {
+        let priority = job.repo_info.priority;
+        let position = self
+            .jobs
+            .iter()
+            .position(|queued| queued.repo_info.priority < priority)
+            .unwrap_or(self.jobs.len());
+        self.jobs.insert(position, job);
+    }
+}
+
+// 取り出しの結果
+enum Next {
+    Job(Box<DebateJob>),
+    // 今は空だが、実行中の議論が戻される可能性がある
+    Wait,
+    Done,
+}
+
+// 議論の共有キュー
+pub struct WorkQueue {
+    state: Mutex<QueueState>,
+}
+
+impl WorkQueue {
+    pub fn new(jobs: impl IntoIterator<Item = DebateJob>) -> Self {
+        WorkQueue {
+            state: Mutex::new(QueueState {
+                jobs: jobs.into_iter().collect(),
+                in_flight: 0,
+                open: false,
+                drained: false,
+                generation: 0,
+            }),
+        }
+    }
+
+    // 空になってもワーカーを終了させないキュー（デーモンモード用）
+    pub fn open() -> Self {
+        WorkQueue {
+            state: Mutex::new(QueueState {
+                jobs: VecDeque::new(),
+                in_flight: 0,
+                open: true,
+                drained: false,
+                generation: 0,
+            }),
+        }
+    }
+
+    // 議論を追加（優先度の低い議論より前に入れる）
+    pub fn push(&self, job: DebateJob) {
+        self.state.lock().unwrap().enqueue(job);
+    }
+
+    // ジャーナルに記録して議論を追加（実行中・完了済みの議論は追加せず現在の状態を返す）
+    pub fn submit(&self, journal: &Journal, job: DebateJob) -> TaskStatus {
+        let existing = journal.status(&job.task_id);
+        if let Some(status) = existing.filter(|status| status.state != TaskState::Failed) {
+            return status;
+        }
+
+        let status = journal.record(
+            &job.task_id,
+            &job.repo_info,
+            &job.debate_type,
+            TaskState::Queued,
+            None,
+            None,
+        );
+        self.push(job);
+        status
+    }
+
+    // まだ始まっていない議論のうち条件に合うものを取り下げる
+    pub fn withdraw(&self, predicate: impl Fn(&DebateJob) -> bool) -> Vec<DebateJob> {
+        let mut state = self.state.lock().unwrap();
+        let (withdrawn, kept): (Vec<_>, Vec<_>) =
+            state.jobs.drain(..).partition(|job| predicate(job));
+        state.jobs = kept.into();
+        withdrawn
+    }
+
+    // 起動するワーカーの世代
+    fn generation(&self) -> usize {
+        self.state.lock().unwrap().generation
+    }
+
+    // 空になってワーカーが終了したキューに議論を積み直したら、新しい世代を始める
+    // （true なら呼び出し側が新しいワーカーを起動する。古い世代のワーカーは次の取り出しで終了する）
+    pub fn reopen(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if !state.drained || state.jobs.is_empty() {
+            return false;
+        }
+        state.drained = false;
+        state.generation += 1;
+        true
+    }
+
+    // このエンドポイントで実行できる議論を取り出す（再試行を待っている議論は日時が来るまで残す）
+    fn next(&self, endpoint: &str, active_endpoints: &[String], generation: usize) -> Next {
+        let mut state = self.state.lock().unwrap();
+        // 新しい世代のワーカーに引き継いだ後は、古い世代のワーカーを終了させる
+        if generation != state.generation {
+            return Next::Done;
+        }
+        let now = Utc::now();
+        let position = state
+            .jobs
+            .iter()
+            .position(|job| job.runnable_on(endpoint, active_endpoints, now));
+        match position.and_then(|position| state.jobs.remove(position)) {
+            Some(job) => {
+                state.in_flight += 1;
+                Next::Job(Box::new(job))
+            }
+            None if state.open || state.in_flight > 0 || !state.jobs.is_empty() => Next::Wait,
+            None => {
+                state.drained = true;
+                Next::Done
+            }
+        }
+    }
+
+    // 実行を終えた議論を報告（失敗した場合は再試行のためキューに戻す）
+    fn finish(&self, retry: Option<DebateJob>) {
+        let mut state = self.state.lock().unwrap();
+        state.in_flight -= 1;
+        if let Some(job) = retry {
+            state.enqueue(job);
+        }
+    }
+}
+
+// 同時実行数を重みに比例して配分し、エンドポイントごとのワーカーを起動
+// （操作コマンドで同時実行数を増やせるよう、有効なら max_concurrency 分のワーカーを用意する）
+pub fn spawn_workers(
+    queue: Arc<WorkQueue>,
+    github_client: Arc<GitHubClient>,
+    endpoints: Arc<Vec<Endpoint>>,
+    config: Arc<Config>,
+    scheduler: Arc<Scheduler>,
+    journal: Arc<Journal>,
+) -> Vec<JoinHandle<Result<()>>> {
+    let mut workers = Vec::new();
+
+    for (endpoint_index, worker_count) in scheduler
+        .allocate_workers(control::worker_count(&config.control, config.concurrency))
+        .into_iter()
+        .enumerate()
+    {
+        workers.extend(spawn_endpoint_workers(
+            &queue,
+            &github_client,
+            &endpoints,
+            endpoint_index,
+            worker_count,
+            &config,
+            &scheduler,
+            &journal,
+        ));
+    }
+
+    workers
+}
+
+// 1エンドポイント分のワーカーを起動
+#[allow(clippy::too_many_arguments)]
+pub fn spawn_endpoint_workers(
+    queue: &Arc<WorkQueue>,
+    github_client: &Arc<GitHubClient>,
+    endpoints: &Arc<Vec<Endpoint>>,
+    endpoint_index: usize,
+    worker_count: usize,
+    config: &Arc<Config>,
+    scheduler: &Arc<Scheduler>,
+    journal: &Arc<Journal>,
+) -> Vec<JoinHandle<Result<()>>> {
+    info!(
+        "👷 [{}] ワーカー数: {}",
+        endpoints[endpoint_index].name, worker_count
+    );
+    scheduler.note_workers(endpoint_index, worker_count);
+    let generation = queue.generation();
+    (0..worker_count)
+        .map(|_| {
+            tokio::spawn(debate_worker(
+                queue.clone(),
+                github_client.clone(),
+                endpoints.clone(),
+                endpoint_index,
+                generation,
+                config.clone(),
+                scheduler.clone(),
+                journal.clone(),
+            ))
+        })
+        .collect()
+}
+
+// ワーカーの終了をスケジューラーに記録する
+// （新しい世代に引き継いだ後の古い世代のワーカーは、数え直した後なので記録しない）
+struct WorkerGuard {
+    queue: Arc<WorkQueue>,
+    scheduler: Arc<Scheduler>,
+    endpoint_index: usize,
+    generation: usize,
+    // retire_worker で記録済み
+    retired: bool,
+}
+
+impl Drop for WorkerGuard {
+    fn drop(&mut self) {
+        if !self.retired && self.queue.generation() == self.generation {
+            self.scheduler.note_worker_exited(self.endpoint_index);
+        }
+    }
+}
+
+// 1エンドポイント分のワーカー
+#[allow(clippy::too_many_arguments)]
+async fn debate_worker(
+    queue: Arc<WorkQueue>,
+    github_client: Arc<GitHubClient>,
+    endpoints: Arc<Vec<Endpoint>>,
+    endpoint_index: usize,
+    generation: usize,
+    config: Arc<Config>,
+    scheduler: Arc<Scheduler>,
+    journal: Arc<Journal>,
+) -> Result<()> {
+    let endpoint_name = endpoints[endpoint_index].name.clone();
+    let mut consecutive_failures = 0;
+    let mut guard = WorkerGuard {
+        queue: queue.clone(),
+        scheduler: scheduler.clone(),
+        endpoint_index,
+        generation,
+        retired: false,
+    };
+
+    loop {
+        // エンドポイントの追加でワーカーの合計が同時実行数を超えたら、配分より多いエンドポイントのワーカーから終了する
+        if generation == queue.generation()
+            && scheduler.retire_worker(
+                endpoint_index,
+                control::worker_count(&config.control, config.concurrency),
+            )
+        {
+            info!(
+                "[{}] 👷 追加したエンドポイントにワーカーを譲るため終了します",
+                endpoint_name
+            );
+            guard.retired = true;
+            break;
+        }
+        // 実行期限が近ければ新しい議論を取らない（残りはジャーナルに登録済みのまま次回に回す）
+        if deadline::reached() {
+            info!(
+                "[{}] ⏳ 実行期限が近いため新しい議論を開始しません",
+                endpoint_name
+            );
+            break;
+        }
+        // 使用額の上限に達していれば新しい議論を取らない（残りは次回に回す）
+        if ledger::ceiling_reached() {
+            break;
+        }
+
+        // グループの予算に達したエンドポイントは、クレジットが枯渇したエンドポイントとして扱う
+        if let Some(message) = groups::exhausted(&endpoints[endpoint_index]) {
+            credits::record_exhausted(&endpoint_name, &message);
+        }
+
+        // クレジットが枯渇したエンドポイントと、設定ファイルから削除されたエンドポイントは
+        // 新しい議論を取らない（残りは他のエンドポイントが実行する）
+        if credits::is_exhausted(&endpoint_name) || reload::is_removed(&endpoint_name) {
+            break;
+        }
+
+        // 操作コマンドで一時停止したエンドポイントは、再開されるまで新しい議論を取らない
+        if control::is_paused(&endpoint_name) {
+            time::sleep(Duration::from_secs(1)).await;
+            continue;
+        }
+        // 操作コマンドで変更した同時実行数の上限に達していれば、空きが出るまで待つ
+        // （実行枠は議論が終わってこのループの次の周回に入るときに返す）
+        let Some(slot) = control::slot() else {
+            time::sleep(Duration::from_secs(1)).await;
+            continue;
+        };
+
+        // 議論を引き受けられるエンドポイント
+        let active_endpoints: Vec<String> = endpoints
+            .iter()
+            .map(|endpoint| endpoint.name.clone())
+            .filter(|name| {
+                !credits::is_exhausted(name)
+                    && !reload::is_removed(name)
+                    && !control::is_paused(name)
+                    && scheduler.has_workers(name)
+            })
+            .collect();
+        let mut job = match queue.next(&endpoint_name, &active_endpoints, generation) {
+            Next::Job(job) => *job,
+            Next::Wait => {
+                // 待つ間は実行枠を他のワーカーに譲る
+                drop(slot);
+                time::sleep(Duration::from_secs(1)).await;
+                continue;
+            }
+            Next::Done => break,
+        };
+
+        // トークン予算に達したリポジトリの議論は開始しない
+        if budget::exhausted(&job.repo_info) {
+            info!(
+                "💰 トークン予算に達したためスキップします: {}/{} ({})",
+                job.repo_info.owner, job.repo_info.repo, job.debate_type
+            );
+            queue.finish(None);
+            continue;
+        }
+        // 操作コマンドでスキップしたリポジトリの議論は開始しない（ジャーナルに登録済みのまま次回に回す）
+        if control::is_skipped(&job.repo_info) {
+            info!(
+                "⏭️ 操作コマンドでスキップしたため開始しません: {}/{} ({})",
+                job.repo_info.owner, job.repo_info.repo, job.debate_type
+            );
+            queue.finish(None);
+            continue;
+        }
+
+        scheduler.note_assigned(endpoint_index);
+        job.attempts += 1;
+        journal.record(
+            &job.task_id,
+            &job.repo_info,
+            &job.debate_type,
+            TaskState::Running,
+            Some(&endpoint_name),
+            None,
+        );
+        status::task_started(
+            &job.task_id,
+            &job.repo_info,
+            &job.debate_type,
+            &endpoint_name,
+        );
+
+        // 止まった議論は時間制限で打ち切り、保存済みのターンから再試行する
+        let runner = debate_runner(
+            github_client.clone(),
+            endpoints.clone(),
+            &job,
+            endpoint_index,
+            config.clone(),
+            scheduler.clone(),
+            journal.clone(),
+        );
+        let result = deadline::with_timeout(config.timeouts.debate(), "議論", runner).await;
+        status::task_finished(&job.task_id, matches!(result, Ok(DebateOutcome::Completed)));
+
+        match result {
+            Ok(DebateOutcome::Interrupted) => {
+                // 実行中の状態のまま残し、次回の実行で保存済みのターンから再開する
+                deadline::note_interrupted();
+                queue.finish(None);
+            }
+            Ok(DebateOutcome::OverBudget) => {
+                queue.finish(None);
+            }
+            Ok(DebateOutcome::Duplicate(original)) => {
+                journal.record_duplicate(&job.task_id, &job.repo_info, &job.debate_type, &original);
+                queue.finish(None);
+            }
+            Ok(DebateOutcome::Completed) => {
+                info!("🎉 タスク完了");
+                journal.record(
+                    &job.task_id,
+                    &job.repo_info,
+                    &job.debate_type,
+                    TaskState::Done,
+                    Some(&endpoint_name),
+                    None,
+                );
+                consecutive_failures = 0;
+                queue.finish(None);
+            }
+            Err(e) => {
+                error!("❌ タスクエラー: [{}] {}", endpoint_name, e);
+                status::record_error(&endpoint_name, &e.to_string());
+
+                // クレジットが枯渇したエンドポイントの失敗は試行回数に数えず、他のエンドポイントに回す
+                let class = BurnerError::of(&e);
+                // 使用額の上限で止めた議論は実行中の状態のまま残し、次回の実行で保存済みのターンから再開する
+                if let Some(BurnerError::SpendCeiling { .. }) = class {
+                    queue.finish(None);
+                    break;
+                }
+                if let Some(BurnerError::QuotaExhausted { message }) = class {
+                    journal.record_failure(
+                        &job.task_id,
+                        &job.repo_info,
+                        &job.debate_type,
+                        Some(&endpoint_name),
+                        &e,
+                    );
+                    credits::record_exhausted(&endpoint_name, message);
+                    job.attempts -= 1;
+                    queue.finish(Some(job));
+                    break;
+                }
+
+                // 待機時間を倍増させながら、できるだけ別のエンドポイントで再試行する
+                // （コンテンツフィルターやクローンできないリポジトリはどこで実行しても同じ結果になる）
+                let retryable = class.is_none_or(|error| error.is_retryable_elsewhere());
+                if retryable && job.attempts < config.retry.max_attempts {
+                    let retry_at = Utc::now() + config.retry.delay(job.attempts);
+                    warn!(
+                        "🔁 議論を再試行キューに入れます: {}/{} ({}) - 試行 {}/{}、{} 以降に再試行",
+                        job.repo_info.owner,
+                        job.repo_info.repo,
+                        job.debate_type,
+                        job.attempts,
+                        config.retry.max_attempts,
+                        retry_at.with_timezone(&chrono::Local).format("%H:%M:%S")
+                    );
+                    journal.record_retry(
+                        &job.task_id,
+                        &job.repo_info,
+                        &job.debate_type,
+                        &endpoint_name,
+                        &e,
+                        job.attempts,
+                        retry_at,
+                    );
+                    if !job.failed_endpoints.contains(&endpoint_name) {
+                        job.failed_endpoints.push(endpoint_name.clone());
+                    }
+                    job.retry_at = Some(retry_at);
+                    queue.finish(Some(job));
+                } else {
+                    journal.record_failure(
+                        &job.task_id,
+                        &job.repo_info,
+                        &job.debate_type,
+                        Some(&endpoint_name),
+                        &e,
+                    );
+                    queue.finish(None);
+                }
+
+                // 議論の内容やリポジトリが原因のエラーはエンドポイントの失敗に数えない
+                if !retryable {
+                    continue;
+                }
+
+                // 調子の悪いエンドポイントはしばらく新しい議論を取らない
+                consecutive_failures += 1;
+                let cooldown = match class {
+                    // 認証エラーはすぐには直らないので最長の時間待機する
+                    Some(BurnerError::AuthFailed { .. }) => MAX_FAILURE_COOLDOWN_SECS,
+                    _ => (FAILURE_COOLDOWN_SECS << (consecutive_failures - 1).min(4))
+                        .min(MAX_FAILURE_COOLDOWN_SECS),
+                };
+                warn!(
+                    "[{}] ⏸️ 連続 {} 回失敗したため {} 秒待機します",
+                    endpoint_name, consecutive_failures, cooldown
+                );
+                time::sleep(Duration::from_secs(cooldown)).await;
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,101 @@
+// 記録したカセットを再生して、APIのエラー処理を実際の通信なしで確かめる
+// tests/fixtures の設定ファイルと保存済みのターンをコピーしたディレクトリで summarize を実行し、
+// サマリー生成のチャット補完に対するレスポンスをカセットから返す
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+// フィクスチャのディレクトリを丸ごとコピーする
+fn copy_dir(from: &Path, to: &Path) {
+    fs::create_dir_all(to).unwrap();
+    for entry in fs::read_dir(from).unwrap() {
+        let entry = entry.unwrap();
+        let target = to.join(entry.file_name());
+        if entry.file_type().unwrap().is_dir() {
+            copy_dir(&entry.path(), &target);
+        } else {
+            fs::copy(entry.path(), &target).unwrap();
+        }
+    }
+}
+
+// テストごとの作業ディレクトリを用意する
+fn workspace(name: &str) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = fs::remove_dir_all(&dir);
+    copy_dir(
+        &Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures"),
+        &dir,
+    );
+    dir
+}
+
+// カセットを再生して summarize を実行し、ログ（標準出力と標準エラー）と所要時間を返す
+fn summarize_with_cassette(dir: &Path, cassette: &str) -> (String, Duration) {
+    let started = Instant::now();
+    let output = Command::new(env!("CARGO_BIN_EXE_azure-credit-burner"))
+        .current_dir(dir)
+        .args(["--replay", &format!("cassettes/{}", cassette), "summarize"])
+        .output()
+        .unwrap();
+    let elapsed = started.elapsed();
+    assert!(output.status.success(), "summarize が失敗しました");
+    let log = format!(
+        "{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    (log, elapsed)
+}
+
+fn summary_path(dir: &Path) -> PathBuf {
+    dir.join("outputs/acme_widget/summary.md")
+}
+
+#[test]
+fn rate_limit_waits_for_retry_after() {
+    let dir = workspace("rate_limited");
+    let (log, elapsed) = summarize_with_cassette(&dir, "rate_limited.jsonl");
+
+    // 429 の本文の "Please retry after 2 seconds" に従って待つ（指数バックオフの1秒ではない）
+    assert!(log.contains("レート制限"), "{}", log);
+    assert!(log.contains("リトライ 1/5, 2秒後"), "{}", log);
+    assert!(elapsed >= Duration::from_secs(2), "{:?}", elapsed);
+
+    // 再試行で記録した2件目のレスポンスを受け取り、サマリーを保存する
+    let summary = fs::read_to_string(summary_path(&dir)).unwrap();
+    assert!(summary.contains("# acme/widget サマリー"), "{}", summary);
+}
+
+#[test]
+fn content_filter_is_not_retried() {
+    let dir = workspace("content_filter");
+    let (log, _) = summarize_with_cassette(&dir, "content_filter.jsonl");
+
+    // コンテンツフィルターとして分類され、同じ入力では再試行しない
+    assert!(
+        log.contains("サマリー生成エラー: acme/widget - コンテンツフィルター:"),
+        "{}",
+        log
+    );
+    assert!(!log.contains("リトライ"), "{}", log);
+    assert!(!log.contains("カセットに記録がありません"), "{}", log);
+    assert!(!summary_path(&dir).exists());
+}
+
+#[test]
+fn filtered_completion_is_not_saved() {
+    let dir = workspace("content_filter_completion");
+    let (log, _) = summarize_with_cassette(&dir, "content_filter_completion.jsonl");
+
+    // 200 でも finish_reason が content_filter なら、途中で打ち切られた回答をサマリーとして保存しない
+    assert!(
+        log.contains("サマリー生成エラー: acme/widget - コンテンツフィルター:"),
+        "{}",
+        log
+    );
+    assert!(!log.contains("リトライ"), "{}", log);
+    assert!(!summary_path(&dir).exists());
+}